@@ -0,0 +1,101 @@
+#ifndef BNET_AUTH_EXPORT_H
+#define BNET_AUTH_EXPORT_H
+
+/* Generated with cbindgen from src/ffi.rs, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The kind of the last failure, mirroring the library's `Error` variants.
+ */
+typedef enum BnetErrorKind {
+  BNET_ERROR_KIND_NONE,
+  BNET_ERROR_KIND_INVALID_INPUT,
+  BNET_ERROR_KIND_TOKEN_EXPIRED,
+  BNET_ERROR_KIND_SSO_FAILED,
+  BNET_ERROR_KIND_RESTORE_REJECTED,
+  BNET_ERROR_KIND_RATE_LIMITED,
+  BNET_ERROR_KIND_API,
+  BNET_ERROR_KIND_NETWORK,
+  BNET_ERROR_KIND_PARSE,
+  BNET_ERROR_KIND_IO,
+} BnetErrorKind;
+
+/**
+ * A Battle.net API client. Create it with `bnet_api_new` and release it with `bnet_api_free`.
+ */
+typedef struct BnetApi BnetApi;
+
+/**
+ * Create a client for `region` ("us", "eu", "kr", "tw" or "cn"), or NULL to detect the region
+ * from the session token.
+ *
+ * # Safety
+ *
+ * `region` must be NULL or a valid NUL-terminated string.
+ */
+struct BnetApi *bnet_api_new(const char *region);
+
+/**
+ * Release a client created with `bnet_api_new`. Passing NULL does nothing.
+ *
+ * # Safety
+ *
+ * `api` must be NULL or a pointer returned by `bnet_api_new` that has not been freed.
+ */
+void bnet_api_free(struct BnetApi *api);
+
+/**
+ * Exchange a session token, in any form the CLI accepts, for a bearer token.
+ *
+ * # Safety
+ *
+ * `api` must come from `bnet_api_new` and `session_token` must be a valid NUL-terminated string.
+ */
+char *bnet_exchange_token(const struct BnetApi *api, const char *session_token);
+
+/**
+ * Restore an authenticator with a bearer token and return its hex device secret.
+ *
+ * # Safety
+ *
+ * `api` must come from `bnet_api_new` and the strings must be valid and NUL-terminated.
+ */
+char *bnet_restore_secret(const struct BnetApi *api,
+                          const char *bearer_token,
+                          const char *serial,
+                          const char *restore_code);
+
+/**
+ * Build the otpauth URI for a serial and hex device secret.
+ *
+ * # Safety
+ *
+ * Both strings must be valid and NUL-terminated.
+ */
+char *bnet_build_otpauth(const char *serial, const char *device_secret);
+
+/**
+ * Zeroize and release a string returned by this interface. Passing NULL does nothing.
+ *
+ * # Safety
+ *
+ * `value` must be NULL or a string returned by this interface that has not been freed.
+ */
+void bnet_string_free(char *value);
+
+/**
+ * The message of the last failure on this thread, or NULL. Valid until the next call.
+ */
+const char *bnet_last_error(void);
+
+/**
+ * The kind of the last failure on this thread.
+ */
+enum BnetErrorKind bnet_last_error_kind(void);
+
+#endif  /* BNET_AUTH_EXPORT_H */
@@ -0,0 +1,902 @@
+use clap::{Args, ValueEnum};
+#[cfg(not(target_arch = "wasm32"))]
+use reqwest::Certificate;
+#[cfg(not(target_arch = "wasm32"))]
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::header::{ACCEPT, HeaderMap, HeaderValue, USER_AGENT};
+#[cfg(not(target_arch = "wasm32"))]
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use reqwest::{Method, StatusCode, Url};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use zeroize::Zeroizing;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::audit::AuditLog;
+use crate::clock;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::correlation;
+use crate::diagnose::diagnose;
+use crate::error::{Error, Result};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::rate_limit::AttemptLimit;
+use crate::region::Region;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::retry::RetryPolicy;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::transcript::Transcript;
+
+const CLIENT_ID: &str = "baedda12fe054e4abdfc3ad7bdea970a";
+
+/// The `User-Agent` of the Battle.net Authenticator app for Android, sent for the requests in
+/// [`ApiOptions::emulate_mobile_app`]. Override it with `mobile_user_agent` once the app's
+/// User-Agent changes.
+pub const MOBILE_USER_AGENT: &str = "Battle.net Authenticator/1.24.0 (Android 14; okhttp/4.12.0)";
+
+/// The API requests that can be sent as the mobile app instead of as this tool.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum AppRequest {
+    All,
+    Sso,
+    Oauth,
+    Restore,
+    UserInfo,
+    Devices,
+    Attach,
+    Detach,
+}
+
+/// An authenticator attached to the account, as listed by [`Api::devices`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Device {
+    pub serial: String,
+    /// The kind of authenticator, such as the mobile app, when the API gives it.
+    pub kind: Option<String>,
+    /// When it was attached, as the API gives it.
+    pub attached_at: Option<String>,
+    /// The restore code, for deployments that list it. With it the export needs nothing but
+    /// the session token.
+    pub restore_code: Option<String>,
+}
+
+/// A new authenticator attached to the account by [`Api::attach`].
+pub struct NewAuthenticator {
+    pub serial: String,
+    /// Needed to restore or detach it later, like the restore code shown in the app.
+    pub restore_code: String,
+    pub device_secret: Zeroizing<String>,
+}
+
+/// Settings for talking to the Battle.net APIs.
+#[derive(Args)]
+pub struct ApiOptions {
+    /// OAuth client_id used for the SSO token exchange.
+    #[arg(long, env = "BNET_CLIENT_ID", default_value = CLIENT_ID)]
+    pub client_id: String,
+
+    /// Account region. Detected from the session token when omitted.
+    #[arg(long, env = "BNET_REGION", ignore_case = true)]
+    pub region: Option<Region>,
+
+    /// URL of the SSO token exchange endpoint. Defaults to the region's endpoint.
+    #[arg(long, env = "BNET_SSO_URL")]
+    pub sso_url: Option<String>,
+
+    /// Base URL of the OAuth authorize and token endpoints. Defaults to the region's endpoint.
+    #[arg(long, env = "BNET_OAUTH_URL")]
+    pub oauth_url: Option<String>,
+
+    /// Base URL of the authenticator REST API. Defaults to the region's endpoint.
+    #[arg(long, env = "BNET_AUTH_BASE_URL")]
+    pub auth_base_url: Option<String>,
+
+    /// Send these requests as the Battle.net mobile app rather than as bnet-auth-export, for if
+    /// Blizzard starts turning unknown clients away: sso, oauth, restore, user-info, devices,
+    /// attach, detach or all.
+    #[arg(
+        long,
+        env = "BNET_EMULATE_MOBILE_APP",
+        value_enum,
+        value_delimiter = ','
+    )]
+    pub emulate_mobile_app: Vec<AppRequest>,
+
+    /// User-Agent sent by --emulate-mobile-app.
+    #[arg(long, env = "BNET_MOBILE_USER_AGENT", default_value = MOBILE_USER_AGENT)]
+    pub mobile_user_agent: String,
+
+    /// Most restore attempts per minute, counted across runs, so retrying by hand doesn't trip
+    /// Blizzard's abuse protection and lock the account. 0 turns the limit off.
+    #[arg(long, env = "BNET_RESTORE_LIMIT", default_value_t = 10)]
+    pub restore_limit: u32,
+
+    /// File that remembers recent restore attempts for --restore-limit, which without one only
+    /// counts those of this run. Defaults to bnet-auth-export/restore-attempts in the user's
+    /// state directory on the command line, and to none in the library.
+    #[arg(long, env = "BNET_RESTORE_ATTEMPTS_FILE")]
+    pub restore_attempts_file: Option<PathBuf>,
+
+    /// Number of times to retry a request after a 5xx, timeout or dropped connection.
+    #[arg(long, env = "BNET_RETRIES", default_value_t = 3)]
+    pub retries: u32,
+
+    /// Seconds to wait for a connection to be established.
+    #[arg(long, env = "BNET_CONNECT_TIMEOUT", default_value_t = 10)]
+    pub connect_timeout: u64,
+
+    /// Seconds to wait for a whole request to complete.
+    #[arg(long, env = "BNET_TIMEOUT", default_value_t = 30)]
+    pub timeout: u64,
+
+    /// Proxy URL for all requests, e.g. http://proxy.example:8080 or socks5h://127.0.0.1:9050.
+    ///
+    /// Without this flag the standard HTTP_PROXY/HTTPS_PROXY/NO_PROXY variables are honored.
+    #[arg(long, env = "BNET_PROXY")]
+    pub proxy: Option<String>,
+
+    /// PEM file with extra root certificates to trust, e.g. a corporate CA.
+    #[arg(long, env = "BNET_CA_BUNDLE")]
+    pub ca_bundle: Option<PathBuf>,
+
+    /// Disable TLS certificate verification. Anyone on the network path can read your secrets.
+    #[arg(long)]
+    pub insecure: bool,
+
+    /// Write a redacted transcript of every request and response to this file.
+    #[arg(long, env = "BNET_DEBUG_DUMP")]
+    pub debug_dump: Option<PathBuf>,
+
+    /// Append what was done to this file: operations, serials, endpoints and HTTP statuses,
+    /// never secrets.
+    #[arg(long, env = "BNET_AUDIT_LOG")]
+    pub audit_log: Option<PathBuf>,
+}
+
+/// The same defaults as the command line.
+impl Default for ApiOptions {
+    fn default() -> Self {
+        Self {
+            client_id: CLIENT_ID.to_owned(),
+            region: None,
+            sso_url: None,
+            oauth_url: None,
+            auth_base_url: None,
+            emulate_mobile_app: Vec::new(),
+            mobile_user_agent: MOBILE_USER_AGENT.to_owned(),
+            restore_limit: 10,
+            restore_attempts_file: None,
+            retries: 3,
+            connect_timeout: 10,
+            timeout: 30,
+            proxy: None,
+            ca_bundle: None,
+            insecure: false,
+            debug_dump: None,
+            audit_log: None,
+        }
+    }
+}
+
+pub(crate) const SSO_EXCHANGE: &str = "SSO token exchange";
+pub(crate) const CODE_EXCHANGE: &str = "authorization code exchange";
+pub(crate) const RESTORE: &str = "restore request";
+pub(crate) const USER_INFO: &str = "user info request";
+pub(crate) const DEVICE_LIST: &str = "device list request";
+pub(crate) const ATTACH: &str = "attach request";
+pub(crate) const DETACH: &str = "detach request";
+
+// The `tracing` span an API call runs in. Like the audit log it has the endpoint without its
+// query, which can carry the session token, and the status is recorded once there is one.
+pub(crate) fn request_span(
+    what: &'static str,
+    request_id: &str,
+    request: Option<(&Method, &Url)>,
+) -> tracing::Span {
+    let endpoint = request.map(|(_, url)| {
+        let mut endpoint = url.clone();
+        endpoint.set_query(None);
+        endpoint.set_fragment(None);
+        endpoint.to_string()
+    });
+    tracing::info_span!(
+        "request",
+        what,
+        request_id,
+        method = request.map(|(method, _)| method.as_str()),
+        endpoint,
+        status = tracing::field::Empty,
+    )
+}
+
+// How an API call ended, as an event in its span.
+pub(crate) fn trace_outcome(result: &Result<serde_json::Value>) {
+    match result {
+        Ok(_) => tracing::info!("request succeeded"),
+        Err(err) => tracing::warn!(kind = err.kind(), "{err}"),
+    }
+}
+
+// Check the response status and decode the JSON body. `request` names the request in errors.
+pub(crate) fn parse_json_response(
+    status: StatusCode,
+    body: &[u8],
+    what: &'static str,
+    request: String,
+) -> Result<serde_json::Value> {
+    if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+        return Err(Error::TokenExpired {
+            what,
+            status,
+            request,
+        });
+    }
+    if !status.is_success() {
+        let message = format!("{}\n({request})", diagnose(what, status, body));
+        return Err(match what {
+            _ if status == StatusCode::TOO_MANY_REQUESTS => Error::RateLimited { what, message },
+            SSO_EXCHANGE | CODE_EXCHANGE => Error::SsoFailed { status, message },
+            RESTORE => Error::RestoreRejected { status, message },
+            _ => Error::Api {
+                what,
+                status,
+                message,
+            },
+        });
+    }
+
+    if status == StatusCode::NO_CONTENT {
+        return Ok(serde_json::Value::Null);
+    }
+    serde_json::from_slice(body).map_err(|err| Error::Parse {
+        what: format!("{what} response"),
+        detail: err.to_string(),
+    })
+}
+
+// A required string field of a decoded response.
+fn required_field(parsed: &serde_json::Value, what: &str, field: &str) -> Result<String> {
+    parsed
+        .get(field)
+        .and_then(|value| value.as_str())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_owned)
+        .ok_or_else(|| Error::Parse {
+            what: format!("{what} response"),
+            detail: format!("missing {field}"),
+        })
+}
+
+pub(crate) fn access_token(parsed: &serde_json::Value, what: &str) -> Result<Zeroizing<String>> {
+    required_field(parsed, what, "access_token").map(Zeroizing::new)
+}
+
+pub(crate) fn device_secret_from(parsed: &serde_json::Value) -> Result<Zeroizing<String>> {
+    required_field(parsed, RESTORE, "deviceSecret").map(Zeroizing::new)
+}
+
+// The device list is either a bare array or `{"devices": [...]}`.
+pub(crate) fn devices_from(parsed: &serde_json::Value) -> Result<Vec<Device>> {
+    let devices = parsed
+        .as_array()
+        .or_else(|| parsed.get("devices").and_then(|devices| devices.as_array()))
+        .ok_or_else(|| Error::Parse {
+            what: format!("{DEVICE_LIST} response"),
+            detail: "missing devices".to_owned(),
+        })?;
+    devices
+        .iter()
+        .map(|device| {
+            let text = |field: &str| match device.get(field)? {
+                serde_json::Value::String(value) => Some(value.trim().to_owned()),
+                serde_json::Value::Number(value) => Some(value.to_string()),
+                _ => None,
+            };
+            Ok(Device {
+                serial: required_field(device, DEVICE_LIST, "serial")?,
+                kind: text("type"),
+                attached_at: text("attachedAt"),
+                restore_code: text("restoreCode"),
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn new_authenticator_from(parsed: &serde_json::Value) -> Result<NewAuthenticator> {
+    Ok(NewAuthenticator {
+        serial: required_field(parsed, ATTACH, "serial")?,
+        restore_code: required_field(parsed, ATTACH, "restoreCode")?,
+        device_secret: Zeroizing::new(required_field(parsed, ATTACH, "deviceSecret")?),
+    })
+}
+
+pub(crate) fn battletag_from(parsed: &serde_json::Value) -> Result<String> {
+    required_field(parsed, USER_INFO, "battletag")
+}
+
+pub(crate) fn missing_date() -> Error {
+    Error::Parse {
+        what: "clock check response".to_owned(),
+        detail: "no Date header".to_owned(),
+    }
+}
+
+pub(crate) fn default_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(USER_AGENT, HeaderValue::from_static("bnet-auth-export/0.1"));
+    headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+    headers
+}
+
+pub(crate) const FORM_CONTENT_TYPE: HeaderValue =
+    HeaderValue::from_static("application/x-www-form-urlencoded; charset=utf-8");
+
+// Read the CA bundle once, for either client builder.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn load_ca_bundle(options: &ApiOptions) -> Result<Vec<Certificate>> {
+    let Some(path) = &options.ca_bundle else {
+        return Ok(Vec::new());
+    };
+    let pem = std::fs::read(path).map_err(|source| Error::Io {
+        path: path.clone(),
+        source,
+    })?;
+    Certificate::from_pem_bundle(&pem).map_err(|err| Error::Parse {
+        what: format!("CA bundle {}", path.display()),
+        detail: err.to_string(),
+    })
+}
+
+// Apply the connection options to a blocking or async `ClientBuilder`, whose methods match
+// but which share no trait.
+#[cfg(not(target_arch = "wasm32"))]
+macro_rules! configure_client {
+    ($builder:expr, $options:expr) => {{
+        let options: &$crate::api::ApiOptions = $options;
+        let mut builder = $builder
+            .default_headers($crate::api::default_headers())
+            .connect_timeout(std::time::Duration::from_secs(options.connect_timeout))
+            .timeout(std::time::Duration::from_secs(options.timeout));
+
+        #[cfg(feature = "rustls")]
+        {
+            builder = builder.use_rustls_tls();
+        }
+
+        if let Some(proxy) = &options.proxy {
+            let proxy = reqwest::Proxy::all(proxy).map_err(|err| {
+                $crate::error::Error::InvalidInput(format!("invalid proxy URL {proxy}: {err}"))
+            })?;
+            builder = builder.proxy(proxy);
+        }
+
+        for certificate in $crate::api::load_ca_bundle(options)? {
+            builder = builder.add_root_certificate(certificate);
+        }
+
+        if options.insecure {
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+
+        builder
+            .build()
+            .map_err(|source| $crate::error::Error::Network {
+                what: "HTTP client setup",
+                request: None,
+                source,
+            })?
+    }};
+}
+#[cfg(all(feature = "tokio", not(target_arch = "wasm32")))]
+pub(crate) use configure_client;
+
+// Where each request goes, shared by the blocking and async clients.
+pub(crate) struct Endpoints {
+    pub(crate) client_id: String,
+    region: Option<Region>,
+    sso_url: Option<String>,
+    oauth_url: Option<String>,
+    auth_base_url: Option<String>,
+    emulate_mobile_app: Vec<AppRequest>,
+    mobile_user_agent: HeaderValue,
+    // The region of the first serial checked, for when neither the options nor the session
+    // token say which deployment to use.
+    serial_region: Mutex<Option<Region>>,
+    // The region of the last session token exchanged, which the bearer token is then valid in.
+    token_region: Mutex<Option<Region>>,
+}
+
+impl Endpoints {
+    pub(crate) fn new(options: &ApiOptions) -> Result<Self> {
+        let mobile_user_agent = HeaderValue::from_str(&options.mobile_user_agent)
+            .map_err(|err| Error::InvalidInput(format!("invalid mobile User-Agent: {err}")))?;
+        Ok(Self {
+            client_id: options.client_id.clone(),
+            region: options.region,
+            sso_url: options.sso_url.clone(),
+            oauth_url: options
+                .oauth_url
+                .as_ref()
+                .map(|url| url.trim_end_matches('/').to_owned()),
+            auth_base_url: options
+                .auth_base_url
+                .as_ref()
+                .map(|url| url.trim_end_matches('/').to_owned()),
+            emulate_mobile_app: options.emulate_mobile_app.clone(),
+            mobile_user_agent,
+            serial_region: Mutex::new(None),
+            token_region: Mutex::new(None),
+        })
+    }
+
+    // Headers that replace the defaults for one kind of request, to send it as the mobile app.
+    pub(crate) fn request_headers(&self, what: &str) -> HeaderMap {
+        let request = match what {
+            SSO_EXCHANGE => AppRequest::Sso,
+            CODE_EXCHANGE => AppRequest::Oauth,
+            RESTORE => AppRequest::Restore,
+            USER_INFO => AppRequest::UserInfo,
+            DEVICE_LIST => AppRequest::Devices,
+            ATTACH => AppRequest::Attach,
+            DETACH => AppRequest::Detach,
+            _ => return HeaderMap::new(),
+        };
+        let mut headers = HeaderMap::new();
+        if self
+            .emulate_mobile_app
+            .iter()
+            .any(|emulated| *emulated == AppRequest::All || *emulated == request)
+        {
+            headers.insert(USER_AGENT, self.mobile_user_agent.clone());
+        }
+        headers
+    }
+
+    // A serial's region wins over --region, which can only have been wrong about it, and is
+    // used when nothing else says. A region from the session token, or else from the last one
+    // exchanged, is kept, since it says which OAuth host issued the token.
+    pub(crate) fn region_for(&self, session_token: Option<&str>) -> Region {
+        let serial_region = *self.serial_region();
+        match (self.region, self.token_region(session_token)) {
+            (Some(region), _) => serial_region.unwrap_or(region),
+            (None, Some(token_region)) => token_region,
+            (None, None) => serial_region.unwrap_or_default(),
+        }
+    }
+
+    // The authenticator API is the same for every region, and only China has an OAuth host of
+    // its own, so a serial from another region than the one in use can still be restored. Its
+    // region is used from then on, and returned when that changes the region in use, for the
+    // caller to point out.
+    pub(crate) fn check_serial_region(
+        &self,
+        session_token: Option<&str>,
+        serial: &str,
+    ) -> Result<Option<Region>> {
+        let Some(serial_region) = Region::from_serial(serial) else {
+            return Ok(None);
+        };
+        if self.region.is_none() && self.token_region(session_token).is_some() {
+            return Ok(None);
+        }
+        let before = self.region_for(session_token);
+        *self.serial_region() = Some(serial_region);
+        Ok((before != serial_region).then_some(serial_region))
+    }
+
+    // The region `session_token` names, or else the last exchanged one's.
+    fn token_region(&self, session_token: Option<&str>) -> Option<Region> {
+        session_token
+            .and_then(Region::from_session_token)
+            .or_else(|| {
+                *self
+                    .token_region
+                    .lock()
+                    .unwrap_or_else(|err| err.into_inner())
+            })
+    }
+
+    pub(crate) fn record_session_token(&self, session_token: &str) {
+        if let Some(region) = Region::from_session_token(session_token) {
+            *self
+                .token_region
+                .lock()
+                .unwrap_or_else(|err| err.into_inner()) = Some(region);
+        }
+    }
+
+    fn serial_region(&self) -> std::sync::MutexGuard<'_, Option<Region>> {
+        self.serial_region
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+
+    pub(crate) fn oauth_url(&self) -> String {
+        match &self.oauth_url {
+            Some(url) => url.clone(),
+            None => self.region_for(None).oauth_url().to_owned(),
+        }
+    }
+
+    pub(crate) fn sso_url(&self, session_token: &str) -> String {
+        match &self.sso_url {
+            Some(url) => url.clone(),
+            None => self.region_for(Some(session_token)).sso_url(),
+        }
+    }
+
+    // Made with the bearer token of the account, so in the region of its session token.
+    pub(crate) fn devices_url(&self) -> String {
+        let base_url = match &self.auth_base_url {
+            Some(url) => url.as_str(),
+            None => self.region_for(None).auth_base_url(),
+        };
+        format!("{base_url}/devices")
+    }
+
+    // New authenticators are created on the collection itself.
+    pub(crate) fn attach_url(&self) -> String {
+        match &self.auth_base_url {
+            Some(url) => url.clone(),
+            None => self.region_for(None).auth_base_url().to_owned(),
+        }
+    }
+
+    pub(crate) fn device_url(&self, serial: &str) -> String {
+        let base_url = match &self.auth_base_url {
+            Some(url) => url.as_str(),
+            None => Region::from_serial(serial)
+                .or(self.region)
+                .unwrap_or_default()
+                .auth_base_url(),
+        };
+        format!("{base_url}/device")
+    }
+
+    pub(crate) fn authorize_url(
+        &self,
+        redirect_uri: &str,
+        code_challenge: &str,
+        state: &str,
+    ) -> String {
+        let query = serde_urlencoded::to_string([
+            ("response_type", "code"),
+            ("client_id", self.client_id.as_str()),
+            ("redirect_uri", redirect_uri),
+            ("scope", "auth.authenticator"),
+            ("state", state),
+            ("code_challenge", code_challenge),
+            ("code_challenge_method", "S256"),
+        ])
+        .unwrap_or_default();
+        format!("{}/authorize?{query}", self.oauth_url())
+    }
+
+    pub(crate) fn sso_form<'a>(&'a self, session_token: &'a str) -> [(&'a str, &'a str); 4] {
+        [
+            ("client_id", self.client_id.as_str()),
+            ("grant_type", "client_sso"),
+            ("scope", "auth.authenticator"),
+            ("token", session_token),
+        ]
+    }
+
+    pub(crate) fn authorization_code_form<'a>(
+        &'a self,
+        code: &'a str,
+        code_verifier: &'a str,
+        redirect_uri: &'a str,
+    ) -> [(&'a str, &'a str); 5] {
+        [
+            ("grant_type", "authorization_code"),
+            ("client_id", self.client_id.as_str()),
+            ("code", code),
+            ("code_verifier", code_verifier),
+            ("redirect_uri", redirect_uri),
+        ]
+    }
+}
+
+pub(crate) fn device_body(serial: &str, restore_code: &str) -> serde_json::Value {
+    serde_json::json!({
+        "serial": serial,
+        "restoreCode": restore_code,
+    })
+}
+
+// The server clock offset from the last response seen.
+#[derive(Default)]
+pub(crate) struct ClockOffset(Mutex<Option<i64>>);
+
+impl ClockOffset {
+    pub(crate) fn get(&self) -> Option<i64> {
+        *self.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    pub(crate) fn record(&self, headers: &HeaderMap) {
+        if let Some(offset) = clock::offset_from_headers(headers) {
+            *self.0.lock().unwrap_or_else(|err| err.into_inner()) = Some(offset);
+        }
+    }
+}
+
+/// Blocking client for the Battle.net OAuth and authenticator APIs. Not available on WebAssembly.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct Api {
+    client: Client,
+    endpoints: Endpoints,
+    retry: RetryPolicy,
+    restore_limit: AttemptLimit,
+    transcript: Option<Transcript>,
+    audit_log: Option<AuditLog>,
+    clock_offset: ClockOffset,
+}
+
+/// A blocking HTTP client with the timeouts, proxy, CA bundle and TLS settings of `options`, for
+/// requests outside the Battle.net APIs.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn http_client(options: &ApiOptions) -> Result<Client> {
+    Ok(configure_client!(Client::builder(), options))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Api {
+    /// Build the HTTP client from the given options.
+    pub fn new(options: ApiOptions) -> Result<Self> {
+        let client = configure_client!(Client::builder(), &options);
+
+        let transcript = options
+            .debug_dump
+            .as_deref()
+            .map(Transcript::create)
+            .transpose()?;
+        let audit_log = options
+            .audit_log
+            .as_deref()
+            .map(AuditLog::open)
+            .transpose()?;
+
+        Ok(Self {
+            client,
+            endpoints: Endpoints::new(&options)?,
+            retry: RetryPolicy {
+                retries: options.retries,
+            },
+            restore_limit: AttemptLimit::new(
+                options.restore_limit,
+                options.restore_attempts_file.clone(),
+            ),
+            transcript,
+            audit_log,
+            clock_offset: ClockOffset::default(),
+        })
+    }
+
+    /// How far the server's clock is ahead of ours, from the last response seen.
+    pub fn clock_offset(&self) -> Option<i64> {
+        self.clock_offset.get()
+    }
+
+    /// Ask Battle.net for the time, for when no other request has been made.
+    pub fn check_clock(&self) -> Result<i64> {
+        let url = self.endpoints.oauth_url();
+        // Any status will do, only the Date header matters.
+        let response = self
+            .client
+            .head(&url)
+            .send()
+            .map_err(|source| Error::Network {
+                what: "clock check",
+                request: None,
+                source,
+            })?;
+        self.clock_offset.record(response.headers());
+
+        self.clock_offset().ok_or_else(missing_date)
+    }
+
+    /// The region given on the command line, or the one the session token belongs to.
+    pub fn region_for(&self, session_token: Option<&str>) -> Region {
+        self.endpoints.region_for(session_token)
+    }
+
+    /// Switch to the region of a normalized serial, over the one in the options, unless the
+    /// session token names a region. Returns the serial's region when it differs from the one
+    /// requests went to before.
+    pub fn check_serial_region(
+        &self,
+        session_token: Option<&str>,
+        serial: &str,
+    ) -> Result<Option<Region>> {
+        self.endpoints.check_serial_region(session_token, serial)
+    }
+
+    /// The browser URL that starts an authorization-code flow with PKCE.
+    pub fn authorize_url(&self, redirect_uri: &str, code_challenge: &str, state: &str) -> String {
+        self.endpoints
+            .authorize_url(redirect_uri, code_challenge, state)
+    }
+
+    /// Exchange an authorization code for an OAuth bearer token.
+    pub fn exchange_authorization_code(
+        &self,
+        code: &str,
+        code_verifier: &str,
+        redirect_uri: &str,
+    ) -> Result<Zeroizing<String>> {
+        let url = format!("{}/token", self.endpoints.oauth_url());
+        let form = self
+            .endpoints
+            .authorization_code_form(code, code_verifier, redirect_uri);
+
+        let parsed = self.send_json(CODE_EXCHANGE, || self.client.post(&url).form(&form))?;
+        access_token(&parsed, "token response")
+    }
+
+    /// Look up the BattleTag of the account a bearer token belongs to.
+    pub fn battletag(&self, bearer_token: &str) -> Result<String> {
+        let url = format!("{}/userinfo", self.endpoints.oauth_url());
+
+        let parsed = self.send_json(USER_INFO, || {
+            self.client
+                .get(&url)
+                .header(AUTHORIZATION, format!("Bearer {bearer_token}"))
+        })?;
+        battletag_from(&parsed)
+    }
+
+    /// List the authenticators attached to the account a bearer token belongs to.
+    pub fn devices(&self, bearer_token: &str) -> Result<Vec<Device>> {
+        let url = self.endpoints.devices_url();
+
+        let parsed = self.send_json(DEVICE_LIST, || {
+            self.client
+                .get(&url)
+                .header(AUTHORIZATION, format!("Bearer {bearer_token}"))
+        })?;
+        devices_from(&parsed)
+    }
+
+    // Send a request with retries, record it in the transcript and audit log and decode the
+    // JSON reply.
+    fn send_json(
+        &self,
+        what: &'static str,
+        build: impl Fn() -> RequestBuilder,
+    ) -> Result<serde_json::Value> {
+        let build = || build().headers(self.endpoints.request_headers(what));
+        let request_id = correlation::next_request_id();
+        let request = build().build().ok();
+        let span = request_span(
+            what,
+            &request_id,
+            request
+                .as_ref()
+                .map(|request| (request.method(), request.url())),
+        );
+        let _entered = span.enter();
+        let result = self.round_trip(what, build, &span, &request_id, request.as_ref());
+        trace_outcome(&result);
+        result
+    }
+
+    fn round_trip(
+        &self,
+        what: &'static str,
+        build: impl Fn() -> RequestBuilder,
+        span: &tracing::Span,
+        request_id: &str,
+        request: Option<&reqwest::blocking::Request>,
+    ) -> Result<serde_json::Value> {
+        if let Some(transcript) = &self.transcript
+            && let Some(request) = &request
+        {
+            transcript.request(request_id, request);
+        }
+
+        // Every restore that is sent counts against the limit, retries included.
+        let response = self.retry.send(|| {
+            if what == RESTORE {
+                self.restore_limit.acquire();
+            }
+            build()
+        });
+        if let Some(audit_log) = &self.audit_log
+            && let Some(request) = &request
+        {
+            let response = response.as_ref().ok();
+            audit_log.request(
+                what,
+                request_id,
+                request.method(),
+                request.url(),
+                response.map(|response| response.status()),
+                response.map(|response| response.headers()),
+            );
+        }
+        let network_error = |source| Error::Network {
+            what,
+            request: Some(correlation::describe(request_id, None)),
+            source,
+        };
+        let response = response.map_err(network_error)?;
+
+        let status = response.status();
+        span.record("status", status.as_u16());
+        let headers = response.headers().clone();
+        self.clock_offset.record(&headers);
+        let body = response.bytes().map_err(network_error)?;
+
+        if let Some(transcript) = &self.transcript {
+            transcript.response(status, &headers, &body);
+        }
+
+        let request = correlation::describe(request_id, Some(&headers));
+        parse_json_response(status, &body, what, request)
+    }
+
+    /// Exchange the user session token for an OAuth bearer token.
+    pub fn exchange_session_token(&self, session_token: &str) -> Result<Zeroizing<String>> {
+        let sso_url = self.endpoints.sso_url(session_token);
+        let form = self.endpoints.sso_form(session_token);
+        self.endpoints.record_session_token(session_token);
+
+        let parsed = self.send_json(SSO_EXCHANGE, || {
+            self.client
+                .post(&sso_url)
+                .header(CONTENT_TYPE, FORM_CONTENT_TYPE)
+                .form(&form)
+        })?;
+        access_token(&parsed, "SSO response")
+    }
+
+    /// Restore the authenticator and return the device secret using a bearer token. Waits
+    /// before each attempt, retries included, when the options' `restore_limit` has been
+    /// reached.
+    pub fn device_secret(
+        &self,
+        bearer_token: &str,
+        serial: &str,
+        restore_code: &str,
+    ) -> Result<Zeroizing<String>> {
+        let url = self.endpoints.device_url(serial);
+        let body = device_body(serial, restore_code);
+
+        let parsed = self.send_json(RESTORE, || {
+            self.client
+                .post(&url)
+                .header(AUTHORIZATION, format!("Bearer {bearer_token}"))
+                .json(&body)
+        })?;
+        device_secret_from(&parsed)
+    }
+
+    /// Attach a new authenticator to the account a bearer token belongs to, alongside any it
+    /// already has, and return its serial, restore code and device secret.
+    pub fn attach(&self, bearer_token: &str) -> Result<NewAuthenticator> {
+        let url = self.endpoints.attach_url();
+
+        let parsed = self.send_json(ATTACH, || {
+            self.client
+                .post(&url)
+                .header(AUTHORIZATION, format!("Bearer {bearer_token}"))
+                .json(&serde_json::json!({}))
+        })?;
+        new_authenticator_from(&parsed)
+    }
+
+    /// Detach an authenticator from the account, proven with its restore code. Codes from it
+    /// stop working straight away.
+    pub fn detach(&self, bearer_token: &str, serial: &str, restore_code: &str) -> Result<()> {
+        let url = self.endpoints.device_url(serial);
+        let body = device_body(serial, restore_code);
+
+        self.send_json(DETACH, || {
+            self.client
+                .delete(&url)
+                .header(AUTHORIZATION, format!("Bearer {bearer_token}"))
+                .json(&body)
+        })?;
+        Ok(())
+    }
+}
@@ -0,0 +1,309 @@
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use reqwest::{Client, RequestBuilder};
+use tracing::Instrument;
+use zeroize::Zeroizing;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::api::configure_client;
+use crate::api::{
+    ATTACH, ApiOptions, CODE_EXCHANGE, ClockOffset, DETACH, DEVICE_LIST, Device, Endpoints,
+    FORM_CONTENT_TYPE, NewAuthenticator, RESTORE, SSO_EXCHANGE, USER_INFO, access_token,
+    battletag_from, device_body, device_secret_from, devices_from, missing_date,
+    new_authenticator_from, parse_json_response, request_span, trace_outcome,
+};
+use crate::audit::AuditLog;
+use crate::correlation;
+use crate::error::{Error, Result};
+use crate::region::Region;
+use crate::retry::RetryPolicy;
+use crate::transcript::Transcript;
+
+/// Async client for the Battle.net OAuth and authenticator APIs, for use inside tokio
+/// applications or in the browser. It mirrors [`crate::api::Api`] method for method.
+pub struct AsyncApi {
+    client: Client,
+    endpoints: Endpoints,
+    retry: RetryPolicy,
+    transcript: Option<Transcript>,
+    audit_log: Option<AuditLog>,
+    clock_offset: ClockOffset,
+}
+
+impl AsyncApi {
+    /// Build the HTTP client from the given options.
+    ///
+    /// On WebAssembly the browser handles timeouts, proxies and certificates, so those options
+    /// are ignored.
+    pub fn new(options: ApiOptions) -> Result<Self> {
+        #[cfg(not(target_arch = "wasm32"))]
+        let client = configure_client!(Client::builder(), &options);
+        #[cfg(target_arch = "wasm32")]
+        let client = Client::builder()
+            .default_headers(crate::api::default_headers())
+            .build()
+            .map_err(|source| Error::Network {
+                what: "HTTP client setup",
+                request: None,
+                source,
+            })?;
+
+        let transcript = options
+            .debug_dump
+            .as_deref()
+            .map(Transcript::create)
+            .transpose()?;
+        let audit_log = options
+            .audit_log
+            .as_deref()
+            .map(AuditLog::open)
+            .transpose()?;
+
+        Ok(Self {
+            client,
+            endpoints: Endpoints::new(&options)?,
+            retry: RetryPolicy {
+                retries: options.retries,
+            },
+            transcript,
+            audit_log,
+            clock_offset: ClockOffset::default(),
+        })
+    }
+
+    /// How far the server's clock is ahead of ours, from the last response seen.
+    pub fn clock_offset(&self) -> Option<i64> {
+        self.clock_offset.get()
+    }
+
+    /// Ask Battle.net for the time, for when no other request has been made.
+    pub async fn check_clock(&self) -> Result<i64> {
+        let url = self.endpoints.oauth_url();
+        // Any status will do, only the Date header matters.
+        let response = self
+            .client
+            .head(&url)
+            .send()
+            .await
+            .map_err(|source| Error::Network {
+                what: "clock check",
+                request: None,
+                source,
+            })?;
+        self.clock_offset.record(response.headers());
+
+        self.clock_offset().ok_or_else(missing_date)
+    }
+
+    /// The region given in the options, or the one the session token belongs to.
+    pub fn region_for(&self, session_token: Option<&str>) -> Region {
+        self.endpoints.region_for(session_token)
+    }
+
+    /// As [`Api::check_serial_region`](crate::api::Api::check_serial_region).
+    pub fn check_serial_region(
+        &self,
+        session_token: Option<&str>,
+        serial: &str,
+    ) -> Result<Option<Region>> {
+        self.endpoints.check_serial_region(session_token, serial)
+    }
+
+    /// The browser URL that starts an authorization-code flow with PKCE.
+    pub fn authorize_url(&self, redirect_uri: &str, code_challenge: &str, state: &str) -> String {
+        self.endpoints
+            .authorize_url(redirect_uri, code_challenge, state)
+    }
+
+    /// Exchange an authorization code for an OAuth bearer token.
+    pub async fn exchange_authorization_code(
+        &self,
+        code: &str,
+        code_verifier: &str,
+        redirect_uri: &str,
+    ) -> Result<Zeroizing<String>> {
+        let url = format!("{}/token", self.endpoints.oauth_url());
+        let form = self
+            .endpoints
+            .authorization_code_form(code, code_verifier, redirect_uri);
+
+        let parsed = self
+            .send_json(CODE_EXCHANGE, || self.client.post(&url).form(&form))
+            .await?;
+        access_token(&parsed, "token response")
+    }
+
+    /// Look up the BattleTag of the account a bearer token belongs to.
+    pub async fn battletag(&self, bearer_token: &str) -> Result<String> {
+        let url = format!("{}/userinfo", self.endpoints.oauth_url());
+
+        let parsed = self
+            .send_json(USER_INFO, || {
+                self.client
+                    .get(&url)
+                    .header(AUTHORIZATION, format!("Bearer {bearer_token}"))
+            })
+            .await?;
+        battletag_from(&parsed)
+    }
+
+    /// List the authenticators attached to the account a bearer token belongs to.
+    pub async fn devices(&self, bearer_token: &str) -> Result<Vec<Device>> {
+        let url = self.endpoints.devices_url();
+
+        let parsed = self
+            .send_json(DEVICE_LIST, || {
+                self.client
+                    .get(&url)
+                    .header(AUTHORIZATION, format!("Bearer {bearer_token}"))
+            })
+            .await?;
+        devices_from(&parsed)
+    }
+
+    // Send a request with retries, record it in the transcript and audit log and decode the
+    // JSON reply.
+    async fn send_json(
+        &self,
+        what: &'static str,
+        build: impl Fn() -> RequestBuilder,
+    ) -> Result<serde_json::Value> {
+        let build = || build().headers(self.endpoints.request_headers(what));
+        let request_id = correlation::next_request_id();
+        let request = build().build().ok();
+        let span = request_span(
+            what,
+            &request_id,
+            request
+                .as_ref()
+                .map(|request| (request.method(), request.url())),
+        );
+        async {
+            let result = self
+                .round_trip(what, build, &span, &request_id, request.as_ref())
+                .await;
+            trace_outcome(&result);
+            result
+        }
+        .instrument(span.clone())
+        .await
+    }
+
+    async fn round_trip(
+        &self,
+        what: &'static str,
+        build: impl Fn() -> RequestBuilder,
+        span: &tracing::Span,
+        request_id: &str,
+        request: Option<&reqwest::Request>,
+    ) -> Result<serde_json::Value> {
+        if let Some(transcript) = &self.transcript
+            && let Some(request) = request
+        {
+            transcript.async_request(request_id, request);
+        }
+
+        let response = self.retry.send_async(&build).await;
+        if let Some(audit_log) = &self.audit_log
+            && let Some(request) = &request
+        {
+            let response = response.as_ref().ok();
+            audit_log.request(
+                what,
+                request_id,
+                request.method(),
+                request.url(),
+                response.map(|response| response.status()),
+                response.map(|response| response.headers()),
+            );
+        }
+        let network_error = |source| Error::Network {
+            what,
+            request: Some(correlation::describe(request_id, None)),
+            source,
+        };
+        let response = response.map_err(network_error)?;
+
+        let status = response.status();
+        span.record("status", status.as_u16());
+        let headers = response.headers().clone();
+        self.clock_offset.record(&headers);
+        let body = response.bytes().await.map_err(network_error)?;
+
+        if let Some(transcript) = &self.transcript {
+            transcript.response(status, &headers, &body);
+        }
+
+        let request = correlation::describe(request_id, Some(&headers));
+        parse_json_response(status, &body, what, request)
+    }
+
+    /// Exchange the user session token for an OAuth bearer token.
+    pub async fn exchange_session_token(&self, session_token: &str) -> Result<Zeroizing<String>> {
+        let sso_url = self.endpoints.sso_url(session_token);
+        let form = self.endpoints.sso_form(session_token);
+        self.endpoints.record_session_token(session_token);
+
+        let parsed = self
+            .send_json(SSO_EXCHANGE, || {
+                self.client
+                    .post(&sso_url)
+                    .header(CONTENT_TYPE, FORM_CONTENT_TYPE)
+                    .form(&form)
+            })
+            .await?;
+        access_token(&parsed, "SSO response")
+    }
+
+    /// Restore the authenticator and return the device secret using a bearer token.
+    pub async fn device_secret(
+        &self,
+        bearer_token: &str,
+        serial: &str,
+        restore_code: &str,
+    ) -> Result<Zeroizing<String>> {
+        let url = self.endpoints.device_url(serial);
+        let body = device_body(serial, restore_code);
+
+        let parsed = self
+            .send_json(RESTORE, || {
+                self.client
+                    .post(&url)
+                    .header(AUTHORIZATION, format!("Bearer {bearer_token}"))
+                    .json(&body)
+            })
+            .await?;
+        device_secret_from(&parsed)
+    }
+
+    /// Attach a new authenticator to the account a bearer token belongs to, alongside any it
+    /// already has, and return its serial, restore code and device secret.
+    pub async fn attach(&self, bearer_token: &str) -> Result<NewAuthenticator> {
+        let url = self.endpoints.attach_url();
+
+        let parsed = self
+            .send_json(ATTACH, || {
+                self.client
+                    .post(&url)
+                    .header(AUTHORIZATION, format!("Bearer {bearer_token}"))
+                    .json(&serde_json::json!({}))
+            })
+            .await?;
+        new_authenticator_from(&parsed)
+    }
+
+    /// Detach an authenticator from the account, proven with its restore code. Codes from it
+    /// stop working straight away.
+    pub async fn detach(&self, bearer_token: &str, serial: &str, restore_code: &str) -> Result<()> {
+        let url = self.endpoints.device_url(serial);
+        let body = device_body(serial, restore_code);
+
+        self.send_json(DETACH, || {
+            self.client
+                .delete(&url)
+                .header(AUTHORIZATION, format!("Bearer {bearer_token}"))
+                .json(&body)
+        })
+        .await?;
+        Ok(())
+    }
+}
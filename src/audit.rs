@@ -0,0 +1,93 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+use reqwest::header::HeaderMap;
+use reqwest::{Method, StatusCode, Url};
+use serde_json::{Value, json};
+use web_time::{SystemTime, UNIX_EPOCH};
+
+use crate::clock;
+use crate::correlation;
+use crate::error::{Error, Result};
+use crate::secure_file;
+
+/// An append-only log of what was done and when, one JSON object per line.
+///
+/// Entries hold operations, serials, endpoints and HTTP statuses, never tokens, restore codes or
+/// secrets, so the log can be shared when troubleshooting an account with Blizzard support.
+/// Every entry carries the run's ID, and requests their own ID and Blizzard's trace IDs.
+pub struct AuditLog(Mutex<File>);
+
+impl AuditLog {
+    /// Open the log for appending, creating it readable only by the user.
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = secure_file::private_options()
+            .append(true)
+            .open(path)
+            .map_err(|source| Error::Io {
+                path: path.to_owned(),
+                source,
+            })?;
+        Ok(Self(Mutex::new(file)))
+    }
+
+    /// Record an operation and how it ended: `ok`, or the kind of error it failed with.
+    pub fn operation(&self, operation: &str, serial: Option<&str>, outcome: &str) {
+        self.write(json!({
+            "event": "operation",
+            "operation": operation,
+            "serial": serial,
+            "outcome": outcome,
+        }));
+    }
+
+    // One API call: the endpoint without its query, and the status and response headers, or
+    // none when the request never got a response.
+    pub(crate) fn request(
+        &self,
+        what: &str,
+        id: &str,
+        method: &Method,
+        url: &Url,
+        status: Option<StatusCode>,
+        headers: Option<&HeaderMap>,
+    ) {
+        let mut endpoint = url.clone();
+        endpoint.set_query(None);
+        endpoint.set_fragment(None);
+        let trace: serde_json::Map<_, _> = headers
+            .map(correlation::trace_headers)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, value)| (name, Value::String(value)))
+            .collect();
+        self.write(json!({
+            "event": "request",
+            "what": what,
+            "requestId": id,
+            "method": method.as_str(),
+            "endpoint": endpoint.as_str(),
+            "status": status.map(|status| status.as_u16()),
+            "trace": trace,
+        }));
+    }
+
+    // Like the transcript, the log is best effort and must not abort the export.
+    fn write(&self, mut entry: Value) {
+        entry["time"] = Value::String(timestamp());
+        entry["runId"] = Value::String(correlation::run_id().to_owned());
+        if let Ok(mut file) = self.0.lock() {
+            let _ = writeln!(file, "{entry}");
+        }
+    }
+}
+
+// The current time in RFC 3339, UTC.
+fn timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    clock::utc_timestamp(secs)
+}
@@ -0,0 +1,519 @@
+// --batch: export every account listed in a CSV or TOML file in one run, each to its own files
+// or keyring entry, for anyone migrating more than a handful of authenticators.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+
+use anyhow::{Context, Result, bail};
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use bnet_auth_export::api::Api;
+use bnet_auth_export::memory::Locked;
+use bnet_auth_export::otpauth::{LabelOptions, OtpAuth};
+use bnet_auth_export::rate_limit::TokenBucket;
+use bnet_auth_export::{restore, token};
+
+use crate::checkpoint::Checkpoint;
+use crate::format::{ExportPayload, OutputOptions};
+use crate::source::{self, Credential, TokenOptions};
+use crate::timings;
+
+// One row of the CSV, or one [[account]] table of the TOML file. Accounts without a session
+// token use the shared one.
+#[derive(Deserialize)]
+struct Account {
+    serial: String,
+    restore_code: Zeroizing<String>,
+    #[serde(default)]
+    session_token: Option<Zeroizing<String>>,
+    #[serde(default)]
+    label: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TomlFile {
+    #[serde(default)]
+    session_token: Option<Zeroizing<String>>,
+    #[serde(default, rename = "account")]
+    accounts: Vec<Account>,
+}
+
+#[derive(Args)]
+pub struct BatchOptions {
+    /// Export every account listed in this CSV or TOML file, each to its own files.
+    ///
+    /// CSV files have a header row with serial, restore_code and optionally session_token and
+    /// label columns. Accounts without a session token share the one from the usual sources.
+    #[arg(long, conflicts_with = "serve_stdio")]
+    pub batch: Option<PathBuf>,
+
+    /// Continue a --batch run from its --checkpoint, skipping the accounts it already exported.
+    #[arg(long, requires = "batch", requires = "checkpoint")]
+    pub resume: bool,
+
+    /// How many --batch accounts to export at the same time.
+    #[arg(long, env = "BNET_CONCURRENCY", default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..=16))]
+    pub concurrency: u32,
+
+    /// Most requests to send per minute during --batch, to stay clear of Blizzard's rate limits.
+    #[arg(long, env = "BNET_RATE_LIMIT", default_value_t = 30, value_parser = clap::value_parser!(u32).range(1..))]
+    pub rate_limit: u32,
+
+    /// Print a JSON array with the outcome of every --batch account, and the export itself for
+    /// the ones that succeeded, on stdout.
+    #[arg(long, requires = "batch")]
+    pub json: bool,
+
+    /// Exit non-zero if any --batch account failed. By default that only happens when none of
+    /// them succeeded.
+    #[arg(long, requires = "batch")]
+    pub strict: bool,
+}
+
+// How one account went, for the summary and --json.
+struct Report {
+    index: usize,
+    serial: String,
+    outcome: Outcome,
+}
+
+enum Outcome {
+    // Holds the export for --json.
+    Exported(Option<OtpAuth>),
+    // Marked done in the checkpoint by an earlier run.
+    Skipped,
+    Failed(anyhow::Error),
+}
+
+// One element of the --json array.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonReport {
+    serial: String,
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<JsonError>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    export: Option<ExportPayload>,
+}
+
+#[derive(Serialize)]
+struct JsonError {
+    kind: &'static str,
+    message: String,
+}
+
+// An account that passed validation and still has to be exported.
+struct Job<'a> {
+    // Position in the input, for progress and the summary.
+    index: usize,
+    account: &'a Account,
+    serial: String,
+    restore_code: String,
+}
+
+pub fn run(
+    options: &BatchOptions,
+    path: &Path,
+    token: &TokenOptions,
+    api: &Api,
+    labels: &LabelOptions,
+    output: &OutputOptions,
+    checkpoint: Option<&Path>,
+) -> Result<()> {
+    if !options.json {
+        output.require_file_destination()?;
+    }
+    let data = Zeroizing::new(
+        fs::read(path).with_context(|| format!("failed to read {}", path.display()))?,
+    );
+    let (file_token, mut accounts) = if path.extension().is_some_and(|ext| ext == "toml") {
+        read_toml(&data)
+    } else {
+        read_csv(&data).map(|accounts| (None, accounts))
+    }
+    .with_context(|| format!("failed to parse {}", path.display()))?;
+    if accounts.is_empty() {
+        bail!("{} lists no accounts", path.display());
+    }
+    for account in &mut accounts {
+        if let Some(session_token) = &mut account.session_token {
+            *session_token = normalize_session_token(session_token);
+        }
+    }
+    let file_token = file_token.map(|session_token| normalize_session_token(&session_token));
+
+    let checkpoint = match checkpoint {
+        Some(checkpoint) if checkpoint.exists() && !options.resume => bail!(
+            "{} holds progress from an earlier run, pass --resume to continue it or delete it to start over",
+            checkpoint.display()
+        ),
+        Some(checkpoint) => Some(Checkpoint::open(checkpoint)?),
+        None => None,
+    };
+
+    // Check every account before contacting Battle.net, so typos show up straight away. Serials
+    // without a region prefix take the region of the account's own session token, the file's
+    // shared one, or --region, and serials with one must agree with --region.
+    let total = accounts.len();
+    let mut reports = Vec::new();
+    let mut jobs = Vec::new();
+    for (index, account) in accounts.iter().enumerate() {
+        let progress = format!("[{}/{total}]", index + 1);
+        let region_token = account.session_token.as_ref().or(file_token.as_ref());
+        let region_token = region_token.map(|token| token.as_str());
+        let checked = api
+            .region_for(region_token)
+            .normalize_serial(&account.serial)
+            .and_then(|serial| {
+                if let Some(region) = api.check_serial_region(region_token, &serial)? {
+                    eprintln!("{progress} Region: {region}, from the serial");
+                }
+                let restore_code = match restore::correct_restore_code(&account.restore_code) {
+                    Some(corrected) => {
+                        eprintln!(
+                            "{progress} {serial}: restore codes never contain I, L, O or S, using {corrected}"
+                        );
+                        corrected
+                    }
+                    None => restore::normalize_restore_code(&account.restore_code)?,
+                };
+                Ok((serial, restore_code))
+            });
+        match checked {
+            Ok((serial, _))
+                if checkpoint
+                    .as_ref()
+                    .is_some_and(|checkpoint| checkpoint.is_done(&serial)) =>
+            {
+                eprintln!("{progress} {serial}: already exported, skipped");
+                reports.push(Report {
+                    index,
+                    serial,
+                    outcome: Outcome::Skipped,
+                });
+            }
+            Ok((serial, restore_code)) => jobs.push(Job {
+                index,
+                account,
+                serial,
+                restore_code,
+            }),
+            Err(err) => {
+                eprintln!("{progress} {}: {err}", account.serial);
+                reports.push(Report {
+                    index,
+                    serial: account.serial.clone(),
+                    outcome: Outcome::Failed(err.into()),
+                });
+            }
+        }
+    }
+
+    // The credential for accounts without their own session token, asked for only if one of
+    // them is left to export. A bearer token saved in the checkpoint stands in for the shared
+    // session token's exchange.
+    let saved_bearer_token = checkpoint.as_ref().and_then(Checkpoint::bearer_token);
+    let mut bearer_tokens = Vec::new();
+    let shared = if jobs.iter().any(|job| job.account.session_token.is_none()) {
+        match (file_token, saved_bearer_token) {
+            (Some(session_token), Some(bearer_token)) => {
+                eprintln!("Using the bearer token saved in the checkpoint");
+                bearer_tokens.push((session_token.clone(), bearer_token));
+                Some(Credential::SessionToken(session_token))
+            }
+            (Some(session_token), None) => Some(Credential::SessionToken(session_token)),
+            (None, Some(bearer_token)) => {
+                eprintln!("Using the bearer token saved in the checkpoint");
+                Some(Credential::BearerToken(bearer_token))
+            }
+            (None, None) => Some(source::credential(token, api, false)?),
+        }
+    } else {
+        None
+    };
+
+    let batch = Batch {
+        api,
+        labels,
+        output,
+        keep_exports: options.json,
+        shared,
+        bearer_tokens: Mutex::new(bearer_tokens),
+        checkpoint: checkpoint.map(Mutex::new),
+        // Let as many requests out at once as there are workers, then settle to the rate.
+        limiter: TokenBucket::new(options.rate_limit, options.concurrency),
+    };
+    let next = AtomicUsize::new(0);
+    let reports = Mutex::new(reports);
+    thread::scope(|scope| {
+        for _ in 0..jobs.len().min(options.concurrency as usize) {
+            scope.spawn(|| {
+                while let Some(job) = jobs.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let progress = format!("[{}/{total}]", job.index + 1);
+                    let outcome = match batch.export(job) {
+                        Ok(export) => {
+                            eprintln!("{progress} {}: exported", job.serial);
+                            Outcome::Exported(export)
+                        }
+                        Err(err) => {
+                            eprintln!("{progress} {}: {err:#}", job.serial);
+                            Outcome::Failed(err)
+                        }
+                    };
+                    lock(&reports).push(Report {
+                        index: job.index,
+                        serial: job.serial.clone(),
+                        outcome,
+                    });
+                }
+            });
+        }
+    });
+    let mut reports = reports.into_inner().unwrap_or_else(|err| err.into_inner());
+    reports.sort_by_key(|report| report.index);
+    if options.json {
+        print_json(&reports)?;
+    }
+
+    let failed: Vec<_> = reports
+        .iter()
+        .filter(|report| matches!(report.outcome, Outcome::Failed(_)))
+        .map(|report| report.serial.as_str())
+        .collect();
+    let skipped = reports
+        .iter()
+        .filter(|report| matches!(report.outcome, Outcome::Skipped))
+        .count();
+    // Keep the checkpoint while anything failed, even before it got to the API, so fixing the
+    // file and passing --resume doesn't redo the rest.
+    if let Some(checkpoint) = batch.checkpoint
+        && failed.is_empty()
+    {
+        checkpoint
+            .into_inner()
+            .unwrap_or_else(|err| err.into_inner())
+            .finish()?;
+    }
+    if !failed.is_empty() {
+        let summary = format!(
+            "{} of {total} accounts failed: {}",
+            failed.len(),
+            failed.join(", ")
+        );
+        if options.strict || failed.len() == total {
+            bail!(summary);
+        }
+        eprintln!("warning: {summary}");
+        return Ok(());
+    }
+    if skipped > 0 {
+        eprintln!("Exported all {total} accounts, {skipped} of them in an earlier run");
+    } else {
+        eprintln!("Exported all {total} accounts");
+    }
+    Ok(())
+}
+
+fn print_json(reports: &[Report]) -> Result<()> {
+    let reports: Vec<_> = reports
+        .iter()
+        .map(|report| {
+            let (status, error, export) = match &report.outcome {
+                Outcome::Exported(export) => ("exported", None, export.as_ref()),
+                Outcome::Skipped => ("skipped", None, None),
+                Outcome::Failed(err) => (
+                    "failed",
+                    Some(JsonError {
+                        kind: crate::error_kind(err),
+                        message: format!("{err:#}"),
+                    }),
+                    None,
+                ),
+            };
+            JsonReport {
+                serial: report.serial.clone(),
+                status,
+                error,
+                export: export.map(ExportPayload::new),
+            }
+        })
+        .collect();
+
+    let mut stdout = io::stdout().lock();
+    serde_json::to_writer_pretty(&mut stdout, &reports)
+        .map_err(io::Error::from)
+        .and_then(|()| writeln!(stdout))
+        .context("failed to write to stdout")
+}
+
+// What the workers share.
+struct Batch<'a> {
+    api: &'a Api,
+    labels: &'a LabelOptions,
+    output: &'a OutputOptions,
+    // Return the exports for --json rather than dropping them once written.
+    keep_exports: bool,
+    // The credential for accounts without their own session token.
+    shared: Option<Credential>,
+    // Bearer tokens by the session token they were exchanged for, so accounts sharing a session
+    // token only exchange it once.
+    bearer_tokens: Mutex<Vec<(Zeroizing<String>, Zeroizing<String>)>>,
+    checkpoint: Option<Mutex<Checkpoint>>,
+    limiter: TokenBucket,
+}
+
+impl Batch<'_> {
+    // Restore one account and write it out. Expired tokens fail the account rather than
+    // prompting, since other accounts may be mid-export; --resume picks it up again.
+    fn export(&self, job: &Job) -> Result<Option<OtpAuth>> {
+        let credential = match &job.account.session_token {
+            Some(session_token) => Credential::SessionToken(session_token.clone()),
+            None => self
+                .shared
+                .clone()
+                .expect("the shared credential is set when an account needs it"),
+        };
+        crate::warn_region_mismatch(self.api.region_for(credential.session_token()), &job.serial);
+        if let Some(checkpoint) = &self.checkpoint {
+            lock(checkpoint).mark_pending(&job.serial, &job.restore_code)?;
+        }
+
+        let restored = self.bearer_token(&credential).and_then(|bearer_token| {
+            self.limiter.acquire();
+            let device_secret = timings::time(&format!("restore {}", job.serial), || {
+                self.api
+                    .device_secret(&bearer_token, &job.serial, &job.restore_code)
+            })?;
+            Ok((Locked::new(device_secret), Locked::new(bearer_token)))
+        });
+        crate::audit("restore", Some(&job.serial), &restored);
+        let (device_secret, bearer_token) = restored?;
+
+        let mut labels = self.labels.clone();
+        if job.account.label.is_some() {
+            labels.label.clone_from(&job.account.label);
+        }
+        let export = crate::build_export(
+            self.api,
+            &job.serial,
+            &job.restore_code,
+            &device_secret,
+            &bearer_token,
+            &labels,
+        )?;
+        if self.output.has_file_destination() {
+            self.output.write_file(&export)?;
+        }
+        if let Some(checkpoint) = &self.checkpoint {
+            lock(checkpoint).mark_done(&job.serial)?;
+        }
+        Ok(self.keep_exports.then_some(export))
+    }
+
+    // The bearer token for `credential`, exchanging a session token only the first time it is
+    // seen. The lock is held through the exchange so concurrent accounts wait for it.
+    fn bearer_token(&self, credential: &Credential) -> Result<Zeroizing<String>> {
+        let session_token = match credential {
+            Credential::SessionToken(session_token) => session_token,
+            Credential::BearerToken(bearer_token) => return Ok(bearer_token.clone()),
+        };
+        let mut bearer_tokens = lock(&self.bearer_tokens);
+        if let Some((_, bearer_token)) = bearer_tokens
+            .iter()
+            .find(|(exchanged, _)| exchanged == session_token)
+        {
+            return Ok(bearer_token.clone());
+        }
+
+        self.limiter.acquire();
+        let bearer_token = timings::time("SSO exchange", || {
+            self.api.exchange_session_token(session_token)
+        })?;
+        if let (Some(checkpoint), Some(shared)) = (&self.checkpoint, &self.shared)
+            && shared.session_token() == Some(session_token.as_str())
+        {
+            lock(checkpoint).set_bearer_token(&bearer_token)?;
+        }
+        bearer_tokens.push((session_token.clone(), bearer_token.clone()));
+        Ok(bearer_token)
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+fn normalize_session_token(session_token: &str) -> Zeroizing<String> {
+    Zeroizing::new(token::normalize_session_token(session_token))
+}
+
+fn read_csv(data: &[u8]) -> Result<Vec<Account>> {
+    csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(data)
+        .deserialize()
+        .enumerate()
+        // Row 1 is the header.
+        .map(|(i, row)| row.with_context(|| format!("row {}", i + 2)))
+        .collect()
+}
+
+fn read_toml(data: &[u8]) -> Result<(Option<Zeroizing<String>>, Vec<Account>)> {
+    let data = std::str::from_utf8(data).context("not UTF-8")?;
+    let file: TomlFile = toml::from_str(data)?;
+    Ok((file.session_token, file.accounts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_csv_with_optional_columns() {
+        let accounts = read_csv(
+            b"serial,restore_code,session_token\n\
+              US-1234-5678-9012, U9DQ1KWKPC ,\n\
+              EU-1234-5678-9012,ABCDE12345,ST=EU-abc\n",
+        )
+        .unwrap();
+
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(*accounts[0].restore_code, "U9DQ1KWKPC");
+        assert!(accounts[0].session_token.is_none());
+        assert_eq!(accounts[1].session_token.as_deref().unwrap(), "ST=EU-abc");
+    }
+
+    #[test]
+    fn reads_toml_with_a_shared_session_token() {
+        let (shared, accounts) = read_toml(
+            br#"
+                session_token = "ST=US-abc"
+
+                [[account]]
+                serial = "US-1234-5678-9012"
+                restore_code = "U9DQ1KWKPC"
+                label = "main"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(shared.as_deref().unwrap(), "ST=US-abc");
+        assert_eq!(accounts[0].label.as_deref(), Some("main"));
+    }
+
+    #[test]
+    fn csv_errors_name_the_row() {
+        let Err(err) = read_csv(b"serial,restore_code\nUS-1234-5678-9012\n") else {
+            panic!("a row without a restore code parsed");
+        };
+
+        assert!(format!("{err:#}").starts_with("row 2"));
+    }
+}
@@ -0,0 +1,260 @@
+// A three-field window around the same export flow as the CLI, for people who would rather not
+// open a terminal.
+
+use std::fmt;
+use std::sync::Arc;
+
+use iced::widget::{button, column, container, qr_code, row, text, text_input};
+use iced::{Element, Length, Task, clipboard};
+use zeroize::Zeroizing;
+
+use bnet_auth_export::api::ApiOptions;
+use bnet_auth_export::async_api::AsyncApi;
+use bnet_auth_export::otpauth::{LabelOptions, OtpAuth};
+use bnet_auth_export::{Error, restore, token};
+
+fn main() -> iced::Result {
+    iced::application(Gui::new, Gui::update, Gui::view)
+        .title("bnet-auth-export")
+        .window_size((520.0, 720.0))
+        .run()
+}
+
+struct Gui {
+    api: Result<Arc<AsyncApi>, String>,
+    session_token: Zeroizing<String>,
+    serial: String,
+    restore_code: String,
+    status: Status,
+    export: Option<Export>,
+}
+
+enum Status {
+    Idle,
+    Working,
+    Failed(String),
+}
+
+// The converted secret, in the forms the export buttons copy.
+struct Export {
+    uri: Zeroizing<String>,
+    base32_secret: Zeroizing<String>,
+    restore_code: String,
+    qr: qr_code::Data,
+}
+
+#[derive(Clone)]
+enum Message {
+    SessionTokenChanged(String),
+    SerialChanged(String),
+    RestoreCodeChanged(String),
+    Export,
+    Restored(Result<Arc<OtpAuth>, String>),
+    Copy(Copy),
+    Clear,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Copy {
+    Uri,
+    Base32Secret,
+    RestoreCode,
+}
+
+// Messages are logged in debug builds, so keep secrets out of them.
+impl fmt::Debug for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SessionTokenChanged(_) => f.write_str("SessionTokenChanged(..)"),
+            Self::SerialChanged(serial) => write!(f, "SerialChanged({serial:?})"),
+            Self::RestoreCodeChanged(_) => f.write_str("RestoreCodeChanged(..)"),
+            Self::Export => f.write_str("Export"),
+            Self::Restored(Ok(_)) => f.write_str("Restored(Ok(..))"),
+            Self::Restored(Err(err)) => write!(f, "Restored(Err({err:?}))"),
+            Self::Copy(what) => write!(f, "Copy({what:?})"),
+            Self::Clear => f.write_str("Clear"),
+        }
+    }
+}
+
+impl Gui {
+    fn new() -> Self {
+        Self {
+            api: AsyncApi::new(ApiOptions::default())
+                .map(Arc::new)
+                .map_err(describe),
+            session_token: Zeroizing::default(),
+            serial: String::new(),
+            restore_code: String::new(),
+            status: Status::Idle,
+            export: None,
+        }
+    }
+
+    fn update(&mut self, message: Message) -> Task<Message> {
+        match message {
+            Message::SessionTokenChanged(value) => self.session_token = Zeroizing::new(value),
+            Message::SerialChanged(value) => self.serial = value,
+            Message::RestoreCodeChanged(value) => self.restore_code = value,
+            Message::Export => return self.start_export(),
+            Message::Restored(Ok(export)) => {
+                self.status = Status::Idle;
+                self.export = Some(Export::new(&export));
+            }
+            Message::Restored(Err(err)) => self.status = Status::Failed(err),
+            Message::Copy(what) => {
+                if let Some(export) = &self.export {
+                    let value = match what {
+                        Copy::Uri => export.uri.to_string(),
+                        Copy::Base32Secret => export.base32_secret.to_string(),
+                        Copy::RestoreCode => export.restore_code.clone(),
+                    };
+                    return clipboard::write(value);
+                }
+            }
+            Message::Clear => {
+                self.session_token = Zeroizing::default();
+                self.restore_code.clear();
+                self.export = None;
+                self.status = Status::Idle;
+            }
+        }
+        Task::none()
+    }
+
+    // Validate the fields, then run the SSO exchange and restore in the background.
+    fn start_export(&mut self) -> Task<Message> {
+        let api = match &self.api {
+            Ok(api) => Arc::clone(api),
+            Err(err) => {
+                self.status = Status::Failed(err.clone());
+                return Task::none();
+            }
+        };
+
+        let session_token = Zeroizing::new(token::normalize_session_token(&self.session_token));
+        if session_token.is_empty() {
+            self.status = Status::Failed("Enter the session token (ST=...).".to_owned());
+            return Task::none();
+        }
+        let prepared = api
+            .region_for(Some(&session_token))
+            .normalize_serial(&self.serial)
+            .and_then(|serial| Ok((serial, restore::normalize_restore_code(&self.restore_code)?)));
+        let (serial, restore_code) = match prepared {
+            Ok(prepared) => prepared,
+            Err(err) => {
+                self.status = Status::Failed(describe(err));
+                return Task::none();
+            }
+        };
+
+        self.status = Status::Working;
+        self.export = None;
+        Task::perform(
+            fetch(api, session_token, serial, restore_code),
+            Message::Restored,
+        )
+    }
+
+    fn view(&self) -> Element<'_, Message> {
+        let working = matches!(self.status, Status::Working);
+        let form = column![
+            text("Session token"),
+            text_input("ST=US-...", &self.session_token)
+                .secure(true)
+                .on_input_maybe((!working).then_some(Message::SessionTokenChanged)),
+            text("Authenticator serial"),
+            text_input("US-2101-2345-6789", &self.serial)
+                .on_input_maybe((!working).then_some(Message::SerialChanged)),
+            text("Restore code"),
+            text_input("ABCDE12345", &self.restore_code)
+                .on_input_maybe((!working).then_some(Message::RestoreCodeChanged))
+                .on_submit_maybe((!working).then_some(Message::Export)),
+            row![
+                button(if working { "Exporting..." } else { "Export" })
+                    .on_press_maybe((!working).then_some(Message::Export)),
+                button("Clear").on_press_maybe((!working).then_some(Message::Clear)),
+            ]
+            .spacing(8),
+        ]
+        .spacing(8);
+
+        let status = match &self.status {
+            Status::Idle | Status::Working => None,
+            Status::Failed(err) => Some(text(err).style(text::danger)),
+        };
+
+        let export = self.export.as_ref().map(|export| {
+            column![
+                text("Scan this with your authenticator app:"),
+                qr_code(&export.qr).cell_size(5),
+                text(format!("Restore code: {}", export.restore_code)),
+                row![
+                    button("Copy otpauth URI").on_press(Message::Copy(Copy::Uri)),
+                    button("Copy Base32 secret").on_press(Message::Copy(Copy::Base32Secret)),
+                    button("Copy restore code").on_press(Message::Copy(Copy::RestoreCode)),
+                ]
+                .spacing(8),
+                text(
+                    "Check the new app shows 8-digit codes before removing the old authenticator."
+                ),
+            ]
+            .spacing(12)
+        });
+
+        container(column![form].push(status).push(export).spacing(20))
+            .padding(20)
+            .width(Length::Fill)
+            .into()
+    }
+}
+
+impl Export {
+    fn new(export: &OtpAuth) -> Self {
+        let uri = export.to_uri();
+        let restore_code = export
+            .serial
+            .as_deref()
+            .map(|serial| restore::restore_code(serial, &export.secret))
+            .unwrap_or_default();
+
+        Self {
+            // Error correction level M fits any otpauth URI this tool produces.
+            qr: qr_code::Data::new(uri.as_bytes()).expect("otpauth URI fits in a QR code"),
+            base32_secret: export.base32_secret(),
+            uri,
+            restore_code,
+        }
+    }
+}
+
+async fn fetch(
+    api: Arc<AsyncApi>,
+    session_token: Zeroizing<String>,
+    serial: String,
+    restore_code: String,
+) -> Result<Arc<OtpAuth>, String> {
+    let restore = async {
+        let bearer_token = api.exchange_session_token(&session_token).await?;
+        let device_secret = api
+            .device_secret(&bearer_token, &serial, &restore_code)
+            .await?;
+        let secret = hex::decode(device_secret.trim()).map_err(|err| Error::Parse {
+            what: "deviceSecret".to_owned(),
+            detail: err.to_string(),
+        })?;
+        Ok(OtpAuth::new(
+            serial.clone(),
+            Zeroizing::new(secret),
+            &LabelOptions::default(),
+        ))
+    };
+
+    restore.await.map(Arc::new).map_err(describe)
+}
+
+// The error with its causes, since there is no terminal to print the chain to.
+fn describe(err: Error) -> String {
+    format!("{:#}", anyhow::Error::from(err))
+}
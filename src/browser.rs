@@ -0,0 +1,553 @@
+use std::env;
+use std::fmt;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use rusqlite::{Connection, OpenFlags};
+
+use crate::prompt;
+use bnet_auth_export::secure_file::TempFile;
+use bnet_auth_export::token::looks_like_session_token;
+
+// Cookie and history entries on these domains are searched for the session token.
+const DOMAINS: &[&str] = &["battle.net", "blizzard.com", "localhost"];
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Browser {
+    Chrome,
+    Firefox,
+    Edge,
+}
+
+impl fmt::Display for Browser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Chrome => "Chrome",
+            Self::Firefox => "Firefox",
+            Self::Edge => "Edge",
+        })
+    }
+}
+
+// A session token found in a browser profile, with when the browser last saw it.
+struct Candidate {
+    token: String,
+    seen: i64,
+}
+
+// Find the most recent Battle.net session token in the browser's local profiles, and how long
+// ago the browser last used it.
+pub fn session_token(browser: Browser) -> Result<(String, Option<Duration>)> {
+    let profiles = profiles(browser);
+    if profiles.is_empty() {
+        bail!("no {browser} profile found on this machine");
+    }
+
+    println!("This reads Battle.net cookies and history from these {browser} profiles:");
+    for profile in &profiles {
+        println!("  {}", profile.display());
+    }
+    if browser != Browser::Firefox {
+        println!("Decrypting them may ask for your OS keychain password.");
+    }
+    let answer = prompt("Continue? [y/N]: ")?;
+    if !answer.eq_ignore_ascii_case("y") && !answer.eq_ignore_ascii_case("yes") {
+        bail!("aborted: reading the {browser} profile was not allowed");
+    }
+
+    let mut candidates = Vec::new();
+    let mut errors = Vec::new();
+    for profile in &profiles {
+        let found = match browser {
+            Browser::Firefox => firefox::candidates(profile),
+            Browser::Chrome | Browser::Edge => chromium::candidates(browser, profile),
+        };
+        match found {
+            Ok(found) => candidates.extend(found),
+            Err(err) => errors.push(format!("{}: {err:#}", profile.display())),
+        }
+    }
+
+    for error in &errors {
+        eprintln!("warning: {error}");
+    }
+
+    let newest = candidates
+        .into_iter()
+        .max_by_key(|candidate| candidate.seen)
+        .with_context(|| {
+            format!(
+                "no Battle.net session token found in {browser}; log in at {} first",
+                crate::login::LOGIN_URL
+            )
+        })?;
+
+    println!("Found a session token in {browser}.");
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_micros());
+    let age = u128::try_from(newest.seen)
+        .ok()
+        .filter(|&seen| seen > 0 && seen <= now)
+        .and_then(|seen| u64::try_from(now - seen).ok())
+        .map(Duration::from_micros);
+    Ok((newest.token, age))
+}
+
+fn home() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+// Profile directories that exist for the browser on this machine.
+fn profiles(browser: Browser) -> Vec<PathBuf> {
+    let roots: Vec<PathBuf> = match browser {
+        Browser::Firefox => firefox::roots(),
+        Browser::Chrome | Browser::Edge => chromium::user_data_dir(browser).into_iter().collect(),
+    };
+
+    let mut profiles = Vec::new();
+    for root in roots {
+        let Ok(entries) = fs::read_dir(&root) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let is_profile = match browser {
+                Browser::Firefox => path.join("cookies.sqlite").exists(),
+                Browser::Chrome | Browser::Edge => {
+                    path.join("Network").join("Cookies").exists() || path.join("Cookies").exists()
+                }
+            };
+            if is_profile {
+                profiles.push(path);
+            }
+        }
+    }
+    profiles.sort();
+    profiles
+}
+
+// Browsers keep their databases locked, so query a private copy instead. The copy holds
+// cookies, so it is shredded once the query is done.
+fn with_copy<T>(path: &Path, query: impl FnOnce(&Connection) -> Result<T>) -> Result<T> {
+    let name = path.file_name().context("database path has no file name")?;
+    let (copy, mut file) =
+        TempFile::create(&name.to_string_lossy()).context("failed to create a temporary file")?;
+    copy_into(path, &mut file)?;
+
+    let mut wal = path.as_os_str().to_owned();
+    wal.push("-wal");
+    let wal = PathBuf::from(wal);
+    let _wal_copy = if wal.exists() {
+        let (wal_copy, mut file) = copy
+            .sibling("-wal")
+            .context("failed to create a temporary file")?;
+        copy_into(&wal, &mut file)?;
+        Some(wal_copy)
+    } else {
+        None
+    };
+
+    let connection = Connection::open_with_flags(copy.path(), OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    let result = query(&connection);
+    // Close the database before its files are shredded.
+    drop(connection);
+    result
+}
+
+fn copy_into(path: &Path, file: &mut File) -> Result<()> {
+    let mut source =
+        File::open(path).with_context(|| format!("failed to copy {}", path.display()))?;
+    io::copy(&mut source, file).with_context(|| format!("failed to copy {}", path.display()))?;
+    Ok(())
+}
+
+// Pull the ST= value out of a visited URL, e.g. the localhost redirect after login.
+fn token_from_url(url: &str) -> Option<String> {
+    let (_, query) = url.split_once('?')?;
+    query
+        .split(['&', '#'])
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == "ST")
+        .map(|(_, value)| value.to_owned())
+        .filter(|value| looks_like_session_token(value))
+}
+
+fn on_bnet_domain(host: &str) -> bool {
+    let host = host.trim_start_matches('.');
+    DOMAINS
+        .iter()
+        .any(|domain| host == *domain || host.ends_with(&format!(".{domain}")))
+}
+
+mod firefox {
+    use super::*;
+
+    pub fn roots() -> Vec<PathBuf> {
+        let Some(home) = home() else {
+            return Vec::new();
+        };
+
+        if cfg!(target_os = "windows") {
+            env::var_os("APPDATA")
+                .map(|appdata| PathBuf::from(appdata).join(r"Mozilla\Firefox\Profiles"))
+                .into_iter()
+                .collect()
+        } else if cfg!(target_os = "macos") {
+            vec![home.join("Library/Application Support/Firefox/Profiles")]
+        } else {
+            vec![
+                home.join(".mozilla/firefox"),
+                home.join("snap/firefox/common/.mozilla/firefox"),
+                home.join(".var/app/org.mozilla.firefox/.mozilla/firefox"),
+            ]
+        }
+    }
+
+    // Firefox stores cookies and history unencrypted.
+    pub fn candidates(profile: &Path) -> Result<Vec<Candidate>> {
+        let mut candidates = with_copy(&profile.join("cookies.sqlite"), |db| {
+            let mut statement = db.prepare("SELECT host, value, lastAccessed FROM moz_cookies")?;
+            let rows = statement.query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, i64>(2)?,
+                ))
+            })?;
+
+            Ok(rows
+                .flatten()
+                .filter(|(host, value, _)| on_bnet_domain(host) && looks_like_session_token(value))
+                .map(|(_, token, seen)| Candidate { token, seen })
+                .collect::<Vec<_>>())
+        })?;
+
+        let places = profile.join("places.sqlite");
+        if places.exists() {
+            candidates.extend(with_copy(&places, |db| {
+                let mut statement = db.prepare(
+                    "SELECT url, COALESCE(last_visit_date, 0) FROM moz_places WHERE url LIKE '%ST=%'",
+                )?;
+                let rows = statement.query_map([], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+                })?;
+
+                Ok(rows
+                    .flatten()
+                    .filter_map(|(url, seen)| Some(Candidate { token: token_from_url(&url)?, seen }))
+                    .collect::<Vec<_>>())
+            })?);
+        }
+
+        Ok(candidates)
+    }
+}
+
+mod chromium {
+    use super::*;
+
+    use aes::Aes128;
+    use cbc::cipher::block_padding::Pkcs7;
+    use cbc::cipher::{BlockDecryptMut, KeyIvInit};
+    use sha2::{Digest, Sha256};
+
+    // Chromium's timestamps count microseconds since 1601; Firefox's since 1970.
+    const WINDOWS_TO_UNIX_MICROS: i64 = 11_644_473_600_000_000;
+
+    pub fn user_data_dir(browser: Browser) -> Option<PathBuf> {
+        let home = home()?;
+
+        let path = if cfg!(target_os = "windows") {
+            let local = PathBuf::from(env::var_os("LOCALAPPDATA")?);
+            match browser {
+                Browser::Edge => local.join(r"Microsoft\Edge\User Data"),
+                _ => local.join(r"Google\Chrome\User Data"),
+            }
+        } else if cfg!(target_os = "macos") {
+            let support = home.join("Library/Application Support");
+            match browser {
+                Browser::Edge => support.join("Microsoft Edge"),
+                _ => support.join("Google/Chrome"),
+            }
+        } else {
+            let config = env::var_os("XDG_CONFIG_HOME")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| home.join(".config"));
+            match browser {
+                Browser::Edge => config.join("microsoft-edge"),
+                _ => config.join("google-chrome"),
+            }
+        };
+
+        Some(path)
+    }
+
+    pub fn candidates(browser: Browser, profile: &Path) -> Result<Vec<Candidate>> {
+        let mut candidates = Vec::new();
+
+        // History is never encrypted, so a recent login redirect is the easiest win.
+        let history = profile.join("History");
+        if history.exists() {
+            candidates.extend(with_copy(&history, |db| {
+                let mut statement =
+                    db.prepare("SELECT url, last_visit_time FROM urls WHERE url LIKE '%ST=%'")?;
+                let rows = statement.query_map([], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+                })?;
+
+                Ok(rows
+                    .flatten()
+                    .filter_map(|(url, seen)| {
+                        Some(Candidate {
+                            token: token_from_url(&url)?,
+                            seen: seen - WINDOWS_TO_UNIX_MICROS,
+                        })
+                    })
+                    .collect::<Vec<_>>())
+            })?);
+        }
+
+        let cookies = [
+            profile.join("Network").join("Cookies"),
+            profile.join("Cookies"),
+        ]
+        .into_iter()
+        .find(|path| path.exists())
+        .context("profile has no cookie database")?;
+        let rows = with_copy(&cookies, |db| {
+            let mut statement = db
+                .prepare("SELECT host_key, value, encrypted_value, last_access_utc FROM cookies")?;
+            let rows = statement.query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Vec<u8>>(2)?,
+                    row.get::<_, i64>(3)?,
+                ))
+            })?;
+            Ok(rows
+                .flatten()
+                .filter(|(host, ..)| on_bnet_domain(host))
+                .collect::<Vec<_>>())
+        })?;
+
+        let mut key = None;
+        for (host, value, encrypted, seen) in rows {
+            let value = if !value.is_empty() || encrypted.is_empty() {
+                value
+            } else {
+                let key = match &key {
+                    Some(key) => key,
+                    None => key.insert(Key::load(browser, profile)?),
+                };
+                key.decrypt(&host, &encrypted)?
+            };
+
+            if looks_like_session_token(&value) {
+                candidates.push(Candidate {
+                    token: value,
+                    seen: seen - WINDOWS_TO_UNIX_MICROS,
+                });
+            }
+        }
+
+        Ok(candidates)
+    }
+
+    // The key Chromium encrypts cookie values with, which depends on the platform.
+    enum Key {
+        // Linux and macOS: AES-128-CBC with a PBKDF2-derived key. Linux has a fixed v10 key.
+        Cbc {
+            v10: [u8; 16],
+            v11: Option<[u8; 16]>,
+        },
+        // Windows: AES-256-GCM with a DPAPI-protected key stored in Local State.
+        #[cfg_attr(not(windows), allow(dead_code))]
+        Gcm([u8; 32]),
+    }
+
+    impl Key {
+        fn load(browser: Browser, profile: &Path) -> Result<Self> {
+            if cfg!(target_os = "windows") {
+                let local_state = profile
+                    .parent()
+                    .context("profile has no parent directory")?
+                    .join("Local State");
+                return windows_key(&local_state).map(Self::Gcm);
+            }
+
+            if cfg!(target_os = "macos") {
+                let service = match browser {
+                    Browser::Edge => "Microsoft Edge Safe Storage",
+                    _ => "Chrome Safe Storage",
+                };
+                let password =
+                    command_output("security", &["find-generic-password", "-w", "-s", service])
+                        .with_context(|| {
+                            format!("failed to read \"{service}\" from the macOS keychain")
+                        })?;
+                let key = derive_key(password.as_bytes(), 1003);
+                return Ok(Self::Cbc {
+                    v10: key,
+                    v11: None,
+                });
+            }
+
+            // Linux: v10 uses a hardcoded password, v11 one stored in the secret service.
+            let application = match browser {
+                Browser::Edge => "microsoft-edge",
+                _ => "chrome",
+            };
+            let v11 = command_output("secret-tool", &["lookup", "application", application])
+                .ok()
+                .map(|password| derive_key(password.as_bytes(), 1));
+            Ok(Self::Cbc {
+                v10: derive_key(b"peanuts", 1),
+                v11,
+            })
+        }
+
+        fn decrypt(&self, host: &str, encrypted: &[u8]) -> Result<String> {
+            let (version, data) = encrypted.split_at(3.min(encrypted.len()));
+
+            let plain = match (self, version) {
+                (Self::Cbc { v10, .. }, b"v10") => decrypt_cbc(v10, data)?,
+                (Self::Cbc { v11: Some(v11), .. }, b"v11") => decrypt_cbc(v11, data)?,
+                (Self::Cbc { v11: None, .. }, b"v11") => {
+                    bail!(
+                        "cookie is protected by the desktop keyring; install secret-tool (libsecret) and try again"
+                    )
+                }
+                (Self::Gcm(key), b"v10" | b"v11") => decrypt_gcm(key, data)?,
+                (_, b"v20") => bail!(
+                    "cookie uses Chrome's app-bound encryption, which other programs cannot decrypt; use the login subcommand instead"
+                ),
+                _ => bail!("unsupported cookie encryption version"),
+            };
+
+            // Newer Chromium versions prefix the value with a hash of the host.
+            let digest = Sha256::digest(host.as_bytes());
+            let plain = plain.strip_prefix(digest.as_slice()).unwrap_or(&plain);
+
+            String::from_utf8(plain.to_vec()).context("decrypted cookie is not valid UTF-8")
+        }
+    }
+
+    fn derive_key(password: &[u8], rounds: u32) -> [u8; 16] {
+        let mut key = [0u8; 16];
+        pbkdf2::pbkdf2_hmac::<sha1::Sha1>(password, b"saltysalt", rounds, &mut key);
+        key
+    }
+
+    fn decrypt_cbc(key: &[u8; 16], data: &[u8]) -> Result<Vec<u8>> {
+        cbc::Decryptor::<Aes128>::new(key.into(), &[b' '; 16].into())
+            .decrypt_padded_vec_mut::<Pkcs7>(data)
+            .ok()
+            .context("failed to decrypt cookie, the keychain password may be wrong")
+    }
+
+    fn decrypt_gcm(key: &[u8; 32], data: &[u8]) -> Result<Vec<u8>> {
+        use aes_gcm::aead::{Aead, KeyInit};
+        use aes_gcm::{Aes256Gcm, Nonce};
+
+        if data.len() < 12 {
+            bail!("encrypted cookie is too short");
+        }
+        let (nonce, ciphertext) = data.split_at(12);
+        Aes256Gcm::new(key.into())
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .ok()
+            .context("failed to decrypt cookie")
+    }
+
+    fn command_output(program: &str, args: &[&str]) -> Result<String> {
+        let output = process::Command::new(program)
+            .args(args)
+            .output()
+            .with_context(|| format!("failed to run {program}"))?;
+        if !output.status.success() {
+            bail!("{program} exited with {}", output.status);
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+    }
+
+    #[cfg(windows)]
+    fn windows_key(local_state: &Path) -> Result<[u8; 32]> {
+        use base64::Engine;
+        use base64::engine::general_purpose::STANDARD;
+
+        let state: serde_json::Value = serde_json::from_slice(
+            &fs::read(local_state)
+                .with_context(|| format!("failed to read {}", local_state.display()))?,
+        )
+        .context("Local State is not valid JSON")?;
+        let encoded = state
+            .pointer("/os_crypt/encrypted_key")
+            .and_then(|value| value.as_str())
+            .context("Local State has no os_crypt.encrypted_key")?;
+        let encrypted = STANDARD
+            .decode(encoded)
+            .context("os_crypt.encrypted_key is not valid base64")?;
+        let protected = encrypted
+            .strip_prefix(b"DPAPI")
+            .context("os_crypt.encrypted_key is not DPAPI protected")?;
+
+        dpapi_unprotect(protected)?
+            .try_into()
+            .ok()
+            .context("decrypted cookie key has the wrong length")
+    }
+
+    #[cfg(not(windows))]
+    fn windows_key(_local_state: &Path) -> Result<[u8; 32]> {
+        bail!("DPAPI is only available on Windows")
+    }
+
+    #[cfg(windows)]
+    fn dpapi_unprotect(data: &[u8]) -> Result<Vec<u8>> {
+        use std::ptr;
+        use windows_sys::Win32::Foundation::LocalFree;
+        use windows_sys::Win32::Security::Cryptography::{CRYPT_INTEGER_BLOB, CryptUnprotectData};
+
+        let input = CRYPT_INTEGER_BLOB {
+            cbData: data.len() as u32,
+            pbData: data.as_ptr() as *mut u8,
+        };
+        let mut output = CRYPT_INTEGER_BLOB {
+            cbData: 0,
+            pbData: ptr::null_mut(),
+        };
+
+        // SAFETY: `input` points at `data` for the duration of the call, and `output` is
+        // allocated by the system and released with LocalFree below.
+        let ok = unsafe {
+            CryptUnprotectData(
+                &input,
+                ptr::null_mut(),
+                ptr::null(),
+                ptr::null(),
+                ptr::null(),
+                0,
+                &mut output,
+            )
+        };
+        if ok == 0 {
+            bail!("DPAPI refused to decrypt the cookie key");
+        }
+
+        // SAFETY: on success `output` describes a valid buffer owned by us.
+        let bytes =
+            unsafe { std::slice::from_raw_parts(output.pbData, output.cbData as usize) }.to_vec();
+        unsafe { LocalFree(output.pbData as _) };
+        Ok(bytes)
+    }
+}
@@ -0,0 +1,557 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use anyhow::{Context, Result, bail};
+use rcgen::{
+    BasicConstraints, CertificateParams, DnType, ExtendedKeyUsagePurpose, GeneralSubtree, IsCa,
+    KeyPair, KeyUsagePurpose, NameConstraints,
+};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName};
+use rustls::{
+    ClientConfig, ClientConnection, RootCertStore, ServerConfig, ServerConnection, StreamOwned,
+};
+
+use crate::login::LOGIN_URL;
+use bnet_auth_export::secure_file::TempFile;
+use bnet_auth_export::token::find_st_parameter;
+
+// Only these domains are intercepted, and the CA is name-constrained to them.
+const INTERCEPT_DOMAINS: &[&str] = &[
+    "battle.net",
+    "blizzard.com",
+    "blizzard.net",
+    "battlenet.com.cn",
+];
+
+const CA_NAME: &str = "bnet-auth-export capture CA";
+
+// Messages larger than this are refused rather than buffered.
+const MAX_HEAD: usize = 64 * 1024;
+const MAX_BODY: usize = 32 * 1024 * 1024;
+
+// Run a local intercepting proxy until the browser's login traffic reveals the session token.
+pub fn session_token(addr: &str) -> Result<String> {
+    let authority = Authority::generate()?;
+    // Removed again when the capture is over, Firefox keeps its own copy once imported.
+    let (ca_file, mut file) =
+        TempFile::create("capture-ca.pem").context("failed to create a temporary file")?;
+    let ca_path = ca_file.path();
+    file.write_all(authority.cert.pem().as_bytes())
+        .with_context(|| format!("failed to write {}", ca_path.display()))?;
+
+    let listener =
+        TcpListener::bind(addr).with_context(|| format!("failed to listen on {addr}"))?;
+    let local = listener
+        .local_addr()
+        .context("failed to read proxy address")?;
+
+    println!("Capture proxy listening on {local}.");
+    println!();
+    println!(
+        "Firefox is the easiest browser for this, since it keeps its own certificates and proxy settings:"
+    );
+    println!(
+        "1. Settings > Privacy & Security > Certificates > View Certificates > Authorities > Import,"
+    );
+    println!(
+        "   pick {} and tick \"Trust this CA to identify websites\".",
+        ca_path.display()
+    );
+    println!(
+        "2. Settings > Network Settings > Manual proxy configuration: HTTP Proxy {}, Port {},",
+        local.ip(),
+        local.port()
+    );
+    println!("   and tick \"Also use this proxy for HTTPS\".");
+    println!("3. Open {LOGIN_URL} and log in.");
+    println!();
+    println!(
+        "The CA only works for Battle.net domains and its private key is thrown away when this"
+    );
+    println!(
+        "program exits. Only Battle.net traffic is decrypted, everything else is passed through."
+    );
+    println!();
+    println!("Waiting for the login...");
+
+    let (sender, receiver) = mpsc::channel();
+    let proxy = Arc::new(Proxy::new(authority, sender)?);
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let proxy = Arc::clone(&proxy);
+            thread::spawn(move || {
+                let _ = proxy.handle(stream);
+            });
+        }
+    });
+
+    let token = receiver
+        .recv()
+        .context("capture proxy stopped without finding a session token")?;
+    drop(ca_file);
+
+    println!("Captured the session token.");
+    println!(
+        "Remember to switch the browser proxy setting back and delete \"{CA_NAME}\" from its authorities."
+    );
+    Ok(token)
+}
+
+// An ephemeral CA that issues leaf certificates for intercepted hosts.
+struct Authority {
+    cert: rcgen::Certificate,
+    key: KeyPair,
+}
+
+impl Authority {
+    fn generate() -> Result<Self> {
+        let mut params = CertificateParams::default();
+        params.distinguished_name.push(DnType::CommonName, CA_NAME);
+        params.is_ca = IsCa::Ca(BasicConstraints::Constrained(0));
+        params.key_usages = vec![KeyUsagePurpose::KeyCertSign, KeyUsagePurpose::CrlSign];
+        params.name_constraints = Some(NameConstraints {
+            permitted_subtrees: INTERCEPT_DOMAINS
+                .iter()
+                .map(|domain| GeneralSubtree::DnsName((*domain).to_owned()))
+                .collect(),
+            excluded_subtrees: Vec::new(),
+        });
+
+        let key = KeyPair::generate().context("failed to generate CA key")?;
+        let cert = params
+            .self_signed(&key)
+            .context("failed to create CA certificate")?;
+        Ok(Self { cert, key })
+    }
+
+    fn issue(&self, host: &str) -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)> {
+        let mut params = CertificateParams::new(vec![host.to_owned()])
+            .with_context(|| format!("invalid host name {host}"))?;
+        params.distinguished_name.push(DnType::CommonName, host);
+        params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+
+        let key = KeyPair::generate().context("failed to generate certificate key")?;
+        let cert = params
+            .signed_by(&key, &self.cert, &self.key)
+            .with_context(|| format!("failed to issue certificate for {host}"))?;
+
+        Ok((
+            vec![cert.der().clone(), self.cert.der().clone()],
+            PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key.serialize_der())),
+        ))
+    }
+}
+
+struct Proxy {
+    authority: Authority,
+    upstream: Arc<ClientConfig>,
+    servers: Mutex<HashMap<String, Arc<ServerConfig>>>,
+    found: Mutex<Sender<String>>,
+}
+
+impl Proxy {
+    fn new(authority: Authority, found: Sender<String>) -> Result<Self> {
+        let roots = RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        };
+        let mut upstream = ClientConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()
+            .context("failed to configure TLS")?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        upstream.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+        Ok(Self {
+            authority,
+            upstream: Arc::new(upstream),
+            servers: Mutex::new(HashMap::new()),
+            found: Mutex::new(found),
+        })
+    }
+
+    fn handle(&self, stream: TcpStream) -> Result<()> {
+        let mut client = BufReader::new(stream);
+        let Some(head) = Head::read(&mut client)? else {
+            return Ok(());
+        };
+
+        if !head.start.0.eq_ignore_ascii_case("CONNECT") {
+            // Plain HTTP, e.g. the final redirect to http://localhost/?ST=...
+            if self.scan(&head.start.1) {
+                let body = "Session token captured, you can close this tab.";
+                write!(
+                    client.get_mut(),
+                    "HTTP/1.1 200 OK\r\ncontent-type: text/plain\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                )?;
+                return Ok(());
+            }
+            return self.forward_plain(client, head);
+        }
+
+        let (host, port) = split_authority(&head.start.1, 443)?;
+        client
+            .get_mut()
+            .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")?;
+
+        if intercepted(&host) {
+            let stream = client.into_inner();
+            let config = self.server_config(&host)?;
+            let tls = StreamOwned::new(ServerConnection::new(config)?, stream);
+            let mut client = BufReader::new(tls);
+
+            while let Some(head) = Head::read(&mut client)? {
+                let upstream = self.connect_tls(&host, port)?;
+                if !self.exchange(&mut client, head, upstream, &host)? {
+                    break;
+                }
+            }
+            Ok(())
+        } else {
+            tunnel(client, &host, port)
+        }
+    }
+
+    fn forward_plain(&self, mut client: BufReader<TcpStream>, mut head: Head) -> Result<()> {
+        let target = head.start.1.clone();
+        let rest = target
+            .strip_prefix("http://")
+            .context("proxy request without an absolute http:// URL")?;
+        let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        let (host, port) = split_authority(authority, 80)?;
+        head.start.1 = if path.is_empty() {
+            "/".to_owned()
+        } else {
+            path.to_owned()
+        };
+
+        let upstream = TcpStream::connect((host.as_str(), port))
+            .with_context(|| format!("failed to connect to {host}:{port}"))?;
+        self.exchange(&mut client, head, upstream, &host)?;
+        Ok(())
+    }
+
+    // Forward one request and its response. Returns whether the client connection can be reused.
+    fn exchange<C: Read + Write, U: Read + Write>(
+        &self,
+        client: &mut BufReader<C>,
+        mut request: Head,
+        upstream: U,
+        host: &str,
+    ) -> Result<bool> {
+        let scan = intercepted(host);
+        if scan {
+            self.scan(&request.start.1);
+            for header in ["cookie", "referer"] {
+                if let Some(value) = request.header(header) {
+                    self.scan(value);
+                }
+            }
+        }
+
+        let keep_alive = !request
+            .header("proxy-connection")
+            .or(request.header("connection"))
+            .is_some_and(|value| value.eq_ignore_ascii_case("close"));
+
+        let body = read_body(client, &request, None)?;
+        request.remove_header("proxy-connection");
+        request.remove_header("transfer-encoding");
+        request.remove_header("expect");
+        request.set_header("content-length", &body.len().to_string());
+        request.set_header("connection", "close");
+        if scan {
+            // Keep bodies readable so redirects embedded in pages can be found too.
+            request.set_header("accept-encoding", "identity");
+        }
+
+        let mut upstream = BufReader::new(upstream);
+        request.write(upstream.get_mut())?;
+        upstream.get_mut().write_all(&body)?;
+        upstream.get_mut().flush()?;
+
+        let mut response = loop {
+            let response = Head::read(&mut upstream)?.context("upstream closed the connection")?;
+            // Informational responses carry no body and are followed by the real one.
+            if !response.start.1.starts_with('1') {
+                break response;
+            }
+        };
+        let body = read_body(&mut upstream, &response, Some(&request.start.0))?;
+
+        if scan {
+            if let Some(location) = response.header("location") {
+                self.scan(location);
+            }
+            if let Ok(text) = std::str::from_utf8(&body) {
+                self.scan(text);
+            }
+        }
+
+        response.remove_header("transfer-encoding");
+        response.set_header("content-length", &body.len().to_string());
+        response.set_header(
+            "connection",
+            if keep_alive { "keep-alive" } else { "close" },
+        );
+
+        response.write(client.get_mut())?;
+        client.get_mut().write_all(&body)?;
+        client.get_mut().flush()?;
+        Ok(keep_alive)
+    }
+
+    // Report a session token if the text contains one. Returns whether one was found.
+    fn scan(&self, text: &str) -> bool {
+        match find_st_parameter(text) {
+            Some(token) => {
+                if let Ok(found) = self.found.lock() {
+                    let _ = found.send(token);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn server_config(&self, host: &str) -> Result<Arc<ServerConfig>> {
+        let mut servers = self
+            .servers
+            .lock()
+            .map_err(|_| anyhow::anyhow!("proxy state poisoned"))?;
+        if let Some(config) = servers.get(host) {
+            return Ok(Arc::clone(config));
+        }
+
+        let (chain, key) = self.authority.issue(host)?;
+        let mut config = ServerConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()
+            .context("failed to configure TLS")?
+            .with_no_client_auth()
+            .with_single_cert(chain, key)
+            .context("failed to configure TLS certificate")?;
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+        let config = Arc::new(config);
+        servers.insert(host.to_owned(), Arc::clone(&config));
+        Ok(config)
+    }
+
+    fn connect_tls(
+        &self,
+        host: &str,
+        port: u16,
+    ) -> Result<StreamOwned<ClientConnection, TcpStream>> {
+        let name = ServerName::try_from(host.to_owned())
+            .with_context(|| format!("invalid host name {host}"))?;
+        let connection = ClientConnection::new(Arc::clone(&self.upstream), name)?;
+        let stream = TcpStream::connect((host, port))
+            .with_context(|| format!("failed to connect to {host}:{port}"))?;
+        Ok(StreamOwned::new(connection, stream))
+    }
+}
+
+fn provider() -> Arc<rustls::crypto::CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+fn intercepted(host: &str) -> bool {
+    INTERCEPT_DOMAINS
+        .iter()
+        .any(|domain| host == *domain || host.ends_with(&format!(".{domain}")))
+}
+
+fn split_authority(authority: &str, default_port: u16) -> Result<(String, u16)> {
+    match authority.rsplit_once(':') {
+        Some((host, port)) => Ok((
+            host.to_owned(),
+            port.parse()
+                .with_context(|| format!("invalid port in {authority}"))?,
+        )),
+        None => Ok((authority.to_owned(), default_port)),
+    }
+}
+
+// Pass a CONNECT tunnel through untouched.
+fn tunnel(client: BufReader<TcpStream>, host: &str, port: u16) -> Result<()> {
+    let leftover = client.buffer().to_vec();
+    let mut client = client.into_inner();
+    let mut upstream = TcpStream::connect((host, port))
+        .with_context(|| format!("failed to connect to {host}:{port}"))?;
+    upstream.write_all(&leftover)?;
+
+    let mut client_reader = client.try_clone()?;
+    let mut upstream_writer = upstream.try_clone()?;
+    let outbound = thread::spawn(move || {
+        let _ = io::copy(&mut client_reader, &mut upstream_writer);
+        let _ = upstream_writer.shutdown(Shutdown::Write);
+    });
+    let _ = io::copy(&mut upstream, &mut client);
+    let _ = client.shutdown(Shutdown::Write);
+    let _ = outbound.join();
+    Ok(())
+}
+
+// The start line and headers of an HTTP/1.1 message.
+struct Head {
+    start: (String, String, String),
+    headers: Vec<(String, String)>,
+}
+
+impl Head {
+    fn read(reader: &mut impl BufRead) -> Result<Option<Self>> {
+        let mut lines = Vec::new();
+        let mut size = 0;
+        loop {
+            let mut line = String::new();
+            let read = match reader.read_line(&mut line) {
+                Ok(read) => read,
+                Err(err) if lines.is_empty() && is_closed(&err) => return Ok(None),
+                Err(err) => return Err(err.into()),
+            };
+            if read == 0 {
+                if lines.is_empty() {
+                    return Ok(None);
+                }
+                bail!("connection closed in the middle of a message");
+            }
+            size += read;
+            if size > MAX_HEAD {
+                bail!("message headers are too large");
+            }
+
+            let line = line.trim_end_matches(['\r', '\n']);
+            if line.is_empty() {
+                if lines.is_empty() {
+                    continue;
+                }
+                break;
+            }
+            lines.push(line.to_owned());
+        }
+
+        let mut start = lines[0].splitn(3, ' ').map(str::to_owned);
+        let start = (
+            start.next().unwrap_or_default(),
+            start.next().unwrap_or_default(),
+            start.next().unwrap_or_default(),
+        );
+        let headers = lines[1..]
+            .iter()
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_owned(), value.trim().to_owned()))
+            .collect();
+
+        Ok(Some(Self { start, headers }))
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn remove_header(&mut self, name: &str) {
+        self.headers
+            .retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+    }
+
+    fn set_header(&mut self, name: &str, value: &str) {
+        self.remove_header(name);
+        self.headers.push((name.to_owned(), value.to_owned()));
+    }
+
+    fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        let (a, b, c) = &self.start;
+        write!(writer, "{a} {b} {c}\r\n")?;
+        for (name, value) in &self.headers {
+            write!(writer, "{name}: {value}\r\n")?;
+        }
+        writer.write_all(b"\r\n")
+    }
+}
+
+// TLS peers often hang up without a close_notify once they are done.
+fn is_closed(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+    )
+}
+
+// Read a whole message body, removing chunked framing. `request_method` is set for responses.
+fn read_body(
+    reader: &mut impl BufRead,
+    head: &Head,
+    request_method: Option<&str>,
+) -> Result<Vec<u8>> {
+    if let Some(method) = request_method {
+        let status = head.start.1.as_str();
+        if method.eq_ignore_ascii_case("HEAD") || status == "204" || status == "304" {
+            return Ok(Vec::new());
+        }
+    }
+
+    let chunked = head
+        .header("transfer-encoding")
+        .is_some_and(|value| value.to_ascii_lowercase().contains("chunked"));
+    if chunked {
+        return read_chunked(reader);
+    }
+
+    if let Some(length) = head.header("content-length") {
+        let length: usize = length.parse().context("invalid content-length")?;
+        if length > MAX_BODY {
+            bail!("message body is too large");
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body)?;
+        return Ok(body);
+    }
+
+    // Requests without a length have no body; responses run until the connection closes.
+    let mut body = Vec::new();
+    if request_method.is_some() {
+        match reader.take(MAX_BODY as u64).read_to_end(&mut body) {
+            Ok(_) => {}
+            Err(err) if is_closed(&err) => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(body)
+}
+
+fn read_chunked(reader: &mut impl BufRead) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let size = line.trim().split(';').next().unwrap_or_default();
+        let size = usize::from_str_radix(size, 16).context("invalid chunk size")?;
+        if size == 0 {
+            // Skip trailers up to the terminating blank line.
+            loop {
+                line.clear();
+                if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+                    return Ok(body);
+                }
+            }
+        }
+        if body.len() + size > MAX_BODY {
+            bail!("message body is too large");
+        }
+
+        let start = body.len();
+        body.resize(start + size, 0);
+        reader.read_exact(&mut body[start..])?;
+        let mut crlf = [0; 2];
+        reader.read_exact(&mut crlf)?;
+    }
+}
@@ -0,0 +1,291 @@
+// --checkpoint: progress saved to a password-encrypted file after every step, so an interrupted
+// run can pick up where it stopped without another SSO exchange or typing the restore code again.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use bnet_auth_export::secure_file;
+
+use crate::seal;
+
+const HEADER: &[u8] = b"BNETCHECKPOINT";
+const WHAT: &str = "checkpoint file";
+const VERSION: u32 = 1;
+// Battle.net doesn't say how long a bearer token lasts. Reuse one well within the hour they are
+// seen to work for, and exchange the session token again after that.
+const BEARER_TOKEN_TTL: u64 = 30 * 60;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct State {
+    version: u32,
+    bearer_token: Option<Zeroizing<String>>,
+    bearer_token_at: Option<u64>,
+    entries: BTreeMap<String, Entry>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry {
+    status: Status,
+    // Only kept until the entry is done.
+    restore_code: Option<Zeroizing<String>>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum Status {
+    Pending,
+    Done,
+}
+
+pub struct Checkpoint {
+    path: PathBuf,
+    key: seal::Key,
+    state: State,
+}
+
+impl Checkpoint {
+    // Load the checkpoint at `path`, or start a new one there if there is none yet.
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::open_with(path, password)
+    }
+
+    // `open` with the password from `password`, which is asked to confirm it for a new one.
+    fn open_with(
+        path: &Path,
+        password: impl Fn(bool) -> Result<Zeroizing<String>>,
+    ) -> Result<Self> {
+        let data = match fs::read(path) {
+            Ok(data) => Some(Zeroizing::new(data)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed to read {}", path.display()));
+            }
+        };
+
+        let Some(data) = data else {
+            let checkpoint = Self {
+                path: path.to_owned(),
+                key: seal::Key::new(password(true)?.as_bytes())?,
+                state: State {
+                    version: VERSION,
+                    bearer_token: None,
+                    bearer_token_at: None,
+                    entries: BTreeMap::new(),
+                },
+            };
+            checkpoint.save()?;
+            return Ok(checkpoint);
+        };
+
+        let key = seal::Key::for_sealed(HEADER, &data, password(false)?.as_bytes(), WHAT)?;
+        let plaintext = key.open(HEADER, &data, WHAT)?;
+        let state: State =
+            serde_json::from_slice(&plaintext).context("failed to parse the checkpoint file")?;
+        if state.version != VERSION {
+            bail!(
+                "unsupported checkpoint version {}, finish the run with the version that started it",
+                state.version
+            );
+        }
+        Ok(Self {
+            path: path.to_owned(),
+            key,
+            state,
+        })
+    }
+
+    // The saved bearer token, unless it is too old to still be accepted.
+    pub fn bearer_token(&self) -> Option<Zeroizing<String>> {
+        let saved_at = self.state.bearer_token_at?;
+        if now().saturating_sub(saved_at) >= BEARER_TOKEN_TTL {
+            return None;
+        }
+        self.state.bearer_token.clone()
+    }
+
+    pub fn set_bearer_token(&mut self, bearer_token: &str) -> Result<()> {
+        self.state.bearer_token = Some(Zeroizing::new(bearer_token.to_owned()));
+        self.state.bearer_token_at = Some(now());
+        self.save()
+    }
+
+    #[cfg(any(feature = "batch", test))]
+    pub fn is_done(&self, serial: &str) -> bool {
+        self.state
+            .entries
+            .get(serial)
+            .is_some_and(|entry| entry.status == Status::Done)
+    }
+
+    // The first entry that was started but not finished, with its restore code.
+    pub fn pending(&self) -> Option<(String, Zeroizing<String>)> {
+        self.state.entries.iter().find_map(|(serial, entry)| {
+            let restore_code = entry.restore_code.clone()?;
+            (entry.status == Status::Pending).then(|| (serial.clone(), restore_code))
+        })
+    }
+
+    pub fn mark_pending(&mut self, serial: &str, restore_code: &str) -> Result<()> {
+        self.state.entries.insert(
+            serial.to_owned(),
+            Entry {
+                status: Status::Pending,
+                restore_code: Some(Zeroizing::new(restore_code.to_owned())),
+            },
+        );
+        self.save()
+    }
+
+    pub fn mark_done(&mut self, serial: &str) -> Result<()> {
+        self.state.entries.insert(
+            serial.to_owned(),
+            Entry {
+                status: Status::Done,
+                restore_code: None,
+            },
+        );
+        self.save()
+    }
+
+    // Remove the checkpoint once nothing is left to resume, since it holds a bearer token, and
+    // return its path then, for the caller to say so.
+    pub fn finish(self) -> Result<Option<PathBuf>> {
+        if self.pending().is_some() {
+            return Ok(None);
+        }
+        fs::remove_file(&self.path)
+            .with_context(|| format!("failed to remove {}", self.path.display()))?;
+        Ok(Some(self.path))
+    }
+
+    // Write the whole state to a temporary file and rename it over the checkpoint, so an
+    // interruption leaves either the old state or the new one.
+    fn save(&self) -> Result<()> {
+        let json = Zeroizing::new(
+            serde_json::to_vec(&self.state).context("failed to encode the checkpoint")?,
+        );
+        let sealed = self.key.seal(HEADER, &json)?;
+
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        let _unfinished = crate::interrupt::remove_on_interrupt(&tmp);
+        secure_file::private_options()
+            .truncate(true)
+            .open(&tmp)
+            .and_then(|mut file| {
+                file.write_all(&sealed)?;
+                file.sync_all()
+            })
+            .and_then(|()| fs::rename(&tmp, &self.path))
+            .with_context(|| format!("failed to write {}", self.path.display()))
+    }
+}
+
+fn password(confirm: bool) -> Result<Zeroizing<String>> {
+    seal::password("Checkpoint password", "BNET_CHECKPOINT_PASSWORD", confirm)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERIAL: &str = "US-2101-2345-6789";
+
+    fn open(path: &Path) -> Checkpoint {
+        Checkpoint::open_with(path, |_| Ok(Zeroizing::new("hunter2".to_owned()))).unwrap()
+    }
+
+    #[test]
+    fn opens_what_was_saved() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("checkpoint");
+        let mut checkpoint = open(&path);
+        checkpoint.set_bearer_token("bearer").unwrap();
+        checkpoint.mark_pending(SERIAL, "ABCDE12345").unwrap();
+        drop(checkpoint);
+
+        let sealed = fs::read(&path).unwrap();
+        assert!(!sealed.windows(10).any(|window| window == b"ABCDE12345"));
+        let checkpoint = open(&path);
+        assert_eq!(
+            checkpoint.bearer_token().as_deref().map(String::as_str),
+            Some("bearer")
+        );
+        let (serial, restore_code) = checkpoint.pending().unwrap();
+        assert_eq!(
+            (serial.as_str(), restore_code.as_str()),
+            (SERIAL, "ABCDE12345")
+        );
+    }
+
+    #[test]
+    fn refuses_a_wrong_password() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("checkpoint");
+        open(&path);
+
+        let wrong = |_| Ok(Zeroizing::new("hunter3".to_owned()));
+        let err = Checkpoint::open_with(&path, wrong).err().unwrap();
+        assert!(
+            format!("{err:#}").contains("password may be wrong"),
+            "{err:#}"
+        );
+    }
+
+    #[test]
+    fn bearer_token_expires() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut checkpoint = open(&dir.path().join("checkpoint"));
+        checkpoint.set_bearer_token("bearer").unwrap();
+
+        checkpoint.state.bearer_token_at = Some(now() - BEARER_TOKEN_TTL + 60);
+        assert!(checkpoint.bearer_token().is_some());
+        checkpoint.state.bearer_token_at = Some(now() - BEARER_TOKEN_TTL);
+        assert!(checkpoint.bearer_token().is_none());
+    }
+
+    #[test]
+    fn pending_is_the_unfinished_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut checkpoint = open(&dir.path().join("checkpoint"));
+        checkpoint
+            .mark_pending("EU-2101-2345-6789", "BCDEF23456")
+            .unwrap();
+        checkpoint.mark_done("EU-2101-2345-6789").unwrap();
+        checkpoint.mark_pending(SERIAL, "ABCDE12345").unwrap();
+
+        assert!(checkpoint.is_done("EU-2101-2345-6789"));
+        assert_eq!(checkpoint.pending().unwrap().0, SERIAL);
+    }
+
+    #[test]
+    fn finish_keeps_the_file_while_an_entry_is_pending() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("checkpoint");
+        let mut checkpoint = open(&path);
+        checkpoint.mark_pending(SERIAL, "ABCDE12345").unwrap();
+        assert_eq!(checkpoint.finish().unwrap(), None);
+        assert!(path.exists());
+
+        let mut checkpoint = open(&path);
+        checkpoint.mark_done(SERIAL).unwrap();
+        assert_eq!(checkpoint.finish().unwrap(), Some(path.clone()));
+        assert!(!path.exists());
+    }
+}
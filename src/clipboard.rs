@@ -0,0 +1,39 @@
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use arboard::Clipboard;
+
+use bnet_auth_export::token::find_session_token;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Poll the clipboard until something containing a session token is copied.
+pub fn watch() -> Result<String> {
+    let mut clipboard = Clipboard::new().context("failed to access the clipboard")?;
+
+    // Whatever was copied before we started may be a stale token, so wait for a change.
+    let mut last = clipboard.get_text().unwrap_or_default();
+
+    println!(
+        "Watching the clipboard. Copy the ST= value or the whole address bar URL (Ctrl-C to stop)."
+    );
+    loop {
+        thread::sleep(POLL_INTERVAL);
+
+        let Ok(text) = clipboard.get_text() else {
+            continue;
+        };
+        if text == last {
+            continue;
+        }
+
+        if let Some(token) = find_session_token(&text) {
+            // Don't leave the token lying around for other applications.
+            let _ = clipboard.clear();
+            println!("Picked up a session token from the clipboard.");
+            return Ok(token);
+        }
+        last = text;
+    }
+}
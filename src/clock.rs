@@ -0,0 +1,101 @@
+use web_time::{SystemTime, UNIX_EPOCH};
+
+use reqwest::header::{DATE, HeaderMap};
+
+/// Beyond this many seconds the user's authenticator app is likely to show wrong codes.
+pub const MAX_SKEW: i64 = 5;
+
+/// Seconds the server's clock is ahead of ours, from a response `Date` header.
+pub fn offset_from_headers(headers: &HeaderMap) -> Option<i64> {
+    let date = headers.get(DATE)?.to_str().ok()?;
+    let server = httpdate::parse_http_date(date).ok()?;
+    // httpdate speaks std's SystemTime, which cannot read the clock on WebAssembly.
+    let server = server.duration_since(std::time::UNIX_EPOCH).ok()?.as_secs();
+    let local = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+
+    Some(i64::try_from(server).ok()? - i64::try_from(local).ok()?)
+}
+
+/// Warn, as a [`NOTICES`](crate::NOTICES) event, when the local clock is far enough off to break
+/// generated codes.
+pub fn warn_if_skewed(offset: i64) {
+    if offset.abs() <= MAX_SKEW {
+        return;
+    }
+
+    let direction = if offset > 0 { "behind" } else { "ahead of" };
+    tracing::warn!(
+        target: crate::NOTICES,
+        "your clock is {}s {direction} Battle.net's. Codes shown here are corrected for it, but authenticator apps on this device will be off until you fix the system time.",
+        offset.abs()
+    );
+}
+
+/// `secs` since the Unix epoch in RFC 3339, UTC, like `2026-10-14T07:38:00Z`.
+pub fn utc_timestamp(secs: u64) -> String {
+    let (days, secs) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since the epoch, after Howard Hinnant's `civil_from_days`.
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    fn date_header(offset: i64) -> HeaderMap {
+        let now = std::time::SystemTime::now();
+        let server = if offset >= 0 {
+            now + std::time::Duration::from_secs(offset.unsigned_abs())
+        } else {
+            now - std::time::Duration::from_secs(offset.unsigned_abs())
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            DATE,
+            HeaderValue::from_str(&httpdate::fmt_http_date(server)).unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn measures_a_clock_behind_or_ahead_of_the_server() {
+        // Whole seconds either way, and a second may tick over in between.
+        let behind = offset_from_headers(&date_header(120)).unwrap();
+        assert!((119..=121).contains(&behind), "{behind}");
+        let ahead = offset_from_headers(&date_header(-120)).unwrap();
+        assert!((-121..=-119).contains(&ahead), "{ahead}");
+    }
+
+    #[test]
+    fn ignores_a_malformed_date() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(offset_from_headers(&headers), None);
+        headers.insert(DATE, HeaderValue::from_static("yesterday, around noon"));
+        assert_eq!(offset_from_headers(&headers), None);
+    }
+
+    #[test]
+    fn formats_timestamps_in_utc() {
+        assert_eq!(utc_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(utc_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(utc_timestamp(1_791_963_480), "2026-10-14T07:38:00Z");
+    }
+}
@@ -0,0 +1,169 @@
+// --store aws, gcp and azure: writing the export to a cloud provider's secrets manager, for
+// infrastructure that already reads its TOTP seeds from there. Each talks to the provider's REST
+// API directly, with the credentials its own CLI would use.
+
+#[cfg(feature = "aws")]
+pub mod aws;
+#[cfg(feature = "azure")]
+pub mod azure;
+#[cfg(feature = "gcp")]
+pub mod gcp;
+
+#[cfg(any(feature = "gcp", feature = "azure"))]
+use std::env;
+#[cfg(any(feature = "gcp", feature = "azure"))]
+use std::io::ErrorKind;
+#[cfg(any(feature = "gcp", feature = "azure"))]
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use clap::Args;
+use reqwest::blocking::{Client, RequestBuilder};
+use serde_json::Value;
+use zeroize::Zeroizing;
+
+use bnet_auth_export::otpauth::OtpAuth;
+
+use crate::store;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Args)]
+pub struct CloudOptions {
+    /// Name of the secret that --store aws, gcp or azure writes. `<serial>` is replaced with the
+    /// authenticator serial.
+    #[arg(
+        long,
+        env = "BNET_SECRET_NAME",
+        default_value = "bnet-auth-export-<serial>"
+    )]
+    pub secret_name: String,
+
+    /// AWS region of the Secrets Manager that --store aws writes to.
+    #[cfg(feature = "aws")]
+    #[arg(long, env = "AWS_REGION")]
+    pub aws_region: Option<String>,
+
+    /// Secrets Manager endpoint, for VPC endpoints. Defaults to the region's.
+    #[cfg(feature = "aws")]
+    #[arg(long, env = "AWS_ENDPOINT_URL_SECRETSMANAGER")]
+    pub aws_endpoint_url: Option<String>,
+
+    /// GCP project whose Secret Manager --store gcp writes to.
+    #[cfg(feature = "gcp")]
+    #[arg(long, env = "GOOGLE_CLOUD_PROJECT")]
+    pub gcp_project: Option<String>,
+
+    /// Secret Manager endpoint, for Private Service Connect or a regional endpoint.
+    #[cfg(feature = "gcp")]
+    #[arg(
+        long,
+        env = "BNET_GCP_ENDPOINT",
+        default_value = "https://secretmanager.googleapis.com"
+    )]
+    pub gcp_endpoint: String,
+
+    /// Name or URL of the Azure Key Vault that --store azure writes to.
+    #[cfg(feature = "azure")]
+    #[arg(long, env = "BNET_AZURE_VAULT")]
+    pub azure_vault: Option<String>,
+}
+
+impl CloudOptions {
+    fn secret_name(&self, serial: &str) -> String {
+        self.secret_name.replace("<serial>", serial)
+    }
+}
+
+// The secret as stored: the same fields as Vault's, as one JSON document.
+fn secret_value(export: &OtpAuth) -> Result<(String, Zeroizing<String>)> {
+    let Some(serial) = &export.serial else {
+        bail!("the secret is named after the serial, and this export has none");
+    };
+    let value = serde_json::to_string(&store::fields(serial, export))
+        .context("failed to encode the secret")?;
+    Ok((serial.clone(), Zeroizing::new(value)))
+}
+
+fn client() -> Result<Client> {
+    Client::builder()
+        .timeout(TIMEOUT)
+        .build()
+        .context("failed to set up the HTTP client")
+}
+
+// An OAuth access token from `env`, or else printed by the provider's CLI.
+#[cfg(any(feature = "gcp", feature = "azure"))]
+fn access_token(env: &str, program: &str, args: &[&str]) -> Result<Zeroizing<String>> {
+    if let Ok(token) = env::var(env) {
+        return Ok(Zeroizing::new(token));
+    }
+
+    let output = match Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+    {
+        Ok(output) => output,
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            bail!("no access token: set {env}, or install {program} and log in with it")
+        }
+        Err(err) => return Err(err).with_context(|| format!("failed to run {program}")),
+    };
+    if !output.status.success() {
+        bail!("{program} {} failed with {}", args.join(" "), output.status);
+    }
+    let token = Zeroizing::new(String::from_utf8_lossy(&output.stdout).trim().to_owned());
+    if token.is_empty() {
+        bail!("{program} printed no access token");
+    }
+    Ok(token)
+}
+
+// Send a request and decode the reply. Failures carry the provider's error message, and the
+// status so callers can tell a missing secret from the rest.
+fn send(request: RequestBuilder, what: &str) -> Result<Value, SendError> {
+    let response = request.send().map_err(|err| {
+        SendError::Other(anyhow::Error::new(err).context(format!("{what} failed")))
+    })?;
+    let status = response.status();
+    let body: Value = response.json().unwrap_or(Value::Null);
+    if status.is_success() {
+        return Ok(body);
+    }
+
+    // GCP and Azure nest the message under `error`, AWS uses `message` and `__type`.
+    let message = body["error"]["message"]
+        .as_str()
+        .or_else(|| body["message"].as_str())
+        .or_else(|| body["Message"].as_str())
+        .or_else(|| body["__type"].as_str())
+        .unwrap_or("no details");
+    Err(SendError::Status {
+        status,
+        kind: body["__type"].as_str().map(str::to_owned),
+        error: anyhow::anyhow!("{what} failed with {status}: {message}"),
+    })
+}
+
+// Only AWS looks at the error type, and only AWS and GCP at the status.
+#[cfg_attr(not(all(feature = "aws", feature = "gcp")), allow(dead_code))]
+enum SendError {
+    Status {
+        status: reqwest::StatusCode,
+        // AWS's error type, e.g. `ResourceNotFoundException`.
+        kind: Option<String>,
+        error: anyhow::Error,
+    },
+    Other(anyhow::Error),
+}
+
+impl From<SendError> for anyhow::Error {
+    fn from(err: SendError) -> Self {
+        match err {
+            SendError::Status { error, .. } | SendError::Other(error) => error,
+        }
+    }
+}
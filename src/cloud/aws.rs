@@ -0,0 +1,332 @@
+// AWS Secrets Manager, with requests signed with Signature Version 4. Credentials come from the
+// AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY and AWS_SESSION_TOKEN variables, or else the
+// AWS_PROFILE (or default) profile in ~/.aws/credentials.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Result, anyhow, bail};
+use hmac::{Hmac, Mac};
+use reqwest::Url;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use bnet_auth_export::clock;
+use bnet_auth_export::otpauth::OtpAuth;
+
+use super::{CloudOptions, SendError, client, secret_value, send};
+
+const SERVICE: &str = "secretsmanager";
+
+struct Credentials {
+    access_key_id: String,
+    secret_access_key: Zeroizing<String>,
+    session_token: Option<Zeroizing<String>>,
+}
+
+// Store the export as a new version of the secret, creating the secret the first time.
+pub fn save(options: &CloudOptions, export: &OtpAuth) -> Result<()> {
+    let (serial, value) = secret_value(export)?;
+    let name = options.secret_name(&serial);
+    let Some(region) = options
+        .aws_region
+        .clone()
+        .or_else(|| env::var("AWS_DEFAULT_REGION").ok())
+    else {
+        bail!("choose the AWS region with --aws-region or AWS_REGION");
+    };
+    let endpoint = match &options.aws_endpoint_url {
+        Some(url) => url.trim_end_matches('/').to_owned(),
+        None => format!("https://{SERVICE}.{region}.amazonaws.com"),
+    };
+    let credentials = credentials()?;
+    let client = client()?;
+    let call = |target: &str, body: &Value| {
+        let body = Zeroizing::new(body.to_string());
+        let request = signed(&client, &endpoint, &region, &credentials, target, &body)?;
+        send(request, &format!("AWS {target}"))
+    };
+
+    let put = json!({
+        "SecretId": name,
+        "SecretString": *value,
+        "ClientRequestToken": request_token()?,
+    });
+    let response = match call("PutSecretValue", &put) {
+        Err(SendError::Status {
+            kind: Some(kind), ..
+        }) if kind.ends_with("ResourceNotFoundException") => {
+            let create = json!({
+                "Name": name,
+                "SecretString": *value,
+                "ClientRequestToken": request_token()?,
+                "Description": format!("Battle.net authenticator {serial}, from bnet-auth-export"),
+            });
+            call("CreateSecret", &create)?
+        }
+        response => response?,
+    };
+
+    let arn = response["ARN"].as_str().unwrap_or(&name);
+    println!("\nSaved to AWS Secrets Manager as {arn}.");
+    Ok(())
+}
+
+// A Secrets Manager JSON request for `target`, signed for `region`.
+fn signed(
+    client: &reqwest::blocking::Client,
+    endpoint: &str,
+    region: &str,
+    credentials: &Credentials,
+    target: &str,
+    body: &str,
+) -> Result<reqwest::blocking::RequestBuilder, SendError> {
+    let url = Url::parse(&format!("{endpoint}/"))
+        .map_err(|err| SendError::Other(anyhow!("invalid AWS endpoint {endpoint}: {err}")))?;
+    let host = match (url.host_str(), url.port()) {
+        (Some(host), Some(port)) => format!("{host}:{port}"),
+        (Some(host), None) => host.to_owned(),
+        (None, _) => {
+            return Err(SendError::Other(anyhow!(
+                "invalid AWS endpoint {endpoint}: no host"
+            )));
+        }
+    };
+
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    // 2026-10-14T07:38:00Z becomes 20261014T073800Z.
+    let amz_date: String = clock::utc_timestamp(secs)
+        .chars()
+        .filter(|c| *c != '-' && *c != ':')
+        .collect();
+    let date = &amz_date[..8];
+    let target = format!("secretsmanager.{target}");
+
+    let mut headers = vec![
+        ("content-type", "application/x-amz-json-1.1".to_owned()),
+        ("host", host),
+        ("x-amz-date", amz_date.clone()),
+    ];
+    if let Some(session_token) = &credentials.session_token {
+        headers.push(("x-amz-security-token", session_token.to_string()));
+    }
+    headers.push(("x-amz-target", target));
+
+    let authorization = authorization(
+        &headers,
+        body,
+        date,
+        &amz_date,
+        region,
+        &credentials.access_key_id,
+        &credentials.secret_access_key,
+    );
+    let mut request = client.post(url).body(body.to_owned());
+    for (name, value) in headers.into_iter().filter(|(name, _)| *name != "host") {
+        request = request.header(name, value);
+    }
+    Ok(request.header("authorization", authorization))
+}
+
+// The Signature Version 4 `Authorization` header for a POST to `/` with these headers, which
+// must be lowercase and sorted by name.
+fn authorization(
+    headers: &[(&str, String)],
+    body: &str,
+    date: &str,
+    amz_date: &str,
+    region: &str,
+    access_key_id: &str,
+    secret_access_key: &str,
+) -> String {
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{name}:{}\n", value.trim()))
+        .collect();
+    let canonical_request = format!(
+        "POST\n/\n\n{canonical_headers}\n{signed_headers}\n{}",
+        hex::encode(Sha256::digest(body))
+    );
+
+    let scope = format!("{date}/{region}/{SERVICE}/aws4_request");
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+        hex::encode(Sha256::digest(&canonical_request))
+    );
+    let key = signing_key(secret_access_key, date, region, SERVICE);
+    let signature = hex::encode(hmac(&key, &string_to_sign));
+
+    format!(
+        "AWS4-HMAC-SHA256 Credential={access_key_id}/{scope}, SignedHeaders={signed_headers}, Signature={signature}"
+    )
+}
+
+fn signing_key(
+    secret_access_key: &str,
+    date: &str,
+    region: &str,
+    service: &str,
+) -> Zeroizing<Vec<u8>> {
+    let key = Zeroizing::new(format!("AWS4{secret_access_key}"));
+    let key = hmac(key.as_bytes(), date);
+    let key = hmac(&key, region);
+    let key = hmac(&key, service);
+    hmac(&key, "aws4_request")
+}
+
+fn hmac(key: &[u8], data: &str) -> Zeroizing<Vec<u8>> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(data.as_bytes());
+    Zeroizing::new(mac.finalize().into_bytes().to_vec())
+}
+
+// Raw API calls, unlike the SDKs, have to bring their own idempotency token: a random UUID.
+fn request_token() -> Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|err| anyhow!("failed to generate a request token: {err}"))?;
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = hex::encode(bytes);
+    Ok(format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    ))
+}
+
+fn credentials() -> Result<Credentials> {
+    if let (Ok(access_key_id), Ok(secret_access_key)) = (
+        env::var("AWS_ACCESS_KEY_ID"),
+        env::var("AWS_SECRET_ACCESS_KEY"),
+    ) {
+        return Ok(Credentials {
+            access_key_id,
+            secret_access_key: Zeroizing::new(secret_access_key),
+            session_token: env::var("AWS_SESSION_TOKEN").ok().map(Zeroizing::new),
+        });
+    }
+
+    let profile = env::var("AWS_PROFILE").unwrap_or_else(|_| "default".to_owned());
+    let path = env::var_os("AWS_SHARED_CREDENTIALS_FILE")
+        .map(PathBuf::from)
+        .or_else(|| {
+            env::var_os("HOME")
+                .or_else(|| env::var_os("USERPROFILE"))
+                .map(|home| PathBuf::from(home).join(".aws").join("credentials"))
+        });
+    let file = path.and_then(|path| fs::read_to_string(path).ok());
+    match file
+        .as_deref()
+        .and_then(|file| from_profile(file, &profile))
+    {
+        Some(credentials) => Ok(credentials),
+        None => bail!(
+            "no AWS credentials: set AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY, or add the {profile} profile to ~/.aws/credentials"
+        ),
+    }
+}
+
+// The keys of one `[profile]` section of an AWS credentials file.
+fn from_profile(file: &str, profile: &str) -> Option<Credentials> {
+    let mut in_profile = false;
+    let mut access_key_id = None;
+    let mut secret_access_key = None;
+    let mut session_token = None;
+    for line in file.lines().map(str::trim) {
+        if let Some(section) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            in_profile = section.trim() == profile;
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        if !in_profile {
+            continue;
+        }
+        let value = value.trim().to_owned();
+        match key.trim() {
+            "aws_access_key_id" => access_key_id = Some(value),
+            "aws_secret_access_key" => secret_access_key = Some(Zeroizing::new(value)),
+            "aws_session_token" => session_token = Some(Zeroizing::new(value)),
+            _ => {}
+        }
+    }
+    Some(Credentials {
+        access_key_id: access_key_id?,
+        secret_access_key: secret_access_key?,
+        session_token,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The key derivation example from the Signature Version 4 documentation.
+    #[test]
+    fn derives_the_documented_signing_key() {
+        let key = signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20120215",
+            "us-east-1",
+            "iam",
+        );
+        assert_eq!(
+            hex::encode(&*key),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+    }
+
+    #[test]
+    fn signs_every_header_it_sends() {
+        let headers = vec![
+            ("content-type", "application/x-amz-json-1.1".to_owned()),
+            ("host", "secretsmanager.us-east-1.amazonaws.com".to_owned()),
+            ("x-amz-date", "20150830T123600Z".to_owned()),
+            ("x-amz-target", "secretsmanager.ListSecrets".to_owned()),
+        ];
+        let authorization = authorization(
+            &headers,
+            "{}",
+            "20150830",
+            "20150830T123600Z",
+            "us-east-1",
+            "AKIDEXAMPLE",
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+        );
+        assert!(authorization.starts_with(
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/secretsmanager/aws4_request, SignedHeaders=content-type;host;x-amz-date;x-amz-target, Signature="
+        ));
+    }
+
+    #[test]
+    fn reads_the_chosen_profile() {
+        let file = "[default]\naws_access_key_id = A\naws_secret_access_key = B\n\n[work]\naws_access_key_id=C\naws_secret_access_key=D\naws_session_token=E\n";
+
+        let credentials = from_profile(file, "work").unwrap();
+        assert_eq!(credentials.access_key_id, "C");
+        assert_eq!(*credentials.secret_access_key, "D");
+        assert_eq!(
+            credentials.session_token.as_deref().map(String::as_str),
+            Some("E")
+        );
+        assert!(from_profile(file, "missing").is_none());
+    }
+}
@@ -0,0 +1,70 @@
+// Azure Key Vault, with the access token from AZURE_ACCESS_TOKEN or
+// `az account get-access-token`.
+
+use anyhow::{Result, bail};
+use serde_json::json;
+
+use bnet_auth_export::otpauth::OtpAuth;
+
+use super::{CloudOptions, access_token, client, secret_value, send};
+
+const API_VERSION: &str = "7.4";
+
+// Set the secret, which adds a version when it already exists.
+pub fn save(options: &CloudOptions, export: &OtpAuth) -> Result<()> {
+    let (serial, value) = secret_value(export)?;
+    let name = options.secret_name(&serial);
+    let Some(vault) = &options.azure_vault else {
+        bail!("choose the Key Vault with --azure-vault or BNET_AZURE_VAULT");
+    };
+    let token = access_token(
+        "AZURE_ACCESS_TOKEN",
+        "az",
+        &[
+            "account",
+            "get-access-token",
+            "--resource",
+            "https://vault.azure.net",
+            "--query",
+            "accessToken",
+            "--output",
+            "tsv",
+        ],
+    )?;
+
+    let response = send(
+        client()?
+            .put(format!("{}/secrets/{name}", vault_url(vault)))
+            .query(&[("api-version", API_VERSION)])
+            .bearer_auth(token.as_str())
+            .json(&json!({ "value": *value, "contentType": "application/json" })),
+        "Azure Key Vault",
+    )?;
+
+    let id = response["id"].as_str().unwrap_or(&name);
+    println!("\nSaved to Azure Key Vault as {id}.");
+    Ok(())
+}
+
+// A bare vault name is in the public cloud, other clouds need the full URL.
+fn vault_url(vault: &str) -> String {
+    if vault.contains("://") {
+        vault.trim_end_matches('/').to_owned()
+    } else {
+        format!("https://{vault}.vault.azure.net")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vault_names_are_in_the_public_cloud() {
+        assert_eq!(vault_url("team-otp"), "https://team-otp.vault.azure.net");
+        assert_eq!(
+            vault_url("https://team-otp.vault.usgovcloudapi.net/"),
+            "https://team-otp.vault.usgovcloudapi.net"
+        );
+    }
+}
@@ -0,0 +1,59 @@
+// GCP Secret Manager, with the access token from GOOGLE_OAUTH_ACCESS_TOKEN or
+// `gcloud auth print-access-token`.
+
+use anyhow::{Result, bail};
+use data_encoding::BASE64;
+use reqwest::StatusCode;
+use serde_json::json;
+
+use bnet_auth_export::otpauth::OtpAuth;
+
+use super::{CloudOptions, SendError, access_token, client, secret_value, send};
+
+// Add the export as a new version of the secret, creating the secret the first time.
+pub fn save(options: &CloudOptions, export: &OtpAuth) -> Result<()> {
+    let (serial, value) = secret_value(export)?;
+    let name = options.secret_name(&serial);
+    let Some(project) = &options.gcp_project else {
+        bail!("choose the GCP project with --gcp-project or GOOGLE_CLOUD_PROJECT");
+    };
+    let token = access_token(
+        "GOOGLE_OAUTH_ACCESS_TOKEN",
+        "gcloud",
+        &["auth", "print-access-token"],
+    )?;
+    let client = client()?;
+    let secrets = format!(
+        "{}/v1/projects/{project}/secrets",
+        options.gcp_endpoint.trim_end_matches('/')
+    );
+    let body = json!({ "payload": { "data": BASE64.encode(value.as_bytes()) } });
+    let add_version = || {
+        send(
+            client
+                .post(format!("{secrets}/{name}:addVersion"))
+                .bearer_auth(token.as_str())
+                .json(&body),
+            "GCP addVersion",
+        )
+    };
+
+    let response = match add_version() {
+        Err(SendError::Status { status, .. }) if status == StatusCode::NOT_FOUND => {
+            send(
+                client
+                    .post(&secrets)
+                    .query(&[("secretId", &name)])
+                    .bearer_auth(token.as_str())
+                    .json(&json!({ "replication": { "automatic": {} } })),
+                "GCP secret creation",
+            )?;
+            add_version()?
+        }
+        response => response?,
+    };
+
+    let version = response["name"].as_str().unwrap_or(&name);
+    println!("\nSaved to GCP Secret Manager as {version}.");
+    Ok(())
+}
@@ -0,0 +1,163 @@
+// `code --serve`: the current code over HTTP on a loopback address, for login automation and
+// launcher scripts that would otherwise each need the secret. Every request has to carry the
+// shared token, so other local users and web pages can't read codes from it.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow, bail};
+use data_encoding::BASE64URL_NOPAD;
+use serde_json::json;
+use zeroize::Zeroizing;
+
+use bnet_auth_export::totp;
+
+const MAX_HEAD: usize = 8 * 1024;
+
+pub fn run(
+    addr: SocketAddr,
+    token: Option<Zeroizing<String>>,
+    secret: &[u8],
+    clock_offset: i64,
+) -> Result<()> {
+    if !addr.ip().is_loopback() {
+        bail!(
+            "--serve only listens on loopback addresses such as 127.0.0.1 or [::1], not {}",
+            addr.ip()
+        );
+    }
+    let token = match token {
+        Some(token) if !token.is_empty() => token,
+        Some(_) => bail!("BNET_CODE_TOKEN is empty"),
+        None => {
+            let token = random_token()?;
+            eprintln!("No BNET_CODE_TOKEN set, generated one for this run:");
+            eprintln!("{}", *token);
+            token
+        }
+    };
+    let listener =
+        TcpListener::bind(addr).with_context(|| format!("failed to listen on {addr}"))?;
+    let addr = listener.local_addr().unwrap_or(addr);
+    eprintln!("Serving the current code at http://{addr}/code until Ctrl-C.");
+    eprintln!("Send the token as `Authorization: Bearer <token>`, e.g.:");
+    eprintln!("  curl -H \"Authorization: Bearer $BNET_CODE_TOKEN\" http://{addr}/code");
+
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        if let Err(err) = handle(stream, &token, secret, clock_offset) {
+            eprintln!("warning: request failed: {err:#}");
+        }
+    }
+    Ok(())
+}
+
+fn handle(mut stream: TcpStream, token: &str, secret: &[u8], clock_offset: i64) -> Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let (method, target, authorization) = read_head(&stream)?;
+
+    if !authorization.is_some_and(|value| {
+        value
+            .strip_prefix("Bearer ")
+            .is_some_and(|presented| same(presented.trim(), token))
+    }) {
+        // A wrong token costs a second, so it can't be guessed quickly.
+        thread::sleep(Duration::from_secs(1));
+        return respond(
+            &mut stream,
+            "401 Unauthorized",
+            "text/plain",
+            "Unauthorized.\n",
+        );
+    }
+    if method != "GET" {
+        return respond(
+            &mut stream,
+            "405 Method Not Allowed",
+            "text/plain",
+            "Use GET.\n",
+        );
+    }
+
+    let now = totp::now_with_offset(clock_offset);
+    let code = totp::code(secret, now);
+    let remaining = totp::remaining(now);
+    match target.as_str() {
+        "/" | "/code" => respond(&mut stream, "200 OK", "text/plain", &format!("{code}\n")),
+        "/code.json" => {
+            let body = json!({ "code": code, "remaining": remaining }).to_string();
+            respond(&mut stream, "200 OK", "application/json", &body)
+        }
+        _ => respond(&mut stream, "404 Not Found", "text/plain", "Not found.\n"),
+    }
+}
+
+// The method, target and Authorization header of a request.
+fn read_head(stream: &TcpStream) -> Result<(String, String, Option<String>)> {
+    let mut reader = BufReader::new(stream).take(MAX_HEAD as u64);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut start = line.split_whitespace();
+    let method = start.next().unwrap_or_default().to_owned();
+    let target = start.next().unwrap_or_default().to_owned();
+
+    let mut authorization = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            bail!("connection closed in the middle of the request");
+        }
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.trim().eq_ignore_ascii_case("authorization")
+        {
+            authorization = Some(value.trim().to_owned());
+        }
+    }
+    Ok((method, target, authorization))
+}
+
+fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &str) -> Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\ncontent-type: {content_type}; charset=utf-8\r\ncontent-length: {}\r\ncache-control: no-store\r\nconnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    Ok(())
+}
+
+// Compares without stopping at the first difference, so timing doesn't give the token away.
+fn same(presented: &str, token: &str) -> bool {
+    presented.len() == token.len()
+        && presented
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn random_token() -> Result<Zeroizing<String>> {
+    let mut bytes = Zeroizing::new([0u8; 32]);
+    getrandom::getrandom(bytes.as_mut())
+        .map_err(|err| anyhow!("failed to generate a token: {err}"))?;
+    Ok(Zeroizing::new(BASE64URL_NOPAD.encode(bytes.as_ref())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_must_match_exactly() {
+        assert!(same("s3cret", "s3cret"));
+        assert!(!same("s3cre", "s3cret"));
+        assert!(!same("s3creT", "s3cret"));
+    }
+}
@@ -0,0 +1,234 @@
+// `compare`: tell whether two exports, of any provenance, hold the same credential. Each side is
+// an otpauth URI, a hex device secret or a file in any format `convert` reads. Parameters that
+// would make the codes differ despite the same secret are pointed out, and fail the comparison
+// like a different secret does.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use reqwest::Url;
+use zeroize::Zeroizing;
+
+use bnet_auth_export::memory::Locked;
+use bnet_auth_export::otpauth::OtpAuth;
+use bnet_auth_export::totp;
+
+use crate::convert;
+
+// One side of the comparison, with the code parameters as written: `None` when the input doesn't
+// say, as with a bare hex secret.
+struct Credential {
+    export: OtpAuth,
+    digits: Option<String>,
+    algorithm: Option<String>,
+    period: Option<String>,
+    // What it was read as, for the report.
+    source: String,
+}
+
+pub fn run(a: &str, b: &str) -> Result<()> {
+    let a = read(a)?;
+    let b = read(b)?;
+    println!("A: {}, {}:{}", a.source, a.export.issuer, a.export.account);
+    println!("B: {}, {}:{}", b.source, b.export.issuer, b.export.account);
+
+    let same_secret = *a.export.secret == *b.export.secret;
+    println!(
+        "\nSecret:    {}",
+        if same_secret { "same" } else { "DIFFERENT" }
+    );
+    match (&a.export.serial, &b.export.serial) {
+        (Some(a), Some(b)) if a == b => println!("Serial:    same, {a}"),
+        (Some(a), Some(b)) => println!("Serial:    DIFFERENT, {a} and {b}"),
+        (Some(serial), None) | (None, Some(serial)) => {
+            println!("Serial:    {serial}, the other doesn't say")
+        }
+        (None, None) => println!("Serial:    neither says"),
+    }
+    let mismatches = mismatches(&a, &b);
+    for (name, _, a, b) in params(&a, &b) {
+        let line = match (a, b) {
+            (Some(a), Some(b)) if a.eq_ignore_ascii_case(b) => format!("same, {a}"),
+            (Some(a), Some(b)) => format!("DIFFERENT, {a} and {b}"),
+            (Some(value), None) | (None, Some(value)) => format!("{value}, the other doesn't say"),
+            (None, None) => "neither says".to_owned(),
+        };
+        println!("{:<10} {line}", format!("{name}:"));
+    }
+
+    if !same_secret {
+        bail!("A and B hold different credentials: their codes will never match");
+    }
+    if !mismatches.is_empty() {
+        bail!(
+            "A and B hold the same secret, but their {} differ, so one of them shows wrong codes. Battle.net's are {} digits, SHA1 and a {}s period",
+            mismatches.join(" and "),
+            totp::DIGITS,
+            totp::PERIOD
+        );
+    }
+    println!("\nA and B hold the same credential.");
+    Ok(())
+}
+
+// Each code parameter's name, as a heading and in a sentence, and its values.
+type Param<'a> = (&'static str, &'static str, Option<&'a str>, Option<&'a str>);
+
+fn params<'a>(a: &'a Credential, b: &'a Credential) -> [Param<'a>; 3] {
+    [
+        ("Digits", "digits", a.digits.as_deref(), b.digits.as_deref()),
+        (
+            "Algorithm",
+            "algorithms",
+            a.algorithm.as_deref(),
+            b.algorithm.as_deref(),
+        ),
+        (
+            "Period",
+            "periods",
+            a.period.as_deref(),
+            b.period.as_deref(),
+        ),
+    ]
+}
+
+// The parameters both sides give, differently.
+fn mismatches(a: &Credential, b: &Credential) -> Vec<&'static str> {
+    params(a, b)
+        .into_iter()
+        .filter_map(|(_, name, a, b)| match (a, b) {
+            (Some(a), Some(b)) if !a.eq_ignore_ascii_case(b) => Some(name),
+            _ => None,
+        })
+        .collect()
+}
+
+fn read(input: &str) -> Result<Credential> {
+    let path = Path::new(input);
+    if path.exists() {
+        return read_file(path);
+    }
+    let input = input.trim();
+    if input.starts_with("otpauth://") {
+        return read_uri(input, "otpauth URI".to_owned());
+    }
+    let compact: Zeroizing<String> =
+        Zeroizing::new(input.chars().filter(|c| !c.is_whitespace()).collect());
+    if let Ok(secret) = hex::decode(&*compact)
+        && !secret.is_empty()
+    {
+        return Ok(Credential {
+            export: OtpAuth {
+                serial: None,
+                secret: Locked::new(Zeroizing::new(secret)),
+                issuer: "Battle.net".to_owned(),
+                account: "(no label)".to_owned(),
+            },
+            digits: None,
+            algorithm: None,
+            period: None,
+            source: "hex secret".to_owned(),
+        });
+    }
+    bail!("{input:?} is not a file, an otpauth URI or a hex secret")
+}
+
+// A file of otpauth URIs is read as written, so that wrong parameters show up instead of failing
+// the import. Other formats only hold Battle.net's parameters.
+fn read_file(path: &Path) -> Result<Credential> {
+    let name = path.display().to_string();
+    if path.is_file() {
+        let data =
+            Zeroizing::new(fs::read(path).with_context(|| format!("failed to read {name}"))?);
+        if let Ok(text) = std::str::from_utf8(&data) {
+            let uris: Vec<&str> = text
+                .lines()
+                .map(str::trim)
+                .filter(|line| line.starts_with("otpauth://totp/"))
+                .collect();
+            if text
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .count()
+                == uris.len()
+                && let [uri] = uris[..]
+            {
+                return read_uri(uri, name);
+            }
+        }
+    }
+
+    let (format, mut exports) = convert::read_format(None, Some(path))?;
+    if exports.len() != 1 {
+        bail!(
+            "{name} holds {} authenticators, compare one at a time",
+            exports.len()
+        );
+    }
+    Ok(Credential {
+        export: exports.remove(0),
+        digits: Some(totp::DIGITS.to_string()),
+        algorithm: Some("SHA1".to_owned()),
+        period: Some(totp::PERIOD.to_string()),
+        source: format!("{name} ({format})"),
+    })
+}
+
+// The code parameters, then the rest checked as any otpauth URI is, with Battle.net's in their
+// place. Unset parameters are the defaults authenticator apps assume.
+fn read_uri(uri: &str, source: String) -> Result<Credential> {
+    let mut url = Url::parse(uri).context("not a valid otpauth URI")?;
+    let param = |name: &str, default: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map_or_else(|| default.to_owned(), |(_, value)| value.into_owned())
+    };
+    let digits = param("digits", "6");
+    let algorithm = param("algorithm", "SHA1").to_ascii_uppercase();
+    let period = param("period", "30");
+
+    let mut rest: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(key, _)| !matches!(key.as_ref(), "digits" | "algorithm" | "period"))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    rest.push(("digits".to_owned(), totp::DIGITS.to_string()));
+    url.query_pairs_mut().clear().extend_pairs(rest);
+    let export = OtpAuth::parse(&Zeroizing::new(url.to_string()))?;
+    Ok(Credential {
+        export,
+        digits: Some(digits),
+        algorithm: Some(algorithm),
+        period: Some(period),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn points_out_parameter_mismatches() {
+        const SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        let exported = read(&format!(
+            "otpauth://totp/Battle.net:US-1234-5678-9012?secret={SECRET}&digits=8&period=30"
+        ))
+        .unwrap();
+        let imported = read(&format!(
+            "otpauth://totp/Battle.net:me@example.com?secret={SECRET}&algorithm=sha1"
+        ))
+        .unwrap();
+        assert_eq!(*exported.export.secret, *imported.export.secret);
+        assert_eq!(exported.export.serial.as_deref(), Some("US-1234-5678-9012"));
+        // Left unset, the app shows 6 digits.
+        assert_eq!(mismatches(&exported, &imported), ["digits"]);
+
+        let hex = read("3132333435363738393031323334353637383930").unwrap();
+        assert_eq!(*hex.export.secret, *exported.export.secret);
+        assert!(mismatches(&hex, &imported).is_empty());
+        assert!(read("not a secret").is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use std::fmt;
+
+use anyhow::{Result, bail};
+
+// How well an app handles Battle.net's 8-digit, SHA1, 30 second codes.
+#[derive(Clone, Copy)]
+enum Support {
+    Works,
+    Caution,
+}
+
+impl fmt::Display for Support {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Works => "works",
+            Self::Caution => "caution",
+        })
+    }
+}
+
+struct App {
+    name: &'static str,
+    support: Support,
+    notes: &'static str,
+}
+
+const APPS: &[App] = &[
+    App {
+        name: "Aegis",
+        support: Support::Works,
+        notes: "Reads digits, algorithm and period from the URI.",
+    },
+    App {
+        name: "2FAS",
+        support: Support::Works,
+        notes: "Reads digits from the URI. When adding manually, set 8 digits under advanced settings.",
+    },
+    App {
+        name: "Bitwarden",
+        support: Support::Works,
+        notes: "Paste the whole otpauth URI into the authenticator key field, a bare secret defaults to 6 digits.",
+    },
+    App {
+        name: "1Password",
+        support: Support::Works,
+        notes: "Paste the whole otpauth URI, a bare secret defaults to 6 digits.",
+    },
+    App {
+        name: "KeePassXC",
+        support: Support::Works,
+        notes: "Use custom settings when adding manually: 8 digits, SHA1, 30 seconds.",
+    },
+    App {
+        name: "Ente Auth",
+        support: Support::Works,
+        notes: "Reads digits from the URI.",
+    },
+    App {
+        name: "FreeOTP",
+        support: Support::Works,
+        notes: "Reads digits from the URI.",
+    },
+    App {
+        name: "Google Authenticator",
+        support: Support::Caution,
+        notes: "Older versions ignore the digits parameter and show 6-digit codes. Update first and compare codes.",
+    },
+    App {
+        name: "Microsoft Authenticator",
+        support: Support::Caution,
+        notes: "Has been reported to show 6-digit codes for 8-digit accounts. Compare codes before relying on it.",
+    },
+    App {
+        name: "Authy",
+        support: Support::Caution,
+        notes: "Handles digits inconsistently across versions and cannot export accounts later. Compare codes before relying on it.",
+    },
+];
+
+// Show what is known about authenticator apps, or one app matching `query`.
+pub fn run(query: Option<&str>) -> Result<()> {
+    let apps: Vec<&App> = match query {
+        Some(query) => {
+            let query = query.to_lowercase();
+            APPS.iter()
+                .filter(|app| app.name.to_lowercase().contains(&query))
+                .collect()
+        }
+        None => APPS.iter().collect(),
+    };
+    if apps.is_empty() {
+        bail!(
+            "no notes for {:?}. Make sure the app supports 8 digits, SHA1 and a 30 second period, and check a code with `bnet-auth-export code`",
+            query.unwrap_or_default()
+        );
+    }
+
+    println!("Battle.net codes have 8 digits, use SHA1 and rotate every 30 seconds.");
+    for app in apps {
+        println!("\n{} ({})", app.name, app.support);
+        println!("  {}", app.notes);
+    }
+    println!(
+        "\nWhatever the app, compare its code with `bnet-auth-export code` before removing the official app."
+    );
+
+    Ok(())
+}
@@ -0,0 +1,105 @@
+// `convert`: turn an export from this tool or another app into any --format, without network
+// access.
+
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use zeroize::Zeroizing;
+
+use bnet_auth_export::otpauth::{LabelOptions, OtpAuth};
+
+use crate::format::{self, Importer};
+
+pub fn run(
+    from: Option<&str>,
+    to: &str,
+    input: Option<&Path>,
+    labels: &LabelOptions,
+    reveal: bool,
+) -> Result<()> {
+    let mut exports = read(from, input)?;
+    for export in &mut exports {
+        export.relabel(labels);
+    }
+
+    // Several exports only fit in separate files, since the formats hold one authenticator each.
+    if exports.len() > 1 && to != "all" {
+        bail!(
+            "the input holds {} authenticators, convert them with --to all to get a file each",
+            exports.len()
+        );
+    }
+    for export in &exports {
+        format::write(to, export, reveal)?;
+    }
+    Ok(())
+}
+
+// Every authenticator in `input`, or stdin when it is omitted or `-`, in the format `from` names
+// or the one detected.
+pub fn read(from: Option<&str>, input: Option<&Path>) -> Result<Vec<OtpAuth>> {
+    Ok(read_format(from, input)?.1)
+}
+
+// As `read`, with the name of the format the input was read as.
+pub fn read_format(from: Option<&str>, input: Option<&Path>) -> Result<(String, Vec<OtpAuth>)> {
+    #[cfg(feature = "ios")]
+    if let Some(dir) = input.filter(|dir| format::ios::is_backup(dir)) {
+        return Ok(("ios".to_owned(), format::ios::read_backup(dir)?));
+    }
+    // A copy of the Android app's data directory is read through the preferences file in it.
+    #[cfg(feature = "android")]
+    let found;
+    #[cfg(feature = "android")]
+    let input = match input {
+        Some(dir) if dir.is_dir() => {
+            found = format::android::preferences_in(dir)?;
+            Some(found.as_path())
+        }
+        input => input,
+    };
+
+    let data = Zeroizing::new(match input {
+        Some(path) if path != Path::new("-") => {
+            fs::read(path).with_context(|| format!("failed to read {}", path.display()))?
+        }
+        _ => {
+            let mut data = Vec::new();
+            io::stdin()
+                .read_to_end(&mut data)
+                .context("failed to read stdin")?;
+            data
+        }
+    });
+
+    let importers = format::importers();
+    let importer = match from {
+        Some(name) => find(&importers, name)?,
+        None => detect(&importers, &data)?,
+    };
+    Ok((importer.name().to_owned(), importer.read(&data)?))
+}
+
+fn find<'a>(importers: &'a [Box<dyn Importer>], name: &str) -> Result<&'a dyn Importer> {
+    // `otpauth` reads more naturally than `uri` as the name of an input.
+    let name = if name == "otpauth" { "uri" } else { name };
+    match importers.iter().find(|importer| importer.name() == name) {
+        Some(importer) => Ok(importer.as_ref()),
+        None => {
+            let names: Vec<_> = importers.iter().map(|importer| importer.name()).collect();
+            bail!(
+                "cannot read {name}, --from takes one of: {}",
+                names.join(", ")
+            )
+        }
+    }
+}
+
+fn detect<'a>(importers: &'a [Box<dyn Importer>], data: &[u8]) -> Result<&'a dyn Importer> {
+    match importers.iter().find(|importer| importer.detect(data)) {
+        Some(importer) => Ok(importer.as_ref()),
+        None => bail!("could not tell what format the input is in, pass it with --from"),
+    }
+}
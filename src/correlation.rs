@@ -0,0 +1,53 @@
+//! IDs that tie an error message to the lines about the same request in the transcript and
+//! audit log, and to Blizzard's own trace IDs for it.
+
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use reqwest::header::HeaderMap;
+
+/// This run's ID: random, and the same for every request made by the process.
+pub fn run_id() -> &'static str {
+    static RUN_ID: OnceLock<String> = OnceLock::new();
+
+    RUN_ID.get_or_init(|| {
+        let mut bytes = [0; 4];
+        // Only used to tell runs apart, so a failing RNG can fall back to zeros.
+        let _ = getrandom::getrandom(&mut bytes);
+        hex::encode(bytes)
+    })
+}
+
+// The ID of the next request of this run, e.g. `5f3a9c21-2`. Retries keep the original ID.
+pub(crate) fn next_request_id() -> String {
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+
+    format!("{}-{}", run_id(), NEXT.fetch_add(1, Ordering::Relaxed))
+}
+
+// The trace and request ID headers a response came with, whatever the service behind it calls
+// them: X-Trace-Id, X-Request-Id, X-Amzn-Trace-Id, traceparent, CF-Ray and the like.
+pub(crate) fn trace_headers(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .filter(|(name, _)| {
+            let name = name.as_str();
+            name.contains("trace")
+                || name.contains("correlation")
+                || name.ends_with("request-id")
+                || name == "cf-ray"
+        })
+        .filter_map(|(name, value)| {
+            Some((name.as_str().to_owned(), value.to_str().ok()?.to_owned()))
+        })
+        .collect()
+}
+
+// How an error message names the request, e.g. `request 5f3a9c21-2, x-trace-id 0af76519`.
+pub(crate) fn describe(request_id: &str, headers: Option<&HeaderMap>) -> String {
+    let mut description = format!("request {request_id}");
+    for (name, value) in headers.map(trace_headers).unwrap_or_default() {
+        description.push_str(&format!(", {name} {value}"));
+    }
+    description
+}
@@ -0,0 +1,203 @@
+// Where a restored export goes when no option says: a checklist of the terminal, a file per
+// format and every --store, offered once the restore succeeded. Everything ticked gets the
+// export in one pass, so a second format doesn't take learning the flags or restoring again.
+// Whatever fails is offered again, ticked, to fix or choose another without restoring again.
+
+use anyhow::{Result, bail};
+#[cfg(feature = "store")]
+use clap::ValueEnum;
+
+use bnet_auth_export::otpauth::OtpAuth;
+
+use crate::format::{self, OutputOptions};
+#[cfg(feature = "store")]
+use crate::store::Store;
+
+enum Destination {
+    Print,
+    File(String),
+    #[cfg(feature = "store")]
+    Store(Store),
+}
+
+impl Destination {
+    fn label(&self, stem: &str) -> String {
+        match self {
+            Self::Print => "Print it in this terminal (--reveal)".to_owned(),
+            Self::File(name) => {
+                let exporters = format::registry();
+                let extension = exporters
+                    .iter()
+                    .find(|exporter| exporter.name() == name)
+                    .map_or(name.as_str(), |exporter| exporter.extension());
+                format!("Write {stem}.{extension} (--format {name})")
+            }
+            #[cfg(feature = "store")]
+            Self::Store(store) => {
+                let help = store
+                    .to_possible_value()
+                    .and_then(|value| value.get_help().map(ToString::to_string))
+                    .unwrap_or_default();
+                // "A HashiCorp Vault KV v2 path, see --vault-path." as "a HashiCorp Vault KV v2
+                // path".
+                let help = help.split(", see").next().unwrap_or_default();
+                let help = help.trim_end_matches('.');
+                let help = match help.split_once(' ') {
+                    Some((article @ ("A" | "The"), rest)) => {
+                        format!("{} {rest}", article.to_lowercase())
+                    }
+                    _ => help.to_owned(),
+                };
+                format!("Save to {help} (--store {})", store.name())
+            }
+        }
+    }
+}
+
+fn destinations() -> Vec<Destination> {
+    let mut destinations = vec![Destination::Print];
+    destinations.extend(
+        format::registry()
+            .iter()
+            .map(|exporter| Destination::File(exporter.name().to_owned())),
+    );
+    #[cfg(feature = "store")]
+    destinations.extend(
+        Store::value_variants()
+            .iter()
+            .copied()
+            .map(Destination::Store),
+    );
+    destinations
+}
+
+pub fn run(export: &OtpAuth, output: &OutputOptions, clock_offset: i64) -> Result<()> {
+    let destinations = destinations();
+    let stem = export.serial.as_deref().unwrap_or("bnet-auth-export");
+    let labels: Vec<String> = destinations
+        .iter()
+        .map(|destination| destination.label(stem))
+        .collect();
+    let mut checked = vec![false; destinations.len()];
+    let mut label = "\nWhere should the export go?";
+    let mut written = false;
+    loop {
+        choose(label, &labels, &mut checked)?;
+        if !checked.contains(&true) {
+            if written {
+                bail!("the export was not written everywhere chosen, see the errors above");
+            }
+            bail!("nothing was chosen, so the export went nowhere");
+        }
+
+        let mut print = false;
+        let mut failed = false;
+        for (at, destination) in destinations.iter().enumerate() {
+            if !checked[at] {
+                continue;
+            }
+            let done = match destination {
+                Destination::Print => {
+                    checked[at] = false;
+                    print = true;
+                    continue;
+                }
+                Destination::File(name) => {
+                    let wrote = format::write_files(name, export);
+                    crate::audit(&format!("format {name}"), export.serial.as_deref(), &wrote);
+                    wrote
+                }
+                #[cfg(feature = "store")]
+                Destination::Store(store) => output.save(*store, export),
+            };
+            match done {
+                Ok(()) => {
+                    checked[at] = false;
+                    written = true;
+                }
+                Err(err) => {
+                    eprintln!("{}: {err:#}", labels[at]);
+                    failed = true;
+                }
+            }
+        }
+        #[cfg(not(feature = "store"))]
+        let _ = output;
+
+        // Last, so the secret is cleared from the screen once it is also saved elsewhere.
+        if print {
+            crate::print_export(export);
+            crate::verify_export(export, clock_offset)?;
+            crate::clear_revealed_secret();
+            written = true;
+        }
+        if !failed {
+            return Ok(());
+        }
+        label = "\nSome destinations failed. Try them again, or another?";
+    }
+}
+
+// The checklist, or without line editing a numbered list to answer with the numbers.
+fn choose(label: &str, labels: &[String], checked: &mut [bool]) -> Result<()> {
+    #[cfg(feature = "line-editing")]
+    if !crate::plain() {
+        return crate::line_edit::checklist(label, labels, checked);
+    }
+
+    println!("{label}");
+    for (at, item) in labels.iter().enumerate() {
+        println!("{:>3}. {item}", at + 1);
+    }
+    let ticked: Vec<String> = (1..=labels.len())
+        .filter(|number| checked[number - 1])
+        .map(|number| number.to_string())
+        .collect();
+    let question = if ticked.is_empty() {
+        "Numbers, separated by commas: ".to_owned()
+    } else {
+        format!("Numbers, separated by commas [{}]: ", ticked.join(","))
+    };
+    loop {
+        let answer = crate::prompt(&question)?;
+        if answer.is_empty() && !ticked.is_empty() {
+            return Ok(());
+        }
+        match parse_numbers(&answer, labels.len()) {
+            Ok(chosen) => {
+                for (at, checked) in checked.iter_mut().enumerate() {
+                    *checked = chosen.contains(&at);
+                }
+                return Ok(());
+            }
+            Err(err) => eprintln!("{err}"),
+        }
+    }
+}
+
+// "1, 3 4" as the indexes of the first, third and fourth item.
+fn parse_numbers(answer: &str, count: usize) -> Result<Vec<usize>> {
+    answer
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|number| !number.is_empty())
+        .map(|number| match number.parse::<usize>() {
+            Ok(number) if (1..=count).contains(&number) => Ok(number - 1),
+            _ => bail!("{number:?} is not one of the numbers 1 to {count}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_numbers_chosen() {
+        assert_eq!(parse_numbers("1, 3 4", 5).unwrap(), [0, 2, 3]);
+        assert_eq!(parse_numbers("2,", 5).unwrap(), [1]);
+        assert!(parse_numbers("", 5).unwrap().is_empty());
+        assert!(parse_numbers("6", 5).is_err());
+        assert!(parse_numbers("0", 5).is_err());
+        assert!(parse_numbers("qr", 5).is_err());
+    }
+}
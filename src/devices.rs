@@ -0,0 +1,128 @@
+// Choosing which authenticator to restore when the account has more than one attached, instead
+// of making the user work out which serial is which.
+
+use anyhow::{Result, bail};
+use reqwest::StatusCode;
+use zeroize::Zeroizing;
+
+use bnet_auth_export::Error;
+use bnet_auth_export::api::{Api, Device};
+
+use crate::source::Credential;
+use crate::timings;
+
+// The authenticator picked from the account's, if there was a choice to make, and the bearer
+// token the list was fetched with, so the restore doesn't exchange the session token again. A
+// list that can't be fetched only means the serial is typed in as before, unless `auto` is set:
+// then the list is all there is to go on, so failures are errors and a lone authenticator is
+// picked without asking.
+pub fn choose(
+    api: &Api,
+    credential: &Credential,
+    auto: bool,
+) -> Result<(Option<Device>, Option<Zeroizing<String>>)> {
+    let bearer_token = match credential {
+        Credential::SessionToken(session_token) => {
+            match timings::time("SSO exchange", || api.exchange_session_token(session_token)) {
+                Ok(bearer_token) => bearer_token,
+                Err(err) if auto => return Err(err.into()),
+                // The restore makes the exchange again and reports the error properly.
+                Err(_) => return Ok((None, None)),
+            }
+        }
+        Credential::BearerToken(bearer_token) => bearer_token.clone(),
+    };
+    let devices = match timings::time("device list", || api.devices(&bearer_token)) {
+        Ok(devices) => devices,
+        Err(err) if auto => {
+            return Err(anyhow::Error::new(err).context(
+                "--auto needs the account's list of authenticators; run without --auto and enter the serial",
+            ));
+        }
+        // Not every deployment offers the list.
+        Err(Error::Api { status, .. }) if status == StatusCode::NOT_FOUND => {
+            return Ok((None, Some(bearer_token)));
+        }
+        Err(err) => {
+            eprintln!("warning: could not list the account's authenticators ({err})");
+            return Ok((None, Some(bearer_token)));
+        }
+    };
+    match devices.len() {
+        0 if auto => bail!("no authenticator is attached to this account"),
+        1 if auto => {
+            let device = devices.into_iter().next();
+            if let Some(device) = &device {
+                eprintln!("Authenticator Serial: {}", device.serial);
+            }
+            return Ok((device, Some(bearer_token)));
+        }
+        0 | 1 => return Ok((None, Some(bearer_token))),
+        _ if !crate::interactive() => bail!(
+            "this account has {} authenticators ({}), choose one in a terminal or run without --auto",
+            devices.len(),
+            devices
+                .iter()
+                .map(|device| device.serial.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        _ => {}
+    }
+
+    eprintln!("This account has {} authenticators:", devices.len());
+    for (index, device) in devices.iter().enumerate() {
+        eprintln!("  {}) {}", index + 1, describe(device));
+    }
+    loop {
+        let answer = crate::prompt(&format!(
+            "Restore which one? [1-{}, or Enter to type the serial]: ",
+            devices.len()
+        ))?;
+        if answer.is_empty() {
+            return Ok((None, Some(bearer_token)));
+        }
+        match answer.parse::<usize>() {
+            Ok(choice) if (1..=devices.len()).contains(&choice) => {
+                let device = devices[choice - 1].clone();
+                eprintln!("Authenticator Serial: {}", device.serial);
+                return Ok((Some(device), Some(bearer_token)));
+            }
+            _ => eprintln!("Enter a number from 1 to {}.", devices.len()),
+        }
+    }
+}
+
+fn describe(device: &Device) -> String {
+    let mut line = device.serial.clone();
+    if let Some(kind) = &device.kind {
+        line.push_str(&format!("  {kind}"));
+    }
+    if let Some(attached_at) = &device.attached_at {
+        line.push_str(&format!("  attached {attached_at}"));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_what_the_api_gives() {
+        let mut device = Device {
+            serial: "US-2101-2345-6789".to_owned(),
+            kind: None,
+            attached_at: None,
+            restore_code: None,
+        };
+        assert_eq!(describe(&device), "US-2101-2345-6789");
+
+        device.kind = Some("mobile".to_owned());
+        device.attached_at = Some("2021-03-04".to_owned());
+        assert_eq!(
+            describe(&device),
+            "US-2101-2345-6789  mobile  attached 2021-03-04"
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+// The handful of fields Blizzard uses across its OAuth and REST error bodies.
+#[derive(Default, Deserialize)]
+struct ErrorBody {
+    error: Option<String>,
+    error_description: Option<String>,
+    #[serde(alias = "errorCode")]
+    code: Option<serde_json::Value>,
+    #[serde(alias = "detail")]
+    message: Option<String>,
+}
+
+impl ErrorBody {
+    // Everything the server told us, lowercased, for keyword matching.
+    fn haystack(&self) -> String {
+        let code = self.code.as_ref().map(|code| match code {
+            serde_json::Value::String(code) => code.clone(),
+            other => other.to_string(),
+        });
+        [
+            self.error.clone(),
+            self.error_description.clone(),
+            code,
+            self.message.clone(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+    }
+
+    // A compact one-line summary of the fields that were present.
+    fn summary(&self) -> Option<String> {
+        let mut parts = Vec::new();
+        if let Some(error) = &self.error {
+            parts.push(format!("error={error}"));
+        }
+        if let Some(code) = &self.code {
+            parts.push(format!("code={code}"));
+        }
+        if let Some(description) = self.error_description.as_ref().or(self.message.as_ref()) {
+            parts.push(format!("\"{description}\""));
+        }
+        (!parts.is_empty()).then(|| parts.join(", "))
+    }
+}
+
+// Turn a failed API response into a message a user can act on.
+pub fn diagnose(what: &str, status: StatusCode, body: &[u8]) -> String {
+    let parsed = serde_json::from_slice::<ErrorBody>(body).ok();
+    let hint = hint(
+        what,
+        status,
+        &parsed.as_ref().map(ErrorBody::haystack).unwrap_or_default(),
+    );
+
+    let details = match parsed.as_ref().and_then(ErrorBody::summary) {
+        Some(summary) => summary,
+        None => {
+            let raw = String::from_utf8_lossy(body);
+            let raw = raw.trim();
+            if raw.is_empty() {
+                "empty response body".to_owned()
+            } else {
+                raw.to_owned()
+            }
+        }
+    };
+
+    match hint {
+        Some(hint) => format!("{what} failed with {status}: {hint}\n(server said: {details})"),
+        None => format!("{what} failed with {status}: {details}"),
+    }
+}
+
+fn hint(what: &str, status: StatusCode, haystack: &str) -> Option<&'static str> {
+    let mentions = |needles: &[&str]| needles.iter().any(|needle| haystack.contains(needle));
+
+    if status == StatusCode::TOO_MANY_REQUESTS || mentions(&["rate limit", "too many"]) {
+        return Some("Battle.net is rate limiting requests, wait a few minutes and try again");
+    }
+    if mentions(&["invalid_client", "unauthorized_client"]) {
+        return Some(
+            "the OAuth client_id was rejected, check --client-id or look for a newer release",
+        );
+    }
+    if mentions(&["invalid_token", "invalid_grant", "expired"]) {
+        return Some(
+            "the session token is invalid or expired, log in again and copy a fresh ST= value",
+        );
+    }
+    if mentions(&["keyfob", "physical", "hardware"]) {
+        return Some(
+            "this is a physical keyfob authenticator, whose secret can't be exported; only the mobile app's can",
+        );
+    }
+    if mentions(&["restore"]) {
+        return Some(
+            "restore code incorrect, note that codes never contain the letters I, L, O or S",
+        );
+    }
+    if what == "restore request"
+        && (mentions(&["serial", "not found", "not_found"]) || status == StatusCode::NOT_FOUND)
+    {
+        return Some(
+            "no authenticator with that serial is attached to this account, double-check the serial and account",
+        );
+    }
+    if status.is_server_error() {
+        return Some("Battle.net had an internal error, try again later");
+    }
+    if what == "SSO token exchange" && status.is_client_error() {
+        return Some(
+            "the session token was not accepted, it may have expired: tokens only last a few minutes and stop working when you log out, so log in again and copy a fresh ST= value",
+        );
+    }
+    if what == "restore request" && status == StatusCode::BAD_REQUEST {
+        return Some(
+            "the serial or restore code was rejected, check both for typos (restore codes never contain I, L, O or S)",
+        );
+    }
+
+    None
+}
@@ -0,0 +1,111 @@
+use std::io;
+use std::path::PathBuf;
+
+use reqwest::StatusCode;
+
+/// Everything the library can fail with, so callers can react to the kind of failure
+/// instead of matching on messages.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// A serial, restore code, secret, URI or option is malformed.
+    #[error("{0}")]
+    InvalidInput(String),
+
+    /// Blizzard rejected the session or bearer token. Fetching a fresh token may help.
+    #[error("{what} was rejected with {status}: the token has expired or is invalid ({request})")]
+    TokenExpired {
+        what: &'static str,
+        status: StatusCode,
+        /// The request's ID and Blizzard's trace IDs for it, see [`crate::correlation`].
+        request: String,
+    },
+
+    /// The SSO or authorization code exchange failed for another reason. This and the other
+    /// failed responses below name the request at the end of their message.
+    #[error("{message}")]
+    SsoFailed { status: StatusCode, message: String },
+
+    /// The restore request was refused, usually because of a wrong serial or restore code.
+    #[error("{message}")]
+    RestoreRejected { status: StatusCode, message: String },
+
+    /// Battle.net is rate limiting requests. Waiting a few minutes usually helps.
+    #[error("{message}")]
+    RateLimited { what: &'static str, message: String },
+
+    /// Any other request returned an error status.
+    #[error("{message}")]
+    Api {
+        what: &'static str,
+        status: StatusCode,
+        message: String,
+    },
+
+    /// The request did not complete: DNS, connect, TLS, a timeout or a dropped connection.
+    #[error("request failed for {what}{}", request.as_ref().map(|request| format!(" ({request})")).unwrap_or_default())]
+    Network {
+        what: &'static str,
+        /// The request's ID, when a request was made.
+        request: Option<String>,
+        #[source]
+        source: reqwest::Error,
+    },
+
+    /// A response or file could not be understood.
+    #[error("failed to parse {what}: {detail}")]
+    Parse { what: String, detail: String },
+
+    /// A local file could not be read or written.
+    #[error("failed to access {}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+}
+
+impl Error {
+    /// A short, stable name for the kind of failure, e.g. `tokenExpired`, for logs and
+    /// machine-readable output.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::InvalidInput(_) => "invalidInput",
+            Self::TokenExpired { .. } => "tokenExpired",
+            Self::SsoFailed { .. } => "ssoFailed",
+            Self::RestoreRejected { .. } => "restoreRejected",
+            Self::RateLimited { .. } => "rateLimited",
+            Self::Api { .. } => "api",
+            Self::Network { .. } => "network",
+            Self::Parse { .. } => "parse",
+            Self::Io { .. } => "io",
+        }
+    }
+
+    // The message followed by its causes, which say what actually went wrong for network and
+    // IO errors, for bindings that cannot walk the source chain themselves.
+    #[cfg(any(feature = "ffi", feature = "python"))]
+    pub(crate) fn full_message(&self) -> String {
+        use std::error::Error as _;
+
+        let mut message = self.to_string();
+        let mut source = self.source();
+        while let Some(inner) = source {
+            message.push_str(": ");
+            message.push_str(&inner.to_string());
+            source = inner.source();
+        }
+        message
+    }
+}
+
+/// `Result` defaulting to this crate's [`Error`].
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+// Return early with `Error::InvalidInput`, like `anyhow::bail!`.
+macro_rules! invalid_input {
+    ($($arg:tt)*) => {
+        return Err($crate::error::Error::InvalidInput(format!($($arg)*)))
+    };
+}
+pub(crate) use invalid_input;
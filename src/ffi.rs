@@ -0,0 +1,263 @@
+//! A small C interface to the export flow, for GUI wrappers written in other languages.
+//!
+//! Functions returning a pointer return NULL on failure; `bnet_last_error` and
+//! `bnet_last_error_kind` then describe what went wrong on the calling thread. Strings returned
+//! by this interface are owned by the caller and must be released with `bnet_string_free`.
+//! The header is generated with `cbindgen --config cbindgen.toml --output include/bnet_auth_export.h`.
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char};
+use std::ptr;
+
+use clap::ValueEnum;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::api::{Api, ApiOptions};
+use crate::error::{Error, Result};
+use crate::otpauth::{LabelOptions, OtpAuth};
+use crate::region::Region;
+use crate::{restore, token};
+
+/// The kind of the last failure, mirroring the library's `Error` variants.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub enum BnetErrorKind {
+    None,
+    InvalidInput,
+    TokenExpired,
+    SsoFailed,
+    RestoreRejected,
+    RateLimited,
+    Api,
+    Network,
+    Parse,
+    Io,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<(BnetErrorKind, CString)>> = const { RefCell::new(None) };
+}
+
+/// A Battle.net API client. Create it with `bnet_api_new` and release it with `bnet_api_free`.
+pub struct BnetApi(Api);
+
+/// Create a client for `region` ("us", "eu", "kr", "tw" or "cn"), or NULL to detect the region
+/// from the session token.
+///
+/// # Safety
+///
+/// `region` must be NULL or a valid NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bnet_api_new(region: *const c_char) -> *mut BnetApi {
+    let result = (|| {
+        // SAFETY: guaranteed by the caller.
+        let region = match unsafe { optional_str(region) }? {
+            Some(region) => Some(
+                Region::from_str(region, true)
+                    .map_err(|_| Error::InvalidInput(format!("unknown region {region}")))?,
+            ),
+            None => None,
+        };
+        Api::new(ApiOptions {
+            region,
+            ..ApiOptions::default()
+        })
+    })();
+
+    match result {
+        Ok(api) => {
+            LAST_ERROR.set(None);
+            Box::into_raw(Box::new(BnetApi(api)))
+        }
+        Err(err) => fail(err),
+    }
+}
+
+/// Release a client created with `bnet_api_new`. Passing NULL does nothing.
+///
+/// # Safety
+///
+/// `api` must be NULL or a pointer returned by `bnet_api_new` that has not been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bnet_api_free(api: *mut BnetApi) {
+    if !api.is_null() {
+        // SAFETY: guaranteed by the caller.
+        drop(unsafe { Box::from_raw(api) });
+    }
+}
+
+/// Exchange a session token, in any form the CLI accepts, for a bearer token.
+///
+/// # Safety
+///
+/// `api` must come from `bnet_api_new` and `session_token` must be a valid NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bnet_exchange_token(
+    api: *const BnetApi,
+    session_token: *const c_char,
+) -> *mut c_char {
+    let result = (|| {
+        // SAFETY: guaranteed by the caller.
+        let api = unsafe { client(api) }?;
+        // SAFETY: guaranteed by the caller.
+        let session_token = unsafe { required_str(session_token, "session token") }?;
+        let session_token = Zeroizing::new(token::normalize_session_token(session_token));
+
+        api.exchange_session_token(&session_token)
+    })();
+
+    respond(result)
+}
+
+/// Restore an authenticator with a bearer token and return its hex device secret.
+///
+/// # Safety
+///
+/// `api` must come from `bnet_api_new` and the strings must be valid and NUL-terminated.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bnet_restore_secret(
+    api: *const BnetApi,
+    bearer_token: *const c_char,
+    serial: *const c_char,
+    restore_code: *const c_char,
+) -> *mut c_char {
+    let result = (|| {
+        // SAFETY: guaranteed by the caller.
+        let api = unsafe { client(api) }?;
+        // SAFETY: guaranteed by the caller.
+        let bearer_token = unsafe { required_str(bearer_token, "bearer token") }?;
+        // SAFETY: guaranteed by the caller.
+        let serial = unsafe { required_str(serial, "serial") }?;
+        // SAFETY: guaranteed by the caller.
+        let restore_code = unsafe { required_str(restore_code, "restore code") }?;
+
+        let serial = api.region_for(None).normalize_serial(serial)?;
+        let restore_code = restore::normalize_restore_code(restore_code)?;
+        api.device_secret(bearer_token, &serial, &restore_code)
+    })();
+
+    respond(result)
+}
+
+/// Build the otpauth URI for a serial and hex device secret.
+///
+/// # Safety
+///
+/// Both strings must be valid and NUL-terminated.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bnet_build_otpauth(
+    serial: *const c_char,
+    device_secret: *const c_char,
+) -> *mut c_char {
+    let result = (|| {
+        // SAFETY: guaranteed by the caller.
+        let serial = unsafe { required_str(serial, "serial") }?;
+        // SAFETY: guaranteed by the caller.
+        let device_secret = unsafe { required_str(device_secret, "device secret") }?;
+
+        let serial = Region::from_serial(serial)
+            .unwrap_or_default()
+            .normalize_serial(serial)?;
+        let secret = hex::decode(device_secret.trim()).map_err(|err| Error::Parse {
+            what: "deviceSecret".to_owned(),
+            detail: err.to_string(),
+        })?;
+        let export = OtpAuth::new(serial, Zeroizing::new(secret), &LabelOptions::default());
+        Ok(export.to_uri())
+    })();
+
+    respond(result)
+}
+
+/// Zeroize and release a string returned by this interface. Passing NULL does nothing.
+///
+/// # Safety
+///
+/// `value` must be NULL or a string returned by this interface that has not been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bnet_string_free(value: *mut c_char) {
+    if !value.is_null() {
+        // SAFETY: guaranteed by the caller.
+        unsafe { CString::from_raw(value) }.into_bytes().zeroize();
+    }
+}
+
+/// The message of the last failure on this thread, or NULL. Valid until the next call.
+#[unsafe(no_mangle)]
+pub extern "C" fn bnet_last_error() -> *const c_char {
+    LAST_ERROR.with_borrow(|last| {
+        last.as_ref()
+            .map_or(ptr::null(), |(_, message)| message.as_ptr())
+    })
+}
+
+/// The kind of the last failure on this thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn bnet_last_error_kind() -> BnetErrorKind {
+    LAST_ERROR.with_borrow(|last| last.as_ref().map_or(BnetErrorKind::None, |(kind, _)| *kind))
+}
+
+// Hand a string to the caller, or record the error and return NULL.
+fn respond(result: Result<Zeroizing<String>>) -> *mut c_char {
+    let value = match result {
+        Ok(value) => value,
+        Err(err) => return fail(err),
+    };
+    match CString::new(value.as_bytes()) {
+        Ok(value) => {
+            LAST_ERROR.set(None);
+            value.into_raw()
+        }
+        Err(_) => fail(Error::Parse {
+            what: "response".to_owned(),
+            detail: "contains a NUL byte".to_owned(),
+        }),
+    }
+}
+
+fn fail<T>(err: Error) -> *mut T {
+    let kind = match &err {
+        Error::InvalidInput(_) => BnetErrorKind::InvalidInput,
+        Error::TokenExpired { .. } => BnetErrorKind::TokenExpired,
+        Error::SsoFailed { .. } => BnetErrorKind::SsoFailed,
+        Error::RestoreRejected { .. } => BnetErrorKind::RestoreRejected,
+        Error::RateLimited { .. } => BnetErrorKind::RateLimited,
+        Error::Api { .. } => BnetErrorKind::Api,
+        Error::Network { .. } => BnetErrorKind::Network,
+        Error::Parse { .. } => BnetErrorKind::Parse,
+        Error::Io { .. } => BnetErrorKind::Io,
+    };
+
+    let message = CString::new(err.full_message().replace('\0', "")).unwrap_or_default();
+
+    LAST_ERROR.set(Some((kind, message)));
+    ptr::null_mut()
+}
+
+// SAFETY: `api` must be NULL or come from `bnet_api_new`.
+unsafe fn client<'a>(api: *const BnetApi) -> Result<&'a Api> {
+    // SAFETY: guaranteed by the caller.
+    match unsafe { api.as_ref() } {
+        Some(api) => Ok(&api.0),
+        None => Err(Error::InvalidInput("no API client given".to_owned())),
+    }
+}
+
+// SAFETY: `value` must be NULL or a valid NUL-terminated string.
+unsafe fn optional_str<'a>(value: *const c_char) -> Result<Option<&'a str>> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    // SAFETY: guaranteed by the caller.
+    let value = unsafe { CStr::from_ptr(value) };
+    value
+        .to_str()
+        .map(Some)
+        .map_err(|_| Error::InvalidInput("argument is not valid UTF-8".to_owned()))
+}
+
+// SAFETY: `value` must be NULL or a valid NUL-terminated string.
+unsafe fn required_str<'a>(value: *const c_char, what: &str) -> Result<&'a str> {
+    // SAFETY: guaranteed by the caller.
+    unsafe { optional_str(value) }?.ok_or_else(|| Error::InvalidInput(format!("no {what} given")))
+}
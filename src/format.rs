@@ -0,0 +1,660 @@
+// Output formats beyond the default printout. Each built-in format is its own module
+// implementing `Exporter`, and `Importer` when `convert` can read it back; plugins on PATH
+// implement `Exporter` too.
+
+mod aegis;
+#[cfg(feature = "android")]
+pub mod android;
+#[cfg(feature = "encrypted-backups")]
+pub mod backup;
+mod bitwarden;
+#[cfg(feature = "ios")]
+pub mod ios;
+mod json;
+#[cfg(feature = "qr")]
+mod qr;
+mod stratum;
+mod text;
+mod twofas;
+mod uri;
+#[cfg(feature = "winauth")]
+mod winauth;
+
+#[cfg(unix)]
+use std::fs::File;
+use std::io::{self, IsTerminal, Write};
+#[cfg(unix)]
+use std::os::fd::{FromRawFd, RawFd};
+#[cfg(feature = "encrypted-backups")]
+use std::sync::Mutex;
+
+use anyhow::{Context, Result, bail};
+use clap::{Args, ValueEnum};
+use serde::Serialize;
+use zeroize::Zeroizing;
+
+use bnet_auth_export::memory::Locked;
+use bnet_auth_export::otpauth::OtpAuth;
+use bnet_auth_export::region::Region;
+use bnet_auth_export::{restore, secure_file, totp};
+
+#[cfg(any(feature = "aws", feature = "gcp", feature = "azure"))]
+use crate::cloud::CloudOptions;
+#[cfg(feature = "plugins")]
+use crate::plugin;
+#[cfg(feature = "store")]
+use crate::store::{self, Store};
+#[cfg(feature = "systemd-creds")]
+use crate::systemd_creds::CredsOptions;
+#[cfg(feature = "vault")]
+use crate::vault::VaultOptions;
+#[cfg(feature = "yubikey")]
+use crate::yubikey::YubikeyOptions;
+
+#[derive(Args)]
+pub struct OutputOptions {
+    /// Write the export to stdout in this format instead of the summary, into a terminal only
+    /// with --reveal, or `all` to write a file per format to the current directory. See
+    /// `bnet-auth-export formats`.
+    #[arg(long, env = "BNET_FORMAT")]
+    pub format: Option<String>,
+
+    /// Save the export here instead of printing it. Print one saved in the keyring again with
+    /// `bnet-auth-export show`.
+    #[cfg(feature = "store")]
+    #[arg(long, value_enum, conflicts_with = "format")]
+    pub store: Option<Store>,
+
+    #[cfg(feature = "vault")]
+    #[command(flatten)]
+    pub vault: VaultOptions,
+
+    #[cfg(any(feature = "aws", feature = "gcp", feature = "azure"))]
+    #[command(flatten)]
+    pub cloud: CloudOptions,
+
+    #[cfg(feature = "yubikey")]
+    #[command(flatten)]
+    pub yubikey: YubikeyOptions,
+
+    #[cfg(feature = "systemd-creds")]
+    #[command(flatten)]
+    pub creds: CredsOptions,
+
+    /// Write the otpauth URI, or the --format given, to this file descriptor inherited from the
+    /// calling script instead, e.g. 3 with `3>&1 >/dev/null` in the shell.
+    #[cfg(unix)]
+    #[arg(long, value_name = "FD")]
+    #[cfg_attr(feature = "store", arg(conflicts_with = "store"))]
+    #[cfg_attr(feature = "batch", arg(conflicts_with = "batch"))]
+    pub output_fd: Option<RawFd>,
+
+    /// Print only these values, one per line in the order given, instead of the usual summary.
+    /// Repeat it or separate them with commas.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FIELD",
+        value_delimiter = ',',
+        conflicts_with = "format"
+    )]
+    #[cfg_attr(feature = "store", arg(conflicts_with = "store"))]
+    #[cfg_attr(feature = "batch", arg(conflicts_with = "batch"))]
+    pub print: Vec<Field>,
+
+    /// Print the secret, Base32 secret and otpauth URI in the terminal, also with --format,
+    /// --print and `hex`. Without it they only go to files, pipes or --store.
+    #[arg(long)]
+    pub reveal: bool,
+}
+
+impl OutputOptions {
+    // Fail before any work is done when the secret would have nowhere to go.
+    pub fn require_destination(&self) -> Result<()> {
+        #[cfg(feature = "store")]
+        if self.store.is_some() {
+            return Ok(());
+        }
+        #[cfg(unix)]
+        if let Some(fd) = self.output_fd {
+            return check_fd(fd, self.format.as_deref());
+        }
+        if let Some(name) = self.format.as_deref().filter(|&name| name != "all") {
+            return check_stdout(self.reveal, &format!("--format {name}"));
+        }
+        if !self.print.is_empty() {
+            return self.check_print();
+        }
+        if self.reveal || self.format.is_some() {
+            return Ok(());
+        }
+
+        #[cfg(feature = "store")]
+        bail!(
+            "choose where the secret goes: --store {}, --format <name> (see `bnet-auth-export formats`), --print <field>, or --reveal to print it in this terminal",
+            Store::value_variants()
+                .iter()
+                .map(|store| store.name())
+                .collect::<Vec<_>>()
+                .join(" or ")
+        );
+        #[cfg(not(feature = "store"))]
+        bail!(
+            "choose where the secret goes: --format <name> (see `bnet-auth-export formats`), --print <field>, or --reveal to print it in this terminal"
+        );
+    }
+
+    // Write the export where the options ask for. Returns false when the caller should print it.
+    pub fn write(&self, export: &OtpAuth) -> Result<bool> {
+        #[cfg(feature = "store")]
+        if let Some(store) = self.store {
+            return self.save(store, export).map(|()| true);
+        }
+        #[cfg(unix)]
+        if let Some(fd) = self.output_fd {
+            let written = write_fd(fd, self.format.as_deref(), &self.print, export);
+            crate::audit(
+                &format!("output fd {fd}"),
+                export.serial.as_deref(),
+                &written,
+            );
+            return written.map(|()| true);
+        }
+        if !self.print.is_empty() {
+            let printed = self
+                .check_print()
+                .and_then(|()| fields(&self.print, export))
+                .and_then(|output| {
+                    let mut stdout = io::stdout().lock();
+                    stdout
+                        .write_all(&output)
+                        .and_then(|()| stdout.flush())
+                        .context("failed to write to stdout")
+                });
+            crate::audit("print", export.serial.as_deref(), &printed);
+            return printed.map(|()| true);
+        }
+        let Some(name) = &self.format else {
+            return Ok(false);
+        };
+        let written = write(name, export, self.reveal);
+        crate::audit(
+            &format!("format {name}"),
+            export.serial.as_deref(),
+            &written,
+        );
+        written.map(|()| true)
+    }
+
+    // --print only shows the secret-bearing fields in a terminal with --reveal.
+    fn check_print(&self) -> Result<()> {
+        if self.print.iter().any(Field::is_secret) {
+            check_stdout(self.reveal, "--print")?;
+        }
+        Ok(())
+    }
+
+    // Save the export to `store`, with the options given for it.
+    #[cfg(feature = "store")]
+    pub fn save(&self, store: Store, export: &OtpAuth) -> Result<()> {
+        let what = format!("store {}", store.name());
+        let saved = crate::timings::time(&what, || {
+            store::save(
+                store,
+                export,
+                #[cfg(feature = "vault")]
+                &self.vault,
+                #[cfg(any(feature = "aws", feature = "gcp", feature = "azure"))]
+                &self.cloud,
+                #[cfg(feature = "yubikey")]
+                &self.yubikey,
+                #[cfg(feature = "systemd-creds")]
+                &self.creds,
+            )
+        });
+        crate::audit(&what, export.serial.as_deref(), &saved);
+        saved
+    }
+
+    // Whether any option says where the export goes, which otherwise is asked after the
+    // restore.
+    pub fn has_destination(&self) -> bool {
+        #[cfg(feature = "store")]
+        if self.store.is_some() {
+            return true;
+        }
+        #[cfg(unix)]
+        if self.output_fd.is_some() {
+            return true;
+        }
+        self.reveal || self.format.is_some() || !self.print.is_empty()
+    }
+
+    // As `require_destination`, for runs that export more than one account and so can't print
+    // them.
+    #[cfg(feature = "batch")]
+    pub fn require_file_destination(&self) -> Result<()> {
+        if !self.has_file_destination() {
+            bail!(
+                "--batch writes each account to its own files, choose the format with --format <name> or `all`"
+            );
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "batch")]
+    pub fn has_file_destination(&self) -> bool {
+        #[cfg(feature = "store")]
+        if self.store.is_some() {
+            return true;
+        }
+        self.format.is_some()
+    }
+
+    // Like `write`, but a single format also goes to `<serial>.<ext>` rather than stdout.
+    #[cfg(feature = "batch")]
+    pub fn write_file(&self, export: &OtpAuth) -> Result<()> {
+        #[cfg(feature = "store")]
+        if self.store.is_some() {
+            return self.write(export).map(drop);
+        }
+        let Some(name) = &self.format else {
+            bail!("no --format to write");
+        };
+        let written = write_files(name, export);
+        crate::audit(
+            &format!("format {name}"),
+            export.serial.as_deref(),
+            &written,
+        );
+        written
+    }
+}
+
+// A value --print can pick out of the export.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Field {
+    /// The Base32 secret, as authenticator apps take it.
+    Secret,
+    /// The hex device secret.
+    DeviceSecret,
+    /// The otpauth URI.
+    Uri,
+    Serial,
+    RestoreCode,
+}
+
+impl Field {
+    // Whether the value is enough to generate codes or restore the authenticator, and so only
+    // goes to a terminal with --reveal.
+    fn is_secret(&self) -> bool {
+        !matches!(self, Field::Serial)
+    }
+}
+
+// The chosen values, each on its own line.
+fn fields(fields: &[Field], export: &OtpAuth) -> Result<Zeroizing<Vec<u8>>> {
+    let mut output = Zeroizing::new(String::new());
+    for field in fields {
+        let value = match field {
+            Field::Secret => export.base32_secret(),
+            Field::DeviceSecret => export.hex_secret(),
+            Field::Uri => export.to_uri(),
+            Field::Serial | Field::RestoreCode => {
+                let Some(serial) = &export.serial else {
+                    bail!("this export has no serial, so no serial or restore code to print");
+                };
+                Zeroizing::new(match field {
+                    Field::Serial => serial.clone(),
+                    _ => restore::restore_code(serial, &export.secret),
+                })
+            }
+        };
+        output.push_str(&value);
+        output.push('\n');
+    }
+    Ok(Zeroizing::new(output.as_bytes().to_vec()))
+}
+
+// Everything a format needs to know about an export. Plugins receive this as JSON on stdin, so
+// fields are only ever added.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportPayload {
+    pub version: u32,
+    pub serial: Option<String>,
+    pub restore_code: Option<String>,
+    pub device_secret: Zeroizing<String>,
+    pub base32_secret: Zeroizing<String>,
+    pub uri: Zeroizing<String>,
+    pub issuer: String,
+    pub account: String,
+    pub digits: u32,
+    pub period: u64,
+    pub algorithm: &'static str,
+}
+
+impl ExportPayload {
+    pub fn new(export: &OtpAuth) -> Self {
+        Self {
+            version: 1,
+            serial: export.serial.clone(),
+            restore_code: export
+                .serial
+                .as_deref()
+                .map(|serial| restore::restore_code(serial, &export.secret)),
+            device_secret: export.hex_secret(),
+            base32_secret: export.base32_secret(),
+            uri: export.to_uri(),
+            issuer: export.issuer.clone(),
+            account: export.account.clone(),
+            digits: totp::DIGITS,
+            period: totp::PERIOD,
+            algorithm: "SHA1",
+        }
+    }
+}
+
+// One output format: how to render the payload, and what to call the file it goes in.
+pub trait Exporter {
+    fn name(&self) -> &str;
+    fn extension(&self) -> &str;
+    fn render(&self, payload: &ExportPayload) -> Result<Vec<u8>>;
+}
+
+// An input format for `convert`: how to recognize it and read the authenticators it holds.
+pub trait Importer {
+    fn name(&self) -> &str;
+    fn detect(&self, input: &[u8]) -> bool;
+    fn read(&self, input: &[u8]) -> Result<Vec<OtpAuth>>;
+}
+
+// An entry read from another app's backup, with the serial when the account name is one.
+fn read_entry(issuer: String, account: String, base32_secret: &str) -> Result<OtpAuth> {
+    let serial =
+        Region::from_serial(&account).and_then(|region| region.normalize_serial(&account).ok());
+    Ok(OtpAuth {
+        secret: Locked::new(totp::decode_base32(base32_secret)?),
+        account: if account.is_empty() {
+            serial.clone().unwrap_or_default()
+        } else {
+            account
+        },
+        serial,
+        issuer,
+    })
+}
+
+// A random version 4 UUID, for the formats that give every entry one.
+fn random_uuid() -> Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|err| anyhow::anyhow!("failed to generate a UUID: {err}"))?;
+    bytes[6] = bytes[6] & 0x0f | 0x40;
+    bytes[8] = bytes[8] & 0x3f | 0x80;
+    let hex = hex::encode(bytes);
+    Ok(format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    ))
+}
+
+// Every format `convert` can read, in the order they are tried when detecting the input.
+pub fn importers() -> Vec<Box<dyn Importer>> {
+    let mut importers: Vec<Box<dyn Importer>> = vec![
+        Box::new(uri::Uri),
+        Box::new(json::Json),
+        Box::new(stratum::Stratum { encrypted: false }),
+        Box::new(aegis::Aegis),
+        Box::new(twofas::TwoFas),
+        Box::new(bitwarden::Bitwarden),
+    ];
+    // First, since its exports can start with another app's otpauth URI.
+    #[cfg(feature = "winauth")]
+    importers.insert(0, Box::new(winauth::WinAuth));
+    #[cfg(feature = "encrypted-backups")]
+    importers.push(Box::new(stratum::Stratum { encrypted: true }));
+    #[cfg(feature = "encrypted-backups")]
+    importers.push(Box::new(backup::Backup));
+    #[cfg(feature = "android")]
+    importers.push(Box::new(android::Android));
+    // Last, since the other formats can hold an otpauth URI too.
+    importers.push(Box::new(text::Text));
+    importers
+}
+
+// Every format, built-ins first. Plugins can add formats but not replace a built-in one.
+pub fn registry() -> Vec<Box<dyn Exporter>> {
+    #[allow(unused_mut)]
+    let mut exporters: Vec<Box<dyn Exporter>> = vec![
+        Box::new(text::Text),
+        Box::new(json::Json),
+        Box::new(uri::Uri),
+        Box::new(stratum::Stratum { encrypted: false }),
+        Box::new(aegis::Aegis),
+        Box::new(twofas::TwoFas),
+        Box::new(bitwarden::Bitwarden),
+    ];
+    #[cfg(feature = "encrypted-backups")]
+    exporters.push(Box::new(stratum::Stratum { encrypted: true }));
+    #[cfg(feature = "encrypted-backups")]
+    exporters.push(Box::new(backup::Backup));
+    #[cfg(feature = "qr")]
+    exporters.push(Box::new(qr::Qr));
+    #[cfg(feature = "plugins")]
+    for plugin in plugin::discover() {
+        if exporters
+            .iter()
+            .all(|exporter| exporter.name() != plugin.name)
+        {
+            exporters.push(Box::new(plugin));
+        }
+    }
+    exporters
+}
+
+// The password for an encrypted backup format. It is asked for once per run, so a --batch
+// export doesn't ask again for every account, and its workers wait for the first prompt.
+#[cfg(feature = "encrypted-backups")]
+pub fn backup_password(confirm: bool) -> Result<Zeroizing<String>> {
+    static PASSWORD: Mutex<Option<Zeroizing<String>>> = Mutex::new(None);
+
+    let mut saved = PASSWORD.lock().unwrap_or_else(|err| err.into_inner());
+    if let Some(password) = &*saved {
+        return Ok(password.clone());
+    }
+    let password = crate::seal::password("Backup password", "BNET_BACKUP_PASSWORD", confirm)?;
+    crate::interrupt::forget_on_interrupt(&PASSWORD);
+    *saved = Some(password.clone());
+    Ok(password)
+}
+
+// Write the export in the named format to stdout, or with `all`, every format to its own file.
+// A terminal only gets it with --reveal.
+pub fn write(name: &str, export: &OtpAuth, reveal: bool) -> Result<()> {
+    if name == "all" {
+        return write_files(name, export);
+    }
+
+    check_stdout(reveal, &format!("--format {name}"))?;
+    let output = render(name, export)?;
+    let mut stdout = io::stdout().lock();
+    stdout
+        .write_all(&output)
+        .and_then(|()| stdout.flush())
+        .context("failed to write to stdout")
+}
+
+fn render(name: &str, export: &OtpAuth) -> Result<Zeroizing<Vec<u8>>> {
+    let payload = ExportPayload::new(export);
+    let exporters = registry();
+    let exporter = find(&exporters, name)?;
+    Ok(Zeroizing::new(crate::timings::time(
+        &format!("render {name}"),
+        || exporter.render(&payload),
+    )?))
+}
+
+// Write the named format, the --print values or else the otpauth URI to an inherited file
+// descriptor. It is written to a duplicate, which leaves the descriptor itself to the calling
+// script.
+#[cfg(unix)]
+fn write_fd(fd: RawFd, name: Option<&str>, print: &[Field], export: &OtpAuth) -> Result<()> {
+    check_fd(fd, name)?;
+    let output = match name {
+        Some(name) => render(name, export)?,
+        None if print.is_empty() => fields(&[Field::Uri], export)?,
+        None => fields(print, export)?,
+    };
+
+    // F_DUPFD_CLOEXEC fails with EBADF when the descriptor isn't open, so `from_raw_fd` only
+    // ever gets a descriptor this process just created and owns.
+    let duplicate = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 3) };
+    if duplicate < 0 {
+        return Err(io::Error::last_os_error()).with_context(|| not_open(fd));
+    }
+    let mut file = unsafe { File::from_raw_fd(duplicate) };
+    file.write_all(&output)
+        .and_then(|()| file.flush())
+        .with_context(|| format!("failed to write to file descriptor {fd}"))
+}
+
+fn find<'a>(exporters: &'a [Box<dyn Exporter>], name: &str) -> Result<&'a dyn Exporter> {
+    match exporters.iter().find(|exporter| exporter.name() == name) {
+        Some(exporter) => Ok(exporter.as_ref()),
+        None => bail!(
+            "unknown format {name}, run `bnet-auth-export formats` to list the available ones"
+        ),
+    }
+}
+
+// Write the named format, or every format for `all`, to `<serial>.<ext>` files. Everything is
+// rendered before anything is written, so a failing plugin doesn't leave half an export.
+pub fn write_files(name: &str, export: &OtpAuth) -> Result<()> {
+    let payload = ExportPayload::new(export);
+    let exporters = registry();
+    let selected = if name == "all" {
+        exporters.iter().map(AsRef::as_ref).collect()
+    } else {
+        vec![find(&exporters, name)?]
+    };
+
+    let stem = payload.serial.as_deref().unwrap_or("bnet-auth-export");
+    let mut outputs = Vec::new();
+    for exporter in selected {
+        let output = Zeroizing::new(
+            crate::timings::time(&format!("render {}", exporter.name()), || {
+                exporter.render(&payload)
+            })
+            .with_context(|| format!("failed to render {}", exporter.name()))?,
+        );
+        outputs.push((format!("{stem}.{}", exporter.extension()), output));
+    }
+
+    for (path, output) in outputs {
+        let mut file = secure_file::private_options()
+            .create_new(true)
+            .open(&path)
+            .with_context(|| format!("failed to write {path}"))?;
+        let unfinished = crate::interrupt::remove_on_interrupt(&path);
+        file.write_all(&output)
+            .with_context(|| format!("failed to write {path}"))?;
+        drop(unfinished);
+        eprintln!("Wrote {path}");
+    }
+    Ok(())
+}
+
+// The secret goes to stdout when it is a pipe or a file, and to a terminal only with --reveal,
+// as the summary does, so it isn't shown on a screen nobody meant to show it on.
+pub fn check_stdout(reveal: bool, what: &str) -> Result<()> {
+    if !reveal && io::stdout().is_terminal() {
+        bail!(
+            "{what} prints the secret in this terminal, pass --reveal to show it or redirect stdout to a file"
+        );
+    }
+    Ok(())
+}
+
+// List the formats that can be passed to --format.
+pub fn run_list() -> Result<()> {
+    for exporter in registry() {
+        println!("{:<16} .{}", exporter.name(), exporter.extension());
+    }
+    println!("{:<16} every format above, one file each", "all");
+    #[cfg(feature = "plugins")]
+    println!(
+        "\nAdd formats by installing a `{}<name>` executable on PATH.",
+        plugin::PREFIX
+    );
+    Ok(())
+}
+
+// Refuse a descriptor that can't be written to before any work is done.
+#[cfg(unix)]
+fn check_fd(fd: RawFd, name: Option<&str>) -> Result<()> {
+    if fd <= 2 {
+        bail!("--output-fd {fd} is stdin, stdout or stderr, use a descriptor from 3 up");
+    }
+    if name == Some("all") {
+        bail!("--output-fd takes a single --format, not `all`");
+    }
+    if unsafe { libc::fcntl(fd, libc::F_GETFD) } < 0 {
+        return Err(io::Error::last_os_error()).with_context(|| not_open(fd));
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn not_open(fd: RawFd) -> String {
+    format!("file descriptor {fd} isn't open, open it in the calling shell, e.g. `{fd}>file`")
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use zeroize::Zeroizing;
+
+    use bnet_auth_export::otpauth::{LabelOptions, OtpAuth};
+
+    use super::*;
+
+    pub fn payload() -> ExportPayload {
+        let export = OtpAuth::new(
+            "US-1234-5678-9012".to_owned(),
+            Zeroizing::new(b"12345678901234567890".to_vec()),
+            &LabelOptions::default(),
+        );
+        ExportPayload::new(&export)
+    }
+
+    #[test]
+    fn print_gives_one_value_per_line() {
+        let export = OtpAuth::new(
+            "US-1234-5678-9012".to_owned(),
+            Zeroizing::new(b"12345678901234567890".to_vec()),
+            &LabelOptions::default(),
+        );
+        let output = fields(&[Field::Serial, Field::Secret, Field::Serial], &export).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&output).unwrap(),
+            "US-1234-5678-9012\nGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ\nUS-1234-5678-9012\n"
+        );
+    }
+
+    #[test]
+    fn builtin_names_are_unique() {
+        let exporters = registry();
+        for (i, exporter) in exporters.iter().enumerate() {
+            assert!(
+                exporters[..i]
+                    .iter()
+                    .all(|other| other.name() != exporter.name())
+            );
+            assert_ne!(exporter.name(), "all");
+        }
+    }
+}
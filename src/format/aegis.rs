@@ -0,0 +1,224 @@
+// Aegis vaults, the plain JSON kind Aegis writes with "Export" and the encryption turned off,
+// and imports from the same file. Reading one picks out the entries with Battle.net's code
+// parameters; an encrypted vault has to be exported again without encryption first.
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use zeroize::Zeroizing;
+
+use bnet_auth_export::otpauth::OtpAuth;
+use bnet_auth_export::totp;
+
+use super::{ExportPayload, Exporter, Importer};
+
+// The vault and database versions Aegis writes at the moment.
+const VERSION: u32 = 1;
+const DB_VERSION: u32 = 3;
+
+pub struct Aegis;
+
+#[derive(Serialize)]
+struct Vault<'a> {
+    version: u32,
+    header: Header,
+    db: Db<'a>,
+}
+
+// No key slots or parameters is what marks a vault as not encrypted.
+#[derive(Serialize)]
+struct Header {
+    slots: Option<()>,
+    params: Option<()>,
+}
+
+#[derive(Serialize)]
+struct Db<'a> {
+    version: u32,
+    entries: [Entry<'a>; 1],
+    groups: [(); 0],
+}
+
+#[derive(Serialize)]
+struct Entry<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    uuid: String,
+    name: &'a str,
+    issuer: &'a str,
+    note: &'static str,
+    favorite: bool,
+    icon: Option<()>,
+    info: Info<'a>,
+    groups: [(); 0],
+}
+
+#[derive(Serialize)]
+struct Info<'a> {
+    secret: &'a str,
+    algo: &'static str,
+    digits: u32,
+    period: u64,
+}
+
+// The parts of a vault needed to tell whether an entry is a Battle.net authenticator.
+#[derive(Deserialize)]
+struct StoredVault {
+    header: StoredHeader,
+    db: Value,
+}
+
+#[derive(Deserialize)]
+struct StoredHeader {
+    slots: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct StoredDb {
+    entries: Vec<StoredEntry>,
+}
+
+#[derive(Deserialize)]
+struct StoredEntry {
+    #[serde(rename = "type")]
+    kind: String,
+    name: String,
+    issuer: String,
+    info: StoredInfo,
+}
+
+#[derive(Deserialize)]
+struct StoredInfo {
+    secret: Zeroizing<String>,
+    algo: String,
+    digits: u32,
+    period: u64,
+}
+
+impl Exporter for Aegis {
+    fn name(&self) -> &str {
+        "aegis"
+    }
+
+    fn extension(&self) -> &str {
+        "aegis.json"
+    }
+
+    fn render(&self, payload: &ExportPayload) -> Result<Vec<u8>> {
+        let vault = Vault {
+            version: VERSION,
+            header: Header {
+                slots: None,
+                params: None,
+            },
+            db: Db {
+                version: DB_VERSION,
+                entries: [Entry {
+                    kind: "totp",
+                    uuid: super::random_uuid()?,
+                    name: &payload.account,
+                    issuer: &payload.issuer,
+                    note: "",
+                    favorite: false,
+                    icon: None,
+                    info: Info {
+                        secret: &payload.base32_secret,
+                        algo: payload.algorithm,
+                        digits: payload.digits,
+                        period: payload.period,
+                    },
+                    groups: [],
+                }],
+                groups: [],
+            },
+        };
+        let mut output =
+            serde_json::to_vec_pretty(&vault).context("failed to encode the Aegis vault")?;
+        output.push(b'\n');
+        Ok(output)
+    }
+}
+
+impl Importer for Aegis {
+    fn name(&self) -> &str {
+        "aegis"
+    }
+
+    fn detect(&self, input: &[u8]) -> bool {
+        serde_json::from_slice::<Value>(input)
+            .is_ok_and(|value| value.get("header").is_some() && value.get("db").is_some())
+    }
+
+    fn read(&self, input: &[u8]) -> Result<Vec<OtpAuth>> {
+        let vault: StoredVault = serde_json::from_slice(input).context("not an Aegis vault")?;
+        // An encrypted vault keeps its database as a Base64 string.
+        if vault.header.slots.is_some() || vault.db.is_string() {
+            bail!("the Aegis vault is encrypted, export it again from Aegis with encryption off");
+        }
+        let db: StoredDb = serde_json::from_value(vault.db).context("not an Aegis vault")?;
+
+        let exports = db
+            .entries
+            .into_iter()
+            .filter(|entry| {
+                entry.kind == "totp"
+                    && entry.info.algo.eq_ignore_ascii_case("SHA1")
+                    && entry.info.digits == totp::DIGITS
+                    && entry.info.period == totp::PERIOD
+            })
+            .map(|entry| super::read_entry(entry.issuer, entry.name, &entry.info.secret))
+            .collect::<Result<Vec<_>>>()?;
+        if exports.is_empty() {
+            bail!("the vault has no entries with Battle.net's 8-digit, SHA1, 30 second codes");
+        }
+        Ok(exports)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::format::tests::payload;
+
+    #[test]
+    fn vault_has_one_totp_entry() {
+        let output = Aegis.render(&payload()).unwrap();
+        let value: Value = serde_json::from_slice(&output).unwrap();
+
+        assert_eq!(value["header"]["slots"], Value::Null);
+        let entry = &value["db"]["entries"][0];
+        assert_eq!(entry["type"], "totp");
+        assert_eq!(entry["issuer"], "Battle.net");
+        assert_eq!(entry["name"], "US-1234-5678-9012");
+        assert_eq!(entry["info"]["secret"], "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(entry["info"]["digits"], 8);
+        assert_eq!(entry["uuid"].as_str().unwrap().len(), 36);
+    }
+
+    #[test]
+    fn reads_back_battle_net_entries() {
+        let output = Aegis.render(&payload()).unwrap();
+        let mut value: Value = serde_json::from_slice(&output).unwrap();
+        let mut other = value["db"]["entries"][0].clone();
+        other["info"]["digits"] = 6.into();
+        value["db"]["entries"].as_array_mut().unwrap().push(other);
+        let input = serde_json::to_vec(&value).unwrap();
+
+        assert!(Aegis.detect(&input));
+        let exports = Aegis.read(&input).unwrap();
+        assert_eq!(exports.len(), 1);
+        assert_eq!(exports[0].serial.as_deref(), Some("US-1234-5678-9012"));
+        assert_eq!(*exports[0].secret, b"12345678901234567890");
+    }
+
+    #[test]
+    fn refuses_an_encrypted_vault() {
+        let input = br#"{"version":1,"header":{"slots":[],"params":{}},"db":"c2VjcmV0"}"#;
+
+        assert!(Aegis.detect(input));
+        let err = Aegis.read(input).err().unwrap();
+        assert!(err.to_string().contains("encrypted"), "{err}");
+    }
+}
@@ -0,0 +1,374 @@
+// The Android Battle.net Authenticator app (com.blizzard.bma) keeps its secret and serial in its
+// shared preferences, XORed with a fixed mask. This reads them from an `adb backup` of the app,
+// plain or with a backup password, or from the preferences file of a copied data directory, for
+// a phone that still has the authenticator but no way to log in.
+
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use aes::Aes256;
+use anyhow::{Context, Result, bail};
+use cbc::cipher::block_padding::Pkcs7;
+use cbc::cipher::{BlockDecryptMut, KeyIvInit};
+use flate2::read::ZlibDecoder;
+use zeroize::Zeroizing;
+
+use bnet_auth_export::otpauth::{LabelOptions, OtpAuth};
+use bnet_auth_export::region::Region;
+
+use super::Importer;
+
+const BACKUP_MAGIC: &[u8] = b"ANDROID BACKUP\n";
+// The preference holding the masked "<hex secret><serial>".
+const HASH_KEY: &str = "AUTH_STORE.HASH";
+const MASK: [u8; 57] = [
+    0x39, 0x8e, 0x27, 0xfc, 0x50, 0x27, 0x6a, 0x65, 0x60, 0x65, 0xb0, 0xe5, 0x25, 0xf4, 0xc0, 0x6c,
+    0x04, 0xc6, 0x10, 0x75, 0x28, 0x6b, 0x8e, 0x7a, 0xed, 0xa5, 0x9d, 0xa9, 0x81, 0x3b, 0x5d, 0xd6,
+    0xc8, 0x0d, 0x2f, 0xb3, 0x80, 0x68, 0x77, 0x3f, 0xa5, 0x9b, 0xa4, 0x7c, 0x17, 0xca, 0x6c, 0x64,
+    0x79, 0x01, 0x5c, 0x1d, 0x5b, 0x8b, 0x8f, 0x6b, 0x9a,
+];
+
+pub struct Android;
+
+// Where the backup password comes from, asked for only if the backup is encrypted.
+type Password = dyn Fn() -> Result<Zeroizing<String>>;
+
+impl Importer for Android {
+    fn name(&self) -> &str {
+        "android"
+    }
+
+    fn detect(&self, input: &[u8]) -> bool {
+        input.starts_with(BACKUP_MAGIC)
+            || std::str::from_utf8(input).is_ok_and(|input| {
+                input.trim_start().starts_with("<?xml") && input.contains(HASH_KEY)
+            })
+    }
+
+    fn read(&self, input: &[u8]) -> Result<Vec<OtpAuth>> {
+        let exports = if input.starts_with(BACKUP_MAGIC) {
+            read_backup(input, &password)?
+        } else {
+            read_preferences(std::str::from_utf8(input).context("input is not valid UTF-8")?)?
+        };
+        if exports.is_empty() {
+            bail!("no Battle.net authenticator found, is this a backup of com.blizzard.bma?");
+        }
+        Ok(exports)
+    }
+}
+
+// The preferences file holding the authenticator in a copy of the app's data directory, such
+// as /data/data/com.blizzard.bma pulled from a rooted phone.
+pub fn preferences_in(dir: &Path) -> Result<PathBuf> {
+    fn find(dir: &Path, depth: usize) -> Option<PathBuf> {
+        let mut entries: Vec<_> = fs::read_dir(dir)
+            .ok()?
+            .flatten()
+            .map(|e| e.path())
+            .collect();
+        entries.sort();
+        for path in &entries {
+            let name = path.file_name()?.to_string_lossy();
+            if path.is_file() && name.contains("AUTH_STORE") && name.ends_with(".xml") {
+                return Some(path.clone());
+            }
+        }
+        if depth == 0 {
+            return None;
+        }
+        entries
+            .iter()
+            .filter(|path| path.is_dir())
+            .find_map(|path| find(path, depth - 1))
+    }
+    find(dir, 4).with_context(|| {
+        format!(
+            "no com.blizzard.bma.AUTH_STORE.xml in {}, give the app's data directory or its shared_prefs",
+            dir.display()
+        )
+    })
+}
+
+// Every authenticator in a shared preferences file.
+fn read_preferences(xml: &str) -> Result<Vec<OtpAuth>> {
+    let document = roxmltree::Document::parse(xml.trim_start_matches('\u{feff}'))
+        .context("the preferences file is not valid XML")?;
+    document
+        .descendants()
+        .filter(|node| node.has_tag_name("string"))
+        .filter(|node| {
+            node.attribute("name")
+                .is_some_and(|name| name.ends_with(HASH_KEY))
+        })
+        .map(|node| unmask(node.text().unwrap_or_default().trim()))
+        .collect()
+}
+
+// The secret and serial from the masked preference value.
+fn unmask(hash: &str) -> Result<OtpAuth> {
+    let Ok(masked) = hex::decode(hash).map(Zeroizing::new) else {
+        bail!("the app's stored authenticator is not hex");
+    };
+    if masked.len() < 40 + 14 || masked.len() > MASK.len() {
+        bail!(
+            "the app's stored authenticator is {} bytes, not the layout this reads",
+            masked.len()
+        );
+    }
+    let plain: Zeroizing<Vec<u8>> = Zeroizing::new(
+        masked
+            .iter()
+            .zip(MASK)
+            .map(|(byte, mask)| byte ^ mask)
+            .collect(),
+    );
+    let (secret, serial) = plain.split_at(40);
+    let Some(secret) = std::str::from_utf8(secret)
+        .ok()
+        .and_then(|hex| hex::decode(hex).ok())
+    else {
+        bail!("the app's stored secret didn't unmask to hex, is the backup from another app?");
+    };
+    let serial = String::from_utf8_lossy(serial);
+    let serial = Region::from_serial(&serial)
+        .unwrap_or_default()
+        .normalize_serial(&serial)?;
+    Ok(OtpAuth::new(
+        serial,
+        Zeroizing::new(secret),
+        &LabelOptions::default(),
+    ))
+}
+
+// An `adb backup` file: a header, then a tar archive that is usually compressed and may be
+// encrypted with the backup password.
+fn read_backup(input: &[u8], password: &Password) -> Result<Vec<OtpAuth>> {
+    let mut rest = &input[BACKUP_MAGIC.len()..];
+    let mut line = || -> Result<String> {
+        let Some(end) = rest.iter().position(|&byte| byte == b'\n') else {
+            bail!("the backup's header is cut short");
+        };
+        let line = String::from_utf8_lossy(&rest[..end]).into_owned();
+        rest = &rest[end + 1..];
+        Ok(line)
+    };
+    let version: u32 = line()?.parse().context("the backup's header is damaged")?;
+    let compressed = line()? == "1";
+    let encryption = line()?;
+
+    let decrypted;
+    let archive = match encryption.as_str() {
+        "none" => rest,
+        "AES-256" => {
+            let header = [line()?, line()?, line()?, line()?, line()?];
+            decrypted = decrypt(version, &header, rest, password)?;
+            &decrypted[..]
+        }
+        other => bail!("the backup is encrypted with {other}, which this can't read"),
+    };
+    let tar = if compressed {
+        let mut tar = Zeroizing::new(Vec::new());
+        ZlibDecoder::new(archive)
+            .read_to_end(&mut tar)
+            .context("the backup is damaged, it doesn't decompress")?;
+        tar
+    } else {
+        Zeroizing::new(archive.to_vec())
+    };
+
+    let mut exports = Vec::new();
+    for (name, data) in tar_files(&tar)? {
+        if name.ends_with(".xml")
+            && let Ok(xml) = std::str::from_utf8(data)
+            && xml.contains(HASH_KEY)
+        {
+            exports.extend(read_preferences(xml)?);
+        }
+    }
+    Ok(exports)
+}
+
+// Android encrypts the archive with a random master key, itself encrypted with a key derived
+// from the backup password. `header` is the user key salt, the checksum salt, the PBKDF2 rounds,
+// the IV and the encrypted master key.
+fn decrypt(
+    version: u32,
+    header: &[String; 5],
+    data: &[u8],
+    password: &Password,
+) -> Result<Zeroizing<Vec<u8>>> {
+    let damaged = || "the backup's encryption header is damaged";
+    let salt = hex::decode(&header[0]).with_context(damaged)?;
+    let rounds: u32 = header[2].parse().with_context(damaged)?;
+    let iv = hex::decode(&header[3]).with_context(damaged)?;
+    let blob = hex::decode(&header[4]).with_context(damaged)?;
+    if version < 2 {
+        bail!(
+            "encrypted backups from Android 4.3 and older aren't supported, make it without a password"
+        );
+    }
+
+    let password = password()?;
+    let mut user_key = Zeroizing::new([0u8; 32]);
+    pbkdf2::pbkdf2_hmac::<sha1::Sha1>(password.as_bytes(), &salt, rounds, &mut *user_key);
+    let wrong = "the backup password is wrong, or the backup is damaged";
+    let blob = Zeroizing::new(
+        cbc::Decryptor::<Aes256>::new_from_slices(&*user_key, &iv)
+            .context(wrong)?
+            .decrypt_padded_vec_mut::<Pkcs7>(&blob)
+            .ok()
+            .context(wrong)?,
+    );
+    // Length-prefixed: the master key's IV, the master key and its checksum.
+    let mut fields = Vec::new();
+    let mut at = 0;
+    while at < blob.len() && fields.len() < 3 {
+        let len = usize::from(blob[at]);
+        let field = blob.get(at + 1..at + 1 + len).context(wrong)?;
+        fields.push(field);
+        at += 1 + len;
+    }
+    let [master_iv, master_key, _] = fields[..] else {
+        bail!(wrong);
+    };
+    let archive = cbc::Decryptor::<Aes256>::new_from_slices(master_key, master_iv)
+        .context(wrong)?
+        .decrypt_padded_vec_mut::<Pkcs7>(data)
+        .ok()
+        .context(wrong)?;
+    Ok(Zeroizing::new(archive))
+}
+
+// The regular files in a tar archive, by path.
+fn tar_files(tar: &[u8]) -> Result<Vec<(String, &[u8])>> {
+    let field = |header: &[u8]| {
+        let end = header
+            .iter()
+            .position(|&byte| byte == 0)
+            .unwrap_or(header.len());
+        String::from_utf8_lossy(&header[..end]).into_owned()
+    };
+    let mut files = Vec::new();
+    let mut at = 0;
+    while let Some(header) = tar.get(at..at + 512) {
+        if header.iter().all(|&byte| byte == 0) {
+            break;
+        }
+        let size = field(&header[124..136]);
+        let Ok(size) = usize::from_str_radix(size.trim(), 8) else {
+            bail!("the backup is damaged, its archive has a bad entry");
+        };
+        let mut name = field(&header[..100]);
+        let prefix = field(&header[345..500]);
+        if header[257..262] == *b"ustar" && !prefix.is_empty() {
+            name = format!("{prefix}/{name}");
+        }
+        let Some(data) = tar.get(at + 512..at + 512 + size) else {
+            bail!("the backup is cut short");
+        };
+        if matches!(header[156], b'0' | 0) {
+            files.push((name, data));
+        }
+        at += 512 + size.div_ceil(512) * 512;
+    }
+    Ok(files)
+}
+
+// The backup password, asked for once.
+fn password() -> Result<Zeroizing<String>> {
+    static PASSWORD: Mutex<Option<Zeroizing<String>>> = Mutex::new(None);
+
+    let mut saved = PASSWORD.lock().unwrap_or_else(|err| err.into_inner());
+    if let Some(password) = &*saved {
+        return Ok(password.clone());
+    }
+    let password =
+        crate::seal::password("Android backup password", "BNET_ANDROID_PASSWORD", false)?;
+    crate::interrupt::forget_on_interrupt(&PASSWORD);
+    *saved = Some(password.clone());
+    Ok(password)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::Compression;
+    use flate2::write::ZlibEncoder;
+
+    use super::*;
+    use cbc::cipher::BlockEncryptMut;
+
+    fn preferences() -> String {
+        let plain = b"3132333435363738393031323334353637383930US-1234-5678-9012";
+        let masked: Vec<u8> = plain
+            .iter()
+            .zip(MASK)
+            .map(|(byte, mask)| byte ^ mask)
+            .collect();
+        format!(
+            "<?xml version='1.0' encoding='utf-8' standalone='yes' ?>\n<map>\n    <string name=\"com.blizzard.bma.AUTH_STORE.HASH\">{}</string>\n    <long name=\"com.blizzard.bma.AUTH_STORE.CLOCKSKEW\" value=\"-1204\" />\n</map>\n",
+            hex::encode(masked)
+        )
+    }
+
+    #[test]
+    fn reads_adb_backups() {
+        let xml = preferences();
+        let mut tar = vec![0u8; 512];
+        let name = b"apps/com.blizzard.bma/sp/com.blizzard.bma.AUTH_STORE.xml";
+        tar[..name.len()].copy_from_slice(name);
+        tar[124..135].copy_from_slice(format!("{:011o}", xml.len()).as_bytes());
+        tar[156] = b'0';
+        tar.extend(xml.as_bytes());
+        tar.resize(tar.len().div_ceil(512) * 512 + 1024, 0);
+
+        let mut backup = b"ANDROID BACKUP\n5\n1\nnone\n".to_vec();
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&tar).unwrap();
+        backup.extend(encoder.finish().unwrap());
+
+        assert!(Android.detect(&backup));
+        let exports = read_backup(&backup, &|| unreachable!()).unwrap();
+        assert_eq!(exports.len(), 1);
+        assert_eq!(exports[0].serial.as_deref(), Some("US-1234-5678-9012"));
+        assert_eq!(*exports[0].secret, b"12345678901234567890");
+
+        assert!(Android.detect(xml.as_bytes()));
+        let exports = Android.read(xml.as_bytes()).unwrap();
+        assert_eq!(*exports[0].secret, b"12345678901234567890");
+
+        // The same archive, uncompressed, with a backup password.
+        let (salt, iv, master_iv, master_key) = ([1u8; 64], [2u8; 16], [3u8; 16], [4u8; 32]);
+        let mut user_key = [0u8; 32];
+        pbkdf2::pbkdf2_hmac::<sha1::Sha1>(b"hunter2", &salt, 10000, &mut user_key);
+        let mut blob = vec![16];
+        blob.extend(master_iv);
+        blob.push(32);
+        blob.extend(master_key);
+        blob.extend([32; 33]);
+        let blob = cbc::Encryptor::<Aes256>::new(&user_key.into(), &iv.into())
+            .encrypt_padded_vec_mut::<Pkcs7>(&blob);
+        let mut backup = format!(
+            "ANDROID BACKUP\n5\n0\nAES-256\n{}\n{}\n10000\n{}\n{}\n",
+            hex::encode_upper(salt),
+            hex::encode_upper([5u8; 64]),
+            hex::encode_upper(iv),
+            hex::encode_upper(blob)
+        )
+        .into_bytes();
+        backup.extend(
+            cbc::Encryptor::<Aes256>::new(&master_key.into(), &master_iv.into())
+                .encrypt_padded_vec_mut::<Pkcs7>(&tar),
+        );
+        let password = || Ok(Zeroizing::new("hunter2".to_owned()));
+        let exports = read_backup(&backup, &password).unwrap();
+        assert_eq!(exports[0].serial.as_deref(), Some("US-1234-5678-9012"));
+        let wrong = || Ok(Zeroizing::new("hunter3".to_owned()));
+        let err = read_backup(&backup, &wrong).err().unwrap();
+        assert!(err.to_string().contains("password is wrong"), "{err}");
+    }
+}
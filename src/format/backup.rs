@@ -0,0 +1,273 @@
+// This tool's own backup format, for exports kept for years: the JSON payload with the format
+// version, when it was written and an HMAC-SHA256 keyed by the backup password, so `verify
+// --backup` can tell a backup that is still intact from one that rotted or was tampered with.
+// The payload itself stays readable, since a lost password shouldn't also lose the secret; use
+// stratum-encrypted for a backup nobody else can read.
+
+use anyhow::{Context, Result, bail};
+use hmac::{Hmac, Mac};
+use serde_json::{Map, Value, json};
+use sha2::Sha256;
+use web_time::{SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+use bnet_auth_export::clock;
+use bnet_auth_export::otpauth::OtpAuth;
+
+use super::{ExportPayload, Exporter, Importer};
+use crate::seal;
+
+const FORMAT: &str = "bnet-auth-export backup";
+const VERSION: u64 = 1;
+
+// How many times the cost this tool writes a backup's key derivation parameters may be.
+const MAX_COST_FACTOR: u32 = 4;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub struct Backup;
+
+// What `verify` reports about an intact backup.
+pub struct Verified {
+    pub version: u64,
+    pub created: String,
+    pub exports: Vec<OtpAuth>,
+}
+
+impl Exporter for Backup {
+    fn name(&self) -> &str {
+        "backup"
+    }
+
+    fn extension(&self) -> &str {
+        "backup.json"
+    }
+
+    fn render(&self, payload: &ExportPayload) -> Result<Vec<u8>> {
+        write(payload, &super::backup_password(true)?)
+    }
+}
+
+impl Importer for Backup {
+    fn name(&self) -> &str {
+        "backup"
+    }
+
+    fn detect(&self, input: &[u8]) -> bool {
+        serde_json::from_slice::<Value>(input)
+            .is_ok_and(|value| value.get("format").and_then(Value::as_str) == Some(FORMAT))
+    }
+
+    fn read(&self, input: &[u8]) -> Result<Vec<OtpAuth>> {
+        Ok(verify(input)?.exports)
+    }
+}
+
+fn write(payload: &ExportPayload, password: &str) -> Result<Vec<u8>> {
+    let salt = seal::new_salt()?;
+    let key = seal::derive_key(
+        password.as_bytes(),
+        &salt,
+        seal::MEMORY_KIB,
+        seal::ITERATIONS,
+        seal::PARALLELISM,
+    )?;
+    let created = clock::utc_timestamp(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+    );
+
+    let mut backup = json!({
+        "format": FORMAT,
+        "version": VERSION,
+        "created": created,
+        "kdf": {
+            "algorithm": "argon2id",
+            "salt": hex::encode(salt),
+            "memoryKib": seal::MEMORY_KIB,
+            "iterations": seal::ITERATIONS,
+            "parallelism": seal::PARALLELISM,
+        },
+        "keyCheck": key_check(&key),
+        "payload": serde_json::to_value(payload).context("failed to encode export")?,
+    });
+    let mac = mac(&key, &backup);
+    backup["mac"] = json!(mac);
+
+    let mut output = serde_json::to_vec_pretty(&backup).context("failed to encode the backup")?;
+    output.push(b'\n');
+    Ok(output)
+}
+
+// Check the backup's MAC with the backup password and read it. Every way it can fail says
+// what is wrong, since that is what `verify` is for.
+pub fn verify(input: &[u8]) -> Result<Verified> {
+    check(input, || super::backup_password(false))
+}
+
+// The password is only asked for once the file looks like a backup.
+fn check(input: &[u8], password: impl FnOnce() -> Result<Zeroizing<String>>) -> Result<Verified> {
+    let Ok(mut backup) = serde_json::from_slice::<Value>(input) else {
+        bail!("the backup is damaged: it is no longer valid JSON");
+    };
+    if backup.get("format").and_then(Value::as_str) != Some(FORMAT) {
+        bail!("not a bnet-auth-export backup");
+    }
+    let version = backup.get("version").and_then(Value::as_u64).unwrap_or(0);
+    if version != VERSION {
+        bail!("unsupported backup version {version}, a newer bnet-auth-export may read it");
+    }
+    let Some(Value::String(expected)) = backup.as_object_mut().and_then(|b| b.remove("mac")) else {
+        bail!("the backup is damaged: it has no MAC");
+    };
+    let Some(kdf) = backup.get("kdf") else {
+        bail!("the backup is damaged: it has no key derivation parameters");
+    };
+    // Bounded, so a tampered backup is reported as damaged rather than asking Argon2 for more
+    // memory or time than any backup this tool wrote would.
+    let param = |name: &str, written: u32| {
+        let value = kdf
+            .get(name)
+            .and_then(Value::as_u64)
+            .and_then(|value| u32::try_from(value).ok())
+            .with_context(|| format!("the backup is damaged: kdf.{name} is missing"))?;
+        if value == 0 || value > written.saturating_mul(MAX_COST_FACTOR) {
+            bail!("the backup is damaged: kdf.{name} is {value}, outside what this tool writes");
+        }
+        Ok(value)
+    };
+    let memory_kib = param("memoryKib", seal::MEMORY_KIB)?;
+    let iterations = param("iterations", seal::ITERATIONS)?;
+    let parallelism = param("parallelism", seal::PARALLELISM)?;
+    if kdf.get("algorithm").and_then(Value::as_str) != Some("argon2id") {
+        bail!("the backup is damaged: unknown key derivation algorithm");
+    }
+    let salt = kdf
+        .get("salt")
+        .and_then(Value::as_str)
+        .and_then(|salt| hex::decode(salt).ok())
+        .context("the backup is damaged: kdf.salt is not valid hex")?;
+
+    let password = password()?;
+    let key = seal::derive_key(
+        password.as_bytes(),
+        &salt,
+        memory_kib,
+        iterations,
+        parallelism,
+    )?;
+    if backup.get("keyCheck").and_then(Value::as_str) != Some(&key_check(&key)) {
+        bail!("the backup password is wrong, or the backup's key check is damaged");
+    }
+    let Ok(expected) = hex::decode(expected) else {
+        bail!("the backup is damaged: the MAC is not valid hex");
+    };
+    let mut hmac = HmacSha256::new_from_slice(&*key).expect("HMAC takes any key length");
+    hmac.update(&canonical(&backup));
+    if hmac.verify_slice(&expected).is_err() {
+        bail!(
+            "the backup was changed or damaged after it was written: its MAC doesn't match. Don't rely on the secret in it."
+        );
+    }
+
+    let created = backup
+        .get("created")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_owned();
+    let payload = backup.get("payload").context("the backup has no payload")?;
+    let exports = super::json::Json.read(&Zeroizing::new(serde_json::to_vec(payload)?))?;
+    Ok(Verified {
+        version,
+        created,
+        exports,
+    })
+}
+
+// The MAC covers everything but itself.
+fn mac(key: &[u8; 32], backup: &Value) -> String {
+    let mut hmac = HmacSha256::new_from_slice(key).expect("HMAC takes any key length");
+    hmac.update(&canonical(backup));
+    hex::encode(hmac.finalize().into_bytes())
+}
+
+// Tells a wrong password apart from a damaged backup. It gives away no more than the MAC does.
+fn key_check(key: &[u8; 32]) -> String {
+    let mut hmac = HmacSha256::new_from_slice(key).expect("HMAC takes any key length");
+    hmac.update(b"key check");
+    hex::encode(&hmac.finalize().into_bytes()[..8])
+}
+
+// JSON without whitespace and with the keys of every object sorted, so the MAC doesn't depend
+// on how the file was formatted or in which order its keys were written.
+fn canonical(value: &Value) -> Vec<u8> {
+    fn sorted(value: &Value) -> Value {
+        match value {
+            Value::Object(object) => {
+                let mut entries: Vec<_> = object.iter().collect();
+                entries.sort_by_key(|(name, _)| *name);
+                Value::Object(
+                    entries
+                        .into_iter()
+                        .map(|(name, value)| (name.clone(), sorted(value)))
+                        .collect::<Map<_, _>>(),
+                )
+            }
+            Value::Array(values) => Value::Array(values.iter().map(sorted).collect()),
+            value => value.clone(),
+        }
+    }
+    serde_json::to_vec(&sorted(value)).expect("JSON values always serialize")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::tests::payload;
+
+    #[test]
+    fn detects_any_change_after_writing() {
+        let password = || Ok(Zeroizing::new("hunter2".to_owned()));
+        let output = write(&payload(), "hunter2").unwrap();
+        assert!(Backup.detect(&output));
+
+        // Reformatting alone keeps it intact.
+        let value: Value = serde_json::from_slice(&output).unwrap();
+        let verified = check(&serde_json::to_vec(&value).unwrap(), password).unwrap();
+        assert_eq!(verified.version, 1);
+        assert_eq!(
+            verified.exports[0].serial.as_deref(),
+            Some("US-1234-5678-9012")
+        );
+        assert_eq!(*verified.exports[0].secret, b"12345678901234567890");
+
+        let mut changed = value.clone();
+        changed["payload"]["serial"] = json!("US-1234-5678-9013");
+        let err = check(&serde_json::to_vec(&changed).unwrap(), password)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("MAC doesn't match"), "{err}");
+
+        let mut changed = value;
+        changed["created"] = json!("1999-01-01T00:00:00Z");
+        assert!(check(&serde_json::to_vec(&changed).unwrap(), password).is_err());
+        let wrong = || Ok(Zeroizing::new("hunter3".to_owned()));
+        let err = check(&output, wrong).err().unwrap();
+        assert!(err.to_string().contains("password is wrong"), "{err}");
+    }
+
+    #[test]
+    fn refuses_an_unreasonable_key_derivation_cost() {
+        let output = write(&payload(), "hunter2").unwrap();
+        let mut value: Value = serde_json::from_slice(&output).unwrap();
+        value["kdf"]["memoryKib"] = json!(u32::MAX);
+
+        let asked = || -> Result<Zeroizing<String>> { panic!("asked for the password") };
+        let err = check(&serde_json::to_vec(&value).unwrap(), asked)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("kdf.memoryKib"), "{err}");
+    }
+}
@@ -0,0 +1,188 @@
+// Bitwarden's unencrypted JSON export, with the authenticator as a login item whose TOTP field
+// holds the otpauth URI. Import it in the web vault under Tools → Import data, as "Bitwarden
+// (json)". Reading one picks out the logins whose URI has Battle.net's code parameters.
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use zeroize::Zeroizing;
+
+use bnet_auth_export::otpauth::OtpAuth;
+
+use super::{ExportPayload, Exporter, Importer};
+
+// Bitwarden's item type for logins.
+const TYPE_LOGIN: u8 = 1;
+
+pub struct Bitwarden;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Export<'a> {
+    encrypted: bool,
+    folders: [(); 0],
+    items: [Item<'a>; 1],
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Item<'a> {
+    id: String,
+    organization_id: Option<()>,
+    folder_id: Option<()>,
+    #[serde(rename = "type")]
+    kind: u8,
+    reprompt: u8,
+    name: &'a str,
+    notes: Option<&'a str>,
+    favorite: bool,
+    login: Login<'a>,
+    collection_ids: Option<()>,
+}
+
+#[derive(Serialize)]
+struct Login<'a> {
+    uris: [LoginUri; 1],
+    username: &'a str,
+    password: Option<()>,
+    totp: &'a str,
+}
+
+#[derive(Serialize)]
+struct LoginUri {
+    #[serde(rename = "match")]
+    kind: Option<()>,
+    uri: &'static str,
+}
+
+// The parts of an export needed to find the authenticators in it.
+#[derive(Deserialize)]
+struct StoredExport {
+    encrypted: bool,
+    #[serde(default)]
+    items: Vec<StoredItem>,
+}
+
+#[derive(Deserialize)]
+struct StoredItem {
+    login: Option<StoredLogin>,
+}
+
+#[derive(Deserialize)]
+struct StoredLogin {
+    totp: Option<Zeroizing<String>>,
+}
+
+impl Exporter for Bitwarden {
+    fn name(&self) -> &str {
+        "bitwarden"
+    }
+
+    fn extension(&self) -> &str {
+        "bitwarden.json"
+    }
+
+    fn render(&self, payload: &ExportPayload) -> Result<Vec<u8>> {
+        let export = Export {
+            encrypted: false,
+            folders: [],
+            items: [Item {
+                id: super::random_uuid()?,
+                organization_id: None,
+                folder_id: None,
+                kind: TYPE_LOGIN,
+                reprompt: 0,
+                name: &payload.issuer,
+                notes: payload.serial.as_deref(),
+                favorite: false,
+                login: Login {
+                    uris: [LoginUri {
+                        kind: None,
+                        uri: "https://account.battle.net",
+                    }],
+                    username: &payload.account,
+                    password: None,
+                    totp: &payload.uri,
+                },
+                collection_ids: None,
+            }],
+        };
+        let mut output =
+            serde_json::to_vec_pretty(&export).context("failed to encode the Bitwarden export")?;
+        output.push(b'\n');
+        Ok(output)
+    }
+}
+
+impl Importer for Bitwarden {
+    fn name(&self) -> &str {
+        "bitwarden"
+    }
+
+    fn detect(&self, input: &[u8]) -> bool {
+        serde_json::from_slice::<Value>(input)
+            .is_ok_and(|value| value.get("encrypted").is_some() && value.get("items").is_some())
+    }
+
+    fn read(&self, input: &[u8]) -> Result<Vec<OtpAuth>> {
+        let export: StoredExport =
+            serde_json::from_slice(input).context("not a Bitwarden export")?;
+        if export.encrypted {
+            bail!("the Bitwarden export is encrypted, export the vault again as .json");
+        }
+
+        // Other logins' TOTP fields are skipped, as are bare secrets, which could be anything.
+        let exports: Vec<_> = export
+            .items
+            .into_iter()
+            .filter_map(|item| item.login?.totp)
+            .filter_map(|totp| OtpAuth::parse(&totp).ok())
+            .collect();
+        if exports.is_empty() {
+            bail!("the export has no logins with Battle.net's 8-digit, SHA1, 30 second codes");
+        }
+        Ok(exports)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::format::tests::payload;
+
+    #[test]
+    fn export_has_one_login_with_the_uri() {
+        let output = Bitwarden.render(&payload()).unwrap();
+        let value: Value = serde_json::from_slice(&output).unwrap();
+
+        assert_eq!(value["encrypted"], false);
+        let item = &value["items"][0];
+        assert_eq!(item["type"], 1);
+        assert_eq!(item["name"], "Battle.net");
+        assert_eq!(item["login"]["username"], "US-1234-5678-9012");
+        assert!(
+            item["login"]["totp"]
+                .as_str()
+                .unwrap()
+                .starts_with("otpauth://totp/Battle.net:US-1234-5678-9012?")
+        );
+    }
+
+    #[test]
+    fn reads_back_battle_net_logins() {
+        let output = Bitwarden.render(&payload()).unwrap();
+        let mut value: Value = serde_json::from_slice(&output).unwrap();
+        let mut other = value["items"][0].clone();
+        other["login"]["totp"] = "JBSWY3DPEHPK3PXP".into();
+        value["items"].as_array_mut().unwrap().push(other);
+        let input = serde_json::to_vec(&value).unwrap();
+
+        assert!(Bitwarden.detect(&input));
+        let exports = Bitwarden.read(&input).unwrap();
+        assert_eq!(exports.len(), 1);
+        assert_eq!(exports[0].serial.as_deref(), Some("US-1234-5678-9012"));
+        assert_eq!(*exports[0].secret, b"12345678901234567890");
+    }
+}
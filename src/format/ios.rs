@@ -0,0 +1,178 @@
+// An iTunes or Finder backup of an iPhone with the Battle.net app: the files of Blizzard's apps
+// are looked up in the backup's Manifest.db and searched for the authenticator secret and
+// serial, for iPhone users with no way to log in. Encrypted backups can't be read, nor the
+// keychain, which a backup only holds encrypted with keys from the device.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use rusqlite::{Connection, OpenFlags};
+use zeroize::Zeroizing;
+
+use bnet_auth_export::otpauth::{LabelOptions, OtpAuth};
+use bnet_auth_export::region::Region;
+
+// Whether `dir` is an iOS backup, as Finder or iTunes write it, named after the device's UDID.
+pub fn is_backup(dir: &Path) -> bool {
+    dir.join("Manifest.db").is_file() || dir.join("Manifest.mbdb").is_file()
+}
+
+pub fn read_backup(dir: &Path) -> Result<Vec<OtpAuth>> {
+    let manifest = dir.join("Manifest.db");
+    if !manifest.is_file() {
+        bail!("backups from iOS 9 and older aren't supported, back the iPhone up again first");
+    }
+    let mut header = [0u8; 16];
+    fs::File::open(&manifest)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .with_context(|| format!("failed to read {}", manifest.display()))?;
+    if header != *b"SQLite format 3\0" {
+        bail!(
+            "the backup is encrypted, which this can't read. Turn off \"Encrypt local backup\" in Finder or iTunes, back up again and read that backup"
+        );
+    }
+
+    let connection = Connection::open_with_flags(&manifest, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .with_context(|| format!("failed to open {}", manifest.display()))?;
+    // Files only, flags 1, of the apps' containers and the app groups they share.
+    let mut statement = connection
+        .prepare("SELECT fileID FROM Files WHERE flags = 1 AND domain LIKE '%blizzard%'")?;
+    let files: Vec<String> = statement
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()
+        .context("failed to read the backup's Manifest.db")?;
+    if files.is_empty() {
+        bail!(
+            "the backup has no data of the Battle.net app, was it installed when the backup was made?"
+        );
+    }
+
+    let mut exports: Vec<OtpAuth> = Vec::new();
+    for id in &files {
+        let Some(prefix) = id.get(..2) else {
+            continue;
+        };
+        // Files that changed during the backup can be listed but missing.
+        let Ok(data) = fs::read(dir.join(prefix).join(id)) else {
+            continue;
+        };
+        let data = Zeroizing::new(data);
+        for export in find(&data) {
+            // The app can keep the same authenticator in several files.
+            if !exports
+                .iter()
+                .any(|other| other.serial == export.serial && *other.secret == *export.secret)
+            {
+                exports.push(export);
+            }
+        }
+    }
+    if exports.is_empty() {
+        bail!(
+            "found no authenticator in the Battle.net app's {} files in the backup. Versions of the app that keep it in the keychain can't be read from a backup",
+            files.len()
+        );
+    }
+    Ok(exports)
+}
+
+// Authenticators in a file of the app's container, which may be a binary plist or a database
+// but keeps its strings as plain bytes: otpauth URIs, or else one hex secret next to one serial.
+fn find(data: &[u8]) -> Vec<OtpAuth> {
+    let uris: Vec<OtpAuth> = starts(data, b"otpauth://totp/")
+        .filter_map(|at| {
+            let end = data[at..]
+                .iter()
+                .position(|&byte| !byte.is_ascii_graphic() || matches!(byte, b'"' | b'<' | b'>'))
+                .map_or(data.len(), |len| at + len);
+            let uri = Zeroizing::new(String::from_utf8_lossy(&data[at..end]).into_owned());
+            OtpAuth::parse(&uri).ok()
+        })
+        .collect();
+    if !uris.is_empty() {
+        return uris;
+    }
+
+    let secrets: HashSet<&[u8]> = runs(data, |byte| byte.is_ascii_hexdigit())
+        .filter(|run| run.len() == 40)
+        .collect();
+    let serials: HashSet<String> = (0..data.len())
+        .filter_map(|at| serial_at(data, at))
+        .collect();
+    let (Ok([secret]), Ok([serial])) = (
+        <[_; 1]>::try_from(secrets.into_iter().collect::<Vec<_>>()),
+        <[_; 1]>::try_from(serials.into_iter().collect::<Vec<_>>()),
+    ) else {
+        return Vec::new();
+    };
+    let Ok(secret) = hex::decode(secret) else {
+        return Vec::new();
+    };
+    vec![OtpAuth::new(
+        serial,
+        Zeroizing::new(secret),
+        &LabelOptions::default(),
+    )]
+}
+
+fn starts<'a>(data: &'a [u8], needle: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+    data.windows(needle.len())
+        .enumerate()
+        .filter(move |(_, window)| *window == needle)
+        .map(|(at, _)| at)
+}
+
+// The longest runs of bytes matching `class`.
+fn runs(data: &[u8], class: impl Fn(u8) -> bool + Copy) -> impl Iterator<Item = &[u8]> {
+    data.split(move |&byte| !class(byte))
+        .filter(|run| !run.is_empty())
+}
+
+// A serial starting at `at`, as "US-1234-5678-9012" or "US123456789012", normalized.
+fn serial_at(data: &[u8], at: usize) -> Option<String> {
+    if at > 0 && data[at - 1].is_ascii_alphanumeric() {
+        return None;
+    }
+    let prefix = data.get(at..at + 2)?;
+    if !prefix.iter().all(u8::is_ascii_uppercase) {
+        return None;
+    }
+    let rest = &data[at + 2..];
+    let len = if rest.first() == Some(&b'-') { 15 } else { 12 };
+    let digits = rest.get(..len)?;
+    let shaped = digits.iter().enumerate().all(|(i, &byte)| match len {
+        15 if i % 5 == 0 => byte == b'-',
+        _ => byte.is_ascii_digit(),
+    });
+    if !shaped || rest.get(len).is_some_and(u8::is_ascii_alphanumeric) {
+        return None;
+    }
+    let serial = String::from_utf8_lossy(&data[at..at + 2 + len]);
+    Region::from_serial(&serial)?.normalize_serial(&serial).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_secret_next_to_the_serial() {
+        // As in a binary plist: strings as bytes, between markers and offsets.
+        let mut plist = b"bplist00\xd2\x01\x02\x03\x04_\x10\x11US-1234-5678-9012_\x10(".to_vec();
+        plist.extend(b"3132333435363738393031323334353637383930\x08\x0d\x00\x01");
+        let exports = find(&plist);
+        assert_eq!(exports.len(), 1);
+        assert_eq!(exports[0].serial.as_deref(), Some("US-1234-5678-9012"));
+        assert_eq!(*exports[0].secret, b"12345678901234567890");
+
+        // Two different secrets are a guess, so nothing is found.
+        plist.extend(b"\x5f\x10\x28ffffffffffffffffffffffffffffffffffffffff");
+        assert!(find(&plist).is_empty());
+
+        let uri = b"\x00otpauth://totp/Battle.net:EU-1234-5678-9012?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&digits=8\x00";
+        assert_eq!(find(uri)[0].serial.as_deref(), Some("EU-1234-5678-9012"));
+    }
+}
@@ -0,0 +1,98 @@
+// The export payload itself, for scripts that would rather parse JSON than the printout.
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use serde_json::Value;
+use zeroize::Zeroizing;
+
+use bnet_auth_export::memory::Locked;
+use bnet_auth_export::otpauth::OtpAuth;
+
+use super::{ExportPayload, Exporter, Importer};
+
+pub struct Json;
+
+// The fields of the payload needed to rebuild the export. The rest are derived from these.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Stored {
+    version: u32,
+    serial: Option<String>,
+    device_secret: Zeroizing<String>,
+    issuer: String,
+    account: String,
+}
+
+impl Exporter for Json {
+    fn name(&self) -> &str {
+        "json"
+    }
+
+    fn extension(&self) -> &str {
+        "json"
+    }
+
+    fn render(&self, payload: &ExportPayload) -> Result<Vec<u8>> {
+        let mut output = serde_json::to_vec_pretty(payload).context("failed to encode export")?;
+        output.push(b'\n');
+        Ok(output)
+    }
+}
+
+impl Importer for Json {
+    fn name(&self) -> &str {
+        "json"
+    }
+
+    fn detect(&self, input: &[u8]) -> bool {
+        serde_json::from_slice::<Value>(input)
+            .is_ok_and(|value| value.get("deviceSecret").is_some())
+    }
+
+    fn read(&self, input: &[u8]) -> Result<Vec<OtpAuth>> {
+        let stored: Stored = serde_json::from_slice(input).context("not a JSON export")?;
+        if stored.version != 1 {
+            bail!("unsupported JSON export version {}", stored.version);
+        }
+        let secret =
+            hex::decode(stored.device_secret.trim()).context("deviceSecret is not valid hex")?;
+
+        Ok(vec![OtpAuth {
+            serial: stored.serial,
+            secret: Locked::new(Zeroizing::new(secret)),
+            issuer: stored.issuer,
+            account: stored.account,
+        }])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::format::tests::payload;
+
+    #[test]
+    fn renders_the_payload() {
+        let output = Json.render(&payload()).unwrap();
+        let value: Value = serde_json::from_slice(&output).unwrap();
+
+        assert_eq!(value["version"], 1);
+        assert_eq!(value["serial"], "US-1234-5678-9012");
+        assert_eq!(value["base32Secret"], "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(value["digits"], 8);
+        assert!(output.ends_with(b"}\n"));
+    }
+
+    #[test]
+    fn reads_back_what_it_renders() {
+        let output = Json.render(&payload()).unwrap();
+
+        assert!(Json.detect(&output));
+        let exports = Json.read(&output).unwrap();
+        assert_eq!(exports[0].serial.as_deref(), Some("US-1234-5678-9012"));
+        assert_eq!(exports[0].issuer, "Battle.net");
+        assert_eq!(*exports[0].secret, b"12345678901234567890");
+    }
+}
@@ -0,0 +1,44 @@
+// The otpauth URI as an SVG QR code, to scan from another screen or print for a safe.
+
+use anyhow::{Context, Result};
+use qrcode::QrCode;
+use qrcode::render::svg;
+
+use super::{ExportPayload, Exporter};
+
+pub struct Qr;
+
+impl Exporter for Qr {
+    fn name(&self) -> &str {
+        "qr"
+    }
+
+    fn extension(&self) -> &str {
+        "svg"
+    }
+
+    fn render(&self, payload: &ExportPayload) -> Result<Vec<u8>> {
+        let code = QrCode::new(payload.uri.as_bytes()).context("failed to encode the QR code")?;
+        let mut output = code
+            .render::<svg::Color>()
+            .min_dimensions(256, 256)
+            .build()
+            .into_bytes();
+        output.push(b'\n');
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::tests::payload;
+
+    #[test]
+    fn renders_an_svg() {
+        let output = String::from_utf8(Qr.render(&payload()).unwrap()).unwrap();
+
+        assert!(output.contains("<svg"));
+        assert!(output.trim_end().ends_with("</svg>"));
+    }
+}
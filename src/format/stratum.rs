@@ -0,0 +1,236 @@
+// Stratum (formerly Authenticator Pro) backups, popular for getting codes onto a Wear OS watch.
+// Stratum imports the plain JSON file as is, and the encrypted one after asking for the password.
+// Reading a backup picks out the entries with Battle.net's code parameters.
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use zeroize::Zeroizing;
+
+use bnet_auth_export::otpauth::OtpAuth;
+use bnet_auth_export::totp;
+
+use super::{ExportPayload, Exporter, Importer};
+
+// Encrypted backups start with this, followed by what `seal` writes.
+#[cfg(feature = "encrypted-backups")]
+const HEADER: &[u8] = b"AUTHENTICATORPRO";
+
+// Stratum's authenticator type and algorithm codes.
+const TYPE_TOTP: u8 = 2;
+const ALGORITHM_SHA1: u8 = 0;
+
+pub struct Stratum {
+    pub encrypted: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct Backup<'a> {
+    authenticators: [Authenticator<'a>; 1],
+    categories: [(); 0],
+    authenticator_categories: [(); 0],
+    custom_icons: [(); 0],
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct Authenticator<'a> {
+    #[serde(rename = "Type")]
+    kind: u8,
+    icon: &'static str,
+    issuer: &'a str,
+    username: &'a str,
+    secret: &'a str,
+    pin: Option<()>,
+    algorithm: u8,
+    digits: u32,
+    period: u64,
+    counter: u32,
+    copy_count: u32,
+    ranking: u32,
+}
+
+// The parts of a backup entry needed to tell whether it is a Battle.net authenticator.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct StoredBackup {
+    authenticators: Vec<StoredAuthenticator>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct StoredAuthenticator {
+    #[serde(rename = "Type")]
+    kind: u8,
+    issuer: String,
+    username: Option<String>,
+    secret: Zeroizing<String>,
+    algorithm: u8,
+    digits: u32,
+    period: u64,
+}
+
+impl Exporter for Stratum {
+    fn name(&self) -> &str {
+        if self.encrypted {
+            "stratum-encrypted"
+        } else {
+            "stratum"
+        }
+    }
+
+    fn extension(&self) -> &str {
+        if self.encrypted {
+            "authpro"
+        } else {
+            "stratum.json"
+        }
+    }
+
+    fn render(&self, payload: &ExportPayload) -> Result<Vec<u8>> {
+        let json = zeroize::Zeroizing::new(plain(payload)?);
+        if !self.encrypted {
+            return Ok(json.to_vec());
+        }
+
+        #[cfg(feature = "encrypted-backups")]
+        {
+            let password = super::backup_password(true)?;
+            crate::seal::seal(HEADER, &json, password.as_bytes())
+        }
+        #[cfg(not(feature = "encrypted-backups"))]
+        unreachable!("stratum-encrypted is only registered with the encrypted-backups feature")
+    }
+}
+
+impl Importer for Stratum {
+    fn name(&self) -> &str {
+        Exporter::name(self)
+    }
+
+    fn detect(&self, input: &[u8]) -> bool {
+        if self.encrypted {
+            return input.starts_with(b"AUTHENTICATORPRO");
+        }
+        serde_json::from_slice::<Value>(input)
+            .is_ok_and(|value| value.get("Authenticators").is_some())
+    }
+
+    fn read(&self, input: &[u8]) -> Result<Vec<OtpAuth>> {
+        if !self.encrypted {
+            return read_plain(input);
+        }
+
+        #[cfg(feature = "encrypted-backups")]
+        {
+            let password = super::backup_password(false)?;
+            read_plain(&crate::seal::open(
+                HEADER,
+                input,
+                password.as_bytes(),
+                "encrypted Stratum backup",
+            )?)
+        }
+        #[cfg(not(feature = "encrypted-backups"))]
+        unreachable!("stratum-encrypted is only registered with the encrypted-backups feature")
+    }
+}
+
+fn read_plain(input: &[u8]) -> Result<Vec<OtpAuth>> {
+    let backup: StoredBackup = serde_json::from_slice(input).context("not a Stratum backup")?;
+    let exports = backup
+        .authenticators
+        .into_iter()
+        .filter(|entry| {
+            entry.kind == TYPE_TOTP
+                && entry.algorithm == ALGORITHM_SHA1
+                && entry.digits == totp::DIGITS
+                && entry.period == totp::PERIOD
+        })
+        .map(|entry| {
+            super::read_entry(
+                entry.issuer,
+                entry.username.unwrap_or_default(),
+                &entry.secret,
+            )
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if exports.is_empty() {
+        bail!("the backup has no entries with Battle.net's 8-digit, SHA1, 30 second codes");
+    }
+    Ok(exports)
+}
+
+fn plain(payload: &ExportPayload) -> Result<Vec<u8>> {
+    let backup = Backup {
+        authenticators: [Authenticator {
+            kind: TYPE_TOTP,
+            icon: "blizzard",
+            issuer: &payload.issuer,
+            username: &payload.account,
+            secret: &payload.base32_secret,
+            pin: None,
+            algorithm: ALGORITHM_SHA1,
+            digits: payload.digits,
+            period: payload.period,
+            counter: 0,
+            copy_count: 0,
+            ranking: 0,
+        }],
+        categories: [],
+        authenticator_categories: [],
+        custom_icons: [],
+    };
+    serde_json::to_vec(&backup).context("failed to encode the Stratum backup")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::format::tests::payload;
+
+    #[test]
+    fn plain_backup_has_one_totp_authenticator() {
+        let output = Stratum { encrypted: false }.render(&payload()).unwrap();
+        let value: Value = serde_json::from_slice(&output).unwrap();
+
+        let authenticator = &value["Authenticators"][0];
+        assert_eq!(authenticator["Type"], 2);
+        assert_eq!(authenticator["Issuer"], "Battle.net");
+        assert_eq!(authenticator["Username"], "US-1234-5678-9012");
+        assert_eq!(authenticator["Secret"], "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(authenticator["Digits"], 8);
+        assert_eq!(authenticator["Period"], 30);
+        assert_eq!(value["Categories"], Value::Array(Vec::new()));
+    }
+
+    #[test]
+    fn reads_back_battle_net_entries() {
+        let output = Stratum { encrypted: false }.render(&payload()).unwrap();
+        let mut value: Value = serde_json::from_slice(&output).unwrap();
+        let mut other = value["Authenticators"][0].clone();
+        other["Digits"] = 6.into();
+        value["Authenticators"].as_array_mut().unwrap().push(other);
+        let input = serde_json::to_vec(&value).unwrap();
+
+        let stratum = Stratum { encrypted: false };
+        assert!(stratum.detect(&input));
+        let exports = stratum.read(&input).unwrap();
+        assert_eq!(exports.len(), 1);
+        assert_eq!(exports[0].serial.as_deref(), Some("US-1234-5678-9012"));
+        assert_eq!(*exports[0].secret, b"12345678901234567890");
+    }
+
+    #[cfg(feature = "encrypted-backups")]
+    #[test]
+    fn encrypted_backup_is_detected() {
+        let output = crate::seal::seal(HEADER, b"{}", b"hunter2").unwrap();
+
+        assert!(Stratum { encrypted: true }.detect(&output));
+        assert!(!Stratum { encrypted: false }.detect(&output));
+    }
+}
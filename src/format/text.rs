@@ -0,0 +1,158 @@
+// Everything needed to import the authenticator by hand, as `key: value` lines. Reads back
+// these files and the printout as saved from the terminal, by this version or an earlier one,
+// which have shown the otpauth URI since the first release.
+
+use std::fmt::Write;
+
+use anyhow::{Context, Result, bail};
+use zeroize::Zeroizing;
+
+use bnet_auth_export::otpauth::{LabelOptions, OtpAuth};
+
+use super::{ExportPayload, Exporter, Importer};
+
+pub struct Text;
+
+impl Exporter for Text {
+    fn name(&self) -> &str {
+        "text"
+    }
+
+    fn extension(&self) -> &str {
+        "txt"
+    }
+
+    fn render(&self, payload: &ExportPayload) -> Result<Vec<u8>> {
+        let mut output = String::new();
+        if let Some(serial) = &payload.serial {
+            writeln!(output, "Serial: {serial}")?;
+        }
+        if let Some(restore_code) = &payload.restore_code {
+            writeln!(output, "Restore code: {restore_code}")?;
+        }
+        writeln!(output, "Account: {}", payload.account)?;
+        writeln!(output, "Issuer: {}", payload.issuer)?;
+        writeln!(output, "Device secret (hex): {}", *payload.device_secret)?;
+        writeln!(output, "Base32 secret: {}", *payload.base32_secret)?;
+        writeln!(
+            output,
+            "Settings: {} digits, {}, {} second period",
+            payload.digits, payload.algorithm, payload.period
+        )?;
+        writeln!(output, "otpauth URI: {}", *payload.uri)?;
+        Ok(output.into_bytes())
+    }
+}
+
+impl Importer for Text {
+    fn name(&self) -> &str {
+        "text"
+    }
+
+    fn detect(&self, input: &[u8]) -> bool {
+        std::str::from_utf8(input).is_ok_and(|input| {
+            input.contains("otpauth://") || input.contains("Device secret (hex):")
+        })
+    }
+
+    // Every otpauth URI in the text, wherever it is on its line. A printout without one, such
+    // as `parse --reveal` cut short, is read from its serial and device secret instead.
+    fn read(&self, input: &[u8]) -> Result<Vec<OtpAuth>> {
+        let input = std::str::from_utf8(input).context("input is not valid UTF-8")?;
+        let mut exports: Vec<OtpAuth> = Vec::new();
+        for line in input.lines() {
+            let Some(at) = line.find("otpauth://") else {
+                continue;
+            };
+            let export = OtpAuth::parse(line[at..].trim())?;
+            // The printout can show the same URI more than once.
+            if !exports
+                .iter()
+                .any(|seen| seen.serial == export.serial && *seen.secret == *export.secret)
+            {
+                exports.push(export);
+            }
+        }
+        if !exports.is_empty() {
+            return Ok(exports);
+        }
+
+        let (Some(serial), Some(secret)) = (
+            value(input, "Serial:"),
+            value(input, "Device secret (hex):"),
+        ) else {
+            bail!("no otpauth URI, or serial and device secret, found in the text");
+        };
+        let secret =
+            Zeroizing::new(hex::decode(secret).context("the device secret is not valid hex")?);
+        Ok(vec![OtpAuth::new(
+            serial.to_owned(),
+            secret,
+            &LabelOptions::default(),
+        )])
+    }
+}
+
+// What follows `label`, on its line as in `text` files or on the next one as in the printout.
+fn value<'a>(input: &'a str, label: &str) -> Option<&'a str> {
+    let mut lines = input.lines().map(str::trim);
+    while let Some(line) = lines.next() {
+        let Some(rest) = line.strip_prefix(label) else {
+            continue;
+        };
+        let rest = rest.trim();
+        if !rest.is_empty() {
+            return Some(rest);
+        }
+        return lines.find(|line| !line.is_empty());
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::tests::payload;
+
+    #[test]
+    fn renders_every_field() {
+        let output = String::from_utf8(Text.render(&payload()).unwrap()).unwrap();
+
+        assert!(output.contains("Serial: US-1234-5678-9012\n"));
+        assert!(output.contains("Restore code: U9DQ1KWKPC\n"));
+        assert!(output.contains("Base32 secret: GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ\n"));
+        assert!(output.contains("Settings: 8 digits, SHA1, 30 second period\n"));
+    }
+
+    #[test]
+    fn reads_back_files_and_printouts() {
+        let output = Text.render(&payload()).unwrap();
+        assert!(Text.detect(&output));
+        let exports = Text.read(&output).unwrap();
+        assert_eq!(exports.len(), 1);
+        assert_eq!(exports[0].serial.as_deref(), Some("US-1234-5678-9012"));
+        assert_eq!(*exports[0].secret, b"12345678901234567890");
+
+        // As printed by the first release.
+        let printout = "Battle.net export succeeded\n\notpauth URI (paste into your authenticator app):\notpauth://totp/Battle.net:US-1234-5678-9012?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=Battle.net&digits=8&algorithm=SHA1&period=30\n";
+        let exports = Text.read(printout.as_bytes()).unwrap();
+        assert_eq!(exports.len(), 1);
+        assert_eq!(*exports[0].secret, b"12345678901234567890");
+
+        let printout = "Serial:\nUS-1234-5678-9012\n\nDevice secret (hex):\n3132333435363738393031323334353637383930\n";
+        let exports = Text.read(printout.as_bytes()).unwrap();
+        assert_eq!(exports[0].serial.as_deref(), Some("US-1234-5678-9012"));
+        assert_eq!(*exports[0].secret, b"12345678901234567890");
+    }
+
+    #[test]
+    fn skips_a_missing_serial() {
+        let mut payload = payload();
+        payload.serial = None;
+        payload.restore_code = None;
+        let output = String::from_utf8(Text.render(&payload).unwrap()).unwrap();
+
+        assert!(!output.contains("Serial:"));
+        assert!(!output.contains("Restore code:"));
+    }
+}
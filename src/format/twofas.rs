@@ -0,0 +1,211 @@
+// 2FAS backups, the `.2fas` file 2FAS writes and imports under Settings → 2FAS Backup, without
+// a password. Reading one picks out the services with Battle.net's code parameters; a backup
+// with a password has to be written again without one first.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use zeroize::Zeroizing;
+
+use bnet_auth_export::otpauth::OtpAuth;
+use bnet_auth_export::totp;
+
+use super::{ExportPayload, Exporter, Importer};
+
+// The backup schema 2FAS writes at the moment.
+const SCHEMA_VERSION: u32 = 4;
+
+pub struct TwoFas;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Backup<'a> {
+    services: [Service<'a>; 1],
+    groups: [(); 0],
+    updated_at: u64,
+    schema_version: u32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Service<'a> {
+    name: &'a str,
+    secret: &'a str,
+    updated_at: u64,
+    otp: Otp<'a>,
+    order: Order,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Otp<'a> {
+    label: &'a str,
+    account: &'a str,
+    issuer: &'a str,
+    digits: u32,
+    period: u64,
+    algorithm: &'static str,
+    token_type: &'static str,
+    source: &'static str,
+}
+
+#[derive(Serialize)]
+struct Order {
+    position: u32,
+}
+
+// The parts of a backup needed to tell whether a service is a Battle.net authenticator.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoredBackup {
+    #[serde(default)]
+    services: Vec<StoredService>,
+    services_encrypted: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct StoredService {
+    name: String,
+    secret: Zeroizing<String>,
+    otp: StoredOtp,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoredOtp {
+    account: Option<String>,
+    issuer: Option<String>,
+    digits: Option<u32>,
+    period: Option<u64>,
+    algorithm: Option<String>,
+    token_type: Option<String>,
+}
+
+impl Exporter for TwoFas {
+    fn name(&self) -> &str {
+        "2fas"
+    }
+
+    fn extension(&self) -> &str {
+        "2fas"
+    }
+
+    fn render(&self, payload: &ExportPayload) -> Result<Vec<u8>> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64);
+        let backup = Backup {
+            services: [Service {
+                name: &payload.issuer,
+                secret: &payload.base32_secret,
+                updated_at: now,
+                otp: Otp {
+                    label: &payload.account,
+                    account: &payload.account,
+                    issuer: &payload.issuer,
+                    digits: payload.digits,
+                    period: payload.period,
+                    algorithm: payload.algorithm,
+                    token_type: "TOTP",
+                    source: "Manual",
+                },
+                order: Order { position: 0 },
+            }],
+            groups: [],
+            updated_at: now,
+            schema_version: SCHEMA_VERSION,
+        };
+        serde_json::to_vec(&backup).context("failed to encode the 2FAS backup")
+    }
+}
+
+impl Importer for TwoFas {
+    fn name(&self) -> &str {
+        "2fas"
+    }
+
+    fn detect(&self, input: &[u8]) -> bool {
+        serde_json::from_slice::<Value>(input)
+            .is_ok_and(|value| value.get("schemaVersion").is_some())
+    }
+
+    fn read(&self, input: &[u8]) -> Result<Vec<OtpAuth>> {
+        let backup: StoredBackup = serde_json::from_slice(input).context("not a 2FAS backup")?;
+        if backup.services_encrypted.is_some() {
+            bail!("the 2FAS backup has a password, write it again from 2FAS without one");
+        }
+
+        // 2FAS leaves out the parameters that have their usual value.
+        let exports = backup
+            .services
+            .into_iter()
+            .filter(|service| {
+                let otp = &service.otp;
+                otp.token_type.as_deref().unwrap_or("TOTP") == "TOTP"
+                    && otp.algorithm.as_deref().unwrap_or("SHA1") == "SHA1"
+                    && otp.digits == Some(totp::DIGITS)
+                    && otp.period.unwrap_or(totp::PERIOD) == totp::PERIOD
+            })
+            .map(|service| {
+                let issuer = service.otp.issuer.unwrap_or(service.name);
+                super::read_entry(
+                    issuer,
+                    service.otp.account.unwrap_or_default(),
+                    &service.secret,
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if exports.is_empty() {
+            bail!("the backup has no services with Battle.net's 8-digit, SHA1, 30 second codes");
+        }
+        Ok(exports)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::format::tests::payload;
+
+    #[test]
+    fn backup_has_one_totp_service() {
+        let output = TwoFas.render(&payload()).unwrap();
+        let value: Value = serde_json::from_slice(&output).unwrap();
+
+        assert_eq!(value["schemaVersion"], 4);
+        let service = &value["services"][0];
+        assert_eq!(service["name"], "Battle.net");
+        assert_eq!(service["secret"], "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(service["otp"]["account"], "US-1234-5678-9012");
+        assert_eq!(service["otp"]["digits"], 8);
+        assert_eq!(service["otp"]["tokenType"], "TOTP");
+    }
+
+    #[test]
+    fn reads_back_battle_net_services() {
+        let output = TwoFas.render(&payload()).unwrap();
+        let mut value: Value = serde_json::from_slice(&output).unwrap();
+        let mut other = value["services"][0].clone();
+        other["otp"]["digits"] = 6.into();
+        value["services"].as_array_mut().unwrap().push(other);
+        let input = serde_json::to_vec(&value).unwrap();
+
+        assert!(TwoFas.detect(&input));
+        let exports = TwoFas.read(&input).unwrap();
+        assert_eq!(exports.len(), 1);
+        assert_eq!(exports[0].serial.as_deref(), Some("US-1234-5678-9012"));
+        assert_eq!(*exports[0].secret, b"12345678901234567890");
+    }
+
+    #[test]
+    fn refuses_a_backup_with_a_password() {
+        let input = br#"{"services":[],"servicesEncrypted":"abc:def:ghi","schemaVersion":4}"#;
+
+        let err = TwoFas.read(input).err().unwrap();
+        assert!(err.to_string().contains("password"), "{err}");
+    }
+}
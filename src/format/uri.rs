@@ -0,0 +1,74 @@
+// Just the otpauth URI, one line, for piping into an app's import or a QR encoder. Reads back
+// one URI per line.
+
+use anyhow::{Context, Result, bail};
+
+use bnet_auth_export::otpauth::OtpAuth;
+
+use super::{ExportPayload, Exporter, Importer};
+
+pub struct Uri;
+
+impl Exporter for Uri {
+    fn name(&self) -> &str {
+        "uri"
+    }
+
+    fn extension(&self) -> &str {
+        "uri"
+    }
+
+    fn render(&self, payload: &ExportPayload) -> Result<Vec<u8>> {
+        Ok(format!("{}\n", *payload.uri).into_bytes())
+    }
+}
+
+impl Importer for Uri {
+    fn name(&self) -> &str {
+        "uri"
+    }
+
+    fn detect(&self, input: &[u8]) -> bool {
+        input.trim_ascii_start().starts_with(b"otpauth://")
+    }
+
+    fn read(&self, input: &[u8]) -> Result<Vec<OtpAuth>> {
+        let input = std::str::from_utf8(input).context("input is not valid UTF-8")?;
+        let exports = input
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(OtpAuth::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        if exports.is_empty() {
+            bail!("no otpauth URI found");
+        }
+        Ok(exports)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::tests::payload;
+
+    #[test]
+    fn renders_one_line() {
+        let output = String::from_utf8(Uri.render(&payload()).unwrap()).unwrap();
+
+        assert!(output.starts_with("otpauth://totp/Battle.net:US-1234-5678-9012?"));
+        assert_eq!(output.lines().count(), 1);
+    }
+
+    #[test]
+    fn reads_one_uri_per_line() {
+        let line = Uri.render(&payload()).unwrap();
+        let input = [line.as_slice(), b"\n", &line].concat();
+
+        assert!(Uri.detect(&input));
+        let exports = Uri.read(&input).unwrap();
+        assert_eq!(exports.len(), 2);
+        assert_eq!(exports[0].serial.as_deref(), Some("US-1234-5678-9012"));
+        assert_eq!(*exports[0].secret, b"12345678901234567890");
+    }
+}
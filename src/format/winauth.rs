@@ -0,0 +1,378 @@
+// WinAuth's Battle.net authenticators, which for many users are the only copy of a secret added
+// years ago: from winauth.xml, in the clear or protected with a WinAuth password, or from the
+// otpauth text file WinAuth exports. Entries protected with the Windows account or a YubiKey
+// can only be decrypted by WinAuth itself, on that machine.
+
+mod blowfish;
+
+use std::sync::Mutex;
+
+use anyhow::{Context, Result, bail};
+use reqwest::Url;
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use bnet_auth_export::otpauth::{LabelOptions, OtpAuth};
+use bnet_auth_export::region::Region;
+use bnet_auth_export::totp;
+
+use super::Importer;
+use blowfish::Blowfish;
+
+// Data encrypted by WinAuth 3 starts with "WINAUTH3" in hex, then a salt and the SHA-256 of
+// the salt and plaintext, which tells a wrong password from a right one.
+const HEADER: &str = "57494E4155544833";
+const SALT_LEN: usize = 8;
+const PBKDF2_ITERATIONS: u32 = 2000;
+// WinAuth asks PBKDF2 for 256 bytes, of which Blowfish's key schedule uses the first 72.
+const KEY_LEN: usize = 256;
+
+pub struct WinAuth;
+
+// Where the password comes from, asked for only once an entry turns out to need it.
+type Password = dyn Fn() -> Result<Zeroizing<String>>;
+
+impl Importer for WinAuth {
+    fn name(&self) -> &str {
+        "winauth"
+    }
+
+    fn detect(&self, input: &[u8]) -> bool {
+        std::str::from_utf8(input).is_ok_and(|input| {
+            let input = input.trim_start_matches('\u{feff}').trim_start();
+            input.starts_with("<?xml") && input.contains("<WinAuth")
+                || input.starts_with("<WinAuth")
+                || input
+                    .lines()
+                    .any(|line| line.trim().starts_with("otpauth://battlenet/"))
+        })
+    }
+
+    fn read(&self, input: &[u8]) -> Result<Vec<OtpAuth>> {
+        let input = std::str::from_utf8(input).context("input is not valid UTF-8")?;
+        let input = input.trim_start_matches('\u{feff}');
+        let exports = if input.trim_start().starts_with('<') {
+            read_config(input, &password)?
+        } else {
+            read_export(input)?
+        };
+        if exports.is_empty() {
+            bail!("the WinAuth file has no Battle.net authenticators");
+        }
+        Ok(exports)
+    }
+}
+
+// The Battle.net entries of winauth.xml. With a WinAuth password on the whole configuration,
+// it holds the encrypted configuration instead.
+fn read_config(xml: &str, password: &Password) -> Result<Vec<OtpAuth>> {
+    let document = roxmltree::Document::parse(xml).context("not a valid WinAuth configuration")?;
+    let root = document.root_element();
+    if !root.has_tag_name("WinAuth") {
+        bail!("not a WinAuth configuration");
+    }
+    if let Some(types) = root
+        .attribute("encrypted")
+        .filter(|types| !types.is_empty())
+    {
+        let plain = decrypt(
+            root.text().unwrap_or_default(),
+            types,
+            "configuration",
+            password,
+        )?;
+        return read_config(&xml_text(&plain)?, password);
+    }
+
+    let mut exports = Vec::new();
+    for entry in root
+        .descendants()
+        .filter(|node| node.has_tag_name("WinAuthAuthenticator"))
+    {
+        let Some(data) = entry
+            .children()
+            .find(|node| node.has_tag_name("authenticatordata"))
+        else {
+            continue;
+        };
+        if !data
+            .attribute("class")
+            .is_some_and(|class| class.ends_with("BattleNetAuthenticator"))
+        {
+            continue;
+        }
+        let name = entry
+            .children()
+            .find(|node| node.has_tag_name("name"))
+            .and_then(|node| node.text())
+            .unwrap_or("Battle.net");
+
+        let secret_data = match data
+            .attribute("encrypted")
+            .filter(|types| !types.is_empty())
+        {
+            Some(types) => {
+                let plain = decrypt(data.text().unwrap_or_default(), types, name, password)?;
+                let xml = xml_text(&plain)?;
+                let inner = roxmltree::Document::parse(&xml)
+                    .with_context(|| format!("failed to read the decrypted {name} entry"))?;
+                secret_data(inner.root_element())
+            }
+            None => secret_data(data),
+        };
+        let Some(secret_data) = secret_data else {
+            bail!("the {name} entry has no secret");
+        };
+        exports.push(battle_net(&secret_data, name)?);
+    }
+    Ok(exports)
+}
+
+fn secret_data(data: roxmltree::Node) -> Option<Zeroizing<String>> {
+    data.descendants()
+        .find(|node| node.has_tag_name("secretdata"))
+        .and_then(|node| node.text())
+        .map(|text| Zeroizing::new(text.trim().to_owned()))
+}
+
+// A Battle.net entry's secret data: the hex secret, optionally followed by tab-separated code
+// settings, then `|` and the hex of the serial.
+fn battle_net(secret_data: &str, name: &str) -> Result<OtpAuth> {
+    let (secret, serial) = secret_data.split_once('|').unwrap_or((secret_data, ""));
+    let secret = secret.split('\t').next().unwrap_or_default();
+    let secret = Zeroizing::new(
+        hex::decode(secret).with_context(|| format!("the {name} entry's secret is not hex"))?,
+    );
+    let serial = hex::decode(serial)
+        .ok()
+        .and_then(|serial| String::from_utf8(serial).ok())
+        .filter(|serial| !serial.is_empty())
+        .with_context(|| format!("the {name} entry has no serial"))?;
+    let serial = Region::from_serial(&serial)
+        .unwrap_or_default()
+        .normalize_serial(&serial)?;
+    Ok(OtpAuth::new(serial, secret, &LabelOptions::default()))
+}
+
+// The `otpauth://battlenet/...?secret=...&serial=...` lines of a WinAuth export, and plain TOTP
+// lines issued by Battle.net or Blizzard. The other apps' entries are skipped.
+fn read_export(input: &str) -> Result<Vec<OtpAuth>> {
+    let mut exports = Vec::new();
+    for line in input.lines().map(str::trim) {
+        if !line.starts_with("otpauth://battlenet/") {
+            if let Ok(export) = OtpAuth::parse(line)
+                && (export.issuer.contains("Battle.net") || export.issuer.contains("Blizzard"))
+            {
+                exports.push(export);
+            }
+            continue;
+        }
+        let url = Url::parse(line).context("not a valid otpauth URI in the WinAuth export")?;
+        let param = |name: &str| {
+            url.query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| Zeroizing::new(value.into_owned()))
+        };
+        let Some(secret) = param("secret") else {
+            bail!("a Battle.net entry in the WinAuth export has no secret");
+        };
+        let Some(serial) = param("serial") else {
+            bail!("a Battle.net entry in the WinAuth export has no serial");
+        };
+        let serial = Region::from_serial(&serial)
+            .unwrap_or_default()
+            .normalize_serial(&serial)?;
+        exports.push(OtpAuth::new(
+            serial,
+            totp::decode_base32(&secret)?,
+            &LabelOptions::default(),
+        ));
+    }
+    Ok(exports)
+}
+
+// Undo WinAuth's encryption of `data`, hex as stored, with the protection `types` names, e.g.
+// "y" for a WinAuth password.
+fn decrypt(data: &str, types: &str, what: &str, password: &Password) -> Result<Zeroizing<Vec<u8>>> {
+    if types.contains(['u', 'm']) {
+        bail!(
+            "WinAuth protected the {what} with the Windows account, which only WinAuth can decrypt on that machine. Remove that protection in WinAuth's settings, or export from WinAuth, first"
+        );
+    }
+    if types.contains(['a', 'b']) {
+        bail!(
+            "WinAuth protected the {what} with a YubiKey, which only WinAuth can decrypt. Remove that protection in WinAuth's settings, or export from WinAuth, first"
+        );
+    }
+    if !types.contains('y') {
+        bail!("the {what} is protected in a way this version of WinAuth import doesn't know");
+    }
+
+    let data = data.trim();
+    let hashed = data.len() >= HEADER.len() && data[..HEADER.len()].eq_ignore_ascii_case(HEADER);
+    let (check, data) = if hashed {
+        let rest = &data[HEADER.len()..];
+        if rest.len() < SALT_LEN * 2 + 64 {
+            bail!("the encrypted {what} is too short");
+        }
+        let (salt, rest) = rest.split_at(SALT_LEN * 2);
+        let (hash, rest) = rest.split_at(64);
+        (Some((salt, hash)), rest)
+    } else {
+        (None, data)
+    };
+
+    let password = password()?;
+    let plain = decrypt_with(data, password.as_bytes())
+        .with_context(|| format!("failed to decrypt the {what}"))?;
+    if let Some((salt, hash)) = check {
+        let salt = hex::decode(salt).context("the encrypted data is not hex")?;
+        let actual = Sha256::new()
+            .chain_update(&salt)
+            .chain_update(&*plain)
+            .finalize();
+        if !hex::encode(actual).eq_ignore_ascii_case(hash) {
+            bail!("the WinAuth password is wrong");
+        }
+    }
+    Ok(plain)
+}
+
+// A salt in hex, then Blowfish-ECB ciphertext in hex with ISO 10126 padding, under a PBKDF2
+// key from the password.
+fn decrypt_with(data: &str, password: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+    if data.len() < SALT_LEN * 2 {
+        bail!("the encrypted data is too short");
+    }
+    let (salt, ciphertext) = data.split_at(SALT_LEN * 2);
+    let salt = hex::decode(salt).context("the encrypted data is not hex")?;
+    let mut plain =
+        Zeroizing::new(hex::decode(ciphertext).context("the encrypted data is not hex")?);
+    if plain.is_empty() || plain.len() % 8 != 0 {
+        bail!("the encrypted data is not whole Blowfish blocks");
+    }
+
+    let mut key = Zeroizing::new([0; KEY_LEN]);
+    pbkdf2::pbkdf2_hmac::<sha1::Sha1>(password, &salt, PBKDF2_ITERATIONS, &mut *key);
+    let cipher = Blowfish::new(&*key);
+    for block in plain.chunks_mut(8) {
+        cipher.decrypt_block(block);
+    }
+    let padding = usize::from(plain[plain.len() - 1]);
+    if !(1..=8).contains(&padding) {
+        bail!("the WinAuth password is wrong, or the file is damaged");
+    }
+    let len = plain.len() - padding;
+    plain.truncate(len);
+    Ok(plain)
+}
+
+// Decrypted XML, which WinAuth writes with a byte order mark.
+fn xml_text(plain: &[u8]) -> Result<String> {
+    let text = std::str::from_utf8(plain)
+        .ok()
+        .filter(|text| {
+            text.trim_start_matches('\u{feff}')
+                .trim_start()
+                .starts_with('<')
+        })
+        .context("the WinAuth password is wrong, or the file is damaged")?;
+    Ok(text.trim_start_matches('\u{feff}').to_owned())
+}
+
+// The WinAuth password, asked for once however many entries it protects.
+fn password() -> Result<Zeroizing<String>> {
+    static PASSWORD: Mutex<Option<Zeroizing<String>>> = Mutex::new(None);
+
+    let mut saved = PASSWORD.lock().unwrap_or_else(|err| err.into_inner());
+    if let Some(password) = &*saved {
+        return Ok(password.clone());
+    }
+    let password = crate::seal::password("WinAuth password", "BNET_WINAUTH_PASSWORD", false)?;
+    crate::interrupt::forget_on_interrupt(&PASSWORD);
+    *saved = Some(password.clone());
+    Ok(password)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET_DATA: &str =
+        "3132333435363738393031323334353637383930\t8\tSHA1\t30|55532D313233342D353637382D39303132";
+
+    // As WinAuth encrypts with a password, for reading it back.
+    fn encrypt(plain: &[u8], password: &[u8]) -> String {
+        let salt = [7; SALT_LEN];
+        let mut key = [0; KEY_LEN];
+        pbkdf2::pbkdf2_hmac::<sha1::Sha1>(password, &salt, PBKDF2_ITERATIONS, &mut key);
+        let cipher = Blowfish::new(&key);
+        let padding = 8 - plain.len() % 8;
+        let mut data = plain.to_vec();
+        data.extend(std::iter::repeat_n(0xa5, padding - 1));
+        data.push(padding as u8);
+        for block in data.chunks_mut(8) {
+            cipher.encrypt_block(block);
+        }
+
+        let hash_salt = [9; SALT_LEN];
+        let hash = Sha256::new()
+            .chain_update(hash_salt)
+            .chain_update(plain)
+            .finalize();
+        format!(
+            "{HEADER}{}{}{}{}",
+            hex::encode_upper(hash_salt),
+            hex::encode_upper(hash),
+            hex::encode_upper(salt),
+            hex::encode_upper(data)
+        )
+    }
+
+    #[test]
+    fn reads_battle_net_entries_from_the_configuration() {
+        let xml = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<WinAuth version="3.5">
+  <WinAuthAuthenticator id="1"><name>Google</name><authenticatordata class="WinAuth.GoogleAuthenticator"><secretdata>00</secretdata></authenticatordata></WinAuthAuthenticator>
+  <WinAuthAuthenticator id="2"><name>Battle.net</name><authenticatordata class="WinAuth.BattleNetAuthenticator"><servertimediff>0</servertimediff><secretdata>{SECRET_DATA}</secretdata></authenticatordata></WinAuthAuthenticator>
+</WinAuth>"#
+        );
+        assert!(WinAuth.detect(xml.as_bytes()));
+        let exports = WinAuth.read(xml.as_bytes()).unwrap();
+        assert_eq!(exports.len(), 1);
+        assert_eq!(exports[0].serial.as_deref(), Some("US-1234-5678-9012"));
+        assert_eq!(*exports[0].secret, b"12345678901234567890");
+    }
+
+    #[test]
+    fn decrypts_a_password_protected_configuration() {
+        let inner = format!(
+            "\u{feff}<?xml version=\"1.0\" encoding=\"utf-8\"?><WinAuth version=\"3.5\"><WinAuthAuthenticator><name>Battle.net</name><authenticatordata class=\"WinAuth.BattleNetAuthenticator\"><secretdata>{SECRET_DATA}</secretdata></authenticatordata></WinAuthAuthenticator></WinAuth>"
+        );
+        let xml = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?><WinAuth version="3.5" encrypted="y">{}</WinAuth>"#,
+            encrypt(inner.as_bytes(), b"hunter2")
+        );
+        assert!(WinAuth.detect(xml.as_bytes()));
+
+        let right = || Ok(Zeroizing::new("hunter2".to_owned()));
+        let exports = read_config(&xml, &right).unwrap();
+        assert_eq!(exports[0].serial.as_deref(), Some("US-1234-5678-9012"));
+        assert_eq!(*exports[0].secret, b"12345678901234567890");
+
+        let wrong = || Ok(Zeroizing::new("hunter3".to_owned()));
+        let err = read_config(&xml, &wrong).err().unwrap();
+        assert!(format!("{err:#}").contains("password is wrong"), "{err:#}");
+    }
+
+    #[test]
+    fn reads_the_otpauth_export() {
+        let input = "otpauth://totp/Google:me?secret=JBSWY3DP\notpauth://battlenet/Battle.net?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&digits=8&serial=US123456789012\n";
+        assert!(WinAuth.detect(input.as_bytes()));
+        let exports = WinAuth.read(input.as_bytes()).unwrap();
+        assert_eq!(exports.len(), 1);
+        assert_eq!(exports[0].serial.as_deref(), Some("US-1234-5678-9012"));
+        assert_eq!(*exports[0].secret, b"12345678901234567890");
+    }
+}
@@ -0,0 +1,111 @@
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+use crate::token::find_session_token;
+
+// The parts of a HAR 1.2 archive that can carry the session token.
+#[derive(Deserialize)]
+struct Har {
+    log: Log,
+}
+
+#[derive(Deserialize)]
+struct Log {
+    #[serde(default)]
+    entries: Vec<Entry>,
+}
+
+#[derive(Deserialize)]
+struct Entry {
+    request: Request,
+    response: Option<Response>,
+}
+
+#[derive(Deserialize)]
+struct Request {
+    url: String,
+    #[serde(default)]
+    headers: Vec<NameValue>,
+    #[serde(default)]
+    cookies: Vec<NameValue>,
+    #[serde(rename = "postData")]
+    post_data: Option<PostData>,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    #[serde(default, rename = "redirectURL")]
+    redirect_url: String,
+    #[serde(default)]
+    headers: Vec<NameValue>,
+    #[serde(default)]
+    cookies: Vec<NameValue>,
+}
+
+#[derive(Deserialize)]
+struct NameValue {
+    name: String,
+    value: String,
+}
+
+#[derive(Deserialize)]
+struct PostData {
+    #[serde(default)]
+    text: String,
+}
+
+/// Scan a HAR export for the session token, preferring the most recent request.
+pub fn session_token(path: &Path) -> Result<String> {
+    let data = fs::read(path).map_err(|source| Error::Io {
+        path: path.to_owned(),
+        source,
+    })?;
+    let har: Har = serde_json::from_slice(&data).map_err(|err| Error::Parse {
+        what: format!("HAR file {}", path.display()),
+        detail: err.to_string(),
+    })?;
+
+    har.log
+        .entries
+        .iter()
+        .rev()
+        .find_map(entry_token)
+        .ok_or_else(|| {
+            Error::InvalidInput(format!(
+                "no Battle.net session token found in {}; record the HAR while logging in with the ?ref=localhost link",
+                path.display()
+            ))
+        })
+}
+
+fn entry_token(entry: &Entry) -> Option<String> {
+    let request = &entry.request;
+    let cookies = request
+        .cookies
+        .iter()
+        .chain(entry.response.iter().flat_map(|response| &response.cookies))
+        .map(|cookie| cookie.value.as_str());
+    let headers = request
+        .headers
+        .iter()
+        .chain(entry.response.iter().flat_map(|response| &response.headers))
+        .filter(|header| {
+            ["location", "referer", "cookie", "set-cookie"]
+                .contains(&header.name.to_ascii_lowercase().as_str())
+        })
+        .map(|header| header.value.as_str());
+
+    // Redirect targets first: the localhost redirect after login is where the token lives.
+    entry
+        .response
+        .iter()
+        .map(|response| response.redirect_url.as_str())
+        .chain([request.url.as_str()])
+        .chain(cookies)
+        .chain(headers)
+        .chain(request.post_data.iter().map(|post| post.text.as_str()))
+        .find_map(find_session_token)
+}
@@ -0,0 +1,151 @@
+// --input-image: the serial and restore code read from a screenshot of the official app's restore
+// screen, from a QR code with `zbarimg` or as text with `tesseract`. Both are found on PATH like
+// format plugins, so no image code is built in. `verify` decodes QR codes the same way.
+
+use std::io::ErrorKind;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, bail};
+
+use bnet_auth_export::region::Region;
+use bnet_auth_export::restore;
+
+#[derive(Default)]
+pub struct Found {
+    pub serial: Option<String>,
+    pub restore_code: Option<String>,
+}
+
+impl Found {
+    fn is_complete(&self) -> bool {
+        self.serial.is_some() && self.restore_code.is_some()
+    }
+}
+
+pub fn read(path: &Path, region: Region) -> Result<Found> {
+    if !path.is_file() {
+        bail!("{} is not a file", path.display());
+    }
+    let path = path.to_string_lossy();
+
+    // A QR code is exact and quick to find, so OCR only runs when it isn't enough.
+    let qr = run("zbarimg", &["--quiet", "--raw", &path])?;
+    let mut found = qr
+        .as_deref()
+        .map(|text| find_account(text, region))
+        .unwrap_or_default();
+    let mut text = qr.is_some();
+    if !found.is_complete() {
+        let ocr = run("tesseract", &[&path, "stdout"])?;
+        text |= ocr.is_some();
+        if let Some(ocr) = ocr {
+            let read = find_account(&ocr, region);
+            found.serial = found.serial.or(read.serial);
+            found.restore_code = found.restore_code.or(read.restore_code);
+        }
+    }
+
+    if !text {
+        bail!(
+            "--input-image needs tesseract (to read text) or zbarimg (to read QR codes) installed on PATH"
+        );
+    }
+    if found.serial.is_none() && found.restore_code.is_none() {
+        bail!("found no serial or restore code in {path}");
+    }
+    Ok(found)
+}
+
+// The contents of every QR code in the image, one per line.
+pub fn decode_qr(path: &Path) -> Result<Vec<String>> {
+    if !path.is_file() {
+        bail!("{} is not a file", path.display());
+    }
+    let Some(output) = run("zbarimg", &["--quiet", "--raw", &path.to_string_lossy()])? else {
+        bail!("reading QR codes needs zbarimg installed on PATH");
+    };
+    let codes: Vec<_> = output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_owned)
+        .collect();
+    if codes.is_empty() {
+        bail!("found no QR code in {}", path.display());
+    }
+    Ok(codes)
+}
+
+// What the tool printed, or `None` when it isn't installed. zbarimg fails when the image has no
+// barcode, which is no reason to stop either.
+fn run(tool: &str, args: &[&str]) -> Result<Option<String>> {
+    match Command::new(tool)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+    {
+        Ok(output) => Ok(Some(String::from_utf8_lossy(&output.stdout).into_owned())),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err).with_context(|| format!("failed to run {tool}")),
+    }
+}
+
+// The first serial and restore code in the text, however it was split across words and lines.
+fn find_account(text: &str, region: Region) -> Found {
+    let mut found = Found::default();
+    for line in text.lines() {
+        let compact: String = line
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .map(|c| c.to_ascii_uppercase())
+            .collect();
+        if found.serial.is_none() {
+            found.serial = find_serial(&compact, region);
+        }
+        if found.restore_code.is_none() {
+            // The code on a line of its own, or as one word after a label.
+            found.restore_code = std::iter::once(compact.as_str())
+                .chain(line.split_whitespace())
+                .find_map(|word| restore::normalize_restore_code(word).ok());
+        }
+    }
+    found
+}
+
+// A region prefix followed by the serial's 12 digits.
+fn find_serial(compact: &str, region: Region) -> Option<String> {
+    let bytes = compact.as_bytes();
+    (0..bytes.len()).find_map(|start| {
+        let digits = bytes.get(start + 2..start + 14)?;
+        if !bytes[start..start + 2].iter().all(u8::is_ascii_uppercase)
+            || !digits.iter().all(u8::is_ascii_digit)
+        {
+            return None;
+        }
+        region.normalize_serial(&compact[start..start + 14]).ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_account_on_the_restore_screen() {
+        let text = "Restore Authenticator\n\nSerial Number\nUS-2101-2345-6789\n\nRestore Code\nABCDE 12345\n";
+
+        let found = find_account(text, Region::Us);
+        assert_eq!(found.serial.as_deref(), Some("US-2101-2345-6789"));
+        assert_eq!(found.restore_code.as_deref(), Some("ABCDE12345"));
+    }
+
+    #[test]
+    fn finds_both_on_one_line() {
+        let found = find_account("Serial: EU 2101 2345 6789 Code: U9DQ1KWKPC", Region::Us);
+
+        assert_eq!(found.serial.as_deref(), Some("EU-2101-2345-6789"));
+        assert_eq!(found.restore_code.as_deref(), Some("U9DQ1KWKPC"));
+    }
+}
@@ -0,0 +1,58 @@
+// `import`: read an artifact of this tool, from the printout saved by the first release to a
+// `backup` file, or another app's export, and export it again as if it had just been restored:
+// to --format, --store, --print or the printout. Nothing is sent to Battle.net, so old backups
+// stay usable as the formats move on.
+
+use std::path::Path;
+
+use anyhow::{Result, bail};
+
+use bnet_auth_export::otpauth::LabelOptions;
+
+use crate::convert;
+use crate::format::OutputOptions;
+
+pub fn run(
+    from: Option<&str>,
+    input: Option<&Path>,
+    labels: &LabelOptions,
+    output: &OutputOptions,
+) -> Result<()> {
+    output.require_destination()?;
+    let (format, mut exports) = convert::read_format(from, input)?;
+    if exports.len() > 1 && !writes_each(output) {
+        bail!(
+            "the input holds {} authenticators, import them with --format all or --store to keep them apart",
+            exports.len()
+        );
+    }
+    eprintln!(
+        "Read {} from the {format} input.",
+        match exports.len() {
+            1 => "1 authenticator".to_owned(),
+            n => format!("{n} authenticators"),
+        }
+    );
+
+    let mut printed = false;
+    for export in &mut exports {
+        export.relabel(labels);
+        if !output.write(export)? {
+            crate::print_export(export);
+            printed = true;
+        }
+    }
+    if printed {
+        crate::confirm_and_clear()?;
+    }
+    Ok(())
+}
+
+// Whether each authenticator gets a place of its own, rather than all going to stdout.
+fn writes_each(output: &OutputOptions) -> bool {
+    #[cfg(feature = "store")]
+    if output.store.is_some() {
+        return true;
+    }
+    output.format.as_deref() == Some("all")
+}
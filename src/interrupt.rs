@@ -0,0 +1,99 @@
+// Ctrl-C, SIGTERM or SIGHUP in the middle of a run. The process then exits without running
+// destructors, so what they would have cleaned up is registered here as well: output files still
+// being written are removed, temp files shredded, cached passwords wiped, a secret left on the
+// screen cleared and the terminal put back. It exits with 128 plus the signal number, 130 for
+// Ctrl-C, as shells report a process a signal ended.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::Result;
+#[cfg(feature = "encrypted-backups")]
+use zeroize::Zeroizing;
+
+use bnet_auth_export::secure_file;
+
+type Hook = Box<dyn FnOnce() + Send>;
+
+// Cleanups in the order they were registered, run newest first. Id 0 is never removed.
+static HOOKS: Mutex<Vec<(u64, Hook)>> = Mutex::new(Vec::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+// Runs its cleanup on an interrupt for as long as it is held.
+#[must_use]
+pub struct Cleanup(u64);
+
+impl Drop for Cleanup {
+    fn drop(&mut self) {
+        hooks().retain(|(id, _)| *id != self.0);
+    }
+}
+
+pub fn on_interrupt(hook: impl FnOnce() + Send + 'static) -> Cleanup {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    hooks().push((id, Box::new(hook)));
+    Cleanup(id)
+}
+
+// Remove `path` if the run is interrupted before the guard is dropped, once the file is complete.
+pub fn remove_on_interrupt(path: impl Into<PathBuf>) -> Cleanup {
+    let path = path.into();
+    on_interrupt(move || {
+        if fs::remove_file(&path).is_ok() {
+            eprintln!("Removed the unfinished {}", path.display());
+        }
+    })
+}
+
+// Wipe a password cached for the rest of the run.
+#[cfg(feature = "encrypted-backups")]
+pub fn forget_on_interrupt(cache: &'static Mutex<Option<Zeroizing<String>>>) {
+    hooks().push((
+        0,
+        Box::new(move || {
+            cache.lock().unwrap_or_else(|err| err.into_inner()).take();
+        }),
+    ));
+}
+
+fn hooks() -> std::sync::MutexGuard<'static, Vec<(u64, Hook)>> {
+    HOOKS.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+// Clean up and exit on the first signal, from a thread of its own, where anything can be done
+// safely rather than only what a signal handler may do.
+#[cfg(unix)]
+pub fn install() -> Result<()> {
+    use anyhow::Context;
+    use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+    use signal_hook::iterator::Signals;
+
+    let mut signals =
+        Signals::new([SIGINT, SIGTERM, SIGHUP]).context("failed to set up Ctrl-C handling")?;
+    std::thread::spawn(move || {
+        if let Some(signal) = signals.forever().next() {
+            clean_up();
+            std::process::exit(128 + signal);
+        }
+    });
+    Ok(())
+}
+
+// Windows still ends the process on Ctrl-C without cleaning up.
+#[cfg(not(unix))]
+pub fn install() -> Result<()> {
+    Ok(())
+}
+
+fn clean_up() {
+    // Taken out first, so a hook that drops a guard doesn't wait on the lock.
+    let hooks: Vec<_> = hooks().drain(..).collect();
+    eprintln!();
+    for (_, hook) in hooks.into_iter().rev() {
+        hook();
+    }
+    secure_file::remove_temp_files();
+    eprintln!("Interrupted.");
+}
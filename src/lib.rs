@@ -0,0 +1,76 @@
+//! Export Battle.net authenticators into standard TOTP apps.
+//!
+//! This is the library behind the `bnet-auth-export` CLI. The export flow is:
+//!
+//! 1. Find the session token in whatever the user has, with [`token::normalize_session_token`].
+//! 2. Exchange it for a bearer token with [`api::Api::exchange_session_token`].
+//! 3. Restore the authenticator with [`api::Api::device_secret`].
+//! 4. Turn the device secret into an otpauth URI with [`otpauth::OtpAuth`].
+//!
+//! ```no_run
+//! use bnet_auth_export::api::{Api, ApiOptions};
+//! use bnet_auth_export::Zeroizing;
+//! use bnet_auth_export::otpauth::{LabelOptions, OtpAuth};
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let api = Api::new(ApiOptions::default())?;
+//! let bearer_token = api.exchange_session_token("US-...")?;
+//! let device_secret = api.device_secret(&bearer_token, "US-2101-2345-6789", "ABCDE12345")?;
+//!
+//! let export = OtpAuth::new(
+//!     "US-2101-2345-6789".to_owned(),
+//!     Zeroizing::new(hex::decode(device_secret.as_str())?),
+//!     &LabelOptions::default(),
+//! );
+//! println!("{}", *export.to_uri());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Tokens and secrets are returned wrapped in [`Zeroizing`], which wipes them from memory when
+//! they are dropped. [`memory::Locked`] additionally keeps them out of swap.
+//!
+//! With the `tokio` feature, `async_api::AsyncApi` offers the same calls as async functions.
+//!
+//! The library also builds for `wasm32-unknown-unknown`, where requests go through the browser's
+//! `fetch` and `wasm` exposes the export to JavaScript.
+
+pub mod api;
+#[cfg(any(feature = "tokio", target_arch = "wasm32"))]
+pub mod async_api;
+pub mod audit;
+pub mod clock;
+pub mod correlation;
+mod diagnose;
+pub mod error;
+#[cfg(all(feature = "ffi", not(target_arch = "wasm32")))]
+pub mod ffi;
+pub mod har;
+pub mod memory;
+pub mod otpauth;
+pub mod paste;
+#[cfg(all(feature = "python", not(target_arch = "wasm32")))]
+mod python;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod rate_limit;
+pub mod region;
+pub mod restore;
+mod retry;
+pub mod secure_file;
+pub mod token;
+pub mod totp;
+mod transcript;
+
+pub use error::{Error, Result};
+pub use zeroize::Zeroizing;
+
+/// The `tracing` target of what the library has to tell the person running it, like a request
+/// being retried or a clock being off. Nothing is printed, a subscriber decides where it goes.
+pub const NOTICES: &str = "bnet_auth_export::notice";
+
+#[cfg(target_arch = "wasm32")]
+pub mod wasm;
+
+// Browsers bring their own TLS.
+#[cfg(not(any(feature = "rustls", feature = "native-tls", target_arch = "wasm32")))]
+compile_error!("enable either the `rustls` or the `native-tls` feature");
@@ -0,0 +1,459 @@
+// Reading an answer from the terminal with line editing: the arrow keys, Home and End, Ctrl-U,
+// Ctrl-K and Ctrl-W, and bracketed paste, so a typo in a long session token doesn't mean typing
+// it all again. Input that doesn't fit is scrolled sideways rather than wrapped. Also the
+// checklist of where an export goes, ticked with the arrow keys and space.
+
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::Mutex;
+
+use anyhow::{Context, Result, bail};
+use crossterm::cursor::{MoveToColumn, MoveToPreviousLine};
+use crossterm::event::{
+    self, DisableBracketedPaste, EnableBracketedPaste, Event, KeyCode, KeyEvent, KeyEventKind,
+    KeyModifiers,
+};
+use crossterm::style::Print;
+use crossterm::terminal::{self, Clear, ClearType};
+use crossterm::{execute, queue};
+use zeroize::Zeroizing;
+
+use crate::interrupt::{self, Cleanup};
+
+// Lines after the first of a multi-line paste, answering the prompts that follow as they would
+// without line editing, and the unfinished line after them. They are often the restore code or
+// a password, so they are wiped as they are used.
+static PENDING: Mutex<Pending> = Mutex::new(Pending {
+    lines: VecDeque::new(),
+    partial: None,
+});
+
+struct Pending {
+    lines: VecDeque<Zeroizing<String>>,
+    partial: Option<Zeroizing<String>>,
+}
+
+// Raw mode for as long as a line is being read, switched off again on every return, and when
+// the run is interrupted.
+struct RawMode {
+    stderr: bool,
+    _interrupt: Cleanup,
+}
+
+impl RawMode {
+    fn enable(stderr: bool) -> Result<Self> {
+        terminal::enable_raw_mode().context("failed to set up the terminal")?;
+        // Windows consoles without it deliver pastes as key presses, which works too.
+        let _ = execute!(output(stderr), EnableBracketedPaste);
+        Ok(Self {
+            stderr,
+            _interrupt: interrupt::on_interrupt(move || restore(stderr)),
+        })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        restore(self.stderr);
+    }
+}
+
+fn restore(stderr: bool) {
+    let _ = execute!(output(stderr), DisableBracketedPaste);
+    let _ = terminal::disable_raw_mode();
+}
+
+fn output(stderr: bool) -> Box<dyn Write> {
+    if stderr {
+        Box::new(io::stderr())
+    } else {
+        Box::new(io::stdout())
+    }
+}
+
+// Read a line after showing `label` on stdout, or stderr, which has to be a terminal. `None`
+// when Ctrl-D is pressed on an empty line.
+pub fn read_line(label: &str, stderr: bool) -> Result<Option<Zeroizing<String>>> {
+    let mut out = output(stderr);
+    let out = &mut out;
+    // Only the label's last line is redrawn while editing.
+    let label = match label.rsplit_once('\n') {
+        Some((above, last)) => {
+            writeln!(out, "{above}")?;
+            last
+        }
+        None => label,
+    };
+
+    let mut pending = PENDING.lock().unwrap_or_else(|err| err.into_inner());
+    if let Some(line) = pending.lines.pop_front() {
+        writeln!(out, "{label}{}", *line)?;
+        return Ok(Some(line));
+    }
+    let mut line = Line::new();
+    if let Some(partial) = pending.partial.take() {
+        line.insert(&partial);
+    }
+
+    let raw = RawMode::enable(stderr)?;
+    line.draw(label, out)?;
+    loop {
+        let event = event::read().context("failed to read input")?;
+        let key = match event {
+            Event::Paste(text) => {
+                let text = normalize_newlines(&Zeroizing::new(text));
+                let Some((first, rest)) = text.split_once('\n') else {
+                    line.insert(&text);
+                    line.draw(label, out)?;
+                    continue;
+                };
+                // Like a paste without line editing: the first line is the answer, and the rest
+                // answer the prompts that follow.
+                line.insert(first);
+                let mut rest: Vec<&str> = rest.split('\n').collect();
+                pending.partial = rest.pop().map(|partial| Zeroizing::new(partial.to_owned()));
+                pending
+                    .lines
+                    .extend(rest.into_iter().map(|line| Zeroizing::new(line.to_owned())));
+                break;
+            }
+            Event::Key(key) if key.kind != KeyEventKind::Release => key,
+            Event::Resize(..) => {
+                line.draw(label, out)?;
+                continue;
+            }
+            _ => continue,
+        };
+        match key_action(key) {
+            Action::Submit => break,
+            Action::Cancel => {
+                drop(raw);
+                writeln!(out)?;
+                bail!("cancelled with Ctrl-C");
+            }
+            Action::EndOfInput if line.chars.is_empty() => {
+                drop(raw);
+                writeln!(out)?;
+                return Ok(None);
+            }
+            Action::EndOfInput | Action::Delete => line.delete(),
+            Action::Insert(c) => line.insert(&c.to_string()),
+            Action::Backspace => {
+                if line.cursor > 0 {
+                    line.cursor -= 1;
+                    line.chars.remove(line.cursor);
+                }
+            }
+            Action::Left => line.cursor = line.cursor.saturating_sub(1),
+            Action::Right => line.cursor = (line.cursor + 1).min(line.chars.len()),
+            Action::Home => line.cursor = 0,
+            Action::End => line.cursor = line.chars.len(),
+            Action::KillBefore => {
+                line.chars.drain(..line.cursor);
+                line.cursor = 0;
+            }
+            Action::KillAfter => line.chars.truncate(line.cursor),
+            Action::KillWord => {
+                let end = line.cursor;
+                while line.cursor > 0 && line.chars[line.cursor - 1] == ' ' {
+                    line.cursor -= 1;
+                }
+                while line.cursor > 0 && line.chars[line.cursor - 1] != ' ' {
+                    line.cursor -= 1;
+                }
+                line.chars.drain(line.cursor..end);
+            }
+            Action::None => continue,
+        }
+        line.draw(label, out)?;
+    }
+
+    // Leave the whole answer on screen, as a terminal without line editing would.
+    line.cursor = line.chars.len();
+    line.draw_full(label, out)?;
+    drop(raw);
+    writeln!(out)?;
+    Ok(Some(Zeroizing::new(line.chars.iter().collect())))
+}
+
+// Have the items below `label` on stdout ticked: up and down or k and j move, space ticks or
+// unticks, Enter confirms. `checked` holds what is ticked to begin with, and then the answer.
+pub fn checklist(label: &str, items: &[String], checked: &mut [bool]) -> Result<()> {
+    if items.is_empty() {
+        return Ok(());
+    }
+    let mut out = io::stdout();
+    writeln!(out, "{label}")?;
+    writeln!(
+        out,
+        "(up and down to move, space to tick, Enter to confirm, Esc to cancel)"
+    )?;
+    let mut current = 0;
+    let raw = RawMode::enable(false)?;
+    draw_checklist(items, checked, Some(current), false, &mut out)?;
+    loop {
+        let key = match event::read().context("failed to read input")? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => key,
+            _ => continue,
+        };
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('c') if control => {
+                drop(raw);
+                bail!("cancelled with Ctrl-C");
+            }
+            KeyCode::Esc | KeyCode::Char('q') => {
+                drop(raw);
+                bail!("cancelled");
+            }
+            KeyCode::Enter => break,
+            KeyCode::Char('m' | 'j') if control => break,
+            KeyCode::Char(' ') => checked[current] = !checked[current],
+            KeyCode::Up | KeyCode::Char('k') => current = current.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => current = (current + 1).min(items.len() - 1),
+            KeyCode::Home => current = 0,
+            KeyCode::End => current = items.len() - 1,
+            _ => continue,
+        }
+        draw_checklist(items, checked, Some(current), true, &mut out)?;
+    }
+    // Leave what was ticked on screen, without the cursor.
+    draw_checklist(items, checked, None, true, &mut out)?;
+    drop(raw);
+    Ok(())
+}
+
+// One line per item, over the ones drawn before when `redraw`. Items are cut to the width of
+// the terminal, so that each takes exactly one line to move back over.
+fn draw_checklist(
+    items: &[String],
+    checked: &[bool],
+    current: Option<usize>,
+    redraw: bool,
+    out: &mut impl Write,
+) -> Result<()> {
+    let width = match terminal::size() {
+        Ok((columns, _)) if columns > 0 => usize::from(columns),
+        _ => 80,
+    };
+    if redraw {
+        queue!(
+            out,
+            MoveToPreviousLine(u16::try_from(items.len()).unwrap_or(u16::MAX))
+        )?;
+    }
+    for (i, item) in items.iter().enumerate() {
+        let line = format!(
+            "{} [{}] {item}",
+            if current == Some(i) { '>' } else { ' ' },
+            if checked[i] { 'x' } else { ' ' }
+        );
+        let line: String = line.chars().take(width.saturating_sub(1)).collect();
+        queue!(
+            out,
+            MoveToColumn(0),
+            Print(line),
+            Clear(ClearType::UntilNewLine),
+            Print("\r\n")
+        )?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+enum Action {
+    Submit,
+    Cancel,
+    EndOfInput,
+    Insert(char),
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Home,
+    End,
+    KillBefore,
+    KillAfter,
+    KillWord,
+    None,
+}
+
+fn key_action(key: KeyEvent) -> Action {
+    if key.modifiers.contains(KeyModifiers::CONTROL) {
+        return match key.code {
+            KeyCode::Char('c') => Action::Cancel,
+            KeyCode::Char('d') => Action::EndOfInput,
+            KeyCode::Char('a') => Action::Home,
+            KeyCode::Char('e') => Action::End,
+            KeyCode::Char('b') => Action::Left,
+            KeyCode::Char('f') => Action::Right,
+            KeyCode::Char('u') => Action::KillBefore,
+            KeyCode::Char('k') => Action::KillAfter,
+            KeyCode::Char('w') | KeyCode::Backspace => Action::KillWord,
+            KeyCode::Char('h') => Action::Backspace,
+            KeyCode::Char('j') | KeyCode::Char('m') => Action::Submit,
+            _ => Action::None,
+        };
+    }
+    match key.code {
+        KeyCode::Enter => Action::Submit,
+        KeyCode::Char(c) => Action::Insert(c),
+        KeyCode::Backspace => Action::Backspace,
+        KeyCode::Delete => Action::Delete,
+        KeyCode::Left => Action::Left,
+        KeyCode::Right => Action::Right,
+        KeyCode::Home => Action::Home,
+        KeyCode::End => Action::End,
+        _ => Action::None,
+    }
+}
+
+// Longer than any pasted cURL command, and all a line takes: the buffer is allocated at this
+// size up front, since growing it would leave a copy of the secret typed so far behind.
+const MAX_LINE: usize = 16 * 1024;
+
+struct Line {
+    chars: Zeroizing<Vec<char>>,
+    cursor: usize,
+    // The first character shown.
+    start: usize,
+}
+
+impl Line {
+    fn new() -> Self {
+        Self {
+            chars: Zeroizing::new(Vec::with_capacity(MAX_LINE)),
+            cursor: 0,
+            start: 0,
+        }
+    }
+
+    // Insert at the cursor, dropping whatever doesn't fit in `MAX_LINE`.
+    fn insert(&mut self, text: &str) {
+        for c in text.chars().filter(|c| !c.is_control()) {
+            if self.chars.len() == MAX_LINE {
+                break;
+            }
+            self.chars.insert(self.cursor, c);
+            self.cursor += 1;
+        }
+    }
+
+    fn delete(&mut self) {
+        if self.cursor < self.chars.len() {
+            self.chars.remove(self.cursor);
+        }
+    }
+
+    // Redraw the label and as much of the line around the cursor as fits.
+    fn draw(&mut self, label: &str, out: &mut impl Write) -> Result<()> {
+        let width = match terminal::size() {
+            Ok((columns, _)) if columns > 0 => usize::from(columns),
+            _ => 80,
+        };
+        let label_width = label.chars().count();
+        // One column is left for the cursor after the last character.
+        let room = width.saturating_sub(label_width + 1).max(8);
+        self.scroll(room);
+        let (start, end) = (self.start, (self.start + room).min(self.chars.len()));
+        let visible: Zeroizing<String> = Zeroizing::new(self.chars[start..end].iter().collect());
+        queue!(
+            out,
+            MoveToColumn(0),
+            Print(label),
+            Print(&*visible),
+            Clear(ClearType::UntilNewLine),
+            MoveToColumn(u16::try_from(label_width + self.cursor - start).unwrap_or(u16::MAX)),
+        )?;
+        out.flush()?;
+        Ok(())
+    }
+
+    // Draw the whole line, wrapping, once editing is done.
+    fn draw_full(&self, label: &str, out: &mut impl Write) -> Result<()> {
+        let text: Zeroizing<String> = Zeroizing::new(self.chars.iter().collect());
+        queue!(
+            out,
+            MoveToColumn(0),
+            Clear(ClearType::UntilNewLine),
+            Print(label),
+            Print(&*text),
+        )?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+impl Line {
+    // Move what is shown of the line, in `room` columns, only as far as it takes to keep the
+    // cursor in view.
+    fn scroll(&mut self, room: usize) {
+        if self.chars.len() < room {
+            self.start = 0;
+        } else if self.cursor < self.start {
+            self.start = self.cursor;
+        } else if self.cursor >= self.start + room {
+            self.start = self.cursor + 1 - room;
+        }
+    }
+}
+
+// A paste with its CR LF and CR line endings turned into LF, written into a buffer that is
+// never reallocated, so no copy of it is left behind unwiped.
+fn normalize_newlines(text: &str) -> Zeroizing<String> {
+    let mut normalized = Zeroizing::new(String::with_capacity(text.len()));
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\r' {
+            chars.next_if_eq(&'\n');
+            normalized.push('\n');
+        } else {
+            normalized.push(c);
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_buffer_never_grows() {
+        let mut line = Line::new();
+        let buffer = line.chars.as_ptr();
+        line.insert(&"x".repeat(MAX_LINE - 1));
+        line.cursor = 0;
+        line.insert("ab");
+
+        assert_eq!(line.chars.len(), MAX_LINE);
+        assert_eq!(line.chars[0], 'a');
+        assert_eq!(line.chars.as_ptr(), buffer);
+        assert_eq!(line.chars.capacity(), MAX_LINE);
+    }
+
+    #[test]
+    fn pastes_get_unix_line_endings_in_place() {
+        let pasted = "ABCDE12345\r\nhunter2\rlast\n";
+        let normalized = normalize_newlines(pasted);
+        assert_eq!(normalized.as_str(), "ABCDE12345\nhunter2\nlast\n");
+        assert_eq!(normalized.capacity(), pasted.len());
+    }
+
+    #[test]
+    fn long_lines_scroll_only_to_keep_the_cursor_in_view() {
+        let mut line = Line::new();
+        line.insert("0123456789abcdefghij");
+        line.scroll(10);
+        assert_eq!(line.start, 11);
+        line.cursor = 15;
+        line.scroll(10);
+        assert_eq!(line.start, 11);
+        line.cursor = 3;
+        line.scroll(10);
+        assert_eq!(line.start, 3);
+        line.chars.truncate(5);
+        line.scroll(10);
+        assert_eq!(line.start, 0);
+    }
+}
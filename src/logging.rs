@@ -0,0 +1,351 @@
+// --log-format: the `tracing` spans around API calls and export steps, and the events in them,
+// as lines of text or JSON on stderr or in --log-file, for runs inside larger automated jobs.
+// Spans carry the same fields as the audit log, so nothing secret ends up in the log either.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{self, Write as _};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use anyhow::{Context, Result, anyhow};
+use clap::ValueEnum;
+use serde_json::{Map, Value, json};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::Interest;
+use tracing::{Event, Level, Metadata, Subscriber};
+use web_time::{SystemTime, UNIX_EPOCH};
+
+use bnet_auth_export::{NOTICES, clock, correlation, secure_file};
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum LogFormat {
+    /// One human-readable line per event.
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
+// Send the tool's spans and events to stderr, or appended to `path`, from here on.
+pub fn init(format: LogFormat, path: Option<&Path>) -> Result<()> {
+    let out: Box<dyn Write + Send> = match path {
+        Some(path) => Box::new(
+            secure_file::private_options()
+                .append(true)
+                .open(path)
+                .with_context(|| format!("failed to open {}", path.display()))?,
+        ),
+        None => Box::new(io::stderr()),
+    };
+    let logger = Logger {
+        format,
+        out: Mutex::new(out),
+        spans: Mutex::new(HashMap::new()),
+        next_id: AtomicU64::new(1),
+    };
+    tracing::subscriber::set_global_default(logger)
+        .map_err(|err| anyhow!("failed to set up logging: {err}"))
+}
+
+// Without --log-format, only the library's notices, as plain lines on stderr like the tool's
+// own messages.
+pub fn init_notices() {
+    let _ = tracing::subscriber::set_global_default(Notices);
+}
+
+struct Notices;
+
+impl Subscriber for Notices {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.target() == NOTICES
+    }
+
+    fn new_span(&self, _attributes: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let message = fields.message.unwrap_or_default();
+        if *event.metadata().level() == Level::WARN {
+            eprintln!("warning: {message}");
+        } else {
+            eprintln!("{message}");
+        }
+    }
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
+
+struct Logger {
+    format: LogFormat,
+    out: Mutex<Box<dyn Write + Send>>,
+    spans: Mutex<HashMap<u64, Span>>,
+    next_id: AtomicU64,
+}
+
+struct Span {
+    metadata: &'static Metadata<'static>,
+    fields: Map<String, Value>,
+    started: Instant,
+    // Handles to the span still open, it is logged as closed when the last one goes.
+    refs: usize,
+}
+
+thread_local! {
+    // The spans entered on this thread, innermost last.
+    static ENTERED: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+impl Subscriber for Logger {
+    // Only the tool's own spans and events, not those of the HTTP stack underneath.
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.target().starts_with("bnet_auth_export") && *metadata.level() <= Level::INFO
+    }
+
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        if self.enabled(metadata) {
+            Interest::always()
+        } else {
+            Interest::never()
+        }
+    }
+
+    fn new_span(&self, attributes: &Attributes<'_>) -> Id {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut fields = Fields::default();
+        attributes.record(&mut fields);
+        self.lock_spans().insert(
+            id,
+            Span {
+                metadata: attributes.metadata(),
+                fields: fields.values,
+                started: Instant::now(),
+                refs: 1,
+            },
+        );
+        Id::from_u64(id)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut fields = Fields::default();
+        values.record(&mut fields);
+        if let Some(span) = self.lock_spans().get_mut(&span.into_u64()) {
+            span.fields.extend(fields.values);
+        }
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let level = *event.metadata().level();
+        self.write(
+            level,
+            fields.message.unwrap_or_default(),
+            fields.values,
+            None,
+        );
+    }
+
+    fn enter(&self, span: &Id) {
+        ENTERED.with(|entered| entered.borrow_mut().push(span.into_u64()));
+    }
+
+    fn exit(&self, span: &Id) {
+        ENTERED.with(|entered| {
+            let mut entered = entered.borrow_mut();
+            if let Some(at) = entered.iter().rposition(|&id| id == span.into_u64()) {
+                entered.remove(at);
+            }
+        });
+    }
+
+    fn clone_span(&self, span: &Id) -> Id {
+        if let Some(span) = self.lock_spans().get_mut(&span.into_u64()) {
+            span.refs += 1;
+        }
+        span.clone()
+    }
+
+    // A closed span is logged with how long it was open, which is how long the API call or
+    // export step took.
+    fn try_close(&self, span: Id) -> bool {
+        let mut spans = self.lock_spans();
+        let Some(open) = spans.get_mut(&span.into_u64()) else {
+            return false;
+        };
+        open.refs -= 1;
+        if open.refs > 0 {
+            return false;
+        }
+        let elapsed = open.started.elapsed();
+        drop(spans);
+
+        let mut fields = Map::new();
+        fields.insert("elapsed_ms".to_owned(), json!(elapsed.as_millis() as u64));
+        self.write(
+            Level::INFO,
+            "close".to_owned(),
+            fields,
+            Some(span.into_u64()),
+        );
+        self.lock_spans().remove(&span.into_u64());
+        true
+    }
+}
+
+impl Logger {
+    fn lock_spans(&self) -> std::sync::MutexGuard<'_, HashMap<u64, Span>> {
+        self.spans.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    // One line for an event, with the spans it happened in, outermost first. A span being
+    // closed is named last, though it is no longer entered.
+    fn write(
+        &self,
+        level: Level,
+        message: String,
+        fields: Map<String, Value>,
+        closing: Option<u64>,
+    ) {
+        let spans: Vec<(&'static str, Map<String, Value>)> = {
+            let spans = self.lock_spans();
+            ENTERED.with(|entered| {
+                let mut ids = entered.borrow().clone();
+                if let Some(closing) = closing
+                    && ids.last() != Some(&closing)
+                {
+                    ids.push(closing);
+                }
+                ids.iter()
+                    .filter_map(|id| spans.get(id))
+                    .map(|span| (span.metadata.name(), span.fields.clone()))
+                    .collect()
+            })
+        };
+
+        let line = match self.format {
+            LogFormat::Json => {
+                let spans: Vec<Value> = spans
+                    .into_iter()
+                    .map(|(name, mut fields)| {
+                        fields.insert("name".to_owned(), json!(name));
+                        Value::Object(fields)
+                    })
+                    .collect();
+                let mut fields = fields;
+                fields.insert("message".to_owned(), json!(message));
+                json!({
+                    "timestamp": timestamp(),
+                    "level": level.as_str(),
+                    "fields": fields,
+                    "span": spans.last(),
+                    "spans": spans,
+                    "runId": correlation::run_id(),
+                })
+                .to_string()
+            }
+            LogFormat::Text => {
+                let mut line = format!("{} {:>5}", timestamp(), level.as_str());
+                for (i, (name, fields)) in spans.iter().enumerate() {
+                    line.push_str(if i == 0 { " " } else { ":" });
+                    line.push_str(name);
+                    if !fields.is_empty() {
+                        line.push('{');
+                        push_fields(&mut line, fields);
+                        line.push('}');
+                    }
+                }
+                line.push_str(if spans.is_empty() { " " } else { ": " });
+                line.push_str(&message);
+                if !fields.is_empty() {
+                    line.push(' ');
+                    push_fields(&mut line, &fields);
+                }
+                line
+            }
+        };
+        // Like the audit log, logging is best effort and must not abort the export.
+        let mut out = self.out.lock().unwrap_or_else(|err| err.into_inner());
+        let _ = writeln!(out, "{line}").and_then(|()| out.flush());
+    }
+}
+
+fn push_fields(line: &mut String, fields: &Map<String, Value>) {
+    for (i, (name, value)) in fields.iter().enumerate() {
+        if i > 0 {
+            line.push(' ');
+        }
+        let _ = write!(line, "{name}={value}");
+    }
+}
+
+// Now in RFC 3339, UTC, with milliseconds.
+fn timestamp() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let seconds = clock::utc_timestamp(now.as_secs());
+    let seconds = seconds.trim_end_matches('Z');
+    format!("{seconds}.{:03}Z", now.subsec_millis())
+}
+
+// The fields of a span or event, with the message kept apart.
+#[derive(Default)]
+struct Fields {
+    message: Option<String>,
+    values: Map<String, Value>,
+}
+
+impl Fields {
+    fn insert(&mut self, field: &Field, value: Value) {
+        self.values.insert(field.name().to_owned(), value);
+    }
+}
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = Some(format!("{value:?}"));
+        } else {
+            self.insert(field, json!(format!("{value:?}")));
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = Some(value.to_owned());
+        } else {
+            self.insert(field, json!(value));
+        }
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, json!(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, json!(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, json!(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.insert(field, json!(value));
+    }
+}
@@ -0,0 +1,145 @@
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, bail};
+
+// Logging in with this ref makes Battle.net redirect to localhost with the token in the URL.
+pub const LOGIN_URL: &str = "https://account.battle.net/login/en/?ref=localhost";
+
+// Open the Battle.net login page and walk the user through copying the token.
+pub fn guide(watch_clipboard: bool) {
+    let browser = detect_browser();
+
+    println!("Opening {LOGIN_URL} in your browser.");
+    if let Err(err) = open_browser(LOGIN_URL) {
+        eprintln!("Could not open a browser ({err:#}), open the link above manually.");
+    }
+
+    println!();
+    println!("1. Log in to the Battle.net account that owns the authenticator.");
+    println!(
+        "   Tip: use a private window ({}).",
+        browser.private_window()
+    );
+    println!("2. After logging in you are sent to a localhost page, usually an error page.");
+    println!("3. Click the address bar and copy the value after ST=, up to the next &.");
+    println!("   It looks like US-h6392c12...1kh10n2p7-531234.");
+    if watch_clipboard {
+        println!("4. The token is picked up as soon as you copy it.");
+    } else {
+        println!("4. Paste it below.");
+    }
+    println!("   The token only works for a few minutes, so don't wait too long.");
+    println!();
+}
+
+#[derive(Clone, Copy)]
+enum Browser {
+    Chrome,
+    Firefox,
+    Edge,
+    Safari,
+    Unknown,
+}
+
+impl Browser {
+    fn private_window(self) -> &'static str {
+        let mac = cfg!(target_os = "macos");
+        match (self, mac) {
+            (Self::Chrome, false) => "Chrome: Ctrl+Shift+N",
+            (Self::Chrome, true) => "Chrome: Cmd+Shift+N",
+            (Self::Firefox, false) => "Firefox: Ctrl+Shift+P",
+            (Self::Firefox, true) => "Firefox: Cmd+Shift+P",
+            (Self::Edge, false) => "Edge: Ctrl+Shift+N",
+            (Self::Edge, true) => "Edge: Cmd+Shift+N",
+            (Self::Safari, _) => "Safari: Cmd+Shift+N",
+            (Self::Unknown, false) => "usually Ctrl+Shift+N or Ctrl+Shift+P",
+            (Self::Unknown, true) => "usually Cmd+Shift+N or Cmd+Shift+P",
+        }
+    }
+
+    fn from_name(name: &str) -> Self {
+        let name = name.to_ascii_lowercase();
+        if name.contains("firefox") {
+            Self::Firefox
+        } else if name.contains("edge") {
+            Self::Edge
+        } else if name.contains("chrom") || name.contains("brave") {
+            Self::Chrome
+        } else if name.contains("safari") {
+            Self::Safari
+        } else {
+            Self::Unknown
+        }
+    }
+}
+
+// Best-effort lookup of the default browser, only used to tailor the instructions.
+fn detect_browser() -> Browser {
+    let output = if cfg!(target_os = "linux") {
+        Command::new("xdg-settings")
+            .args(["get", "default-web-browser"])
+            .output()
+    } else if cfg!(target_os = "windows") {
+        Command::new("reg")
+            .args([
+                "query",
+                r"HKCU\Software\Microsoft\Windows\Shell\Associations\UrlAssociations\https\UserChoice",
+                "/v",
+                "ProgId",
+            ])
+            .output()
+    } else if cfg!(target_os = "macos") {
+        Command::new("defaults")
+            .args([
+                "read",
+                "com.apple.LaunchServices/com.apple.launchservices.secure",
+                "LSHandlers",
+            ])
+            .output()
+    } else {
+        return Browser::Unknown;
+    };
+
+    match output {
+        Ok(output) if output.status.success() => {
+            let text = String::from_utf8_lossy(&output.stdout);
+            if cfg!(target_os = "macos") {
+                // Find the handler registered right next to the https scheme.
+                let https = text.find("LSHandlerURLScheme = https;");
+                return https
+                    .and_then(|index| text[..index].rsplit("LSHandlerRoleAll").next())
+                    .map(Browser::from_name)
+                    .unwrap_or(Browser::Safari);
+            }
+            Browser::from_name(&text)
+        }
+        _ => Browser::Unknown,
+    }
+}
+
+pub fn open_browser(url: &str) -> Result<()> {
+    let mut command = if cfg!(target_os = "windows") {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", "", url]);
+        command
+    } else if cfg!(target_os = "macos") {
+        let mut command = Command::new("open");
+        command.arg(url);
+        command
+    } else {
+        let mut command = Command::new("xdg-open");
+        command.arg(url);
+        command
+    };
+
+    let status = command
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .context("failed to launch the browser")?;
+    if !status.success() {
+        bail!("browser launcher exited with {status}");
+    }
+
+    Ok(())
+}
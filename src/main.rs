@@ -1,141 +1,1295 @@
-use anyhow::{Context, Result};
-use data_encoding::BASE32_NOPAD;
-use reqwest::blocking::Client;
-use reqwest::header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE, HeaderMap, HeaderValue, USER_AGENT};
-use std::io::{self, Write};
-
-const SSO_URL: &str = "https://oauth.battle.net/oauth/sso";
-const AUTH_BASE_URL: &str =
-    "https://authenticator-rest-api.bnet-identity.blizzard.net/v1/authenticator";
-const CLIENT_ID: &str = "baedda12fe054e4abdfc3ad7bdea970a";
-
-struct Api(Client);
-
-impl Api {
-    fn new() -> Result<Self> {
-        let mut headers = HeaderMap::new();
-        headers.insert(USER_AGENT, HeaderValue::from_static("bnet-auth-export/0.1"));
-        headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
-
-        let client = Client::builder()
-            .default_headers(headers)
-            .build()
-            .context("failed to build HTTP client")?;
-
-        Ok(Self(client))
-    }
-
-    // Exchange the user session token for an OAuth bearer token.
-    fn exchange_session_token(&self, session_token: &str) -> Result<String> {
-        let response = self
-            .0
-            .post(SSO_URL)
-            .header(
-                CONTENT_TYPE,
-                HeaderValue::from_static("application/x-www-form-urlencoded; charset=utf-8"),
+#[cfg(feature = "batch")]
+mod batch;
+#[cfg(feature = "browser")]
+mod browser;
+#[cfg(feature = "capture")]
+mod capture;
+#[cfg(feature = "encrypted-backups")]
+mod checkpoint;
+#[cfg(feature = "clipboard")]
+mod clipboard;
+#[cfg(any(feature = "aws", feature = "gcp", feature = "azure"))]
+mod cloud;
+mod code_server;
+mod compare;
+mod compat;
+mod convert;
+mod destinations;
+mod devices;
+mod doctor;
+mod format;
+#[cfg(feature = "image-input")]
+mod image_input;
+mod import;
+mod interrupt;
+#[cfg(feature = "line-editing")]
+mod line_edit;
+mod logging;
+mod login;
+mod oauth;
+#[cfg(feature = "plugins")]
+mod plugin;
+mod rotate;
+mod rpc;
+#[cfg(feature = "encrypted-backups")]
+mod seal;
+#[cfg(feature = "self-update")]
+mod self_update;
+mod source;
+#[cfg(feature = "store")]
+mod store;
+#[cfg(feature = "systemd-creds")]
+mod systemd_creds;
+mod timings;
+#[cfg(feature = "tui")]
+mod tui;
+#[cfg(feature = "vault")]
+mod vault;
+#[cfg(any(feature = "image-input", feature = "encrypted-backups"))]
+mod verify;
+#[cfg(feature = "yubikey")]
+mod yubikey;
+
+use anyhow::{Context, Result, bail};
+use clap::{Parser, Subcommand};
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+use bnet_auth_export::Error;
+use bnet_auth_export::api::{Api, ApiOptions};
+use bnet_auth_export::audit::AuditLog;
+use bnet_auth_export::memory::{self, Locked};
+use bnet_auth_export::otpauth::{LabelOptions, OtpAuth};
+use bnet_auth_export::rate_limit::AttemptLimit;
+use bnet_auth_export::region::Region;
+use bnet_auth_export::{clock, restore, totp};
+#[cfg(feature = "encrypted-backups")]
+use checkpoint::Checkpoint;
+use format::OutputOptions;
+use source::{Credential, TokenOptions};
+
+/// Export a Battle.net authenticator into third-party TOTP apps.
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    token: TokenOptions,
+
+    #[command(flatten)]
+    api: ApiOptions,
+
+    #[command(flatten)]
+    labels: LabelOptions,
+
+    #[command(flatten)]
+    output: OutputOptions,
+
+    /// Serve JSON-RPC 2.0 requests on stdin and stdout, one per line, instead of prompting.
+    ///
+    /// Methods: exchangeToken, restore, convert and generateCode.
+    #[arg(long)]
+    serve_stdio: bool,
+
+    /// Save progress to this encrypted file, and resume from it if it already exists.
+    ///
+    /// The bearer token and restore code are kept in it until the export is written, so a run
+    /// interrupted after the SSO exchange continues without repeating it. The password is read
+    /// from BNET_CHECKPOINT_PASSWORD or prompted for.
+    #[cfg(feature = "encrypted-backups")]
+    #[arg(long, env = "BNET_CHECKPOINT", conflicts_with = "serve_stdio")]
+    checkpoint: Option<PathBuf>,
+
+    /// Read the serial and restore code from this screenshot of the official app's restore
+    /// screen, by OCR with tesseract or from a QR code with zbarimg.
+    #[cfg(feature = "image-input")]
+    #[arg(long, conflicts_with = "serve_stdio")]
+    input_image: Option<PathBuf>,
+
+    #[cfg(feature = "batch")]
+    #[command(flatten)]
+    batch: batch::BatchOptions,
+
+    /// Export with nothing but the session token: pick the authenticator from the account's list
+    /// (asking only when there are several) and take its restore code from the list too when
+    /// Battle.net includes it there.
+    #[arg(long, conflicts_with = "serve_stdio")]
+    auto: bool,
+
+    /// Walk through the export in a full-screen wizard instead of sequential prompts.
+    #[cfg(feature = "tui")]
+    #[arg(long, conflicts_with = "serve_stdio")]
+    tui: bool,
+
+    /// Print how long the SSO exchange, the restore and each export step took when the run
+    /// ends, to tell a slow network or API apart from slow local work.
+    #[arg(long)]
+    timings: bool,
+
+    /// Output for screen readers: each value on one line after its label, and prompts read
+    /// without line editing, which redraws the line. Rules out --tui.
+    #[arg(long, env = "BNET_PLAIN")]
+    plain: bool,
+
+    /// Log each API call, export step and operation as it happens, as text or one JSON object
+    /// per line for log pipelines. Goes to stderr unless --log-file is given.
+    #[arg(long, value_enum, env = "BNET_LOG_FORMAT")]
+    log_format: Option<logging::LogFormat>,
+
+    /// Append the --log-format lines to this file instead of stderr.
+    #[arg(long, env = "BNET_LOG_FILE", requires = "log_format")]
+    log_file: Option<PathBuf>,
+
+    /// Don't look up the latest release on GitHub before talking to Battle.net. The check only
+    /// warns when a newer release follows a change in Battle.net's API.
+    #[cfg(feature = "self-update")]
+    #[arg(long, env = "BNET_NO_UPDATE_CHECK", value_parser = clap::builder::BoolishValueParser::new())]
+    no_update_check: bool,
+
+    /// GitHub API URL of the latest release, for testing against a mirror.
+    #[cfg(feature = "self-update")]
+    #[arg(long, env = "BNET_RELEASES_URL", default_value = self_update::RELEASES_URL, hide = true)]
+    releases_url: String,
+}
+
+impl Cli {
+    // Whether the update check is worth its request: only when this run talks to Battle.net,
+    // so offline commands stay offline.
+    #[cfg(feature = "self-update")]
+    fn wants_update_check(&self) -> bool {
+        !self.no_update_check
+            && !self.serve_stdio
+            && matches!(
+                self.command,
+                None | Some(Command::Login)
+                    | Some(Command::Rotate { .. })
+                    | Some(Command::Code(CodeArgs {
+                        sync_clock: true,
+                        ..
+                    }))
             )
-            .form(&[
-                ("client_id", CLIENT_ID),
-                ("grant_type", "client_sso"),
-                ("scope", "auth.authenticator"),
-                ("token", session_token),
-            ])
-            .send()
-            .context("request failed for Battle.net SSO token exchange")?;
-
-        let parsed: serde_json::Value = response
-            .error_for_status()
-            .context("SSO token exchange failed")?
-            .json()
-            .context("failed to parse SSO token exchange response")?;
-        let access_token = parsed
-            .get("access_token")
-            .and_then(|value| value.as_str())
-            .map(str::trim)
-            .filter(|value| !value.is_empty())
-            .context("SSO response did not include access_token")?;
-
-        Ok(access_token.to_owned())
-    }
-
-    // Restore the authenticator and return the device secret using a bearer token.
-    fn device_secret(
-        &self,
-        bearer_token: &str,
-        serial: &str,
-        restore_code: &str,
-    ) -> Result<String> {
-        let url = format!("{AUTH_BASE_URL}/device");
-
-        let response = self
-            .0
-            .post(&url)
-            .header(AUTHORIZATION, format!("Bearer {bearer_token}"))
-            .json(&serde_json::json!({
-                "serial": serial,
-                "restoreCode": restore_code,
-            }))
-            .send()
-            .with_context(|| format!("request failed for {url}"))?;
-
-        let parsed: serde_json::Value = response
-            .error_for_status()
-            .context("restore request failed")?
-            .json()
-            .context("failed to parse restore response")?;
-        let device_secret = parsed
-            .get("deviceSecret")
-            .and_then(|value| value.as_str())
-            .map(str::trim)
-            .filter(|value| !value.is_empty())
-            .context("restore response missing deviceSecret")?;
-
-        Ok(device_secret.to_owned())
-    }
-}
-
-// Convert Blizzard's hex device secret into Base32 for otpauth URIs.
-fn to_base32_secret(hex_secret: &str) -> Result<String> {
-    let bytes = hex::decode(hex_secret.trim()).context("deviceSecret is not valid hex")?;
-    Ok(BASE32_NOPAD.encode(&bytes))
-}
-
-// Build the otpauth URI with Battle.net's TOTP parameters.
-fn build_otpauth_uri(serial: &str, base32_secret: &str) -> String {
-    format!(
-        "otpauth://totp/Battle.net:{serial}?secret={base32_secret}&issuer=Battle.net&digits=8&algorithm=SHA1&period=30"
-    )
-}
-
-// Prompt for a single line of input and return the trimmed value.
-fn prompt(label: &str) -> Result<String> {
-    print!("{label}");
-    io::stdout().flush().context("failed to flush stdout")?;
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Open the Battle.net login page and guide you through copying the session token.
+    Login,
+
+    /// Convert an already known hex device secret without contacting Battle.net.
+    Offline {
+        /// Authenticator serial, e.g. US-2101-2345-6789. Prompted for when omitted.
+        #[arg(long)]
+        serial: Option<String>,
+
+        /// Hex device secret. Prompted for when omitted, which keeps it out of shell history.
+        #[arg(long)]
+        secret: Option<String>,
+    },
+
+    /// Convert a Base32 secret back into Blizzard's hex device secret, shown in a terminal only
+    /// with --reveal.
+    Hex {
+        /// Base32 secret. Prompted for when omitted, which keeps it out of shell history.
+        #[arg(long)]
+        secret: Option<String>,
+    },
+
+    /// Check an otpauth URI from a previous export and show what it contains.
+    Parse {
+        /// otpauth://totp/... URI. Prompted for when omitted, which keeps it out of shell history.
+        #[arg(long)]
+        uri: Option<String>,
+    },
+
+    /// Tell whether two exports hold the same credential, pointing out digits, algorithm or period
+    /// that differ. Works offline.
+    Compare {
+        /// An otpauth URI, a hex device secret or an export file in any format `convert` reads.
+        /// Prompted for when omitted, which keeps a secret out of shell history.
+        a: Option<String>,
+
+        /// What to compare it with, as for the first.
+        b: Option<String>,
+    },
+
+    /// Print an export saved with --store keyring.
+    #[cfg(feature = "keyring")]
+    Show {
+        /// Authenticator serial the export was saved under. Prompted for when omitted.
+        serial: Option<String>,
+    },
+
+    /// Convert an export from this tool or another app into another format, offline.
+    Convert {
+        /// Format of the input: uri (or otpauth), json, stratum, stratum-encrypted, backup, aegis,
+        /// 2fas, bitwarden, winauth, android or text. Detected when omitted.
+        #[arg(long)]
+        from: Option<String>,
+
+        /// Format to write, as for --format. `all` writes a file per format.
+        #[arg(long)]
+        to: String,
+
+        /// File to read, or the directory of an iPhone backup or of a copy of the Android app's
+        /// data. Reads stdin when omitted or `-`.
+        input: Option<PathBuf>,
+    },
+
+    /// Export again what an earlier run of this tool, of any version, or another app saved:
+    /// to --format, --store or --print, as after a restore. Works offline.
+    Import {
+        /// Format of the input, as for `convert`, or `text` for the `text` format and saved
+        /// printouts. Detected when omitted.
+        #[arg(long)]
+        from: Option<String>,
+
+        /// File to read, or the directory of an iPhone backup or of a copy of the Android app's
+        /// data. Reads stdin when omitted or `-`.
+        input: Option<PathBuf>,
+    },
+
+    /// Check that a QR code, e.g. from the new authenticator app's own export, holds the same
+    /// secret as the export (needs zbarimg on PATH), or that a --format backup file is intact.
+    #[cfg(any(feature = "image-input", feature = "encrypted-backups"))]
+    Verify(VerifyArgs),
+
+    /// List the formats available to --format, including plugins found on PATH.
+    Formats,
+
+    /// Show which authenticator apps handle Battle.net's 8-digit codes correctly.
+    Compat {
+        /// App to look up, e.g. "google". Lists every known app when omitted.
+        app: Option<String>,
+    },
+
+    /// Check DNS, IPv6, proxy, TLS and clock problems that get in the way of talking to
+    /// Battle.net.
+    Doctor,
+
+    /// Print the current authenticator code for an exported secret.
+    Code(CodeArgs),
+
+    /// Replace the authenticator with a new one whose secret is exported, and detach the old one
+    /// once a code from the new one checks out.
+    Rotate {
+        /// Go ahead without asking first.
+        #[arg(long)]
+        yes: bool,
+    },
+
+    /// Replace this binary with the latest release from GitHub, after checking its SHA-256
+    /// checksum.
+    #[cfg(feature = "self-update")]
+    SelfUpdate {
+        /// Only report whether a newer release is available.
+        #[arg(long)]
+        check: bool,
+
+        /// Replace the binary without asking first.
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+#[cfg(any(feature = "image-input", feature = "encrypted-backups"))]
+#[derive(clap::Args)]
+struct VerifyArgs {
+    /// Photo or screenshot of the QR code, or the file written by --format qr.
+    #[cfg(feature = "image-input")]
+    #[arg(long)]
+    qr_image: Option<PathBuf>,
+
+    /// Export to compare the QR code with, in any format `convert` reads. Defaults to the
+    /// export saved with --store keyring for the QR code's serial.
+    #[cfg(feature = "image-input")]
+    #[arg(requires = "qr_image")]
+    export: Option<PathBuf>,
+
+    /// File written by --format backup to check for damage or tampering, with the backup
+    /// password.
+    #[cfg(feature = "encrypted-backups")]
+    #[arg(long, value_name = "FILE")]
+    #[cfg_attr(feature = "image-input", arg(conflicts_with = "qr_image"))]
+    backup: Option<PathBuf>,
+}
+
+#[derive(clap::Args)]
+struct CodeArgs {
+    /// Hex device secret or Base32 secret. Prompted for when omitted.
+    #[arg(long)]
+    secret: Option<String>,
+
+    /// Read the secret saved for this serial with --store keyring, vault or systemd-creds
+    /// instead, from the store given with --store (the keyring when omitted).
+    #[cfg(any(feature = "keyring", feature = "vault", feature = "systemd-creds"))]
+    #[arg(long, conflicts_with = "secret")]
+    serial: Option<String>,
+
+    /// Keep showing the code as it rotates, with a countdown, until Ctrl-C.
+    #[arg(long, conflicts_with = "serve")]
+    watch: bool,
+
+    /// Correct for a wrong system clock using the time reported by Battle.net.
+    #[arg(long)]
+    sync_clock: bool,
+
+    /// Serve the current code over HTTP on this loopback address, e.g. 127.0.0.1:8765, to
+    /// requests carrying the BNET_CODE_TOKEN as a bearer token, until Ctrl-C.
+    #[arg(long)]
+    serve: Option<std::net::SocketAddr>,
+
+    /// Shared token --serve requires. Generated and printed when not set.
+    #[arg(
+        long,
+        env = "BNET_CODE_TOKEN",
+        hide_env_values = true,
+        requires = "serve"
+    )]
+    code_token: Option<String>,
+}
+
+// The --audit-log for operations, opened once in main. The API records its own requests.
+static AUDIT_LOG: OnceLock<AuditLog> = OnceLock::new();
+static PLAIN: AtomicBool = AtomicBool::new(false);
+
+// Record how an operation ended, by the kind of error rather than its message.
+pub fn audit<T>(operation: &str, serial: Option<&str>, result: &Result<T>) {
+    let outcome = match result {
+        Ok(_) => "ok",
+        Err(err) => error_kind(err),
+    };
+    match result {
+        Ok(_) => tracing::info!(operation, serial, outcome, "operation"),
+        Err(_) => tracing::warn!(operation, serial, outcome, "operation"),
+    }
+    let Some(audit_log) = AUDIT_LOG.get() else {
+        return;
+    };
+    audit_log.operation(operation, serial, outcome);
+}
+
+// The kind of failure, as in the audit log and JSON-RPC errors, or "error" for failures outside
+// the API.
+pub fn error_kind(err: &anyhow::Error) -> &'static str {
+    err.downcast_ref::<Error>().map_or("error", Error::kind)
+}
+
+// Decode Blizzard's hex device secret.
+fn decode_device_secret(hex_secret: &str) -> Result<Zeroizing<Vec<u8>>> {
+    let secret = hex::decode(hex_secret.trim()).context("deviceSecret is not valid hex")?;
+    Ok(Zeroizing::new(secret))
+}
+
+// Whether a person is typing the answers. Otherwise they are piped in, one per line.
+pub fn interactive() -> bool {
+    io::stdin().is_terminal()
+}
+
+// Whether --plain asked for output a screen reader reads out well.
+pub fn plain() -> bool {
+    PLAIN.load(Ordering::Relaxed)
+}
+
+// A value under its label, or with --plain on one line with it, which is read out as one.
+fn print_value(label: &str, value: &str) {
+    if plain() {
+        println!("{label}: {value}");
+    } else {
+        println!("\n{label}:");
+        println!("{value}");
+    }
+}
+
+// Prompt for a single line of input and return the trimmed value. Piped answers are read
+// without printing the prompt, and running out of them is an error rather than an empty answer.
+pub fn prompt(label: &str) -> Result<String> {
+    let interactive = interactive();
+    #[cfg(feature = "line-editing")]
+    if interactive && io::stdout().is_terminal() && !plain() {
+        let input = line_edit::read_line(label, false)?;
+        return Ok(input
+            .map(|input| input.trim().to_owned())
+            .unwrap_or_default());
+    }
+    if interactive {
+        print!("{label}");
+        io::stdout().flush().context("failed to flush stdout")?;
+    }
 
     let mut input = String::new();
-    io::stdin()
+    let read = io::stdin()
         .read_line(&mut input)
         .context("failed to read input")?;
+    if read == 0 && !interactive {
+        bail!(
+            "stdin ended before the answer to {:?}",
+            label.trim().trim_end_matches(':')
+        );
+    }
 
     Ok(input.trim().to_owned())
 }
 
-fn main() -> Result<()> {
-    let session_token = prompt("Session Token (ST=...): ")?;
-    let serial = prompt("Authenticator Serial: ")?;
-    let restore_code = prompt("Restore Code: ")?;
+// Make the user explicitly acknowledge that TLS verification is off.
+fn confirm_insecure() -> Result<()> {
+    eprintln!("WARNING: --insecure disables TLS certificate verification.");
+    eprintln!(
+        "Your session token, restore code and device secret can be read or altered by anyone who can intercept the connection."
+    );
+    let answer = prompt("Type \"insecure\" to continue: ")?;
+    if answer != "insecure" {
+        bail!("aborted: --insecure was not confirmed");
+    }
+
+    Ok(())
+}
+
+// Check the serial against the region requests go to, switching to the serial's region when it
+// differs from --region or nothing else chose one.
+fn check_serial_region(api: &Api, session_token: Option<&str>, serial: &str) -> Result<()> {
+    let before = api.region_for(session_token);
+    if let Some(region) = api.check_serial_region(session_token, serial)? {
+        eprintln!(
+            "warning: serial {serial} is a {region} authenticator, using {region} rather than {before}"
+        );
+    }
+    warn_region_mismatch(api.region_for(session_token), serial);
+    Ok(())
+}
+
+// Authenticators can only be restored through the account they are attached to.
+fn warn_region_mismatch(region: Region, serial: &str) {
+    if let Some(serial_region) = Region::from_serial(serial)
+        && serial_region != region
+    {
+        eprintln!(
+            "warning: serial {serial} is a {serial_region} authenticator but the session token is for {region}"
+        );
+    }
+}
+
+// Ask whether to retry with a fresh session token after an expired-token error. Piped input has
+// no one to ask.
+fn confirm_reauth(err: &Error) -> Result<bool> {
+    if !matches!(err, Error::TokenExpired { .. }) || !interactive() {
+        return Ok(false);
+    }
+
+    eprintln!("\n{err}");
+    let answer = prompt("Enter a new session token and try again? [Y/n]: ")?;
+    Ok(!answer.eq_ignore_ascii_case("n") && !answer.eq_ignore_ascii_case("no"))
+}
+
+// Run the SSO exchange and restore, re-prompting for the session token if it expires.
+// `exchanged` is given each bearer token from an exchange before it is used.
+// Returns the device secret and the bearer token it was fetched with, locked into memory.
+fn fetch_device_secret(
+    api: &Api,
+    mut credential: Credential,
+    serial: &str,
+    restore_code: &str,
+    mut exchanged: impl FnMut(&str) -> Result<()>,
+) -> Result<(Locked<String>, Locked<String>)> {
+    loop {
+        let bearer_token = match &credential {
+            Credential::SessionToken(session_token) => {
+                match timings::time("SSO exchange", || api.exchange_session_token(session_token)) {
+                    Ok(bearer_token) => {
+                        exchanged(&bearer_token)?;
+                        Ok(bearer_token)
+                    }
+                    Err(err) => Err(err),
+                }
+            }
+            Credential::BearerToken(bearer_token) => Ok(bearer_token.clone()),
+        };
+        let result = bearer_token.and_then(|bearer_token| {
+            let device_secret = timings::time("restore", || {
+                api.device_secret(&bearer_token, serial, restore_code)
+            })?;
+            Ok((Locked::new(device_secret), Locked::new(bearer_token)))
+        });
+
+        match result {
+            Ok(restored) => return Ok(restored),
+            Err(err) if confirm_reauth(&err)? => {
+                credential = Credential::SessionToken(source::prompt_session_token()?);
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+// Print the converted secret in every form users need to import it.
+fn print_export(export: &OtpAuth) {
+    if let Some(serial) = &export.serial {
+        print_value(
+            "Restore code",
+            &restore::restore_code(serial, &export.secret),
+        );
+    }
+    print_value("Base32 secret (for manual entry)", &export.base32_secret());
+    print_value(
+        "otpauth URI (paste into your authenticator app)",
+        &export.to_uri(),
+    );
+}
+
+// Have the user type the code from their new authenticator app to catch import mistakes.
+fn verify_export(export: &OtpAuth, clock_offset: i64) -> Result<()> {
+    if !interactive() {
+        eprintln!("Skipped verification. Check the new authenticator before removing the old one.");
+        return Ok(());
+    }
+    println!(
+        "\nAdd the secret to your authenticator app, then enter the code it shows to verify it."
+    );
+    loop {
+        let input = prompt("Code (leave empty to skip): ")?;
+        if input.is_empty() {
+            println!(
+                "Skipped verification. Check the new authenticator before removing the old one."
+            );
+            return Ok(());
+        }
+        if totp::verify(&export.secret, &input, totp::now_with_offset(clock_offset)) {
+            println!("Verified: the code matches.");
+            return Ok(());
+        }
+        eprintln!(
+            "That code does not match. Check the secret was entered correctly and that the app uses 8 digits, SHA1 and a 30 second period."
+        );
+    }
+}
+
+// Whether to ask where the export goes once it is done, as no option says and someone is there
+// to answer.
+fn choose_destinations(output: &OutputOptions) -> bool {
+    !output.has_destination() && interactive() && io::stdout().is_terminal()
+}
+
+// Wipe the revealed secret from the screen, and from the scrollback where the terminal supports
+// it, once the user has saved it.
+fn clear_revealed_secret() {
+    if !io::stdout().is_terminal() {
+        return;
+    }
+    // Home, clear the screen, then clear the scrollback (xterm's ED 3, ignored where unsupported).
+    print!("\x1b[H\x1b[2J\x1b[3J");
+    println!("The secret was cleared from the screen.");
+}
+
+// For revealed secrets without a verification step to confirm them.
+fn confirm_and_clear() -> Result<()> {
+    if io::stdin().is_terminal() && io::stdout().is_terminal() {
+        let _clear = interrupt::on_interrupt(clear_revealed_secret);
+        let answered = prompt("\nPress Enter once it is saved to clear it from the screen.");
+        // Also when the prompt is cancelled.
+        clear_revealed_secret();
+        answered?;
+    }
+    Ok(())
+}
+
+fn run_offline(
+    serial: Option<String>,
+    secret: Option<String>,
+    region: Option<Region>,
+    labels: &LabelOptions,
+    output: &OutputOptions,
+) -> Result<()> {
+    let choose = choose_destinations(output);
+    if !choose {
+        output.require_destination()?;
+    }
+    // Normalized as for a restore, so the export is labelled the same and keyfobs are refused.
+    let region = region.unwrap_or_default();
+    let serial = match serial {
+        Some(serial) => region.normalize_serial(&serial)?,
+        None => prompt_serial(region)?,
+    };
+    let device_secret = match secret {
+        Some(secret) => secret,
+        None => prompt("Device Secret (hex): ")?,
+    };
+
+    let secret = decode_device_secret(&device_secret);
+    audit("offline", Some(&serial), &secret);
+    let export = OtpAuth::new(serial, secret?, labels);
+    eprintln!("\nConversion succeeded for {}", export.account);
+    if choose {
+        return destinations::run(&export, output, 0);
+    }
+    if output.write(&export)? {
+        return Ok(());
+    }
 
-    let api = Api::new()?;
+    print_export(&export);
+    verify_export(&export, 0)?;
+    clear_revealed_secret();
 
-    let bearer_token = api.exchange_session_token(&session_token)?;
-    let device_secret = api.device_secret(&bearer_token, &serial, &restore_code)?;
-    let base32_secret = to_base32_secret(&device_secret)?;
+    Ok(())
+}
 
-    let otpauth = build_otpauth_uri(&serial, &base32_secret);
+fn run_hex(secret: Option<String>, reveal: bool) -> Result<()> {
+    format::check_stdout(reveal, "hex")?;
+    let secret = match secret {
+        Some(secret) => secret,
+        None => prompt("Base32 secret: ")?,
+    };
+    let secret = totp::decode_base32(&secret)?;
 
-    println!("\nBattle.net export succeeded");
-    println!("\notpauth URI (paste into your authenticator app):");
-    println!("{otpauth}");
+    print_value("Device secret (hex)", &Zeroizing::new(hex::encode(secret)));
+
+    confirm_and_clear()
+}
+
+fn run_parse(uri: Option<String>, labels: &LabelOptions, output: &OutputOptions) -> Result<()> {
+    let uri = match uri {
+        Some(uri) => uri,
+        None => prompt("otpauth URI: ")?,
+    };
+    let mut export = OtpAuth::parse(&uri)?;
+    export.relabel(labels);
+    if output.write(&export)? {
+        return Ok(());
+    }
+
+    println!("\nThe URI is a valid Battle.net authenticator");
+    print_value("Account", &export.account);
+    if let Some(serial) = &export.serial {
+        print_value("Serial", serial);
+    }
+    if !output.reveal {
+        println!("\nRun with --reveal to print the secret, or convert it with --format.");
+        return Ok(());
+    }
+    print_value("Device secret (hex)", &export.hex_secret());
+    print_export(&export);
+    confirm_and_clear()?;
 
     Ok(())
 }
+
+#[cfg(feature = "keyring")]
+fn run_show(serial: Option<String>, output: &OutputOptions) -> Result<()> {
+    let serial = match serial {
+        Some(serial) => serial,
+        None => prompt("Authenticator Serial: ")?,
+    };
+    let serial = Region::from_serial(&serial)
+        .unwrap_or_default()
+        .normalize_serial(&serial)?;
+    let export = store::load(&serial);
+    audit("show", Some(&serial), &export);
+    let export = export?;
+    if let Some(name) = &output.format {
+        return format::write(name, &export, output.reveal);
+    }
+    if !output.reveal {
+        print_value("Account", &export.account);
+        println!("\nRun with --reveal to print the secret, or convert it with --format.");
+        return Ok(());
+    }
+
+    print_export(&export);
+    confirm_and_clear()
+}
+
+#[cfg(any(feature = "image-input", feature = "encrypted-backups"))]
+fn run_verify(args: &VerifyArgs) -> Result<()> {
+    #[cfg(feature = "encrypted-backups")]
+    if let Some(backup) = &args.backup {
+        return verify::run_backup(backup);
+    }
+    #[cfg(feature = "image-input")]
+    if let Some(qr_image) = &args.qr_image {
+        return verify::run(qr_image, args.export.as_deref());
+    }
+    let targets: &[&str] = &[
+        #[cfg(feature = "image-input")]
+        "--qr-image <photo>",
+        #[cfg(feature = "encrypted-backups")]
+        "--backup <file>",
+    ];
+    bail!("choose what to check: {}", targets.join(" or "))
+}
+
+fn run_code(args: CodeArgs, options: ApiOptions, _output: &OutputOptions) -> Result<()> {
+    #[cfg(any(feature = "keyring", feature = "vault", feature = "systemd-creds"))]
+    let saved = match &args.serial {
+        Some(serial) => {
+            let serial = Region::from_serial(serial)
+                .unwrap_or_default()
+                .normalize_serial(serial)?;
+            #[cfg(feature = "keyring")]
+            let from = _output.store.unwrap_or(store::Store::Keyring);
+            #[cfg(not(feature = "keyring"))]
+            let Some(from) = _output.store else {
+                bail!("choose the store to read the secret from with --store");
+            };
+            let export = store::load_from(
+                from,
+                &serial,
+                #[cfg(feature = "vault")]
+                &_output.vault,
+                #[cfg(feature = "systemd-creds")]
+                &_output.creds,
+            );
+            audit("code", Some(&serial), &export);
+            Some(Zeroizing::new(export?.secret.to_vec()))
+        }
+        None => None,
+    };
+    #[cfg(not(any(feature = "keyring", feature = "vault", feature = "systemd-creds")))]
+    let saved = None;
+    let secret = match (saved, args.secret) {
+        (Some(secret), _) => secret,
+        (None, Some(secret)) => totp::parse_secret(&secret)?,
+        (None, None) => totp::parse_secret(&prompt("Secret (hex or Base32): ")?)?,
+    };
+
+    let clock_offset = if args.sync_clock {
+        let api = Api::new(options)?;
+        let offset = timings::time("clock check", || api.check_clock())?;
+        clock::warn_if_skewed(offset);
+        offset
+    } else {
+        0
+    };
+
+    if let Some(addr) = args.serve {
+        return code_server::run(
+            addr,
+            args.code_token.map(Zeroizing::new),
+            &secret,
+            clock_offset,
+        );
+    }
+    if !args.watch {
+        let now = totp::now_with_offset(clock_offset);
+        println!(
+            "{} (valid for {}s)",
+            totp::code(&secret, now),
+            totp::remaining(now)
+        );
+        return Ok(());
+    }
+
+    println!("Press Ctrl-C to stop.");
+    loop {
+        let now = totp::now_with_offset(clock_offset);
+        let remaining = totp::remaining(now);
+        let filled = usize::try_from(remaining).unwrap_or_default();
+        let empty = usize::try_from(totp::PERIOD - remaining).unwrap_or_default();
+        print!(
+            "\r{}  [{}{}] {remaining:>2}s",
+            totp::code(&secret, now),
+            "#".repeat(filled),
+            " ".repeat(empty)
+        );
+        io::stdout().flush().context("failed to flush stdout")?;
+        thread::sleep(Duration::from_secs(1));
+    }
+}
+
+// Where the export gets the account from, besides the session token options.
+struct ExportInputs {
+    guided_login: bool,
+    auto: bool,
+    #[cfg(feature = "encrypted-backups")]
+    checkpoint: Option<PathBuf>,
+    #[cfg(feature = "image-input")]
+    input_image: Option<PathBuf>,
+}
+
+fn run_export(
+    token: TokenOptions,
+    options: ApiOptions,
+    labels: LabelOptions,
+    output: &OutputOptions,
+    inputs: ExportInputs,
+) -> Result<()> {
+    let ExportInputs {
+        guided_login,
+        auto,
+        #[cfg(feature = "encrypted-backups")]
+        checkpoint,
+        #[cfg(feature = "image-input")]
+        input_image,
+    } = inputs;
+    let choose = choose_destinations(output);
+    if !choose {
+        output.require_destination()?;
+    }
+    #[cfg(feature = "encrypted-backups")]
+    let mut checkpoint = checkpoint.as_deref().map(Checkpoint::open).transpose()?;
+    let api = Api::new(options)?;
+
+    #[cfg(feature = "encrypted-backups")]
+    let saved_bearer_token = checkpoint.as_ref().and_then(Checkpoint::bearer_token);
+    #[cfg(not(feature = "encrypted-backups"))]
+    let saved_bearer_token = None;
+    let mut credential = match saved_bearer_token {
+        Some(bearer_token) => {
+            eprintln!("Using the bearer token saved in the checkpoint");
+            Credential::BearerToken(bearer_token)
+        }
+        None => source::credential(&token, &api, guided_login)?,
+    };
+    let region = api.region_for(credential.session_token());
+    eprintln!("Region: {region}");
+
+    #[cfg(feature = "encrypted-backups")]
+    let resumed = checkpoint.as_ref().and_then(Checkpoint::pending);
+    #[cfg(not(feature = "encrypted-backups"))]
+    let resumed: Option<(String, Zeroizing<String>)> = None;
+    let mut listed_bearer_token = None;
+    let (serial, restore_code) = match resumed {
+        Some((serial, restore_code)) => {
+            eprintln!("Resuming {serial} from the checkpoint");
+            check_serial_region(&api, credential.session_token(), &serial)?;
+            (serial, restore_code.to_string())
+        }
+        None => {
+            #[cfg(feature = "image-input")]
+            let (mut serial, mut restore_code) = match input_image.as_deref() {
+                Some(path) => account_from_image(path, region)?,
+                None => (None, None),
+            };
+            #[cfg(not(feature = "image-input"))]
+            let (mut serial, mut restore_code) = (None, None);
+            if serial.is_none() && (interactive() || auto) {
+                let (device, bearer_token) = devices::choose(&api, &credential, auto)?;
+                listed_bearer_token = bearer_token;
+                if let Some(device) = device {
+                    serial = Some(device.serial);
+                    if restore_code.is_none() {
+                        restore_code = listed_restore_code(device.restore_code, auto);
+                    }
+                }
+            }
+            prompt_account(&api, credential.session_token(), serial, restore_code)?
+        }
+    };
+    if let Some(bearer_token) = listed_bearer_token {
+        #[cfg(feature = "encrypted-backups")]
+        if let Some(checkpoint) = &mut checkpoint {
+            checkpoint.set_bearer_token(&bearer_token)?;
+        }
+        credential = Credential::BearerToken(bearer_token);
+    }
+    #[cfg(feature = "encrypted-backups")]
+    if let Some(checkpoint) = &mut checkpoint {
+        checkpoint.mark_pending(&serial, &restore_code)?;
+    }
+
+    let restored = fetch_device_secret(&api, credential, &serial, &restore_code, |_bearer_token| {
+        #[cfg(feature = "encrypted-backups")]
+        if let Some(checkpoint) = &mut checkpoint {
+            checkpoint.set_bearer_token(_bearer_token)?;
+        }
+        Ok(())
+    });
+    audit("restore", Some(&serial), &restored);
+    let (device_secret, bearer_token) = restored?;
+    let export = build_export(
+        &api,
+        &serial,
+        &restore_code,
+        &device_secret,
+        &bearer_token,
+        &labels,
+    )?;
+
+    let clock_offset = api.clock_offset().unwrap_or_default();
+    clock::warn_if_skewed(clock_offset);
+
+    eprintln!("\nBattle.net export succeeded for {serial}");
+    let written = if choose {
+        destinations::run(&export, output, clock_offset)?;
+        true
+    } else {
+        output.write(&export)?
+    };
+    #[cfg(feature = "encrypted-backups")]
+    if let Some(mut checkpoint) = checkpoint {
+        checkpoint.mark_done(&serial)?;
+        if let Some(path) = checkpoint.finish()? {
+            eprintln!("Removed the checkpoint {}", path.display());
+        }
+    }
+    if written {
+        return Ok(());
+    }
+    print_export(&export);
+    verify_export(&export, clock_offset)?;
+    clear_revealed_secret();
+
+    Ok(())
+}
+
+// Turn a restored device secret into the export, looking up the BattleTag if asked to.
+fn build_export(
+    api: &Api,
+    serial: &str,
+    restore_code: &str,
+    device_secret: &str,
+    bearer_token: &str,
+    labels: &LabelOptions,
+) -> Result<OtpAuth> {
+    let mut export = OtpAuth::new(
+        serial.to_owned(),
+        decode_device_secret(device_secret)?,
+        labels,
+    );
+    if labels.label_battletag {
+        match timings::time("BattleTag lookup", || api.battletag(bearer_token)) {
+            Ok(battletag) => export.account = battletag,
+            Err(err) => {
+                eprintln!("warning: could not look up the BattleTag ({err:#}), using the serial")
+            }
+        }
+    }
+
+    let derived = restore::restore_code(serial, &export.secret);
+    if derived != restore_code {
+        eprintln!(
+            "warning: the restore code derived from the secret ({derived}) differs from the one entered"
+        );
+    }
+    Ok(export)
+}
+
+// Read the serial and restore code from a screenshot, and have the user check them against the
+// app before they are used. Whatever wasn't found, or is rejected, is prompted for instead.
+#[cfg(feature = "image-input")]
+fn account_from_image(
+    path: &std::path::Path,
+    region: Region,
+) -> Result<(Option<String>, Option<String>)> {
+    let found = image_input::read(path, region)?;
+    eprintln!(
+        "Read from {}: serial {}, restore code {}",
+        path.display(),
+        found.serial.as_deref().unwrap_or("(not found)"),
+        found.restore_code.as_deref().unwrap_or("(not found)")
+    );
+    if interactive() {
+        let answer = prompt("Use what was read? [Y/n]: ")?;
+        if answer.eq_ignore_ascii_case("n") || answer.eq_ignore_ascii_case("no") {
+            return Ok((None, None));
+        }
+    }
+    Ok((found.serial, found.restore_code))
+}
+
+// The restore code from the device list, when it is there and valid. --auto says why it still
+// asks for one.
+fn listed_restore_code(restore_code: Option<String>, auto: bool) -> Option<String> {
+    match restore_code.as_deref().map(restore::normalize_restore_code) {
+        Some(Ok(restore_code)) => {
+            eprintln!("Restore Code: from the account's list");
+            Some(restore_code)
+        }
+        Some(Err(err)) => {
+            eprintln!("warning: ignoring the restore code in the account's list: {err}");
+            None
+        }
+        None => {
+            if auto {
+                eprintln!(
+                    "Battle.net doesn't list the restore code for this authenticator; find it in the app under Authenticator > Settings"
+                );
+            }
+            None
+        }
+    }
+}
+
+// Prompt for the serial and restore code until both are valid, unless they are already known.
+// Piped answers get one try, since the next line is already the answer to the next question.
+fn prompt_account(
+    api: &Api,
+    session_token: Option<&str>,
+    serial: Option<String>,
+    restore_code: Option<String>,
+) -> Result<(String, String)> {
+    let region = api.region_for(session_token);
+    // A serial from the device list or a photo is normalized as a typed one is, so it reaches
+    // the restore in the same form and keyfobs are refused.
+    let serial = match serial {
+        Some(serial) => region.normalize_serial(&serial)?,
+        None => prompt_serial(region)?,
+    };
+    check_serial_region(api, session_token, &serial)?;
+    let restore_code = restore_code.map_or_else(prompt_restore_code, Ok)?;
+    Ok((serial, restore_code))
+}
+
+fn prompt_serial(region: Region) -> Result<String> {
+    loop {
+        let input = prompt("Authenticator Serial: ")?;
+        if input.is_empty() {
+            bail!("no serial entered");
+        }
+        match region.normalize_serial(&input) {
+            Ok(serial) => return Ok(serial),
+            Err(err) if interactive() => eprintln!("{err:#}"),
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+fn prompt_restore_code() -> Result<String> {
+    loop {
+        let input = prompt("Restore Code: ")?;
+        if input.is_empty() {
+            bail!("no restore code entered");
+        }
+        match restore::normalize_restore_code(&input) {
+            Ok(restore_code) => return Ok(restore_code),
+            Err(err) => {
+                if let Some(corrected) = restore::correct_restore_code(&input) {
+                    // Nobody is there to confirm it, and it can only be what was meant.
+                    if !interactive() {
+                        eprintln!(
+                            "warning: restore codes never contain I, L, O or S, using {corrected}"
+                        );
+                        return Ok(corrected);
+                    }
+                    let answer = prompt(&format!(
+                        "Restore codes never contain I, L, O or S. Use {corrected}? [Y/n]: "
+                    ))?;
+                    if !answer.eq_ignore_ascii_case("n") && !answer.eq_ignore_ascii_case("no") {
+                        return Ok(corrected);
+                    }
+                } else if interactive() {
+                    eprintln!("{err}");
+                } else {
+                    return Err(err.into());
+                }
+            }
+        }
+    }
+}
+
+fn main() -> Result<()> {
+    let mut cli = Cli::parse();
+    // Only the CLI keeps restore attempts across runs, the library leaves that to its caller.
+    if cli.api.restore_attempts_file.is_none() {
+        cli.api.restore_attempts_file = AttemptLimit::default_path();
+    }
+    if cli.timings {
+        timings::enable();
+    }
+    PLAIN.store(cli.plain, Ordering::Relaxed);
+    match cli.log_format {
+        Some(format) => logging::init(format, cli.log_file.as_deref())?,
+        None => logging::init_notices(),
+    }
+    run(cli)
+}
+
+fn run(cli: Cli) -> Result<()> {
+    if let Some(path) = &cli.api.audit_log {
+        let _ = AUDIT_LOG.set(AuditLog::open(path)?);
+    }
+    if let Err(err) = memory::disable_core_dumps() {
+        eprintln!("warning: could not disable core dumps: {err}");
+    }
+    if let Err(err) = interrupt::install() {
+        eprintln!("warning: {err:#}");
+    }
+
+    // Once for every command, before any of them connects anywhere.
+    if cli.api.insecure {
+        if cli.serve_stdio {
+            bail!(
+                "--insecure needs interactive confirmation and cannot be used with --serve-stdio"
+            );
+        }
+        #[cfg(feature = "self-update")]
+        if matches!(cli.command, Some(Command::SelfUpdate { .. })) {
+            bail!(
+                "self-update always verifies GitHub's certificate, since it replaces this binary, run it without --insecure"
+            );
+        }
+        confirm_insecure()?;
+    }
+
+    #[cfg(feature = "self-update")]
+    let update_check = cli
+        .wants_update_check()
+        .then(|| self_update::start_check(&cli.api, &cli.releases_url));
+
+    let result = run_command(cli);
+    timings::report();
+    // Reported last, next to any error it may explain.
+    #[cfg(feature = "self-update")]
+    if let Some(update_check) = update_check {
+        update_check.report();
+    }
+    result
+}
+
+fn run_command(cli: Cli) -> Result<()> {
+    if cli.serve_stdio {
+        if cli.command.is_some() {
+            bail!("--serve-stdio cannot be combined with a subcommand");
+        }
+        let api = Api::new(cli.api)?;
+        return rpc::serve(&api, &cli.labels);
+    }
+
+    #[cfg(feature = "batch")]
+    if let Some(path) = &cli.batch.batch {
+        if cli.command.is_some() {
+            bail!("--batch cannot be combined with a subcommand");
+        }
+        #[cfg(feature = "image-input")]
+        if cli.input_image.is_some() {
+            bail!("--input-image reads a single account, the --batch file lists them instead");
+        }
+        if cli.auto {
+            bail!("--auto finds a single account, the --batch file lists them instead");
+        }
+        let api = Api::new(cli.api)?;
+        return batch::run(
+            &cli.batch,
+            path,
+            &cli.token,
+            &api,
+            &cli.labels,
+            &cli.output,
+            cli.checkpoint.as_deref(),
+        );
+    }
+
+    #[cfg(feature = "tui")]
+    if cli.tui {
+        if cli.command.is_some() {
+            bail!("--tui cannot be combined with a subcommand");
+        }
+        if cli.plain {
+            bail!("--plain cannot be combined with --tui");
+        }
+        #[cfg(feature = "image-input")]
+        if cli.input_image.is_some() {
+            bail!("--input-image cannot be combined with --tui");
+        }
+        if cli.auto {
+            bail!("--auto cannot be combined with --tui");
+        }
+        // As for a restore without the TUI, the secret only reaches the terminal with --reveal.
+        let choose = choose_destinations(&cli.output);
+        if !choose {
+            cli.output.require_destination()?;
+        }
+        let Some(export) = tui::run(Api::new(cli.api)?, cli.labels)? else {
+            return Ok(());
+        };
+        if choose {
+            return destinations::run(&export, &cli.output, 0);
+        }
+        if !cli.output.write(&export)? {
+            print_export(&export);
+            confirm_and_clear()?;
+        }
+        return Ok(());
+    }
+
+    match cli.command {
+        Some(Command::Offline { serial, secret }) => {
+            run_offline(serial, secret, cli.api.region, &cli.labels, &cli.output)
+        }
+        Some(Command::Hex { secret }) => run_hex(secret, cli.output.reveal),
+        Some(Command::Parse { uri }) => run_parse(uri, &cli.labels, &cli.output),
+        Some(Command::Compare { a, b }) => {
+            let a = match a {
+                Some(a) => a,
+                None => prompt("First (otpauth URI, hex secret or file): ")?,
+            };
+            let b = match b {
+                Some(b) => b,
+                None => prompt("Second (otpauth URI, hex secret or file): ")?,
+            };
+            compare::run(&Zeroizing::new(a), &Zeroizing::new(b))
+        }
+        #[cfg(feature = "keyring")]
+        Some(Command::Show { serial }) => run_show(serial, &cli.output),
+        Some(Command::Convert { from, to, input }) => {
+            let converted = convert::run(
+                from.as_deref(),
+                &to,
+                input.as_deref(),
+                &cli.labels,
+                cli.output.reveal,
+            );
+            audit("convert", None, &converted);
+            converted
+        }
+        Some(Command::Import { from, input }) => {
+            let imported = import::run(from.as_deref(), input.as_deref(), &cli.labels, &cli.output);
+            audit("import", None, &imported);
+            imported
+        }
+        #[cfg(any(feature = "image-input", feature = "encrypted-backups"))]
+        Some(Command::Verify(args)) => {
+            let verified = run_verify(&args);
+            audit("verify", None, &verified);
+            verified
+        }
+        Some(Command::Formats) => format::run_list(),
+        Some(Command::Compat { app }) => compat::run(app.as_deref()),
+        Some(Command::Doctor) => doctor::run(&cli.api),
+        Some(Command::Code(args)) => run_code(args, cli.api, &cli.output),
+        #[cfg(feature = "self-update")]
+        Some(Command::SelfUpdate { check, yes }) => {
+            self_update::run(&cli.api, &cli.releases_url, check, yes)
+        }
+        Some(Command::Rotate { yes }) => rotate::run(
+            &cli.token,
+            &Api::new(cli.api)?,
+            &cli.labels,
+            &cli.output,
+            yes,
+        ),
+        Some(Command::Login) => run_export(
+            cli.token,
+            cli.api,
+            cli.labels,
+            &cli.output,
+            ExportInputs {
+                guided_login: true,
+                auto: cli.auto,
+                #[cfg(feature = "encrypted-backups")]
+                checkpoint: cli.checkpoint,
+                #[cfg(feature = "image-input")]
+                input_image: cli.input_image,
+            },
+        ),
+        None => run_export(
+            cli.token,
+            cli.api,
+            cli.labels,
+            &cli.output,
+            ExportInputs {
+                guided_login: false,
+                auto: cli.auto,
+                #[cfg(feature = "encrypted-backups")]
+                checkpoint: cli.checkpoint,
+                #[cfg(feature = "image-input")]
+                input_image: cli.input_image,
+            },
+        ),
+    }
+}
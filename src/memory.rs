@@ -0,0 +1,85 @@
+use std::io;
+use std::ops::Deref;
+
+use zeroize::{Zeroize, Zeroizing};
+
+/// A secret whose buffer is locked into RAM, so it is never written to swap, and wiped on drop.
+///
+/// Locking is best effort: it fails when the process is over its locked memory limit, in which
+/// case the secret is still zeroized. Locks apply to whole pages and do not nest, so unlocking one
+/// secret can unlock a neighbour sharing its page.
+pub struct Locked<T: Zeroize + AsRef<[u8]>> {
+    // Dropped first, so the buffer is wiped before its pages are unlocked.
+    secret: Zeroizing<T>,
+    #[cfg(not(target_arch = "wasm32"))]
+    guard: Option<region::LockGuard>,
+}
+
+impl<T: Zeroize + AsRef<[u8]>> Locked<T> {
+    /// Take ownership of `secret` and lock its buffer.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn new(secret: Zeroizing<T>) -> Self {
+        let bytes = secret.as_ref();
+        let guard = if bytes.is_empty() {
+            None
+        } else {
+            region::lock(bytes.as_ptr(), bytes.len()).ok()
+        };
+
+        Self { secret, guard }
+    }
+
+    /// Take ownership of `secret`. WebAssembly has no swap to lock it out of.
+    #[cfg(target_arch = "wasm32")]
+    pub fn new(secret: Zeroizing<T>) -> Self {
+        Self { secret }
+    }
+
+    /// Whether the buffer is actually locked into RAM.
+    pub fn is_locked(&self) -> bool {
+        #[cfg(not(target_arch = "wasm32"))]
+        return self.guard.is_some();
+        #[cfg(target_arch = "wasm32")]
+        false
+    }
+}
+
+impl<T: Zeroize + AsRef<[u8]>> Deref for Locked<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.secret
+    }
+}
+
+impl<T: Zeroize + AsRef<[u8]>> AsRef<[u8]> for Locked<T> {
+    fn as_ref(&self) -> &[u8] {
+        (*self.secret).as_ref()
+    }
+}
+
+/// Stop the process from writing core dumps, and on Linux from being attached to by other
+/// processes of the same user. Does nothing on platforms without either.
+pub fn disable_core_dumps() -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        let limit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        // SAFETY: `limit` is a valid rlimit for the duration of the call.
+        if unsafe { libc::setrlimit(libc::RLIMIT_CORE, &limit) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        // SAFETY: PR_SET_DUMPABLE only takes an integer argument.
+        if unsafe { libc::prctl(libc::PR_SET_DUMPABLE, 0) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,205 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, anyhow, bail};
+use data_encoding::BASE64URL_NOPAD;
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use crate::{login, timings};
+use bnet_auth_export::api::Api;
+
+// Run the OAuth authorization-code flow in the browser and return a bearer token.
+//
+// Returns `None` when Battle.net doesn't let this client use the flow, so the caller can
+// fall back to the session token.
+pub fn bearer_token(api: &Api, timeout: Duration) -> Result<Option<Zeroizing<String>>> {
+    let listener = TcpListener::bind("127.0.0.1:0").context("failed to start callback listener")?;
+    let port = listener.local_addr()?.port();
+    let redirect_uri = format!("http://localhost:{port}/callback");
+
+    let verifier = random_string()?;
+    let challenge = BASE64URL_NOPAD.encode(&Sha256::digest(verifier.as_bytes()));
+    let state = random_string()?;
+    let url = api.authorize_url(&redirect_uri, &challenge, &state);
+
+    println!("Opening the Battle.net authorization page in your browser:");
+    println!("{url}");
+    if let Err(err) = login::open_browser(&url) {
+        eprintln!("Could not open a browser ({err:#}), open the link above manually.");
+    }
+    println!(
+        "Waiting up to {}s for the authorization (Ctrl-C to stop)...",
+        timeout.as_secs()
+    );
+
+    let code = match wait_for_code(&listener, &state, timeout) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("OAuth authorization did not complete: {err:#}");
+            eprintln!("Falling back to the session token.");
+            return Ok(None);
+        }
+    };
+
+    match timings::time("authorization code exchange", || {
+        api.exchange_authorization_code(&code, &verifier, &redirect_uri)
+    }) {
+        Ok(token) => Ok(Some(token)),
+        Err(err) => {
+            eprintln!("OAuth token exchange failed: {err:#}");
+            eprintln!("Falling back to the session token.");
+            Ok(None)
+        }
+    }
+}
+
+fn random_string() -> Result<String> {
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|err| anyhow!("failed to generate random bytes: {err}"))?;
+    Ok(BASE64URL_NOPAD.encode(&bytes))
+}
+
+// Accept browser requests until the redirect with our state arrives. A request that fails to
+// arrive, such as a connection the browser opened and dropped, is only reported, but a redirect
+// with another state or an error ends the wait.
+fn wait_for_code(listener: &TcpListener, state: &str, timeout: Duration) -> Result<String> {
+    listener.set_nonblocking(true)?;
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
+                if Instant::now() >= deadline {
+                    bail!("timed out; the client may not be allowed to use a localhost redirect");
+                }
+                thread::sleep(Duration::from_millis(100));
+                continue;
+            }
+            Err(err) => return Err(err).context("failed to accept callback"),
+        };
+
+        match handle_callback(stream, state) {
+            Ok(Some(result)) => return result,
+            Ok(None) => {}
+            Err(err) => eprintln!("Ignoring a failed request to the callback listener: {err:#}"),
+        }
+    }
+}
+
+// Returns `None` for unrelated requests such as /favicon.ico.
+fn handle_callback(mut stream: TcpStream, state: &str) -> Result<Option<Result<String>>> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let target = line.split_whitespace().nth(1).unwrap_or_default();
+
+    let Some(query) = target.strip_prefix("/callback?") else {
+        respond(&mut stream, "404 Not Found", "Not found.")?;
+        return Ok(None);
+    };
+    let params: Vec<(String, String)> = serde_urlencoded::from_str(query).unwrap_or_default();
+    let param = |name: &str| {
+        params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    };
+
+    // Not our redirect, possibly one forged to log in to another account, so none is trusted.
+    if param("state") != Some(state) {
+        respond(
+            &mut stream,
+            "400 Bad Request",
+            "Unexpected state parameter, return to the terminal.",
+        )?;
+        return Ok(Some(Err(anyhow!(
+            "the redirect's state parameter doesn't match the one sent"
+        ))));
+    }
+
+    if let Some(error) = param("error") {
+        let description = param("error_description").unwrap_or_default();
+        respond(
+            &mut stream,
+            "200 OK",
+            "Authorization failed, return to the terminal.",
+        )?;
+        return Ok(Some(Err(anyhow!(
+            "Battle.net returned {error} {description}"
+        ))));
+    }
+
+    let Some(code) = param("code") else {
+        respond(&mut stream, "400 Bad Request", "Missing code parameter.")?;
+        return Ok(None);
+    };
+
+    respond(
+        &mut stream,
+        "200 OK",
+        "Authorized, you can close this tab and return to the terminal.",
+    )?;
+    Ok(Some(Ok(code.to_owned())))
+}
+
+fn respond(stream: &mut TcpStream, status: &str, body: &str) -> Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\ncontent-type: text/plain; charset=utf-8\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    fn request(port: u16, target: &str) -> String {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        write!(stream, "GET {target} HTTP/1.1\r\nhost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn waits_past_requests_that_fail() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let browser = thread::spawn(move || {
+            // Dropped before sending anything.
+            drop(TcpStream::connect(("127.0.0.1", port)).unwrap());
+            assert!(request(port, "/favicon.ico").starts_with("HTTP/1.1 404"));
+            request(port, "/callback?code=abc&state=ours")
+        });
+
+        let code = wait_for_code(&listener, "ours", Duration::from_secs(20)).unwrap();
+        assert_eq!(code, "abc");
+        assert!(browser.join().unwrap().starts_with("HTTP/1.1 200"));
+    }
+
+    #[test]
+    fn stops_at_another_state_or_an_error() {
+        for target in [
+            "/callback?code=abc&state=theirs",
+            "/callback?error=access_denied&state=ours",
+        ] {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let port = listener.local_addr().unwrap().port();
+            let browser = thread::spawn(move || request(port, target));
+
+            assert!(wait_for_code(&listener, "ours", Duration::from_secs(20)).is_err());
+            browser.join().unwrap();
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use clap::Args;
+use data_encoding::BASE32_NOPAD;
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, percent_decode_str, utf8_percent_encode};
+use reqwest::Url;
+use zeroize::Zeroizing;
+
+use crate::error::{Error, Result, invalid_input};
+use crate::memory::Locked;
+use crate::region::Region;
+use crate::totp;
+
+const ISSUER: &str = "Battle.net";
+
+// Everything but RFC 3986 unreserved characters is escaped in the label and issuer.
+const ESCAPE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// How the entry is named in the authenticator app.
+#[derive(Args, Clone, Default)]
+pub struct LabelOptions {
+    /// Account name shown in the authenticator app, e.g. an email address. Defaults to the serial.
+    #[arg(long, conflicts_with = "label_battletag")]
+    pub label: Option<String>,
+
+    /// Issuer shown in the authenticator app. Defaults to Battle.net.
+    #[arg(long)]
+    pub issuer: Option<String>,
+
+    /// Use the account's BattleTag as the account name (online exports only).
+    #[arg(long)]
+    pub label_battletag: bool,
+}
+
+/// An exported authenticator: its raw device secret and how it is labelled.
+pub struct OtpAuth {
+    /// Unknown when parsing a URI whose label was customized.
+    pub serial: Option<String>,
+    pub secret: Locked<Vec<u8>>,
+    pub issuer: String,
+    pub account: String,
+}
+
+impl OtpAuth {
+    pub fn new(serial: String, secret: Zeroizing<Vec<u8>>, labels: &LabelOptions) -> Self {
+        let mut export = Self {
+            account: serial.clone(),
+            serial: Some(serial),
+            secret: Locked::new(secret),
+            issuer: ISSUER.to_owned(),
+        };
+        export.relabel(labels);
+        export
+    }
+
+    /// Apply the label and issuer given on the command line.
+    pub fn relabel(&mut self, labels: &LabelOptions) {
+        if let Some(label) = &labels.label {
+            self.account = label.clone();
+        }
+        if let Some(issuer) = &labels.issuer {
+            self.issuer = issuer.clone();
+        }
+    }
+
+    pub fn base32_secret(&self) -> Zeroizing<String> {
+        Zeroizing::new(BASE32_NOPAD.encode(&self.secret))
+    }
+
+    pub fn hex_secret(&self) -> Zeroizing<String> {
+        Zeroizing::new(hex::encode(&self.secret))
+    }
+
+    /// The otpauth URI with Battle.net's TOTP parameters.
+    pub fn to_uri(&self) -> Zeroizing<String> {
+        let issuer = utf8_percent_encode(&self.issuer, ESCAPE);
+        Zeroizing::new(format!(
+            "otpauth://totp/{issuer}:{}?secret={}&issuer={issuer}&digits={}&algorithm=SHA1&period={}",
+            utf8_percent_encode(&self.account, ESCAPE),
+            self.base32_secret().as_str(),
+            totp::DIGITS,
+            totp::PERIOD
+        ))
+    }
+
+    /// Parse an otpauth URI and check it describes a Battle.net authenticator.
+    pub fn parse(uri: &str) -> Result<Self> {
+        let url = Url::parse(uri.trim())
+            .map_err(|err| Error::InvalidInput(format!("not a valid otpauth URI: {err}")))?;
+        if url.scheme() != "otpauth" {
+            invalid_input!("expected an otpauth:// URI, got {}://", url.scheme());
+        }
+        if url.host_str() != Some("totp") {
+            invalid_input!(
+                "expected a totp URI, got {}",
+                url.host_str().unwrap_or_default()
+            );
+        }
+
+        let label = percent_decode_str(url.path().trim_start_matches('/'))
+            .decode_utf8()
+            .map_err(|_| Error::InvalidInput("label is not valid UTF-8".to_owned()))?;
+        let (label_issuer, account) = match label.split_once(':') {
+            Some((issuer, account)) => (Some(issuer.trim()), account.trim()),
+            None => (None, label.trim()),
+        };
+        if account.is_empty() {
+            invalid_input!("URI has no account name");
+        }
+
+        let mut secret = None;
+        let mut issuer = None;
+        let mut digits = None;
+        let mut algorithm = None;
+        let mut period = None;
+        for (key, value) in url.query_pairs() {
+            match key.as_ref() {
+                "secret" => secret = Some(totp::decode_base32(&value)?),
+                "issuer" => issuer = Some(value.into_owned()),
+                "digits" => digits = Some(value.into_owned()),
+                "algorithm" => algorithm = Some(value.into_owned()),
+                "period" => period = Some(value.into_owned()),
+                _ => {}
+            }
+        }
+
+        // Parameters left out are the defaults from the Key URI format, which authenticator apps
+        // assume: 6 digits, SHA1 and 30s. The digits aren't Battle.net's.
+        match digits.as_deref() {
+            None => invalid_input!(
+                "URI has no digits parameter, so authenticator apps show 6 digits, Battle.net codes have {}",
+                totp::DIGITS
+            ),
+            Some(value) if value != totp::DIGITS.to_string() => {
+                invalid_input!("digits={value}, Battle.net codes have {}", totp::DIGITS)
+            }
+            Some(_) => {}
+        }
+        if let Some(value) = algorithm
+            && !value.eq_ignore_ascii_case("SHA1")
+        {
+            invalid_input!("algorithm={value}, Battle.net codes use SHA1");
+        }
+        if let Some(value) = period
+            && value != totp::PERIOD.to_string()
+        {
+            invalid_input!(
+                "period={value}, Battle.net codes rotate every {}s",
+                totp::PERIOD
+            );
+        }
+
+        if let (Some(label_issuer), Some(issuer)) = (label_issuer, &issuer)
+            && label_issuer != issuer
+        {
+            invalid_input!(
+                "label issuer {label_issuer:?} does not match issuer parameter {issuer:?}"
+            );
+        }
+
+        let Some(secret) = secret else {
+            invalid_input!("URI has no secret parameter");
+        };
+        let issuer = issuer
+            .or_else(|| label_issuer.map(str::to_owned))
+            .unwrap_or_else(|| ISSUER.to_owned());
+
+        Ok(Self {
+            serial: Region::from_serial(account)
+                .and_then(|region| region.normalize_serial(account).ok()),
+            secret: Locked::new(secret),
+            issuer,
+            account: account.to_owned(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URI: &str = "otpauth://totp/Battle.net:US-1234-5678-9012?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=Battle.net";
+
+    fn reason(uri: &str) -> String {
+        match OtpAuth::parse(uri) {
+            Err(Error::InvalidInput(reason)) => reason,
+            Err(err) => panic!("unexpected error {err}"),
+            Ok(_) => panic!("{uri} was accepted"),
+        }
+    }
+
+    #[test]
+    fn accepts_battle_net_parameters() {
+        let export = OtpAuth::parse(&format!("{URI}&digits=8&algorithm=sha1&period=30")).unwrap();
+        assert_eq!(export.serial.as_deref(), Some("US-1234-5678-9012"));
+        assert_eq!(*export.secret, b"12345678901234567890");
+        // Period and algorithm default to Battle.net's.
+        assert!(OtpAuth::parse(&format!("{URI}&digits=8")).is_ok());
+        // And it reads back what it writes.
+        assert_eq!(
+            *OtpAuth::parse(&export.to_uri()).unwrap().secret,
+            *export.secret
+        );
+    }
+
+    #[test]
+    fn rejects_a_missing_digits_parameter() {
+        assert!(reason(URI).contains("no digits parameter"));
+    }
+
+    #[test]
+    fn rejects_other_parameters() {
+        assert!(reason(&format!("{URI}&digits=6")).starts_with("digits=6"));
+        assert!(reason(&format!("{URI}&digits=8&algorithm=SHA256")).starts_with("algorithm="));
+        assert!(reason(&format!("{URI}&digits=8&period=60")).starts_with("period=60"));
+    }
+
+    #[test]
+    fn rejects_a_bad_secret() {
+        let uri = "otpauth://totp/Battle.net:US-1234-5678-9012?secret=not-base32!&digits=8";
+        assert!(OtpAuth::parse(uri).is_err());
+        let uri = "otpauth://totp/Battle.net:US-1234-5678-9012?digits=8";
+        assert!(reason(uri).contains("no secret"));
+    }
+}
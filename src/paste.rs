@@ -0,0 +1,72 @@
+//! Cleaning up values copied from the Battle.net site, chat apps and notes, which come with
+//! labels, quotes and invisible characters that would otherwise fail validation.
+
+/// Quote pairs stripped from around a value, including the typographic ones chat apps and word
+/// processors substitute.
+const QUOTES: &[(char, char)] = &[
+    ('"', '"'),
+    ('\'', '\''),
+    ('`', '`'),
+    ('\u{201c}', '\u{201d}'),
+    ('\u{2018}', '\u{2019}'),
+    ('\u{201e}', '\u{201c}'),
+    ('\u{00ab}', '\u{00bb}'),
+];
+
+/// Remove zero-width and direction-control characters, turn other Unicode spaces into plain
+/// ones, then strip any of `labels` (e.g. `Serial:`) and surrounding quotes, in whatever order
+/// they were pasted. Labels match case-insensitively and need a `:` or `=` after them, so a
+/// value that merely starts with the same letters is left alone.
+pub fn clean(input: &str, labels: &[&str]) -> String {
+    let cleaned: String = input
+        .chars()
+        .filter(|&c| !is_invisible(c))
+        .map(|c| if c.is_whitespace() { ' ' } else { c })
+        .collect();
+
+    let mut value = cleaned.trim();
+    loop {
+        let before = value;
+        value = strip_label(value, labels).trim();
+        value = strip_quotes(value).trim();
+        if value == before {
+            return value.to_owned();
+        }
+    }
+}
+
+fn is_invisible(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00ad}'
+            | '\u{180e}'
+            | '\u{200b}'..='\u{200f}'
+            | '\u{202a}'..='\u{202e}'
+            | '\u{2060}'..='\u{2064}'
+            | '\u{2066}'..='\u{2069}'
+            | '\u{feff}'
+    )
+}
+
+fn strip_label<'a>(value: &'a str, labels: &[&str]) -> &'a str {
+    for label in labels {
+        let Some(head) = value.get(..label.len()) else {
+            continue;
+        };
+        if !head.eq_ignore_ascii_case(label) {
+            continue;
+        }
+        let rest = value[label.len()..].trim_start();
+        if let Some(rest) = rest.strip_prefix([':', '=']) {
+            return rest;
+        }
+    }
+    value
+}
+
+fn strip_quotes(value: &str) -> &str {
+    QUOTES
+        .iter()
+        .find_map(|&(open, close)| value.strip_prefix(open)?.strip_suffix(close))
+        .unwrap_or(value)
+}
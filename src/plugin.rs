@@ -0,0 +1,104 @@
+// External format plugins: `bnet-auth-export-format-<name>` executables on PATH that read the
+// export payload as JSON on stdin and write the rendered format to stdout.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, bail};
+use zeroize::Zeroizing;
+
+use crate::format::{ExportPayload, Exporter};
+
+pub const PREFIX: &str = "bnet-auth-export-format-";
+
+pub struct Plugin {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl Exporter for Plugin {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    // Plugins don't declare an extension, so files are named after the format.
+    fn extension(&self) -> &str {
+        &self.name
+    }
+
+    fn render(&self, payload: &ExportPayload) -> Result<Vec<u8>> {
+        let input = Zeroizing::new(serde_json::to_vec(payload).context("failed to encode export")?);
+
+        let mut child = Command::new(&self.path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .with_context(|| format!("failed to run {}", self.path.display()))?;
+        // Write in full, then close stdin so the plugin sees the end of the payload.
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(&input)
+                .with_context(|| format!("failed to send the export to {}", self.name))?;
+        }
+
+        let output = child
+            .wait_with_output()
+            .with_context(|| format!("failed to run {}", self.path.display()))?;
+        if !output.status.success() {
+            bail!("format plugin {} failed with {}", self.name, output.status);
+        }
+        Ok(output.stdout)
+    }
+}
+
+// Every plugin on PATH, by name. Earlier PATH entries win, like the shell.
+pub fn discover() -> Vec<Plugin> {
+    let mut plugins = BTreeMap::new();
+    for dir in env::split_paths(&env::var_os("PATH").unwrap_or_default()) {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if let Some(name) = plugin_name(&path)
+                && is_executable(&path)
+            {
+                plugins.entry(name).or_insert(path);
+            }
+        }
+    }
+
+    plugins
+        .into_iter()
+        .map(|(name, path)| Plugin { name, path })
+        .collect()
+}
+
+fn plugin_name(path: &Path) -> Option<String> {
+    let file_name = path.file_name()?.to_str()?;
+    let name = file_name.strip_prefix(PREFIX)?;
+    let name = if cfg!(windows) {
+        name.strip_suffix(".exe")?
+    } else {
+        name
+    };
+    (!name.is_empty()).then(|| name.to_owned())
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::metadata(path)
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
@@ -0,0 +1,220 @@
+//! The `bnet_auth_export` Python module, built with `maturin build --features python`.
+//!
+//! Secrets become ordinary Python strings once returned, so they are not zeroized past that point.
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::api::{Api, ApiOptions};
+use crate::error::Error;
+use crate::otpauth::{LabelOptions, OtpAuth};
+use crate::region::Region;
+use crate::{restore, token, totp};
+
+create_exception!(
+    bnet_auth_export,
+    BnetError,
+    PyException,
+    "Base class of every error raised by this module."
+);
+create_exception!(
+    bnet_auth_export,
+    InvalidInput,
+    BnetError,
+    "A serial, restore code, secret or option is malformed."
+);
+create_exception!(
+    bnet_auth_export,
+    TokenExpired,
+    BnetError,
+    "The session or bearer token was rejected."
+);
+create_exception!(
+    bnet_auth_export,
+    SsoFailed,
+    BnetError,
+    "The token exchange failed for another reason."
+);
+create_exception!(
+    bnet_auth_export,
+    RestoreRejected,
+    BnetError,
+    "The serial or restore code was refused."
+);
+create_exception!(
+    bnet_auth_export,
+    RateLimited,
+    BnetError,
+    "Battle.net is rate limiting requests."
+);
+create_exception!(
+    bnet_auth_export,
+    ApiError,
+    BnetError,
+    "A request returned an error status."
+);
+create_exception!(
+    bnet_auth_export,
+    NetworkError,
+    BnetError,
+    "A request did not complete."
+);
+create_exception!(
+    bnet_auth_export,
+    ParseError,
+    BnetError,
+    "A response or file could not be understood."
+);
+
+impl From<Error> for PyErr {
+    fn from(err: Error) -> Self {
+        let message = err.full_message();
+        match err {
+            Error::InvalidInput(_) => InvalidInput::new_err(message),
+            Error::TokenExpired { .. } => TokenExpired::new_err(message),
+            Error::SsoFailed { .. } => SsoFailed::new_err(message),
+            Error::RestoreRejected { .. } => RestoreRejected::new_err(message),
+            Error::RateLimited { .. } => RateLimited::new_err(message),
+            Error::Api { .. } => ApiError::new_err(message),
+            Error::Network { .. } => NetworkError::new_err(message),
+            Error::Parse { .. } => ParseError::new_err(message),
+            Error::Io { .. } => BnetError::new_err(message),
+        }
+    }
+}
+
+/// Blocking client for the Battle.net OAuth and authenticator APIs.
+///
+/// Requests release the GIL while they wait on the network.
+#[pyclass(name = "Client", frozen)]
+struct PyClient(Api);
+
+#[pymethods]
+impl PyClient {
+    #[new]
+    #[pyo3(signature = (region=None, sso_url=None, oauth_url=None, auth_base_url=None, retries=3, timeout=30))]
+    fn new(
+        region: Option<&str>,
+        sso_url: Option<String>,
+        oauth_url: Option<String>,
+        auth_base_url: Option<String>,
+        retries: u32,
+        timeout: u64,
+    ) -> PyResult<Self> {
+        let region = region.map(parse_region).transpose()?;
+        let api = Api::new(ApiOptions {
+            region,
+            sso_url,
+            oauth_url,
+            auth_base_url,
+            retries,
+            timeout,
+            ..ApiOptions::default()
+        })?;
+        Ok(Self(api))
+    }
+
+    /// Exchange a session token, in any form the CLI accepts, for a bearer token.
+    fn exchange_session_token(&self, py: Python<'_>, session_token: &str) -> PyResult<String> {
+        let session_token = Zeroizing::new(token::normalize_session_token(session_token));
+        let bearer_token = py.allow_threads(|| self.0.exchange_session_token(&session_token))?;
+        Ok(bearer_token.to_string())
+    }
+
+    /// Restore an authenticator with a bearer token and return its hex device secret.
+    fn device_secret(
+        &self,
+        py: Python<'_>,
+        bearer_token: &str,
+        serial: &str,
+        restore_code: &str,
+    ) -> PyResult<String> {
+        let serial = self.0.region_for(None).normalize_serial(serial)?;
+        let restore_code = restore::normalize_restore_code(restore_code)?;
+        let device_secret =
+            py.allow_threads(|| self.0.device_secret(bearer_token, &serial, &restore_code))?;
+        Ok(device_secret.to_string())
+    }
+
+    /// Look up the BattleTag of the account a bearer token belongs to.
+    fn battletag(&self, py: Python<'_>, bearer_token: &str) -> PyResult<String> {
+        Ok(py.allow_threads(|| self.0.battletag(bearer_token))?)
+    }
+}
+
+/// Build the otpauth URI for a serial and hex device secret.
+#[pyfunction]
+#[pyo3(signature = (serial, device_secret, label=None, issuer=None))]
+fn otpauth_uri(
+    serial: &str,
+    device_secret: &str,
+    label: Option<String>,
+    issuer: Option<String>,
+) -> PyResult<String> {
+    let labels = LabelOptions {
+        label,
+        issuer,
+        label_battletag: false,
+    };
+    Ok(export(serial, device_secret, &labels)?.to_uri().to_string())
+}
+
+/// The restore code Blizzard derives from a serial and hex device secret.
+#[pyfunction]
+fn restore_code(serial: &str, device_secret: &str) -> PyResult<String> {
+    let export = export(serial, device_secret, &LabelOptions::default())?;
+    Ok(restore::restore_code(serial, &export.secret))
+}
+
+/// The current code for a hex or Base32 secret, or the code at `time` in Unix seconds.
+#[pyfunction]
+#[pyo3(signature = (secret, time=None))]
+fn code(secret: &str, time: Option<u64>) -> PyResult<String> {
+    let secret = totp::parse_secret(secret)?;
+    Ok(totp::code(&secret, time.unwrap_or_else(totp::now)))
+}
+
+/// Extract the session token from a URL, cookie header, cURL command or bare token.
+#[pyfunction]
+fn normalize_session_token(input: &str) -> String {
+    token::normalize_session_token(input)
+}
+
+fn parse_region(region: &str) -> Result<Region, Error> {
+    clap::ValueEnum::from_str(region, true)
+        .map_err(|_| Error::InvalidInput(format!("unknown region {region}")))
+}
+
+fn export(serial: &str, device_secret: &str, labels: &LabelOptions) -> Result<OtpAuth, Error> {
+    let serial = Region::from_serial(serial)
+        .unwrap_or_default()
+        .normalize_serial(serial)?;
+    let secret = hex::decode(device_secret.trim()).map_err(|err| Error::Parse {
+        what: "deviceSecret".to_owned(),
+        detail: err.to_string(),
+    })?;
+    Ok(OtpAuth::new(serial, Zeroizing::new(secret), labels))
+}
+
+#[pymodule]
+fn bnet_auth_export(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = module.py();
+    module.add_class::<PyClient>()?;
+    module.add_function(wrap_pyfunction!(otpauth_uri, module)?)?;
+    module.add_function(wrap_pyfunction!(restore_code, module)?)?;
+    module.add_function(wrap_pyfunction!(code, module)?)?;
+    module.add_function(wrap_pyfunction!(normalize_session_token, module)?)?;
+
+    module.add("BnetError", py.get_type::<BnetError>())?;
+    module.add("InvalidInput", py.get_type::<InvalidInput>())?;
+    module.add("TokenExpired", py.get_type::<TokenExpired>())?;
+    module.add("SsoFailed", py.get_type::<SsoFailed>())?;
+    module.add("RestoreRejected", py.get_type::<RestoreRejected>())?;
+    module.add("RateLimited", py.get_type::<RateLimited>())?;
+    module.add("ApiError", py.get_type::<ApiError>())?;
+    module.add("NetworkError", py.get_type::<NetworkError>())?;
+    module.add("ParseError", py.get_type::<ParseError>())?;
+    Ok(())
+}
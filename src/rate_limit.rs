@@ -0,0 +1,253 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::secure_file;
+
+/// A token bucket that spaces out requests made from any number of threads.
+///
+/// The bucket holds up to `burst` tokens and refills at `per_minute` tokens a minute. Each
+/// request takes one, waiting for the next refill when the bucket is empty.
+pub struct TokenBucket {
+    capacity: f64,
+    per_second: f64,
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    /// Start with a full bucket, so the first `burst` requests go out right away.
+    pub fn new(per_minute: u32, burst: u32) -> Self {
+        let capacity = f64::from(burst.max(1));
+        Self {
+            capacity,
+            per_second: f64::from(per_minute.max(1)) / 60.0,
+            state: Mutex::new((capacity, Instant::now())),
+        }
+    }
+
+    /// Take a token, blocking until one is available.
+    pub fn acquire(&self) {
+        while let Err(wait) = self.try_acquire(Instant::now()) {
+            thread::sleep(wait);
+        }
+    }
+
+    /// Take a token if there is one, or return how long until there will be.
+    pub fn try_acquire(&self, now: Instant) -> Result<(), Duration> {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        let (tokens, refilled_at) = &mut *state;
+        let elapsed = now.saturating_duration_since(*refilled_at).as_secs_f64();
+        *tokens = (*tokens + elapsed * self.per_second).min(self.capacity);
+        *refilled_at = now.max(*refilled_at);
+
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - *tokens) / self.per_second))
+        }
+    }
+}
+
+/// A limit on restore attempts per minute that holds across runs, so retrying by hand doesn't
+/// trip Blizzard's abuse protection, which can lock the account for a while.
+///
+/// Attempts are recorded in a small file of timestamps, updated under a lock on a `.lock` file
+/// next to it so concurrent runs don't lose each other's. Without a file, or when it can't be
+/// read or written, the limit only covers the attempts of this process.
+pub struct AttemptLimit {
+    per_minute: u32,
+    path: Option<PathBuf>,
+    recent: Mutex<Vec<u64>>,
+}
+
+const WINDOW_MILLIS: u64 = 60_000;
+
+impl AttemptLimit {
+    /// Allow `per_minute` attempts in any minute, or any number when it is 0.
+    pub fn new(per_minute: u32, path: Option<PathBuf>) -> Self {
+        Self {
+            per_minute,
+            path,
+            recent: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Where attempts are kept by default: `bnet-auth-export/restore-attempts` in the user's
+    /// state directory.
+    pub fn default_path() -> Option<PathBuf> {
+        let home = || {
+            env::var_os("HOME")
+                .or_else(|| env::var_os("USERPROFILE"))
+                .map(PathBuf::from)
+        };
+        let dir = if cfg!(windows) {
+            env::var_os("LOCALAPPDATA").map(PathBuf::from)
+        } else if cfg!(target_os = "macos") {
+            home().map(|home| home.join("Library/Application Support"))
+        } else {
+            env::var_os("XDG_STATE_HOME")
+                .map(PathBuf::from)
+                .or_else(|| home().map(|home| home.join(".local/state")))
+        };
+        Some(dir?.join("bnet-auth-export").join("restore-attempts"))
+    }
+
+    /// Record an attempt, first waiting for one to fall out of the last minute if needed.
+    pub fn acquire(&self) {
+        loop {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_millis() as u64);
+            let Err(wait) = self.try_acquire(now) else {
+                return;
+            };
+            tracing::info!(
+                target: crate::NOTICES,
+                "Waiting {}s before the next restore attempt, to stay under {} a minute",
+                wait.as_secs().max(1),
+                self.per_minute
+            );
+            thread::sleep(wait);
+        }
+    }
+
+    /// Record an attempt at `now`, in milliseconds since the Unix epoch, or return how long
+    /// until one fits within the limit.
+    pub fn try_acquire(&self, now: u64) -> Result<(), Duration> {
+        if self.per_minute == 0 {
+            return Ok(());
+        }
+        let mut recent = self.recent.lock().unwrap_or_else(|err| err.into_inner());
+        let _locked = self.lock_file();
+        if let Some(saved) = self.load() {
+            *recent = saved;
+        }
+        recent.retain(|&at| at <= now && now - at < WINDOW_MILLIS);
+        recent.sort_unstable();
+
+        if recent.len() >= self.per_minute as usize {
+            let oldest = recent[recent.len() - self.per_minute as usize];
+            return Err(Duration::from_millis(WINDOW_MILLIS - (now - oldest)));
+        }
+        recent.push(now);
+        self.save(&recent);
+        Ok(())
+    }
+
+    // Held from reading the attempts to writing them back, released when dropped.
+    fn lock_file(&self) -> Option<File> {
+        let path = self.path.as_ref()?;
+        fs::create_dir_all(path.parent()?).ok()?;
+        let mut lock_path = path.clone().into_os_string();
+        lock_path.push(".lock");
+        let file = secure_file::private_options().open(lock_path).ok()?;
+        file.lock().ok()?;
+        Some(file)
+    }
+
+    fn load(&self) -> Option<Vec<u64>> {
+        let data = fs::read_to_string(self.path.as_ref()?).ok()?;
+        Some(
+            data.lines()
+                .filter_map(|line| line.trim().parse().ok())
+                .collect(),
+        )
+    }
+
+    // Best effort: replace the file in one rename, so a run interrupted while writing leaves the
+    // previous version.
+    fn save(&self, recent: &[u64]) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let data: String = recent.iter().map(|at| format!("{at}\n")).collect();
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(format!(".{}.tmp", process::id()));
+        let tmp = PathBuf::from(tmp);
+        let _ = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| {
+                secure_file::private_options()
+                    .truncate(true)
+                    .open(&tmp)?
+                    .write_all(data.as_bytes())
+            })
+            .and_then(|()| fs::rename(&tmp, path));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The attempts file at `name` in the temporary directory, and its lock, removed when dropped.
+    struct AttemptsFile(PathBuf);
+
+    impl AttemptsFile {
+        fn new(name: &str) -> Self {
+            let path = env::temp_dir().join(format!("{name}-{}", process::id()));
+            let _ = fs::remove_file(&path);
+            Self(path)
+        }
+    }
+
+    impl Drop for AttemptsFile {
+        fn drop(&mut self) {
+            let mut lock_path = self.0.clone().into_os_string();
+            lock_path.push(".lock");
+            let _ = fs::remove_file(&self.0);
+            let _ = fs::remove_file(lock_path);
+        }
+    }
+
+    #[test]
+    fn limits_attempts_across_runs() {
+        let file = AttemptsFile::new("bnet-restore-attempts");
+
+        let limit = AttemptLimit::new(2, Some(file.0.clone()));
+        assert!(limit.try_acquire(1_000).is_ok());
+        assert!(limit.try_acquire(21_000).is_ok());
+        assert_eq!(limit.try_acquire(31_000), Err(Duration::from_secs(30)));
+
+        // A later run sees the same attempts, until the first one is a minute old.
+        let limit = AttemptLimit::new(2, Some(file.0.clone()));
+        assert!(limit.try_acquire(40_000).is_err());
+        assert!(limit.try_acquire(61_000).is_ok());
+    }
+
+    #[test]
+    fn spaces_out_requests_past_the_burst() {
+        let bucket = TokenBucket::new(60, 2);
+        let start = Instant::now();
+        assert!(bucket.try_acquire(start).is_ok());
+        assert!(bucket.try_acquire(start).is_ok());
+        assert_eq!(bucket.try_acquire(start), Err(Duration::from_secs(1)));
+        assert!(bucket.try_acquire(start + Duration::from_secs(1)).is_ok());
+    }
+
+    #[test]
+    fn concurrent_runs_share_the_limit() {
+        let file = AttemptsFile::new("bnet-concurrent-attempts");
+
+        // Each thread stands for a run of its own, with nothing but the file in common.
+        let runs: Vec<_> = (0..8)
+            .map(|_| {
+                let path = file.0.clone();
+                thread::spawn(move || AttemptLimit::new(3, Some(path)).try_acquire(1_000).is_ok())
+            })
+            .collect();
+        let allowed = runs
+            .into_iter()
+            .map(|run| run.join().unwrap())
+            .filter(|&allowed| allowed)
+            .count();
+        assert_eq!(allowed, 3);
+    }
+}
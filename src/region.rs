@@ -0,0 +1,159 @@
+use std::fmt;
+
+use clap::ValueEnum;
+
+use crate::error::{Error, Result, invalid_input};
+use crate::paste;
+
+pub const GLOBAL_OAUTH_URL: &str = "https://oauth.battle.net/oauth";
+pub const CN_OAUTH_URL: &str = "https://oauth.battlenet.com.cn/oauth";
+pub const AUTH_BASE_URL: &str =
+    "https://authenticator-rest-api.bnet-identity.blizzard.net/v1/authenticator";
+
+const SERIAL_DIGITS: usize = 12;
+// The physical keyfob authenticators show a 10-digit serial such as 12-3456789-0 on the back.
+const KEYFOB_SERIAL_DIGITS: usize = 10;
+// How the serial is labelled on the Battle.net site and in the app, longest first.
+const SERIAL_LABELS: &[&str] = &["authenticator serial", "serial number", "serial"];
+
+/// The Battle.net region an account and its authenticator belong to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Region {
+    #[default]
+    Us,
+    Eu,
+    Kr,
+    Tw,
+    Cn,
+}
+
+impl Region {
+    /// Session tokens look like `EU-abc123...-1234`, the prefix is the account region.
+    pub fn from_session_token(token: &str) -> Option<Self> {
+        let (prefix, _) = token.split_once('-')?;
+        Self::from_code(prefix)
+    }
+
+    /// Serials look like `US-2101-2345-6789`, the prefix is the authenticator region.
+    pub fn from_serial(serial: &str) -> Option<Self> {
+        let prefix: String = serial
+            .chars()
+            .take_while(char::is_ascii_alphabetic)
+            .collect();
+        Self::from_code(&prefix)
+    }
+
+    fn from_code(code: &str) -> Option<Self> {
+        match code.to_ascii_uppercase().as_str() {
+            "US" => Some(Self::Us),
+            "EU" => Some(Self::Eu),
+            "KR" => Some(Self::Kr),
+            "TW" => Some(Self::Tw),
+            "CN" => Some(Self::Cn),
+            _ => None,
+        }
+    }
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::Us => "US",
+            Self::Eu => "EU",
+            Self::Kr => "KR",
+            Self::Tw => "TW",
+            Self::Cn => "CN",
+        }
+    }
+
+    /// China runs its own OAuth host, every other region shares the global one.
+    pub fn oauth_url(self) -> &'static str {
+        match self {
+            Self::Cn => CN_OAUTH_URL,
+            _ => GLOBAL_OAUTH_URL,
+        }
+    }
+
+    pub fn sso_url(self) -> String {
+        format!("{}/sso", self.oauth_url())
+    }
+
+    /// The authenticator REST API is shared by all regions.
+    pub fn auth_base_url(self) -> &'static str {
+        AUTH_BASE_URL
+    }
+
+    /// Bring a typed serial into the `US-2101-2345-6789` form the API expects. Spaces, dashes
+    /// and case don't matter, and neither do a pasted `Serial:` label or quotes. A serial
+    /// without a region prefix gets this region's. Serials of physical keyfobs are refused,
+    /// since their secrets can't be exported.
+    pub fn normalize_serial(self, input: &str) -> Result<String> {
+        let input = paste::clean(input, SERIAL_LABELS);
+        let compact: String = input
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .map(|c| c.to_ascii_uppercase())
+            .collect();
+        let digits_start = compact
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(compact.len());
+        let (prefix, digits) = compact.split_at(digits_start);
+        if digits.len() == KEYFOB_SERIAL_DIGITS && digits.chars().all(|c| c.is_ascii_digit()) {
+            invalid_input!(
+                "{} looks like the serial of a physical keyfob authenticator; their secret is built into the device and Battle.net never hands it out, so it can't be exported. Only the Battle.net mobile app's authenticator (serial like US-2101-2345-6789) can be",
+                input
+            );
+        }
+
+        let region = match prefix {
+            "" => self,
+            prefix => Self::from_code(prefix).ok_or_else(|| {
+                Error::InvalidInput(format!("unknown serial region prefix {prefix:?}"))
+            })?,
+        };
+        if digits.len() != SERIAL_DIGITS || !digits.chars().all(|c| c.is_ascii_digit()) {
+            invalid_input!(
+                "serials look like US-2101-2345-6789 (a region and {SERIAL_DIGITS} digits)"
+            );
+        }
+
+        Ok(format!(
+            "{}-{}-{}-{}",
+            region.code(),
+            &digits[..4],
+            &digits[4..8],
+            &digits[8..]
+        ))
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuses_keyfob_serials() {
+        let err = Region::Us.normalize_serial("12-3456789-0").unwrap_err();
+
+        assert!(matches!(err, Error::InvalidInput(_)), "{err}");
+        assert!(err.to_string().contains("physical keyfob"), "{err}");
+        assert_eq!(
+            Region::Eu.normalize_serial("2101 2345 6789").unwrap(),
+            "EU-2101-2345-6789"
+        );
+    }
+
+    #[test]
+    fn cleans_a_pasted_serial() {
+        assert_eq!(
+            Region::Us
+                .normalize_serial("Serial: \u{201c}EU-2101-2345-6789\u{201d}")
+                .unwrap(),
+            "EU-2101-2345-6789"
+        );
+    }
+}
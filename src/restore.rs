@@ -0,0 +1,125 @@
+use sha1::{Digest, Sha1};
+
+use crate::error::{Result, invalid_input};
+use crate::paste;
+
+// Restore codes use digits and uppercase letters, without the easily confused I, L, O and S.
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRTUVWXYZ";
+const LENGTH: usize = 10;
+
+/// Derive the restore code Blizzard shows for an authenticator: the last 10 bytes of
+/// SHA1(serial + secret), each mapped onto the restore code alphabet.
+pub fn restore_code(serial: &str, secret: &[u8]) -> String {
+    let serial: String = serial
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+
+    let mut hasher = Sha1::new();
+    hasher.update(serial.as_bytes());
+    hasher.update(secret);
+    let digest = hasher.finalize();
+
+    digest[digest.len() - LENGTH..]
+        .iter()
+        .map(|byte| char::from(ALPHABET[usize::from(byte & 0x1f)]))
+        .collect()
+}
+
+/// Check a typed restore code before spending a rate-limited restore attempt on it. Pasted
+/// labels, quotes and invisible characters are dropped first.
+pub fn normalize_restore_code(input: &str) -> Result<String> {
+    let code = compact(input);
+    if code.len() != LENGTH {
+        invalid_input!(
+            "restore codes are {LENGTH} characters long, got {}",
+            code.len()
+        );
+    }
+
+    let invalid: String = code.chars().filter(|&c| !in_alphabet(c)).collect();
+    if !invalid.is_empty() {
+        let hint = match correct_restore_code(&code) {
+            Some(corrected) => format!("; did you mean {corrected}?"),
+            None => " (1, 0 and 5 look similar)".to_owned(),
+        };
+        invalid_input!(
+            "restore codes only use digits and the letters A-Z without I, L, O and S, but {invalid:?} was entered{hint}"
+        );
+    }
+
+    Ok(code)
+}
+
+/// The restore code with the letters it never contains replaced by the digits they are mistaken
+/// for (O for 0, I and L for 1, S for 5), if that is all that made it invalid.
+pub fn correct_restore_code(input: &str) -> Option<String> {
+    let code = compact(input);
+    let corrected: String = code
+        .chars()
+        .map(|c| match c {
+            'O' => '0',
+            'I' | 'L' => '1',
+            'S' => '5',
+            c => c,
+        })
+        .collect();
+    (corrected != code && corrected.len() == LENGTH && corrected.chars().all(in_alphabet))
+        .then_some(corrected)
+}
+
+fn compact(input: &str) -> String {
+    paste::clean(input, &["restore code"])
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+fn in_alphabet(c: char) -> bool {
+    ALPHABET.iter().any(|&b| char::from(b) == c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_the_restore_code_blizzard_shows() {
+        let secret = b"12345678901234567890";
+        assert_eq!(restore_code("US-2101-2345-6789", secret), "RCFM9605YN");
+        // The serial is hashed without dashes, in uppercase.
+        assert_eq!(restore_code("us210123456789", secret), "RCFM9605YN");
+        assert_ne!(restore_code("EU-2101-2345-6789", secret), "RCFM9605YN");
+    }
+
+    #[test]
+    fn cleans_a_pasted_restore_code() {
+        assert_eq!(
+            normalize_restore_code("  Restore code:\u{a0}'ABCDE\u{200b}12345'\n").unwrap(),
+            "ABCDE12345"
+        );
+    }
+
+    #[test]
+    fn corrects_lookalikes() {
+        assert_eq!(
+            correct_restore_code("abcde-1234s").as_deref(),
+            Some("ABCDE12345")
+        );
+        assert_eq!(
+            correct_restore_code("OIL2345678").as_deref(),
+            Some("0112345678")
+        );
+        // Nothing to correct, or more wrong than lookalikes.
+        assert_eq!(correct_restore_code("ABCDE12345"), None);
+        assert_eq!(correct_restore_code("ABCDE1234#"), None);
+
+        let err = normalize_restore_code("ABCDE1234S").unwrap_err();
+        assert!(
+            err.to_string().contains("did you mean ABCDE12345?"),
+            "{err}"
+        );
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::hash_map::RandomState;
+use std::error::Error as _;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread;
+use std::time::Duration;
+
+use reqwest::StatusCode;
+#[cfg(not(target_arch = "wasm32"))]
+use reqwest::blocking::{RequestBuilder, Response};
+
+const BASE_DELAY: Duration = Duration::from_millis(500);
+const MAX_DELAY: Duration = Duration::from_secs(8);
+
+// How many times to retry a request that failed for a transient reason.
+#[derive(Clone, Copy)]
+pub struct RetryPolicy {
+    pub retries: u32,
+}
+
+impl RetryPolicy {
+    // Send the request built by `build`, retrying transient failures with jittered backoff.
+    //
+    // A 5xx response on the final attempt is returned as-is so the caller can report it.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn send(&self, build: impl Fn() -> RequestBuilder) -> reqwest::Result<Response> {
+        let mut attempt = 0;
+        loop {
+            let result = build().send();
+            let status = result.as_ref().map(Response::status);
+            let Some(delay) = self.retry_delay(attempt, status) else {
+                return result;
+            };
+            thread::sleep(delay);
+            attempt += 1;
+        }
+    }
+
+    // The async counterpart of `send`.
+    #[cfg(any(feature = "tokio", target_arch = "wasm32"))]
+    pub async fn send_async(
+        &self,
+        build: impl Fn() -> reqwest::RequestBuilder,
+    ) -> reqwest::Result<reqwest::Response> {
+        let mut attempt = 0;
+        loop {
+            let result = build().send().await;
+            let status = result.as_ref().map(reqwest::Response::status);
+            let Some(delay) = self.retry_delay(attempt, status) else {
+                return result;
+            };
+            #[cfg(not(target_arch = "wasm32"))]
+            tokio::time::sleep(delay).await;
+            #[cfg(target_arch = "wasm32")]
+            gloo_timers::future::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    // How long to wait before retrying after this outcome, or `None` to stop here.
+    fn retry_delay(
+        &self,
+        attempt: u32,
+        outcome: Result<StatusCode, &reqwest::Error>,
+    ) -> Option<Duration> {
+        let transient = match outcome {
+            Ok(status) => status.is_server_error(),
+            Err(err) => is_transient(err),
+        };
+        if !transient || attempt >= self.retries {
+            return None;
+        }
+
+        let delay = backoff(attempt);
+        let reason = match outcome {
+            Ok(status) => status.to_string(),
+            Err(err) => err.to_string(),
+        };
+        tracing::info!(
+            target: crate::NOTICES,
+            "request failed ({reason}), retrying in {:.1}s ({}/{})",
+            delay.as_secs_f64(),
+            attempt + 1,
+            self.retries
+        );
+        Some(delay)
+    }
+}
+
+// Timeouts, failed connects and dropped connections are worth another try.
+fn is_transient(err: &reqwest::Error) -> bool {
+    if err.is_timeout() {
+        return true;
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    if err.is_connect() {
+        return true;
+    }
+
+    let mut source = err.source();
+    while let Some(inner) = source {
+        if let Some(io_err) = inner.downcast_ref::<io::Error>() {
+            return matches!(
+                io_err.kind(),
+                io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::UnexpectedEof
+                    | io::ErrorKind::TimedOut
+            );
+        }
+        source = inner.source();
+    }
+
+    false
+}
+
+// Exponential backoff with full jitter, capped at MAX_DELAY.
+fn backoff(attempt: u32) -> Duration {
+    let ceiling = BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(MAX_DELAY);
+    let jitter = RandomState::new().build_hasher().finish() % 1000;
+    ceiling.mul_f64(0.5 + jitter as f64 / 2000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backs_off_exponentially_up_to_the_cap() {
+        for (attempt, ceiling) in [(0, 500), (1, 1000), (3, 4000), (4, 8000), (10, 8000)] {
+            let ceiling = Duration::from_millis(ceiling);
+            let delay = backoff(attempt);
+            assert!(
+                delay >= ceiling / 2 && delay < ceiling,
+                "{delay:?} for attempt {attempt}"
+            );
+        }
+    }
+
+    #[test]
+    fn retries_only_server_errors_while_attempts_are_left() {
+        let policy = RetryPolicy { retries: 2 };
+        let unavailable = Ok(StatusCode::SERVICE_UNAVAILABLE);
+        assert!(policy.retry_delay(0, unavailable).is_some());
+        assert!(policy.retry_delay(1, unavailable).is_some());
+        assert_eq!(policy.retry_delay(2, unavailable), None);
+        assert_eq!(
+            policy.retry_delay(0, Ok(StatusCode::TOO_MANY_REQUESTS)),
+            None
+        );
+        assert_eq!(policy.retry_delay(0, Ok(StatusCode::OK)), None);
+    }
+}
@@ -0,0 +1,133 @@
+// `rotate`: moving the account onto a new authenticator with a secret of its own, for leaving
+// the official app for good. The old authenticator is only detached once the new one is saved
+// and has produced a correct code, so a failure at any step leaves a working authenticator.
+
+use anyhow::{Context, Result, bail};
+
+use bnet_auth_export::api::Api;
+use bnet_auth_export::otpauth::LabelOptions;
+use bnet_auth_export::totp;
+
+use crate::devices;
+use crate::format::OutputOptions;
+use crate::source::{self, Credential, TokenOptions};
+use crate::timings;
+
+pub fn run(
+    token: &TokenOptions,
+    api: &Api,
+    labels: &LabelOptions,
+    output: &OutputOptions,
+    yes: bool,
+) -> Result<()> {
+    output.require_destination()?;
+    if !crate::interactive() {
+        bail!(
+            "rotate needs a terminal: the new authenticator's code is checked before the old one is detached"
+        );
+    }
+
+    // The restore proves the old restore code is right before anything changes.
+    let mut credential = source::credential(token, api, false)?;
+    let (device, listed_bearer_token) = devices::choose(api, &credential, false)?;
+    let (old_serial, old_restore_code) = crate::prompt_account(
+        api,
+        credential.session_token(),
+        device.map(|device| device.serial),
+        None,
+    )?;
+    if let Some(bearer_token) = listed_bearer_token {
+        credential = Credential::BearerToken(bearer_token);
+    }
+    let restored =
+        crate::fetch_device_secret(api, credential, &old_serial, &old_restore_code, |_| Ok(()));
+    crate::audit("restore", Some(&old_serial), &restored);
+    let (_, bearer_token) = restored?;
+
+    eprintln!(
+        "\nThis attaches a new authenticator to the account and saves its secret, then detaches {old_serial} once a code from the new one checks out. Codes from {old_serial}, e.g. in the official app, stop working then."
+    );
+    if !yes {
+        let answer = crate::prompt("Continue? [y/N]: ")?;
+        if !answer.eq_ignore_ascii_case("y") && !answer.eq_ignore_ascii_case("yes") {
+            bail!("cancelled, nothing was changed");
+        }
+    }
+
+    let attached =
+        timings::time("attach", || api.attach(&bearer_token)).map_err(anyhow::Error::from);
+    crate::audit(
+        "attach",
+        attached.as_ref().ok().map(|new| new.serial.as_str()),
+        &attached,
+    );
+    let new = attached.context("failed to attach a new authenticator, nothing was changed")?;
+    eprintln!("\nAttached {}.", new.serial);
+    let export = crate::build_export(
+        api,
+        &new.serial,
+        &new.restore_code,
+        &new.device_secret,
+        &bearer_token,
+        labels,
+    )?;
+    // Shown before anything else can fail, and again after the screen is cleared: the restore
+    // code is the way back in.
+    let show_restore_code = || {
+        eprintln!(
+            "The restore code of {} is {}. Keep it somewhere safe, it is needed to restore or detach it.",
+            new.serial, new.restore_code
+        )
+    };
+    show_restore_code();
+    let revealed = !output.write(&export)?;
+    if revealed {
+        crate::print_export(&export);
+    }
+    let clear = || {
+        if revealed {
+            crate::clear_revealed_secret();
+            show_restore_code();
+        }
+    };
+
+    let clock_offset = api.clock_offset().unwrap_or_default();
+    println!("\nAdd the new secret to your authenticator app, then enter the code it shows.");
+    loop {
+        let input = crate::prompt(&format!(
+            "Code (leave empty to keep {old_serial} attached for now): "
+        ))?;
+        if input.is_empty() {
+            clear();
+            eprintln!(
+                "{old_serial} is still attached, alongside {}. Both work until one is detached.",
+                new.serial
+            );
+            return Ok(());
+        }
+        if totp::verify(&export.secret, &input, totp::now_with_offset(clock_offset)) {
+            clear();
+            break;
+        }
+        eprintln!(
+            "That code does not match. Check the secret was entered correctly and that the app uses 8 digits, SHA1 and a 30 second period."
+        );
+    }
+
+    let detached = timings::time("detach", || {
+        api.detach(&bearer_token, &old_serial, &old_restore_code)
+    })
+    .map_err(anyhow::Error::from);
+    crate::audit("detach", Some(&old_serial), &detached);
+    detached.with_context(|| {
+        format!(
+            "the code matches, but detaching {old_serial} failed; it is still attached, alongside {}",
+            new.serial
+        )
+    })?;
+    println!(
+        "Verified, and detached {old_serial}. The account now uses {}.",
+        new.serial
+    );
+    Ok(())
+}
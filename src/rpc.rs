@@ -0,0 +1,210 @@
+// JSON-RPC 2.0 over stdin and stdout, one message per line, for frontends that keep the tool
+// running as a subprocess.
+
+use std::io::{self, BufRead, Write};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use zeroize::Zeroizing;
+
+use bnet_auth_export::Error;
+use bnet_auth_export::api::Api;
+use bnet_auth_export::otpauth::{LabelOptions, OtpAuth};
+use bnet_auth_export::region::Region;
+use bnet_auth_export::{restore, token, totp};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+// Failures of the operation itself, with the kind of failure in `data.kind`.
+const OPERATION_FAILED: i64 = -32000;
+
+#[derive(Deserialize)]
+struct Request {
+    jsonrpc: Option<String>,
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExchangeParams {
+    session_token: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RestoreParams {
+    bearer_token: String,
+    serial: String,
+    restore_code: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ConvertParams {
+    serial: String,
+    device_secret: String,
+    label: Option<String>,
+    issuer: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CodeParams {
+    secret: String,
+    time: Option<u64>,
+}
+
+struct RpcError {
+    code: i64,
+    message: String,
+    data: Option<Value>,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+}
+
+impl From<Error> for RpcError {
+    fn from(err: Error) -> Self {
+        let kind = err.kind();
+        Self {
+            code: OPERATION_FAILED,
+            message: format!("{:#}", anyhow::Error::from(err)),
+            data: Some(json!({ "kind": kind })),
+        }
+    }
+}
+
+// Answer requests until stdin is closed.
+pub fn serve(api: &Api, labels: &LabelOptions) -> Result<()> {
+    let stdin = io::stdin().lock();
+    let mut stdout = io::stdout().lock();
+
+    for line in stdin.lines() {
+        let line = Zeroizing::new(line.context("failed to read from stdin")?);
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Some(response) = handle(api, labels, &line) else {
+            continue;
+        };
+        writeln!(stdout, "{response}").context("failed to write to stdout")?;
+        stdout.flush().context("failed to flush stdout")?;
+    }
+
+    Ok(())
+}
+
+// The response to one line of input, or `None` for a notification.
+fn handle(api: &Api, labels: &LabelOptions, line: &str) -> Option<Value> {
+    let request: Request = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(err) => {
+            let code = if serde_json::from_str::<Value>(line).is_ok() {
+                INVALID_REQUEST
+            } else {
+                PARSE_ERROR
+            };
+            return Some(error_response(
+                Value::Null,
+                RpcError::new(code, err.to_string()),
+            ));
+        }
+    };
+
+    let id = request.id.clone();
+    let result = if request.jsonrpc.as_deref() == Some("2.0") {
+        call(api, labels, &request.method, request.params)
+    } else {
+        Err(RpcError::new(INVALID_REQUEST, "jsonrpc must be \"2.0\""))
+    };
+
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(err) => error_response(id, err),
+    })
+}
+
+fn call(api: &Api, labels: &LabelOptions, method: &str, params: Value) -> Result<Value, RpcError> {
+    match method {
+        "exchangeToken" => {
+            let params: ExchangeParams = parse_params(params)?;
+            let session_token = Zeroizing::new(params.session_token);
+            let session_token = Zeroizing::new(token::normalize_session_token(&session_token));
+            let region = api.region_for(Some(&session_token));
+            let bearer_token = api.exchange_session_token(&session_token)?;
+            Ok(json!({ "bearerToken": *bearer_token, "region": region.code() }))
+        }
+        "restore" => {
+            let params: RestoreParams = parse_params(params)?;
+            let bearer_token = Zeroizing::new(params.bearer_token);
+            let serial = api.region_for(None).normalize_serial(&params.serial)?;
+            let restore_code = restore::normalize_restore_code(&params.restore_code)?;
+            let device_secret = api.device_secret(&bearer_token, &serial, &restore_code)?;
+            Ok(json!({ "serial": serial, "deviceSecret": *device_secret }))
+        }
+        "convert" => {
+            let params: ConvertParams = parse_params(params)?;
+            let device_secret = Zeroizing::new(params.device_secret);
+            let serial = Region::from_serial(&params.serial)
+                .unwrap_or_default()
+                .normalize_serial(&params.serial)?;
+            let secret = hex::decode(device_secret.trim()).map_err(|err| Error::Parse {
+                what: "deviceSecret".to_owned(),
+                detail: err.to_string(),
+            })?;
+
+            let mut export = OtpAuth::new(serial.clone(), Zeroizing::new(secret), labels);
+            export.relabel(&LabelOptions {
+                label: params.label,
+                issuer: params.issuer,
+                label_battletag: false,
+            });
+            Ok(json!({
+                "uri": *export.to_uri(),
+                "base32Secret": *export.base32_secret(),
+                "restoreCode": restore::restore_code(&serial, &export.secret),
+            }))
+        }
+        "generateCode" => {
+            let params: CodeParams = parse_params(params)?;
+            let secret = totp::parse_secret(&Zeroizing::new(params.secret))?;
+            let time = params
+                .time
+                .unwrap_or_else(|| totp::now_with_offset(api.clock_offset().unwrap_or_default()));
+            Ok(json!({
+                "code": totp::code(&secret, time),
+                "remaining": totp::remaining(time),
+            }))
+        }
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("unknown method {method}"),
+        )),
+    }
+}
+
+fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|err| RpcError::new(INVALID_PARAMS, err.to_string()))
+}
+
+fn error_response(id: Value, err: RpcError) -> Value {
+    let mut error = json!({ "code": err.code, "message": err.message });
+    if let Some(data) = err.data {
+        error["data"] = data;
+    }
+    json!({ "jsonrpc": "2.0", "id": id, "error": error })
+}
@@ -0,0 +1,187 @@
+// Password-based encryption for backups and checkpoint files: Argon2id stretches the password,
+// AES-256-GCM encrypts and authenticates. A sealed file is a format-specific header, then the
+// salt, the nonce and the ciphertext with its tag, which is also Stratum's backup layout.
+
+use std::io;
+#[cfg(feature = "line-editing")]
+use std::io::IsTerminal;
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::{Context, Result, anyhow, bail};
+use argon2::{Algorithm, Argon2, Params, Version};
+use zeroize::Zeroizing;
+
+pub const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+// Argon2id's cost: 64 MiB, 3 passes, 4 lanes, as Stratum uses.
+pub const MEMORY_KIB: u32 = 64 * 1024;
+pub const ITERATIONS: u32 = 3;
+pub const PARALLELISM: u32 = 4;
+
+// A key derived from a password, kept to seal the same file again without another slow
+// derivation. Every seal still gets a fresh nonce.
+pub struct Key {
+    salt: [u8; SALT_LEN],
+    key: Zeroizing<[u8; 32]>,
+}
+
+impl Key {
+    // A key with a new random salt.
+    pub fn new(password: &[u8]) -> Result<Self> {
+        Self::derive(password, new_salt()?)
+    }
+
+    // The key `input` was sealed with, if `password` is the right one.
+    pub fn for_sealed(header: &[u8], input: &[u8], password: &[u8], what: &str) -> Result<Self> {
+        let (salt, _) = split(header, input, what)?;
+        Self::derive(password, salt.try_into().expect("salt length"))
+    }
+
+    fn derive(password: &[u8], salt: [u8; SALT_LEN]) -> Result<Self> {
+        let key = derive_key(password, &salt, MEMORY_KIB, ITERATIONS, PARALLELISM)?;
+        Ok(Self { salt, key })
+    }
+
+    pub fn seal(&self, header: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+        let mut nonce = [0; NONCE_LEN];
+        random(&mut nonce)?;
+        let ciphertext = Aes256Gcm::new((&*self.key).into())
+            .encrypt(Nonce::from_slice(&nonce), plaintext)
+            .ok()
+            .context("failed to encrypt")?;
+
+        Ok([header, &self.salt, &nonce, &ciphertext].concat())
+    }
+
+    pub fn open(&self, header: &[u8], input: &[u8], what: &str) -> Result<Zeroizing<Vec<u8>>> {
+        let (_, input) = split(header, input, what)?;
+        let (nonce, ciphertext) = input.split_at(NONCE_LEN);
+        Aes256Gcm::new((&*self.key).into())
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map(Zeroizing::new)
+            .ok()
+            .with_context(|| format!("failed to decrypt the {what}, the password may be wrong"))
+    }
+}
+
+// A 256-bit key from a password with Argon2id at the given cost. Files that record their cost,
+// like `backup`, pass it back in, so older files still open when the default goes up.
+pub fn derive_key(
+    password: &[u8],
+    salt: &[u8],
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+) -> Result<Zeroizing<[u8; 32]>> {
+    let params = Params::new(memory_kib, iterations, parallelism, Some(32))
+        .map_err(|err| anyhow!("invalid key derivation parameters: {err}"))?;
+    let mut key = Zeroizing::new([0; 32]);
+    crate::timings::time("key derivation", || {
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(password, salt, &mut *key)
+    })
+    .ok()
+    .context("failed to derive the key")?;
+    Ok(key)
+}
+
+pub fn new_salt() -> Result<[u8; SALT_LEN]> {
+    let mut salt = [0; SALT_LEN];
+    random(&mut salt)?;
+    Ok(salt)
+}
+
+pub fn seal(header: &[u8], plaintext: &[u8], password: &[u8]) -> Result<Vec<u8>> {
+    Key::new(password)?.seal(header, plaintext)
+}
+
+// `what` names the kind of file in errors, e.g. "encrypted Stratum backup".
+pub fn open(
+    header: &[u8],
+    input: &[u8],
+    password: &[u8],
+    what: &str,
+) -> Result<Zeroizing<Vec<u8>>> {
+    Key::for_sealed(header, input, password, what)?.open(header, input, what)
+}
+
+// The salt, and the nonce with the ciphertext.
+fn split<'a>(header: &[u8], input: &'a [u8], what: &str) -> Result<(&'a [u8], &'a [u8])> {
+    let Some(input) = input.strip_prefix(header) else {
+        bail!("not a valid {what}");
+    };
+    if input.len() < SALT_LEN + NONCE_LEN {
+        bail!("the {what} is too short");
+    }
+    Ok(input.split_at(SALT_LEN))
+}
+
+fn random(buf: &mut [u8]) -> Result<()> {
+    getrandom::getrandom(buf).map_err(|err| anyhow!("failed to generate random bytes: {err}"))
+}
+
+// The password from `env`, or asked for on stderr so the prompt stays out of an export written
+// to stdout. Only new passwords are asked for twice, and piped ones are read once, silently.
+pub fn password(label: &str, env: &str, confirm: bool) -> Result<Zeroizing<String>> {
+    if let Ok(password) = std::env::var(env) {
+        return Ok(Zeroizing::new(password));
+    }
+    if !crate::interactive() {
+        let password = prompt_stderr("", env)?;
+        if password.is_empty() {
+            bail!("the piped {} is empty", label.to_lowercase());
+        }
+        return Ok(password);
+    }
+
+    loop {
+        let password = prompt_stderr(&format!("{label}: "), env)?;
+        if password.is_empty() {
+            eprintln!("The password cannot be empty.");
+            continue;
+        }
+        if !confirm || *prompt_stderr("Repeat the password: ", env)? == *password {
+            return Ok(password);
+        }
+        eprintln!("The passwords do not match.");
+    }
+}
+
+fn prompt_stderr(label: &str, env: &str) -> Result<Zeroizing<String>> {
+    #[cfg(feature = "line-editing")]
+    if io::stdin().is_terminal() && io::stderr().is_terminal() && !crate::plain() {
+        return crate::line_edit::read_line(label, true)?.with_context(|| {
+            format!("no password entered, set {env} when stdin is not a terminal")
+        });
+    }
+    if !label.is_empty() {
+        eprint!("{label}");
+    }
+    let mut input = Zeroizing::new(String::new());
+    let read = io::stdin()
+        .read_line(&mut input)
+        .context("failed to read input")?;
+    if read == 0 {
+        bail!("no password entered, set {env} when stdin is not a terminal");
+    }
+    Ok(Zeroizing::new(
+        input.trim_end_matches(['\r', '\n']).to_owned(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_with_the_password_only() {
+        let sealed = seal(b"TEST", b"{}", b"hunter2").unwrap();
+
+        assert!(sealed.starts_with(b"TEST"));
+        assert_eq!(*open(b"TEST", &sealed, b"hunter2", "test").unwrap(), b"{}");
+        assert!(open(b"TEST", &sealed, b"hunter3", "test").is_err());
+        assert!(open(b"OTHER", &sealed, b"hunter2", "test").is_err());
+    }
+}
@@ -0,0 +1,97 @@
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use data_encoding::HEXLOWER;
+
+/// Options for a file only the current user can read, for anything that may hold secrets.
+///
+/// Add `create_new`, `truncate` or `append` as the file needs.
+pub fn private_options() -> OpenOptions {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+}
+
+// The temp files not dropped yet, for `remove_temp_files`.
+static LIVE: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// A private file in the temp directory that is overwritten and removed when dropped,
+/// including while unwinding from a panic.
+pub struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    /// Create a new, empty file named `bnet-auth-export-<random>-<name>`.
+    pub fn create(name: &str) -> io::Result<(Self, File)> {
+        let mut random = [0; 8];
+        getrandom::getrandom(&mut random).map_err(|err| io::Error::other(err.to_string()))?;
+        let path = env::temp_dir().join(format!(
+            "bnet-auth-export-{}-{name}",
+            HEXLOWER.encode(&random)
+        ));
+        Self::create_at(path)
+    }
+
+    /// Create a file next to this one with `suffix` appended to the name, for companion files
+    /// such as a database's `-wal`.
+    pub fn sibling(&self, suffix: &str) -> io::Result<(Self, File)> {
+        let mut path = self.path.clone().into_os_string();
+        path.push(suffix);
+        Self::create_at(PathBuf::from(path))
+    }
+
+    fn create_at(path: PathBuf) -> io::Result<(Self, File)> {
+        let file = private_options().create_new(true).open(&path)?;
+        live().push(path.clone());
+        Ok((Self { path }, file))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        // Best effort: on SSDs and copy-on-write filesystems the old blocks may survive.
+        let _ = shred(&self.path);
+        let _ = fs::remove_file(&self.path);
+        live().retain(|path| *path != self.path);
+    }
+}
+
+/// Overwrite and remove every temp file still open, as their destructors would.
+///
+/// For a process about to exit without running destructors, e.g. on a signal.
+pub fn remove_temp_files() {
+    for path in live().drain(..) {
+        let _ = shred(&path);
+        let _ = fs::remove_file(&path);
+    }
+}
+
+fn live() -> std::sync::MutexGuard<'static, Vec<PathBuf>> {
+    LIVE.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+fn shred(path: &Path) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    let mut remaining = file.metadata()?.len();
+    file.rewind()?;
+    let zeros = [0; 8192];
+    while remaining > 0 {
+        let chunk = remaining.min(zeros.len() as u64) as usize;
+        file.write_all(&zeros[..chunk])?;
+        remaining -= chunk as u64;
+    }
+    file.sync_all()
+}
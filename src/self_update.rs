@@ -0,0 +1,358 @@
+// `self-update`: replace this binary with the latest GitHub release, after checking the download
+// against the release's SHA256SUMS. Runs that talk to Battle.net also look the release up in the
+// background, to warn when it follows a change in Battle.net's API.
+
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use reqwest::blocking::Client;
+use reqwest::header::ACCEPT;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use bnet_auth_export::api::ApiOptions;
+
+pub const RELEASES_URL: &str =
+    "https://api.github.com/repos/casperstorm/bnet-auth-export/releases/latest";
+const CHECKSUMS: &str = "SHA256SUMS";
+// Written by the release workflow from api-changes.json in the repository.
+const API_CHANGES: &str = "api-changes.json";
+// Release binaries are a few MB, more than the API timeout allows on a slow connection.
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(300);
+// How long a run waits at the end for an update check that hasn't finished yet.
+const CHECK_WAIT: Duration = Duration::from_secs(2);
+
+#[derive(Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    pub html_url: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+// A release that follows a change in Battle.net's API, which older versions break on.
+#[derive(Deserialize)]
+struct ApiChange {
+    version: String,
+    change: String,
+}
+
+impl Release {
+    // The release's version, without the `v` of the tag.
+    pub fn version(&self) -> &str {
+        self.tag_name.trim_start_matches('v')
+    }
+
+    fn asset(&self, name: &str) -> Result<&Asset> {
+        self.assets
+            .iter()
+            .find(|asset| asset.name == name)
+            .with_context(|| format!("release {} has no {name}", self.tag_name))
+    }
+}
+
+// The latest published release.
+pub fn latest(client: &Client, url: &str) -> Result<Release> {
+    client
+        .get(url)
+        .header(ACCEPT, "application/vnd.github+json")
+        .send()
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.json())
+        .context("failed to look up the latest release")
+}
+
+// Whether `version` is newer than `current`, comparing dotted numbers. Anything that doesn't
+// parse is never newer.
+pub fn is_newer(version: &str, current: &str) -> bool {
+    fn parse(version: &str) -> Option<Vec<u64>> {
+        version
+            .split('.')
+            .map(|part| part.parse().ok())
+            .collect::<Option<_>>()
+    }
+    match (parse(version), parse(current)) {
+        (Some(version), Some(current)) => version > current,
+        _ => false,
+    }
+}
+
+// A client of its own, verifying certificates against the TLS stack's own roots whatever
+// --insecure and --ca-bundle say: SHA256SUMS comes over the same connection as the binary, so it
+// only proves anything when nobody on that connection can stand in for GitHub. The timeouts and
+// --proxy are taken from `options`; a proxy only ever sees the TLS stream.
+fn client(options: &ApiOptions) -> Result<Client> {
+    let mut builder = Client::builder()
+        .user_agent(concat!("bnet-auth-export/", env!("CARGO_PKG_VERSION")))
+        .connect_timeout(Duration::from_secs(options.connect_timeout))
+        .timeout(Duration::from_secs(options.timeout))
+        .tls_built_in_root_certs(true);
+    #[cfg(feature = "rustls")]
+    {
+        builder = builder.use_rustls_tls();
+    }
+    if let Some(proxy) = &options.proxy {
+        let proxy =
+            reqwest::Proxy::all(proxy).with_context(|| format!("invalid proxy URL {proxy}"))?;
+        builder = builder.proxy(proxy);
+    }
+    builder
+        .build()
+        .context("failed to set up the HTTP client for GitHub")
+}
+
+pub fn run(options: &ApiOptions, url: &str, check: bool, yes: bool) -> Result<()> {
+    let client = client(options)?;
+    let release = latest(&client, url)?;
+    let current = env!("CARGO_PKG_VERSION");
+    if !is_newer(release.version(), current) {
+        println!("bnet-auth-export {current} is up to date.");
+        return Ok(());
+    }
+    println!(
+        "bnet-auth-export {} is available (this is {current}): {}",
+        release.version(),
+        release.html_url
+    );
+    if check {
+        return Ok(());
+    }
+
+    let Some(name) = asset_name() else {
+        bail!(
+            "there is no release build for this platform, update from source with `cargo install --git https://github.com/casperstorm/bnet-auth-export`"
+        );
+    };
+    let checksums = download(&client, release.asset(CHECKSUMS)?)?;
+    let expected = checksum_for(&String::from_utf8_lossy(&checksums), name).with_context(|| {
+        format!(
+            "{CHECKSUMS} of release {} lists no checksum for {name}, not installing it",
+            release.tag_name
+        )
+    })?;
+
+    let current_exe = env::current_exe().context("failed to find this binary")?;
+    if !yes {
+        let answer = crate::prompt(&format!(
+            "Replace {} with {}? [y/N]: ",
+            current_exe.display(),
+            release.version()
+        ))?;
+        if !answer.eq_ignore_ascii_case("y") && !answer.eq_ignore_ascii_case("yes") {
+            bail!("update cancelled");
+        }
+    }
+
+    eprintln!("Downloading {name}...");
+    let binary = download(&client, release.asset(name)?)?;
+    let actual = hex::encode(Sha256::digest(&binary));
+    if !actual.eq_ignore_ascii_case(&expected) {
+        bail!("{name} does not match its checksum in {CHECKSUMS}, not installing it");
+    }
+
+    replace(&current_exe, &binary)?;
+    println!("Updated to bnet-auth-export {}.", release.version());
+    Ok(())
+}
+
+pub struct UpdateCheck(mpsc::Receiver<Result<Vec<String>>>);
+
+// Look the latest release up on another thread, so the run doesn't wait for GitHub.
+pub fn start_check(options: &ApiOptions, url: &str) -> UpdateCheck {
+    let (sender, receiver) = mpsc::channel();
+    let client = client(options);
+    let url = url.to_owned();
+    thread::spawn(move || {
+        let _ = sender.send(client.and_then(|client| check(&client, &url)));
+    });
+    UpdateCheck(receiver)
+}
+
+impl UpdateCheck {
+    // Print what the check found. A check that fails or takes too long says nothing, since it
+    // is no reason to fail or slow down the run.
+    pub fn report(self) {
+        let Ok(Ok(warnings)) = self.0.recv_timeout(CHECK_WAIT) else {
+            return;
+        };
+        for warning in warnings {
+            eprintln!("warning: {warning}");
+        }
+    }
+}
+
+// Warnings for every API change the latest release follows and this version predates.
+fn check(client: &Client, url: &str) -> Result<Vec<String>> {
+    let release = latest(client, url)?;
+    let current = env!("CARGO_PKG_VERSION");
+    if !is_newer(release.version(), current) {
+        return Ok(Vec::new());
+    }
+    // Releases from before the list existed don't have it.
+    let Ok(asset) = release.asset(API_CHANGES) else {
+        return Ok(Vec::new());
+    };
+    let changes: Vec<ApiChange> = serde_json::from_slice(&download(client, asset)?)
+        .with_context(|| format!("failed to parse {API_CHANGES}"))?;
+    Ok(missing_changes(&changes, current)
+        .map(|change| {
+            format!(
+                "Battle.net changed its API ({}), which this version {current} doesn't handle. Failures in this run may come from that rather than your input; bnet-auth-export {} and newer follow the change, update with `bnet-auth-export self-update`.",
+                change.change, change.version
+            )
+        })
+        .collect())
+}
+
+fn missing_changes<'a>(
+    changes: &'a [ApiChange],
+    current: &'a str,
+) -> impl Iterator<Item = &'a ApiChange> {
+    changes
+        .iter()
+        .filter(move |change| is_newer(&change.version, current))
+}
+
+// The bare binary the release workflow uploads for this platform.
+fn asset_name() -> Option<&'static str> {
+    if cfg!(all(
+        target_os = "linux",
+        target_arch = "x86_64",
+        target_env = "musl"
+    )) {
+        Some("bnet-auth-export-linux-x86_64-musl")
+    } else if cfg!(all(target_os = "linux", target_arch = "x86_64")) {
+        Some("bnet-auth-export-linux-x86_64")
+    } else if cfg!(all(target_os = "macos", target_arch = "aarch64")) {
+        Some("bnet-auth-export-macos-aarch64")
+    } else if cfg!(all(target_os = "windows", target_arch = "x86_64")) {
+        Some("bnet-auth-export-windows-x86_64.exe")
+    } else {
+        None
+    }
+}
+
+fn download(client: &Client, asset: &Asset) -> Result<Vec<u8>> {
+    client
+        .get(&asset.browser_download_url)
+        .header(ACCEPT, "application/octet-stream")
+        .timeout(DOWNLOAD_TIMEOUT)
+        .send()
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.bytes())
+        .map(|bytes| bytes.to_vec())
+        .with_context(|| format!("failed to download {}", asset.name))
+}
+
+// The checksum for `name` in `sha256sum` output. A line for it that isn't a SHA-256 counts as
+// none, so a damaged list can't let a download through unchecked.
+fn checksum_for(checksums: &str, name: &str) -> Option<String> {
+    checksums.lines().find_map(|line| {
+        let (checksum, file) = line.split_once(char::is_whitespace)?;
+        // `sha256sum -b` marks binary files with `*`.
+        (file.trim_start().trim_start_matches('*') == name
+            && checksum.len() == 64
+            && checksum.bytes().all(|byte| byte.is_ascii_hexdigit()))
+        .then(|| checksum.to_owned())
+    })
+}
+
+// Write the new binary next to the current one, check it runs, then swap it in. Windows can't
+// overwrite a running executable but can rename it, so the old one is moved aside there.
+fn replace(current_exe: &Path, binary: &[u8]) -> Result<()> {
+    let staged = sibling(current_exe, ".new");
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o755);
+    }
+    options
+        .open(&staged)
+        .and_then(|mut file| file.write_all(binary))
+        .with_context(|| format!("failed to write {}", staged.display()))?;
+
+    let runs = Command::new(&staged)
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| output.status.success());
+    if !runs {
+        let _ = fs::remove_file(&staged);
+        bail!("the downloaded binary doesn't run on this system, not installing it");
+    }
+
+    #[cfg(windows)]
+    {
+        let old = sibling(current_exe, ".old");
+        let _ = fs::remove_file(&old);
+        fs::rename(current_exe, &old)
+            .with_context(|| format!("failed to move {} aside", current_exe.display()))?;
+    }
+    fs::rename(&staged, current_exe)
+        .with_context(|| format!("failed to replace {}", current_exe.display()))
+}
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_versions_numerically() {
+        assert!(is_newer("0.10.0", "0.9.1"));
+        assert!(is_newer("1.0", "0.9.9"));
+        assert!(!is_newer("0.1.0", "0.1.0"));
+        assert!(!is_newer("0.2.0-rc.1", "0.1.0"));
+    }
+
+    #[test]
+    fn warns_only_about_changes_after_this_version() {
+        let changes: Vec<ApiChange> = serde_json::from_str(
+            r#"[{"version":"0.1.0","change":"old"},{"version":"0.3.0","change":"new"}]"#,
+        )
+        .unwrap();
+
+        let missing: Vec<_> = missing_changes(&changes, "0.2.0")
+            .map(|change| change.change.as_str())
+            .collect();
+        assert_eq!(missing, ["new"]);
+    }
+
+    #[test]
+    fn finds_checksums_in_sha256sum_output() {
+        let linux = "ab".repeat(32);
+        let windows = "cd".repeat(32);
+        let checksums = format!(
+            "{linux}  bnet-auth-export-linux-x86_64\n{windows} *bnet-auth-export-windows-x86_64.exe\nabc123  bnet-auth-export-macos-aarch64\n"
+        );
+
+        assert_eq!(
+            checksum_for(&checksums, "bnet-auth-export-windows-x86_64.exe"),
+            Some(windows)
+        );
+        assert_eq!(checksum_for(&checksums, "bnet-auth-export-linux"), None);
+        // Listed, but not with a checksum that could be checked.
+        assert_eq!(
+            checksum_for(&checksums, "bnet-auth-export-macos-aarch64"),
+            None
+        );
+    }
+}
@@ -0,0 +1,198 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::Result;
+use bnet_auth_export::api::Api;
+use bnet_auth_export::har;
+use bnet_auth_export::token::{looks_like_session_token, normalize_session_token};
+use clap::Args;
+use zeroize::Zeroizing;
+
+#[cfg(feature = "browser")]
+use crate::browser::{self, Browser};
+#[cfg(feature = "capture")]
+use crate::capture;
+#[cfg(feature = "clipboard")]
+use crate::clipboard;
+use crate::{login, oauth, prompt};
+
+// How old a session token may be before it is probably no longer accepted.
+const STALE_AFTER: Duration = Duration::from_secs(5 * 60);
+
+// Where to get the session token from, when not prompting for it.
+#[derive(Args)]
+pub struct TokenOptions {
+    /// Read the session token from a local browser profile instead of prompting for it.
+    #[cfg(feature = "browser")]
+    #[arg(long, value_enum, value_name = "BROWSER")]
+    pub from_browser: Option<Browser>,
+
+    /// Read the session token from a HAR file saved from the browser's network tab.
+    #[arg(long, value_name = "FILE")]
+    pub har: Option<PathBuf>,
+
+    /// Capture the session token with a local intercepting proxy while you log in.
+    #[cfg(feature = "capture")]
+    #[arg(
+        long,
+        value_name = "ADDR",
+        num_args = 0..=1,
+        default_missing_value = "127.0.0.1:8080"
+    )]
+    pub capture_proxy: Option<String>,
+
+    /// Try the OAuth authorization-code flow in the browser first, without a session token.
+    #[arg(long)]
+    pub oauth: bool,
+
+    /// Seconds to wait for the OAuth redirect before falling back to the session token.
+    #[arg(long, default_value_t = 180, requires = "oauth")]
+    pub oauth_timeout: u64,
+
+    /// Wait for a session token to be copied to the clipboard instead of prompting for it.
+    #[cfg(feature = "clipboard")]
+    #[arg(long)]
+    pub watch_clipboard: bool,
+}
+
+// What the restore request is authorized with.
+#[derive(Clone)]
+pub enum Credential {
+    // A session token that still has to go through the SSO exchange.
+    SessionToken(Zeroizing<String>),
+    // A bearer token from the OAuth authorization-code flow.
+    BearerToken(Zeroizing<String>),
+}
+
+impl Credential {
+    pub fn session_token(&self) -> Option<&str> {
+        match self {
+            Self::SessionToken(token) => Some(token),
+            Self::BearerToken(_) => None,
+        }
+    }
+}
+
+// Obtain a credential, trying OAuth first when asked to.
+pub fn credential(options: &TokenOptions, api: &Api, guided_login: bool) -> Result<Credential> {
+    if options.oauth
+        && let Some(token) = oauth::bearer_token(api, Duration::from_secs(options.oauth_timeout))?
+    {
+        return Ok(Credential::BearerToken(token));
+    }
+
+    session_token(options, guided_login).map(Credential::SessionToken)
+}
+
+// Obtain the session token from the configured source, warning about one that is unlikely to
+// be accepted before it is sent anywhere.
+pub fn session_token(options: &TokenOptions, guided_login: bool) -> Result<Zeroizing<String>> {
+    let (session_token, age) = find_session_token(options, guided_login)?;
+    warn_if_stale(&session_token, age);
+    Ok(session_token)
+}
+
+// The session token and, when the source knows it, how long ago it was last used.
+fn find_session_token(
+    options: &TokenOptions,
+    guided_login: bool,
+) -> Result<(Zeroizing<String>, Option<Duration>)> {
+    #[cfg(feature = "browser")]
+    if let Some(browser) = options.from_browser {
+        let (session_token, age) = browser::session_token(browser)?;
+        return Ok((Zeroizing::new(session_token), age));
+    }
+
+    if let Some(path) = &options.har {
+        return Ok((Zeroizing::new(har::session_token(path)?), None));
+    }
+
+    #[cfg(feature = "capture")]
+    if let Some(addr) = &options.capture_proxy {
+        return Ok((Zeroizing::new(capture::session_token(addr)?), None));
+    }
+
+    #[cfg(feature = "clipboard")]
+    let watch_clipboard = options.watch_clipboard;
+    #[cfg(not(feature = "clipboard"))]
+    let watch_clipboard = false;
+
+    if guided_login {
+        login::guide(watch_clipboard);
+    }
+
+    #[cfg(feature = "clipboard")]
+    if watch_clipboard {
+        return Ok((Zeroizing::new(clipboard::watch()?), None));
+    }
+
+    Ok((prompt_session_token()?, None))
+}
+
+// Session tokens stop working a few minutes after they are issued, and as soon as the browser
+// session they came from logs out. The SSO exchange then fails with little explanation.
+fn warn_if_stale(session_token: &str, age: Option<Duration>) {
+    if !looks_like_session_token(session_token) {
+        eprintln!(
+            "warning: that doesn't look like a session token, which looks like US-h6392c12...1kh10n2p7-531234; copy the value after ST= in the address bar after logging in"
+        );
+    }
+    if let Some(age) = age
+        && age > STALE_AFTER
+    {
+        eprintln!(
+            "warning: the session token was last used {} minutes ago and has probably expired; if the exchange fails, log in again (without logging out) and use the fresh one",
+            age.as_secs() / 60
+        );
+    }
+}
+
+// Prompt for the session token. Multi-line pastes such as "Copy as cURL" output or raw
+// request headers are read in full and the token is extracted from them.
+pub fn prompt_session_token() -> Result<Zeroizing<String>> {
+    let mut input = Zeroizing::new(prompt("Session Token (ST=...): ")?);
+
+    if starts_multiline_paste(&input) {
+        loop {
+            // Through `prompt`, which has the rest of a paste when line editing read the first
+            // line. Running out of input ends the paste like an empty line does.
+            let line = match crate::prompt("") {
+                Ok(line) => Zeroizing::new(line),
+                Err(_) if !crate::interactive() => break,
+                Err(err) => return Err(err),
+            };
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                break;
+            }
+            input.push('\n');
+            input.push_str(trimmed);
+            if continues_command(&input) {
+                continue;
+            }
+            if input.starts_with("curl") {
+                break;
+            }
+        }
+    }
+
+    Ok(Zeroizing::new(normalize_session_token(&input)))
+}
+
+// A shell command continued on the next line, or the start of a raw HTTP request.
+fn starts_multiline_paste(input: &str) -> bool {
+    continues_command(input) || is_request_line(input)
+}
+
+fn continues_command(input: &str) -> bool {
+    input.ends_with('\\') || input.ends_with('^') || input.ends_with('`')
+}
+
+// `GET /path HTTP/1.1` or `POST https://... HTTP/2`.
+fn is_request_line(input: &str) -> bool {
+    let mut parts = input.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let version = parts.nth(1).unwrap_or_default();
+    ["GET", "POST", "PUT", "HEAD", "OPTIONS", "PATCH", "DELETE"].contains(&method)
+        && version.starts_with("HTTP/")
+}
@@ -0,0 +1,172 @@
+// Saving the export in a secret store instead of printing it: the platform credential store
+// (Keychain, Credential Manager or Secret Service), read back with `show`, HashiCorp Vault, a
+// cloud provider's secrets manager, a YubiKey, or a systemd credential.
+
+use anyhow::Result;
+#[cfg(feature = "keyring")]
+use anyhow::{Context, bail};
+use clap::ValueEnum;
+#[cfg(feature = "keyring")]
+use keyring::Entry;
+#[cfg(feature = "keyring")]
+use zeroize::Zeroizing;
+
+#[cfg(any(feature = "vault", feature = "aws", feature = "gcp", feature = "azure"))]
+use serde_json::{Value, json};
+
+use bnet_auth_export::otpauth::OtpAuth;
+#[cfg(any(feature = "vault", feature = "aws", feature = "gcp", feature = "azure"))]
+use bnet_auth_export::restore;
+
+#[cfg(any(feature = "aws", feature = "gcp", feature = "azure"))]
+use crate::cloud::{self, CloudOptions};
+#[cfg(feature = "systemd-creds")]
+use crate::systemd_creds::{self, CredsOptions};
+#[cfg(feature = "vault")]
+use crate::vault::{self, VaultOptions};
+#[cfg(feature = "yubikey")]
+use crate::yubikey::{self, YubikeyOptions};
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Store {
+    /// The platform credential store.
+    #[cfg(feature = "keyring")]
+    Keyring,
+    /// A HashiCorp Vault KV v2 path, see --vault-path.
+    #[cfg(feature = "vault")]
+    Vault,
+    /// AWS Secrets Manager, see --secret-name and --aws-region.
+    #[cfg(feature = "aws")]
+    Aws,
+    /// GCP Secret Manager, see --secret-name and --gcp-project.
+    #[cfg(feature = "gcp")]
+    Gcp,
+    /// Azure Key Vault, see --secret-name and --azure-vault.
+    #[cfg(feature = "azure")]
+    Azure,
+    /// A YubiKey's OATH applet, written with ykman, see --yubikey-touch.
+    #[cfg(feature = "yubikey")]
+    Yubikey,
+    /// A file sealed with systemd-creds, TPM-bound where available, see --creds-file.
+    #[cfg(feature = "systemd-creds")]
+    SystemdCreds,
+}
+
+impl Store {
+    pub fn name(self) -> &'static str {
+        match self {
+            #[cfg(feature = "keyring")]
+            Self::Keyring => "keyring",
+            #[cfg(feature = "vault")]
+            Self::Vault => "vault",
+            #[cfg(feature = "aws")]
+            Self::Aws => "aws",
+            #[cfg(feature = "gcp")]
+            Self::Gcp => "gcp",
+            #[cfg(feature = "azure")]
+            Self::Azure => "azure",
+            #[cfg(feature = "yubikey")]
+            Self::Yubikey => "yubikey",
+            #[cfg(feature = "systemd-creds")]
+            Self::SystemdCreds => "systemd-creds",
+        }
+    }
+}
+
+// What the stores other than the keyring hold: everything needed to set the authenticator up
+// again, under names that don't depend on which store it is.
+#[cfg(any(feature = "vault", feature = "aws", feature = "gcp", feature = "azure"))]
+pub fn fields(serial: &str, export: &OtpAuth) -> Value {
+    json!({
+        "serial": serial,
+        "restore_code": restore::restore_code(serial, &export.secret),
+        "secret": *export.base32_secret(),
+        "device_secret": *export.hex_secret(),
+        "otpauth_uri": *export.to_uri(),
+    })
+}
+
+// One entry per authenticator, named `bnet-auth-export/<serial>`, holding the otpauth URI.
+#[cfg(feature = "keyring")]
+fn entry(serial: &str) -> Result<Entry> {
+    Entry::new(&format!("bnet-auth-export/{serial}"), serial)
+        .context("failed to open the keyring entry")
+}
+
+pub fn save(
+    store: Store,
+    export: &OtpAuth,
+    #[cfg(feature = "vault")] vault: &VaultOptions,
+    #[cfg(any(feature = "aws", feature = "gcp", feature = "azure"))] cloud: &CloudOptions,
+    #[cfg(feature = "yubikey")] yubikey: &YubikeyOptions,
+    #[cfg(feature = "systemd-creds")] creds: &CredsOptions,
+) -> Result<()> {
+    match store {
+        #[cfg(feature = "keyring")]
+        Store::Keyring => save_keyring(export),
+        #[cfg(feature = "vault")]
+        Store::Vault => vault::save(vault, export),
+        #[cfg(feature = "aws")]
+        Store::Aws => cloud::aws::save(cloud, export),
+        #[cfg(feature = "gcp")]
+        Store::Gcp => cloud::gcp::save(cloud, export),
+        #[cfg(feature = "azure")]
+        Store::Azure => cloud::azure::save(cloud, export),
+        #[cfg(feature = "yubikey")]
+        Store::Yubikey => yubikey::save(yubikey, export),
+        #[cfg(feature = "systemd-creds")]
+        Store::SystemdCreds => systemd_creds::save(creds, export),
+    }
+}
+
+// Read an export back, from the stores that can give it back.
+#[cfg(any(feature = "keyring", feature = "vault", feature = "systemd-creds"))]
+pub fn load_from(
+    store: Store,
+    serial: &str,
+    #[cfg(feature = "vault")] vault: &VaultOptions,
+    #[cfg(feature = "systemd-creds")] creds: &CredsOptions,
+) -> Result<OtpAuth> {
+    match store {
+        #[cfg(feature = "keyring")]
+        Store::Keyring => load(serial),
+        #[cfg(feature = "vault")]
+        Store::Vault => vault::load(vault, serial),
+        #[cfg(feature = "systemd-creds")]
+        Store::SystemdCreds => systemd_creds::load(creds, serial),
+        #[cfg(any(
+            feature = "aws",
+            feature = "gcp",
+            feature = "azure",
+            feature = "yubikey"
+        ))]
+        _ => anyhow::bail!(
+            "--store {} is only written to, read from the keyring, Vault or systemd-creds instead",
+            store.name()
+        ),
+    }
+}
+
+#[cfg(feature = "keyring")]
+fn save_keyring(export: &OtpAuth) -> Result<()> {
+    let Some(serial) = &export.serial else {
+        bail!("the keyring entry is named after the serial, and this export has none");
+    };
+
+    entry(serial)?
+        .set_password(&export.to_uri())
+        .context("failed to save to the keyring")?;
+    println!("\nSaved to the keyring as bnet-auth-export/{serial}.");
+    println!("Run `bnet-auth-export show {serial}` to print it.");
+    Ok(())
+}
+
+#[cfg(feature = "keyring")]
+pub fn load(serial: &str) -> Result<OtpAuth> {
+    let uri = match entry(serial)?.get_password() {
+        Ok(uri) => Zeroizing::new(uri),
+        Err(keyring::Error::NoEntry) => bail!("nothing is saved in the keyring for {serial}"),
+        Err(err) => return Err(err).context("failed to read from the keyring"),
+    };
+    Ok(OtpAuth::parse(&uri)?)
+}
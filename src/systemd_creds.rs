@@ -0,0 +1,137 @@
+// --store systemd-creds: the otpauth URI sealed with `systemd-creds encrypt`, bound to the TPM
+// where there is one, as a file a unit can load with LoadCredentialEncrypted=. `code --serial`
+// reads it back, from $CREDENTIALS_DIRECTORY when running as such a unit.
+
+use std::env;
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+use anyhow::{Context, Result, bail};
+use clap::Args;
+use zeroize::Zeroizing;
+
+use bnet_auth_export::otpauth::OtpAuth;
+use bnet_auth_export::secure_file;
+
+#[derive(Args)]
+pub struct CredsOptions {
+    /// Name of the credential --store systemd-creds seals, as given to LoadCredentialEncrypted=.
+    /// `<serial>` is replaced with the authenticator serial.
+    #[arg(
+        long,
+        env = "BNET_CREDS_NAME",
+        default_value = "bnet-auth-export-<serial>"
+    )]
+    pub creds_name: String,
+
+    /// File --store systemd-creds writes the sealed credential to. `<serial>` is replaced with
+    /// the authenticator serial.
+    #[arg(long, env = "BNET_CREDS_FILE", default_value = "<serial>.cred")]
+    pub creds_file: String,
+
+    /// What the credential is sealed with, passed to `systemd-creds --with-key`: `auto` uses the
+    /// TPM when there is one, `tpm2` insists on it.
+    #[arg(long, env = "BNET_CREDS_WITH_KEY", default_value = "auto")]
+    pub creds_with_key: String,
+}
+
+impl CredsOptions {
+    fn name(&self, serial: &str) -> String {
+        self.creds_name.replace("<serial>", serial)
+    }
+
+    fn file(&self, serial: &str) -> PathBuf {
+        PathBuf::from(self.creds_file.replace("<serial>", serial))
+    }
+}
+
+pub fn save(options: &CredsOptions, export: &OtpAuth) -> Result<()> {
+    let Some(serial) = &export.serial else {
+        bail!("the credential is named after the serial, and this export has none");
+    };
+    let name = options.name(serial);
+    let path = options.file(serial);
+
+    let with_key = format!("--with-key={}", options.creds_with_key);
+    let sealed = systemd_creds(
+        &["encrypt", &format!("--name={name}"), &with_key, "-", "-"],
+        export.to_uri().as_bytes(),
+    )?;
+    let unfinished = crate::interrupt::remove_on_interrupt(&path);
+    secure_file::private_options()
+        .truncate(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(&sealed.stdout))
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    drop(unfinished);
+
+    println!("\nSealed as the {name} credential in {}.", path.display());
+    println!(
+        "Load it in a unit with LoadCredentialEncrypted={name}:{}",
+        absolute(&path).display()
+    );
+    Ok(())
+}
+
+pub fn load(options: &CredsOptions, serial: &str) -> Result<OtpAuth> {
+    let name = options.name(serial);
+    // A unit with LoadCredentialEncrypted= gets it already decrypted.
+    if let Some(dir) = env::var_os("CREDENTIALS_DIRECTORY") {
+        let path = Path::new(&dir).join(&name);
+        match fs::read_to_string(&path) {
+            Ok(uri) => return Ok(OtpAuth::parse(&Zeroizing::new(uri))?),
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed to read {}", path.display()));
+            }
+        }
+    }
+
+    let path = options.file(serial);
+    if !path.exists() {
+        bail!(
+            "nothing is sealed for {serial} at {}, see --creds-file",
+            path.display()
+        );
+    }
+    let path = path.to_string_lossy();
+    let decrypted = systemd_creds(&["decrypt", &format!("--name={name}"), &path, "-"], &[])?;
+    let stdout = Zeroizing::new(decrypted.stdout);
+    let uri = Zeroizing::new(String::from_utf8_lossy(&stdout).trim().to_owned());
+    Ok(OtpAuth::parse(&uri)?)
+}
+
+// Run systemd-creds with `input` on stdin, which keeps the secret off the command line.
+fn systemd_creds(args: &[&str], input: &[u8]) -> Result<Output> {
+    let mut child = match Command::new("systemd-creds")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            bail!("--store systemd-creds needs systemd-creds, from systemd 250 or later")
+        }
+        Err(err) => return Err(err).context("failed to run systemd-creds"),
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // A failed write shows up as systemd-creds failing below.
+        let _ = stdin.write_all(input);
+    }
+    let output = child
+        .wait_with_output()
+        .context("failed to run systemd-creds")?;
+    if !output.status.success() {
+        bail!("systemd-creds {} failed with {}", args[0], output.status);
+    }
+    Ok(output)
+}
+
+// LoadCredentialEncrypted= wants an absolute path.
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_owned())
+}
@@ -0,0 +1,59 @@
+// --timings: how long each step of the run took, printed when it ends, to tell slow requests
+// apart from slow local work such as key derivation.
+
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+struct Timings {
+    started: Instant,
+    steps: Mutex<Vec<(String, Duration)>>,
+}
+
+static TIMINGS: OnceLock<Timings> = OnceLock::new();
+
+// Start recording. Until then `time` only runs the step.
+pub fn enable() {
+    let _ = TIMINGS.set(Timings {
+        started: Instant::now(),
+        steps: Mutex::new(Vec::new()),
+    });
+}
+
+// Run one step, in a `tracing` span of its own for --log-format, and record how long it took.
+// Steps from concurrent --batch workers are recorded in the order they finish.
+pub fn time<T>(step: &str, run: impl FnOnce() -> T) -> T {
+    let _span = tracing::info_span!("step", step).entered();
+    let Some(timings) = TIMINGS.get() else {
+        return run();
+    };
+    let started = Instant::now();
+    let result = run();
+    let elapsed = started.elapsed();
+    timings
+        .steps
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .push((step.to_owned(), elapsed));
+    result
+}
+
+// Print the recorded steps to stderr, if --timings is on. The total includes the time spent
+// waiting for input, which no step covers.
+pub fn report() {
+    let Some(timings) = TIMINGS.get() else {
+        return;
+    };
+    let steps = timings.steps.lock().unwrap_or_else(|err| err.into_inner());
+    let width = steps
+        .iter()
+        .map(|(step, _)| step.len())
+        .chain(["total".len()])
+        .max()
+        .unwrap_or_default();
+
+    eprintln!("\nTimings:");
+    for (step, elapsed) in steps.iter() {
+        eprintln!("  {step:<width$}  {elapsed:>9.1?}");
+    }
+    eprintln!("  {:<width$}  {:>9.1?}", "total", timings.started.elapsed());
+}
@@ -0,0 +1,189 @@
+use percent_encoding::percent_decode_str;
+use reqwest::Url;
+
+use crate::paste;
+
+/// Extract the session token from whatever the user pasted: the bare token, `ST=...`,
+/// a full URL, a `Cookie:` header, a cURL command or raw request headers, with any
+/// `Session token:` label, quotes and invisible characters around it.
+pub fn normalize_session_token(input: &str) -> String {
+    let input = &paste::clean(input, &["session token"]);
+    find_session_token(input)
+        .unwrap_or_else(|| input.strip_prefix("ST=").unwrap_or(input).to_owned())
+}
+
+/// Find a session token anywhere in a blob of text.
+pub fn find_session_token(input: &str) -> Option<String> {
+    let input = input.trim().trim_matches(['"', '\'']);
+    if looks_like_session_token(input) {
+        return Some(input.to_owned());
+    }
+
+    let found = token_from_url(input)
+        .or_else(|| token_from_cookie_header(input))
+        .or_else(|| find_st_parameter(input))
+        .or_else(|| {
+            // Otherwise any word shaped like a session token.
+            input
+                .split(|c: char| !is_token_char(c))
+                .find(|word| looks_like_session_token(word))
+                .map(str::to_owned)
+        });
+    if found.is_some() {
+        return found;
+    }
+
+    // Copied URLs are sometimes percent-encoded, e.g. when nested in a redirect parameter.
+    let decoded = percent_decode_str(input).decode_utf8().ok()?;
+    if decoded != input {
+        return find_session_token(&decoded);
+    }
+
+    None
+}
+
+// `https://localhost/?ST=...&accountId=...`, with ST in the query or the fragment.
+fn token_from_url(input: &str) -> Option<String> {
+    let url = Url::parse(input).ok()?;
+    let fragment: Vec<(String, String)> =
+        serde_urlencoded::from_str(url.fragment().unwrap_or_default()).unwrap_or_default();
+
+    url.query_pairs()
+        .into_owned()
+        .chain(fragment)
+        .find(|(key, value)| key.eq_ignore_ascii_case("ST") && looks_like_session_token(value))
+        .map(|(_, value)| value)
+}
+
+// `Cookie: a=1; ST=...; b=2`, with or without the header name.
+fn token_from_cookie_header(input: &str) -> Option<String> {
+    let (name, cookies) = input.split_once(':').unwrap_or(("cookie", input));
+    if !name.trim().eq_ignore_ascii_case("cookie") || !cookies.contains('=') {
+        return None;
+    }
+
+    let cookies: Vec<_> = cookies
+        .split(';')
+        .filter_map(|cookie| cookie.split_once('='))
+        .map(|(name, value)| (name.trim(), value.trim().trim_matches('"')))
+        .collect();
+    if cookies.is_empty() {
+        return None;
+    }
+
+    // Prefer a cookie named ST, then any cookie whose value has the token's shape.
+    cookies
+        .iter()
+        .find(|(name, value)| name.eq_ignore_ascii_case("ST") && looks_like_session_token(value))
+        .or_else(|| {
+            cookies
+                .iter()
+                .find(|(_, value)| looks_like_session_token(value))
+        })
+        .map(|(_, value)| (*value).to_owned())
+}
+
+/// Find an explicit `ST=<token>` in text, as a query parameter, cookie or form field.
+pub fn find_st_parameter(input: &str) -> Option<String> {
+    input.match_indices("ST=").find_map(|(index, _)| {
+        let preceded_by_word = input[..index]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_');
+        if preceded_by_word {
+            return None;
+        }
+        let value: String = input[index + 3..]
+            .chars()
+            .take_while(|&c| is_token_char(c))
+            .collect();
+        looks_like_session_token(&value).then_some(value)
+    })
+}
+
+fn is_token_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-'
+}
+
+/// Session tokens look like `US-h6392c12...1kh10n2p7-531234`.
+pub fn looks_like_session_token(value: &str) -> bool {
+    let Some((region, rest)) = value.split_once('-') else {
+        return false;
+    };
+    let Some((body, suffix)) = rest.rsplit_once('-') else {
+        return false;
+    };
+
+    region.len() == 2
+        && region.chars().all(|c| c.is_ascii_uppercase())
+        && body.len() >= 16
+        && body.chars().all(|c| c.is_ascii_alphanumeric())
+        && !suffix.is_empty()
+        && suffix.chars().all(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: &str = "US-h6392c12abcdef1kh10n2p7-531234";
+
+    #[test]
+    fn cleans_a_pasted_token() {
+        assert_eq!(
+            normalize_session_token("\u{feff}\"US-h6392c12abcdef\u{200b}1kh10n2p7-531234\""),
+            TOKEN
+        );
+    }
+
+    #[test]
+    fn finds_the_token_in_a_curl_command() {
+        let curl = format!(
+            "curl 'https://account.battle.net/overview' \\\n  -H 'accept: text/html' \\\n  -b 'BA-tassadar=abc; ST={TOKEN}; loc=en-us' \\\n  --compressed"
+        );
+        assert_eq!(normalize_session_token(&curl), TOKEN);
+
+        let headers = format!(
+            "GET /overview HTTP/2\nHost: account.battle.net\nCookie: loc=en-us; ST={TOKEN}\n"
+        );
+        assert_eq!(normalize_session_token(&headers), TOKEN);
+    }
+
+    #[test]
+    fn finds_the_token_in_a_url_or_cookie_header() {
+        for url in [
+            format!("https://localhost/?ST={TOKEN}&accountId=1"),
+            format!("http://localhost:0/#accountId=1&ST={TOKEN}"),
+            // Nested in a redirect parameter.
+            format!(
+                "https://account.battle.net/login?ref=https%3A%2F%2Flocalhost%2F%3FST%3D{TOKEN}"
+            ),
+        ] {
+            assert_eq!(normalize_session_token(&url), TOKEN, "{url}");
+        }
+
+        for cookies in [
+            format!("Cookie: loc=en-us; ST={TOKEN}; BA-tassadar=abc"),
+            format!("loc=en-us; ST=\"{TOKEN}\""),
+            format!("loc=en-us; web.id={TOKEN}"),
+        ] {
+            assert_eq!(normalize_session_token(&cookies), TOKEN, "{cookies}");
+        }
+    }
+
+    #[test]
+    fn rejects_input_without_a_token() {
+        assert_eq!(
+            find_session_token("curl 'https://account.battle.net/' -b 'loc=en-us'"),
+            None
+        );
+        // Too short a body to be one.
+        assert!(!looks_like_session_token("US-abc-123"));
+        assert_eq!(find_st_parameter(&format!("XST={TOKEN}")), None);
+        assert_eq!(
+            find_session_token("https://localhost/?ST=not-a-token&accountId=1"),
+            None
+        );
+        assert_eq!(find_session_token("Cookie: loc=en-us; ST="), None);
+    }
+}
@@ -0,0 +1,231 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+#[cfg(not(target_arch = "wasm32"))]
+use reqwest::blocking::Request;
+use reqwest::header::{CONTENT_TYPE, HeaderMap};
+use reqwest::{Method, StatusCode, Url};
+
+use crate::error::{Error, Result};
+use crate::secure_file;
+
+// Bodies longer than this are cut off in the transcript.
+const MAX_BODY: usize = 4096;
+
+// Header values that carry credentials.
+const SECRET_HEADERS: &[&str] = &["authorization", "cookie", "set-cookie"];
+
+// JSON and form fields that carry credentials or key material.
+const SECRET_FIELDS: &[&str] = &[
+    "token",
+    "code",
+    "code_verifier",
+    "access_token",
+    "refresh_token",
+    "id_token",
+    "restoreCode",
+    "deviceSecret",
+];
+
+// A redacted log of HTTP traffic that is safe to attach to bug reports.
+pub struct Transcript(Mutex<File>);
+
+impl Transcript {
+    pub fn create(path: &Path) -> Result<Self> {
+        // Redacted, but still more about the account than anyone else needs to read.
+        let file = secure_file::private_options()
+            .truncate(true)
+            .open(path)
+            .map_err(|source| Error::Io {
+                path: path.to_owned(),
+                source,
+            })?;
+        Ok(Self(Mutex::new(file)))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn request(&self, id: &str, request: &Request) {
+        let body = request.body().and_then(|body| body.as_bytes());
+        self.record_request(id, request.method(), request.url(), request.headers(), body);
+    }
+
+    #[cfg(any(feature = "tokio", target_arch = "wasm32"))]
+    pub fn async_request(&self, id: &str, request: &reqwest::Request) {
+        let body = request.body().and_then(|body| body.as_bytes());
+        self.record_request(id, request.method(), request.url(), request.headers(), body);
+    }
+
+    fn record_request(
+        &self,
+        id: &str,
+        method: &Method,
+        url: &Url,
+        headers: &HeaderMap,
+        body: Option<&[u8]>,
+    ) {
+        let mut entry = format!("# request {id}\n> {method} {url}\n");
+        entry.push_str(&format_headers("> ", headers));
+        entry.push_str(">\n");
+        entry.push_str(&format_body(headers, body.unwrap_or(&[])));
+        self.write(&entry);
+    }
+
+    pub fn response(&self, status: StatusCode, headers: &HeaderMap, body: &[u8]) {
+        let mut entry = format!("< {status}\n");
+        entry.push_str(&format_headers("< ", headers));
+        entry.push_str("<\n");
+        entry.push_str(&format_body(headers, body));
+        self.write(&entry);
+    }
+
+    // The transcript is best effort; a failed write must not abort the export.
+    fn write(&self, entry: &str) {
+        if let Ok(mut file) = self.0.lock() {
+            let _ = writeln!(file, "{entry}");
+        }
+    }
+}
+
+fn format_headers(prefix: &str, headers: &HeaderMap) -> String {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if SECRET_HEADERS.contains(&name.as_str()) {
+                "<redacted>"
+            } else {
+                value.to_str().unwrap_or("<binary>")
+            };
+            format!("{prefix}{name}: {value}\n")
+        })
+        .collect()
+}
+
+fn format_body(headers: &HeaderMap, body: &[u8]) -> String {
+    if body.is_empty() {
+        return String::new();
+    }
+
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok());
+    let mut text = redact_body(content_type, body);
+    if text.len() > MAX_BODY {
+        let cut = (0..=MAX_BODY)
+            .rev()
+            .find(|&index| text.is_char_boundary(index))
+            .unwrap_or(0);
+        text.truncate(cut);
+        text.push_str("... <truncated>");
+    }
+    text.push('\n');
+    text
+}
+
+// Redact secret fields in bodies the content type says are JSON or form-encoded. Anything
+// else is dropped entirely, since we can't tell what it contains: nearly any text parses as a
+// form, with the secret in a field name.
+fn redact_body(content_type: Option<&str>, body: &[u8]) -> String {
+    let essence = content_type
+        .and_then(|value| value.split(';').next())
+        .map(|essence| essence.trim().to_ascii_lowercase());
+    let dropped = || {
+        format!(
+            "<{} bytes of {} body>",
+            body.len(),
+            content_type.unwrap_or("untyped")
+        )
+    };
+
+    match essence.as_deref() {
+        Some(essence) if essence == "application/json" || essence.ends_with("+json") => {
+            let Ok(mut json) = serde_json::from_slice::<serde_json::Value>(body) else {
+                return dropped();
+            };
+            redact_json(&mut json);
+            json.to_string()
+        }
+        Some("application/x-www-form-urlencoded") => redact_form(body).unwrap_or_else(dropped),
+        _ => dropped(),
+    }
+}
+
+fn redact_form(body: &[u8]) -> Option<String> {
+    let pairs = serde_urlencoded::from_bytes::<Vec<(String, String)>>(body).ok()?;
+    Some(
+        pairs
+            .into_iter()
+            .map(|(key, value)| {
+                if SECRET_FIELDS.contains(&key.as_str()) {
+                    format!("{key}=<redacted>")
+                } else {
+                    format!("{key}={value}")
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("&"),
+    )
+}
+
+fn redact_json(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if SECRET_FIELDS.contains(&key.as_str()) {
+                    *value = serde_json::Value::String("<redacted>".to_owned());
+                } else {
+                    redact_json(value);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(redact_json),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn redacts_secret_json_fields_at_any_depth() {
+        let mut value = json!({
+            "access_token": "bearer",
+            "devices": [{ "serial": "US-2101-2345-6789", "deviceSecret": "7b0b" }],
+        });
+        redact_json(&mut value);
+        assert_eq!(
+            value,
+            json!({
+                "access_token": "<redacted>",
+                "devices": [{ "serial": "US-2101-2345-6789", "deviceSecret": "<redacted>" }],
+            })
+        );
+
+        let body = br#"{"restoreCode":"ABCDE12345","serial":"US-2101-2345-6789"}"#;
+        let text = redact_body(Some("application/json; charset=utf-8"), body);
+        assert!(!text.contains("ABCDE12345"));
+        assert!(text.contains("US-2101-2345-6789"));
+    }
+
+    #[test]
+    fn redacts_secret_form_fields() {
+        let text = redact_body(
+            Some("application/x-www-form-urlencoded"),
+            b"grant_type=client_sso&token=US-secret&client_id=abc",
+        );
+        assert_eq!(text, "grant_type=client_sso&token=<redacted>&client_id=abc");
+    }
+
+    #[test]
+    fn drops_other_bodies() {
+        // It parses as a form, with the secret for a field name, had the type not been checked.
+        let text = redact_body(Some("text/plain"), b"US-secret-session-token");
+        assert_eq!(text, "<23 bytes of text/plain body>");
+        assert!(!redact_body(None, br#"{"token":"US-secret"}"#).contains("US-secret"));
+        assert!(!redact_body(Some("application/json"), b"token=US-secret").contains("US-secret"));
+    }
+}
@@ -0,0 +1,465 @@
+// A full-screen wizard over the same export flow as the sequential prompts.
+
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use qrcode::QrCode;
+use qrcode::render::unicode::Dense1x2;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::{Line, Text};
+use ratatui::widgets::{Block, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use zeroize::Zeroizing;
+
+use bnet_auth_export::Error;
+use bnet_auth_export::api::Api;
+use bnet_auth_export::memory::Locked;
+use bnet_auth_export::otpauth::{LabelOptions, OtpAuth};
+use bnet_auth_export::region::Region;
+use bnet_auth_export::{restore, token};
+
+use crate::timings;
+
+const SPINNER: &[char] = &['|', '/', '-', '\\'];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Field {
+    SessionToken,
+    Serial,
+    RestoreCode,
+}
+
+enum Step {
+    Input(Field),
+    Working(Progress),
+    Done(Box<Done>),
+    Failed(String),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd)]
+enum Progress {
+    Exchanging,
+    Restoring,
+    LookingUpBattletag,
+}
+
+struct Done {
+    export: OtpAuth,
+    qr: String,
+    show_uri: bool,
+    show_secret: bool,
+}
+
+// Sent by the worker thread running the API calls.
+enum Update {
+    Progress(Progress),
+    Finished(Result<(Locked<String>, Option<String>), Error>),
+}
+
+struct Wizard {
+    api: Arc<Api>,
+    labels: LabelOptions,
+    step: Step,
+    input: Zeroizing<String>,
+    error: Option<String>,
+    session_token: Zeroizing<String>,
+    region: Region,
+    serial: String,
+    restore_code: String,
+    updates: Option<Receiver<Update>>,
+    tick: usize,
+    // Set when the user asks to print the export after leaving the wizard.
+    print: bool,
+}
+
+// Run the wizard. Returns the export if the user asked to print it once the screen is restored.
+pub fn run(api: Api, labels: LabelOptions) -> Result<Option<OtpAuth>> {
+    let mut wizard = Wizard {
+        api: Arc::new(api),
+        labels,
+        step: Step::Input(Field::SessionToken),
+        input: Zeroizing::default(),
+        error: None,
+        session_token: Zeroizing::default(),
+        region: Region::default(),
+        serial: String::new(),
+        restore_code: String::new(),
+        updates: None,
+        tick: 0,
+        print: false,
+    };
+
+    let _restore = crate::interrupt::on_interrupt(ratatui::restore);
+    let result = ratatui::run(|terminal| wizard.event_loop(terminal));
+    result?;
+
+    Ok(match wizard.step {
+        Step::Done(done) if wizard.print => Some(done.export),
+        _ => None,
+    })
+}
+
+impl Wizard {
+    fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        loop {
+            terminal
+                .draw(|frame| self.draw(frame))
+                .context("failed to draw the terminal")?;
+
+            self.receive_updates();
+            if !event::poll(Duration::from_millis(100)).context("failed to read terminal input")? {
+                self.tick += 1;
+                continue;
+            }
+            let Event::Key(key) = event::read().context("failed to read terminal input")? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                return Ok(());
+            }
+            if !self.handle_key(key) {
+                return Ok(());
+            }
+        }
+    }
+
+    // Returns false when the wizard should close.
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        match &mut self.step {
+            Step::Input(field) => {
+                let field = *field;
+                match key.code {
+                    KeyCode::Char(c) => self.input.push(c),
+                    KeyCode::Backspace => {
+                        self.input.pop();
+                    }
+                    KeyCode::Enter => self.submit(field),
+                    KeyCode::Esc => match field {
+                        Field::SessionToken => return false,
+                        Field::Serial => self.edit(Field::SessionToken),
+                        Field::RestoreCode => self.edit(Field::Serial),
+                    },
+                    _ => {}
+                }
+            }
+            Step::Working(_) => {}
+            Step::Done(done) => match key.code {
+                KeyCode::Char('u') => done.show_uri = !done.show_uri,
+                KeyCode::Char('s') => done.show_secret = !done.show_secret,
+                KeyCode::Char('p') => {
+                    self.print = true;
+                    return false;
+                }
+                KeyCode::Char('q') | KeyCode::Esc => return false,
+                _ => {}
+            },
+            Step::Failed(_) => match key.code {
+                KeyCode::Enter => self.start(),
+                KeyCode::Char('q') | KeyCode::Esc => return false,
+                _ => {}
+            },
+        }
+        true
+    }
+
+    // Go back to a field, with its previous value ready for editing.
+    fn edit(&mut self, field: Field) {
+        self.input = Zeroizing::new(match field {
+            Field::SessionToken => self.session_token.to_string(),
+            Field::Serial => self.serial.clone(),
+            Field::RestoreCode => self.restore_code.clone(),
+        });
+        self.error = None;
+        self.step = Step::Input(field);
+    }
+
+    // Validate the field being edited and move on to the next step.
+    fn submit(&mut self, field: Field) {
+        let input = self.input.trim();
+        if input.is_empty() {
+            self.error = Some("This field is required.".to_owned());
+            return;
+        }
+
+        let next = match field {
+            Field::SessionToken => {
+                self.session_token = Zeroizing::new(token::normalize_session_token(input));
+                self.region = self.api.region_for(Some(&self.session_token));
+                Field::Serial
+            }
+            Field::Serial => match self.region.normalize_serial(input).and_then(|serial| {
+                self.api
+                    .check_serial_region(Some(&self.session_token), &serial)
+                    .map(|region| (serial, region))
+            }) {
+                Ok((serial, region)) => {
+                    if let Some(region) = region {
+                        self.region = region;
+                    }
+                    self.serial = serial;
+                    Field::RestoreCode
+                }
+                Err(err) => {
+                    self.error = Some(err.to_string());
+                    return;
+                }
+            },
+            Field::RestoreCode => match restore::normalize_restore_code(input) {
+                Ok(restore_code) => {
+                    self.restore_code = restore_code;
+                    self.start();
+                    return;
+                }
+                Err(err) => {
+                    self.error = Some(err.to_string());
+                    return;
+                }
+            },
+        };
+        self.edit(next);
+    }
+
+    // Run the API calls on a worker thread so the screen keeps updating.
+    fn start(&mut self) {
+        let (sender, receiver) = mpsc::channel();
+        let api = Arc::clone(&self.api);
+        let session_token = self.session_token.clone();
+        let serial = self.serial.clone();
+        let restore_code = self.restore_code.clone();
+        let battletag = self.labels.label_battletag;
+
+        thread::spawn(move || {
+            let result = (|| {
+                let bearer_token = timings::time("SSO exchange", || {
+                    api.exchange_session_token(&session_token)
+                })?;
+                let _ = sender.send(Update::Progress(Progress::Restoring));
+                let device_secret = Locked::new(timings::time("restore", || {
+                    api.device_secret(&bearer_token, &serial, &restore_code)
+                })?);
+
+                let account = if battletag {
+                    let _ = sender.send(Update::Progress(Progress::LookingUpBattletag));
+                    timings::time("BattleTag lookup", || api.battletag(&bearer_token)).ok()
+                } else {
+                    None
+                };
+                Ok((device_secret, account))
+            })();
+            let _ = sender.send(Update::Finished(result));
+        });
+
+        self.updates = Some(receiver);
+        self.error = None;
+        self.step = Step::Working(Progress::Exchanging);
+    }
+
+    fn receive_updates(&mut self) {
+        let Some(updates) = &self.updates else {
+            return;
+        };
+        let update = match updates.try_recv() {
+            Ok(update) => update,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => {
+                self.updates = None;
+                return;
+            }
+        };
+
+        match update {
+            Update::Progress(progress) => self.step = Step::Working(progress),
+            Update::Finished(result) => {
+                self.updates = None;
+                self.finish(result);
+            }
+        }
+    }
+
+    fn finish(&mut self, result: Result<(Locked<String>, Option<String>), Error>) {
+        let (device_secret, account) = match result {
+            Ok(restored) => restored,
+            // Send the user back to the field that needs fixing.
+            Err(err @ Error::TokenExpired { .. }) => {
+                self.edit(Field::SessionToken);
+                self.input = Zeroizing::default();
+                self.error = Some(format!("{err}. Log in again and paste a fresh token."));
+                return;
+            }
+            Err(err @ Error::RestoreRejected { .. }) => {
+                self.edit(Field::RestoreCode);
+                self.error = Some(err.to_string());
+                return;
+            }
+            Err(err) => {
+                self.step = Step::Failed(format!("{:#}", anyhow::Error::from(err)));
+                return;
+            }
+        };
+
+        let secret = match hex::decode(device_secret.trim()) {
+            Ok(secret) => Zeroizing::new(secret),
+            Err(err) => {
+                self.step = Step::Failed(format!("deviceSecret is not valid hex: {err}"));
+                return;
+            }
+        };
+        let mut export = OtpAuth::new(self.serial.clone(), secret, &self.labels);
+        if let Some(account) = account {
+            export.account = account;
+        }
+        let qr = QrCode::new(export.to_uri().as_bytes())
+            .map(|code| {
+                code.render::<Dense1x2>()
+                    .dark_color(Dense1x2::Light)
+                    .light_color(Dense1x2::Dark)
+                    .build()
+            })
+            .unwrap_or_default();
+
+        self.step = Step::Done(Box::new(Done {
+            export,
+            qr,
+            show_uri: false,
+            show_secret: false,
+        }));
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [header, body, footer] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let title = match &self.step {
+            Step::Input(Field::SessionToken) => "Step 1 of 3: session token",
+            Step::Input(Field::Serial) => "Step 2 of 3: authenticator serial",
+            Step::Input(Field::RestoreCode) => "Step 3 of 3: restore code",
+            Step::Working(_) => "Talking to Battle.net",
+            Step::Done(_) => "Export succeeded",
+            Step::Failed(_) => "Export failed",
+        };
+        frame.render_widget(
+            Paragraph::new(title.bold()).block(Block::bordered().title(" bnet-auth-export ")),
+            header,
+        );
+
+        let keys = match &self.step {
+            Step::Input(Field::SessionToken) => "Enter: next  Esc: quit",
+            Step::Input(_) => "Enter: next  Esc: back  Ctrl-C: quit",
+            Step::Working(_) => "Ctrl-C: quit",
+            Step::Done(_) => "u: URI  s: Base32 secret  p: print and quit  q: quit",
+            Step::Failed(_) => "Enter: retry  q: quit",
+        };
+        frame.render_widget(Paragraph::new(keys.dark_gray()), footer);
+
+        let text = match &self.step {
+            Step::Input(field) => self.input_text(*field),
+            Step::Working(progress) => self.progress_text(*progress),
+            Step::Done(done) => done_text(done),
+            Step::Failed(message) => Text::from(vec![
+                Line::from(message.as_str().red()),
+                Line::default(),
+                Line::from("Check your connection and try again."),
+            ]),
+        };
+        frame.render_widget(
+            Paragraph::new(text)
+                .wrap(Wrap { trim: false })
+                .block(Block::bordered()),
+            body,
+        );
+    }
+
+    fn input_text(&self, field: Field) -> Text<'_> {
+        let (help, value) = match field {
+            Field::SessionToken => (
+                "Paste the session token (ST=...), or the whole URL or cookie header it came in.",
+                // Never show the token itself.
+                "*".repeat(self.input.chars().count()),
+            ),
+            Field::Serial => (
+                "Enter the serial shown in the Battle.net app, e.g. US-2101-2345-6789.",
+                self.input.to_string(),
+            ),
+            Field::RestoreCode => (
+                "Enter the 10-character restore code shown next to the serial.",
+                self.input.to_string(),
+            ),
+        };
+
+        let mut lines = vec![
+            Line::from(help),
+            Line::default(),
+            Line::from(format!("> {value}_")),
+        ];
+        if field != Field::SessionToken {
+            lines.push(Line::from(format!("Region: {}", self.region).dark_gray()));
+        }
+        if let Some(error) = &self.error {
+            lines.push(Line::default());
+            lines.push(Line::from(error.as_str().red()));
+        }
+        Text::from(lines)
+    }
+
+    fn progress_text(&self, progress: Progress) -> Text<'_> {
+        let spinner = SPINNER[self.tick % SPINNER.len()];
+        let mut steps = vec![
+            (Progress::Exchanging, "Exchanging the session token"),
+            (Progress::Restoring, "Restoring the authenticator"),
+        ];
+        if self.labels.label_battletag {
+            steps.push((Progress::LookingUpBattletag, "Looking up the BattleTag"));
+        }
+
+        let lines = steps.into_iter().map(|(step, label)| {
+            if step < progress {
+                Line::from(format!("[x] {label}")).style(Style::new().fg(Color::Green))
+            } else if step == progress {
+                Line::from(format!("[{spinner}] {label}..."))
+            } else {
+                Line::from(format!("[ ] {label}")).dark_gray()
+            }
+        });
+        Text::from_iter(lines)
+    }
+}
+
+fn done_text(done: &Done) -> Text<'_> {
+    let mut lines = vec![Line::from(
+        "Scan the QR code with your authenticator app, then check it shows 8-digit codes.",
+    )];
+    lines.push(Line::default());
+    lines.extend(done.qr.lines().map(Line::from));
+    lines.push(Line::default());
+    if let Some(serial) = &done.export.serial {
+        lines.push(Line::from(format!(
+            "Restore code: {}",
+            restore::restore_code(serial, &done.export.secret)
+        )));
+    }
+    if done.show_secret {
+        lines.push(Line::from(format!(
+            "Base32 secret: {}",
+            *done.export.base32_secret()
+        )));
+    }
+    if done.show_uri {
+        lines.push(Line::from(format!(
+            "otpauth URI: {}",
+            *done.export.to_uri()
+        )));
+    }
+    Text::from(lines)
+}
@@ -0,0 +1,213 @@
+// --store vault: writing the export to a HashiCorp Vault KV version 2 secrets engine, for those
+// who keep their secrets there. Authenticates with a token, or logs in with AppRole.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use clap::Args;
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use serde_json::{Value, json};
+use zeroize::Zeroizing;
+
+use bnet_auth_export::otpauth::OtpAuth;
+
+use crate::store;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Args)]
+pub struct VaultOptions {
+    /// KV v2 path that --store vault writes to, starting with the mount. `<serial>` is replaced
+    /// with the authenticator serial.
+    #[arg(long, env = "BNET_VAULT_PATH", default_value = "secret/bnet/<serial>")]
+    pub vault_path: String,
+
+    /// Address of the Vault server.
+    #[arg(long, env = "VAULT_ADDR", default_value = "https://127.0.0.1:8200")]
+    pub vault_addr: String,
+
+    /// Vault namespace, for Vault Enterprise and HCP Vault.
+    #[arg(long, env = "VAULT_NAMESPACE")]
+    pub vault_namespace: Option<String>,
+
+    /// Log in with AppRole using this role_id, and the secret_id from VAULT_SECRET_ID, instead
+    /// of using VAULT_TOKEN or ~/.vault-token.
+    #[arg(long, env = "VAULT_ROLE_ID")]
+    pub vault_role_id: Option<String>,
+
+    /// PEM file with the CA certificate the Vault server's certificate is signed with.
+    #[arg(long, env = "VAULT_CACERT")]
+    pub vault_ca_cert: Option<PathBuf>,
+}
+
+// Write the secret, otpauth URI, serial and restore code to the configured path, as a new
+// version of the secret there.
+pub fn save(options: &VaultOptions, export: &OtpAuth) -> Result<()> {
+    let Some(serial) = &export.serial else {
+        bail!("the Vault path is named after the serial, and this export has none");
+    };
+    let (path, url) = secret_url(options, serial)?;
+
+    let client = client(options)?;
+    let token = token(options, &client)?;
+    let body = json!({ "data": store::fields(serial, export) });
+    let response = send(
+        options,
+        client
+            .post(&url)
+            .header("X-Vault-Token", token.as_str())
+            .json(&body),
+        "write to Vault",
+    )?;
+
+    match response["data"]["version"].as_u64() {
+        Some(version) => println!("\nSaved to Vault at {path} (version {version})."),
+        None => println!("\nSaved to Vault at {path}."),
+    }
+    Ok(())
+}
+
+// Read back the latest version `save` wrote for the serial.
+pub fn load(options: &VaultOptions, serial: &str) -> Result<OtpAuth> {
+    let (path, url) = secret_url(options, serial)?;
+    let client = client(options)?;
+    let token = token(options, &client)?;
+    let response = send(
+        options,
+        client.get(&url).header("X-Vault-Token", token.as_str()),
+        "read from Vault",
+    )?;
+
+    let Some(uri) = response["data"]["data"]["otpauth_uri"].as_str() else {
+        bail!("{path} in Vault holds no otpauth_uri");
+    };
+    Ok(OtpAuth::parse(&Zeroizing::new(uri.to_owned()))?)
+}
+
+// The --vault-path for the serial, and its API URL.
+fn secret_url(options: &VaultOptions, serial: &str) -> Result<(String, String)> {
+    let path = options.vault_path.replace("<serial>", serial);
+    let url = format!(
+        "{}/v1/{}",
+        options.vault_addr.trim_end_matches('/'),
+        data_path(&path)?
+    );
+    Ok((path, url))
+}
+
+// KV v2 serves `<mount>/<path>` at `<mount>/data/<path>`.
+fn data_path(path: &str) -> Result<String> {
+    let path = path.trim_matches('/');
+    match path.split_once('/') {
+        Some((mount, rest)) if !mount.is_empty() && !rest.is_empty() => {
+            if rest.starts_with("data/") {
+                Ok(path.to_owned())
+            } else {
+                Ok(format!("{mount}/data/{rest}"))
+            }
+        }
+        _ => bail!(
+            "--vault-path {path} needs the mount and a path below it, e.g. secret/bnet/<serial>"
+        ),
+    }
+}
+
+fn client(options: &VaultOptions) -> Result<Client> {
+    let mut builder = Client::builder().timeout(TIMEOUT);
+    if let Some(path) = &options.vault_ca_cert {
+        let pem = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        let certificate = reqwest::Certificate::from_pem(&pem)
+            .with_context(|| format!("{} is not a PEM certificate", path.display()))?;
+        builder = builder.add_root_certificate(certificate);
+    }
+    builder.build().context("failed to set up the Vault client")
+}
+
+// An AppRole login when a role_id is given, otherwise the token the Vault CLI would use.
+fn token(options: &VaultOptions, client: &Client) -> Result<Zeroizing<String>> {
+    if let Some(role_id) = &options.vault_role_id {
+        let secret_id = Zeroizing::new(
+            env::var("VAULT_SECRET_ID")
+                .context("set VAULT_SECRET_ID to the AppRole secret_id to log in to Vault")?,
+        );
+        let url = format!(
+            "{}/v1/auth/approle/login",
+            options.vault_addr.trim_end_matches('/')
+        );
+        let response = send(
+            options,
+            client
+                .post(&url)
+                .json(&json!({ "role_id": role_id, "secret_id": *secret_id })),
+            "AppRole login",
+        )?;
+        return match response["auth"]["client_token"].as_str() {
+            Some(token) => Ok(Zeroizing::new(token.to_owned())),
+            None => bail!("the Vault AppRole login returned no client_token"),
+        };
+    }
+
+    if let Ok(token) = env::var("VAULT_TOKEN") {
+        return Ok(Zeroizing::new(token));
+    }
+    let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"));
+    if let Some(token) = home
+        .map(|home| PathBuf::from(home).join(".vault-token"))
+        .and_then(|path| fs::read_to_string(path).ok())
+    {
+        return Ok(Zeroizing::new(token.trim().to_owned()));
+    }
+    bail!("no Vault token: set VAULT_TOKEN, log in with `vault login`, or use --vault-role-id")
+}
+
+// Send a request and decode the reply, with the errors Vault lists when it refuses.
+fn send(options: &VaultOptions, request: RequestBuilder, what: &str) -> Result<Value> {
+    let request = match &options.vault_namespace {
+        Some(namespace) => request.header("X-Vault-Namespace", namespace),
+        None => request,
+    };
+    let response: Response = request
+        .send()
+        .with_context(|| format!("failed to reach Vault at {}", options.vault_addr))?;
+    let status = response.status();
+    let body: Value = response.json().unwrap_or(Value::Null);
+    if !status.is_success() {
+        let errors = body["errors"]
+            .as_array()
+            .map(|errors| {
+                errors
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            })
+            .filter(|errors| !errors.is_empty())
+            .unwrap_or_else(|| match status {
+                reqwest::StatusCode::NOT_FOUND => {
+                    "nothing there, check that a KV v2 engine is mounted at the start of --vault-path"
+                        .to_owned()
+                }
+                _ => "no details".to_owned(),
+            });
+        bail!("{what} failed with {status}: {errors}");
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_go_through_the_kv2_data_endpoint() {
+        assert_eq!(
+            data_path("secret/bnet/US-2101-2345-6789").unwrap(),
+            "secret/data/bnet/US-2101-2345-6789"
+        );
+        assert_eq!(data_path("/kv/data/bnet/x/").unwrap(), "kv/data/bnet/x");
+        assert!(data_path("secret").is_err());
+    }
+}
@@ -0,0 +1,113 @@
+// `verify`: check that a QR code, e.g. photographed from the new authenticator app's own export,
+// holds the same secret as the export, before the original authenticator is removed. With
+// --backup, check that a backup kept since is still intact.
+
+#[cfg(feature = "encrypted-backups")]
+use std::fs;
+use std::path::Path;
+
+#[cfg(feature = "encrypted-backups")]
+use anyhow::Context;
+use anyhow::Result;
+#[cfg(feature = "image-input")]
+use anyhow::bail;
+#[cfg(feature = "encrypted-backups")]
+use zeroize::Zeroizing;
+
+#[cfg(feature = "image-input")]
+use bnet_auth_export::otpauth::OtpAuth;
+#[cfg(feature = "image-input")]
+use bnet_auth_export::totp;
+
+#[cfg(feature = "image-input")]
+use crate::{convert, image_input};
+
+#[cfg(feature = "image-input")]
+pub fn run(qr_image: &Path, export: Option<&Path>) -> Result<()> {
+    let codes = image_input::decode_qr(qr_image)?;
+    if codes.len() > 1 {
+        bail!(
+            "{} holds {} QR codes, crop it to the one to check",
+            qr_image.display(),
+            codes.len()
+        );
+    }
+    let scanned = match OtpAuth::parse(&codes[0]) {
+        Ok(scanned) => scanned,
+        // Wrong digits or period are exactly the import mistakes this is for.
+        Err(err) => bail!("the QR code is not a working Battle.net authenticator: {err}"),
+    };
+    println!("QR code: {}:{}", scanned.issuer, scanned.account);
+
+    let reference = reference(&scanned, export)?;
+    let name = reference.serial.as_deref().unwrap_or(&reference.account);
+    if let (Some(expected), Some(actual)) = (&reference.serial, &scanned.serial)
+        && expected != actual
+    {
+        bail!(
+            "the QR code is for {actual}, not {expected}. Keep the original authenticator until the right account is imported."
+        );
+    }
+    if *scanned.secret != *reference.secret {
+        bail!(
+            "the QR code's secret differs from the export of {name}, so its codes will be wrong. Keep the original authenticator and import the export again."
+        );
+    }
+
+    println!(
+        "The QR code matches the export of {name}: same secret, {} digits, SHA1, {}s period.",
+        totp::DIGITS,
+        totp::PERIOD
+    );
+    println!(
+        "Check that the new app shows the same code as the original before removing the original."
+    );
+    Ok(())
+}
+
+// Check a --format backup file's MAC, which fails on any change since it was written.
+#[cfg(feature = "encrypted-backups")]
+pub fn run_backup(path: &Path) -> Result<()> {
+    let data = Zeroizing::new(
+        fs::read(path).with_context(|| format!("failed to read {}", path.display()))?,
+    );
+    let verified = crate::format::backup::verify(&data)?;
+    for export in &verified.exports {
+        println!("Backup of {}:{}", export.issuer, export.account);
+    }
+    println!(
+        "{} is intact: written {}, backup format version {}, and its MAC matches, so nothing in it has changed since.",
+        path.display(),
+        verified.created,
+        verified.version
+    );
+    Ok(())
+}
+
+// The export to compare with: from the file, picking the scanned serial when it holds several,
+// or else the one saved in the keyring for the scanned serial.
+#[cfg(feature = "image-input")]
+fn reference(scanned: &OtpAuth, export: Option<&Path>) -> Result<OtpAuth> {
+    let Some(path) = export else {
+        #[cfg(feature = "keyring")]
+        if let Some(serial) = &scanned.serial {
+            return crate::store::load(serial);
+        }
+        bail!("pass the export to compare the QR code with, e.g. its .json or .txt file");
+    };
+
+    let mut exports = convert::read(None, Some(path))?;
+    if exports.len() == 1 {
+        return Ok(exports.remove(0));
+    }
+    match exports
+        .into_iter()
+        .find(|export| scanned.serial.is_some() && export.serial == scanned.serial)
+    {
+        Some(export) => Ok(export),
+        None => bail!(
+            "{} holds several authenticators and none has the QR code's serial",
+            path.display()
+        ),
+    }
+}
@@ -0,0 +1,90 @@
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::api::ApiOptions;
+use crate::async_api::AsyncApi;
+use crate::error::Error;
+use crate::otpauth::{LabelOptions, OtpAuth};
+use crate::region::Region;
+use crate::{restore, token};
+
+/// A converted authenticator, in every form an authenticator app can import.
+///
+/// These are JavaScript strings once they leave WebAssembly, so they cannot be zeroized.
+#[wasm_bindgen(getter_with_clone)]
+pub struct Export {
+    pub uri: String,
+    #[wasm_bindgen(js_name = base32Secret)]
+    pub base32_secret: String,
+    #[wasm_bindgen(js_name = restoreCode)]
+    pub restore_code: String,
+}
+
+impl From<OtpAuth> for Export {
+    fn from(export: OtpAuth) -> Self {
+        let restore_code = export
+            .serial
+            .as_deref()
+            .map(|serial| restore::restore_code(serial, &export.secret))
+            .unwrap_or_default();
+
+        Self {
+            uri: export.to_uri().to_string(),
+            base32_secret: export.base32_secret().to_string(),
+            restore_code,
+        }
+    }
+}
+
+/// Restore an authenticator with a session token and convert it, all from the page.
+///
+/// Battle.net does not send CORS headers, so `ssoUrl` and `authBaseUrl` can point the requests
+/// at a proxy that adds them.
+#[wasm_bindgen(js_name = exportAuthenticator)]
+pub async fn export_authenticator(
+    session_token: String,
+    serial: String,
+    restore_code: String,
+    sso_url: Option<String>,
+    auth_base_url: Option<String>,
+) -> Result<Export, JsError> {
+    let session_token = Zeroizing::new(token::normalize_session_token(&session_token));
+    let api = AsyncApi::new(ApiOptions {
+        sso_url,
+        auth_base_url,
+        ..ApiOptions::default()
+    })?;
+
+    let serial = api
+        .region_for(Some(&session_token))
+        .normalize_serial(&serial)?;
+    let restore_code = restore::normalize_restore_code(&restore_code)?;
+
+    let bearer_token = api.exchange_session_token(&session_token).await?;
+    let device_secret = api
+        .device_secret(&bearer_token, &serial, &restore_code)
+        .await?;
+
+    convert(serial, &device_secret)
+}
+
+/// Convert an already known hex device secret without contacting Battle.net.
+#[wasm_bindgen(js_name = convertSecret)]
+pub fn convert_secret(serial: String, device_secret: String) -> Result<Export, JsError> {
+    let device_secret = Zeroizing::new(device_secret);
+    let serial = Region::from_serial(&serial)
+        .unwrap_or_default()
+        .normalize_serial(&serial)?;
+
+    convert(serial, &device_secret)
+}
+
+fn convert(serial: String, device_secret: &str) -> Result<Export, JsError> {
+    let secret = hex::decode(device_secret.trim()).map_err(|err| Error::Parse {
+        what: "deviceSecret".to_owned(),
+        detail: err.to_string(),
+    })?;
+    let export = OtpAuth::new(serial, Zeroizing::new(secret), &LabelOptions::default());
+
+    Ok(export.into())
+}
@@ -0,0 +1,95 @@
+// --store yubikey: writing the credential into a YubiKey's OATH applet with `ykman`, so the seed
+// ends up in hardware only and codes come from `ykman oath accounts code` or Yubico
+// Authenticator.
+
+use std::io::{ErrorKind, Write};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+use anyhow::{Context, Result, bail};
+use clap::Args;
+
+use bnet_auth_export::otpauth::OtpAuth;
+use bnet_auth_export::totp;
+
+#[derive(Args)]
+pub struct YubikeyOptions {
+    /// Make the credential --store yubikey writes need a touch of the YubiKey for every code.
+    #[arg(long)]
+    pub yubikey_touch: bool,
+
+    /// Serial number of the YubiKey --store yubikey writes to, when more than one is plugged in.
+    #[arg(long, env = "BNET_YUBIKEY_DEVICE")]
+    pub yubikey_device: Option<String>,
+}
+
+pub fn save(options: &YubikeyOptions, export: &OtpAuth) -> Result<()> {
+    // --batch workers take turns, the applet can't be written by two at once.
+    static YKMAN: Mutex<()> = Mutex::new(());
+    let _turn = YKMAN.lock().unwrap_or_else(|err| err.into_inner());
+
+    let mut command = Command::new("ykman");
+    if let Some(device) = &options.yubikey_device {
+        command.args(["--device", device]);
+    }
+    let (digits, period) = (totp::DIGITS.to_string(), totp::PERIOD.to_string());
+    command.args([
+        "oath",
+        "accounts",
+        "add",
+        "--oath-type",
+        "TOTP",
+        "--algorithm",
+        "SHA1",
+        "--digits",
+        &digits,
+        "--period",
+        &period,
+        "--issuer",
+        &export.issuer,
+        // Like the other stores, a later export of the same serial replaces the credential.
+        "--force",
+    ]);
+    if options.yubikey_touch {
+        command.arg("--touch");
+    }
+    command.arg(&export.account);
+
+    // The secret is left off the command line, where other processes could read it, and given
+    // to ykman's prompt for it on stdin instead. The prompt itself is discarded.
+    let mut child = match command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::inherit())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(err) if err.kind() == ErrorKind::NotFound => bail!(
+            "--store yubikey needs ykman from YubiKey Manager, see https://developers.yubico.com/yubikey-manager/"
+        ),
+        Err(err) => return Err(err).context("failed to run ykman"),
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // A failed write shows up as ykman failing below.
+        let _ = writeln!(stdin, "{}", *export.base32_secret());
+    }
+    let status = child.wait().context("failed to run ykman")?;
+    if !status.success() {
+        bail!(
+            "ykman failed to add the credential ({status}). If the OATH applet has a password, run `ykman oath access remember` first"
+        );
+    }
+
+    println!(
+        "\nSaved to the YubiKey as {}:{}.",
+        export.issuer, export.account
+    );
+    if options.yubikey_touch {
+        println!("Touch the YubiKey for each code.");
+    }
+    println!(
+        "Run `ykman oath accounts code {}` to show codes.",
+        export.issuer
+    );
+    Ok(())
+}
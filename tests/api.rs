@@ -0,0 +1,489 @@
+use bnet_auth_export::Error;
+use bnet_auth_export::api::{Api, ApiOptions, AppRequest, MOBILE_USER_AGENT};
+use bnet_auth_export::region::Region;
+use httpmock::Method::{DELETE, GET, POST};
+use httpmock::MockServer;
+
+const SESSION_TOKEN: &str = "US-h6392c12abcdef1kh10n2p7-531234";
+const SERIAL: &str = "US-2101-2345-6789";
+const RESTORE_CODE: &str = "ABCDE12345";
+
+fn api(server: &MockServer) -> Api {
+    Api::new(ApiOptions {
+        sso_url: Some(server.url("/oauth/sso")),
+        auth_base_url: Some(server.url("/v1/authenticator")),
+        retries: 0,
+        restore_limit: 0,
+        ..ApiOptions::default()
+    })
+    .unwrap()
+}
+
+#[test]
+fn exchanges_session_token() {
+    let server = MockServer::start();
+    let sso = server.mock(|when, then| {
+        when.method(POST)
+            .path("/oauth/sso")
+            .x_www_form_urlencoded_tuple("grant_type", "client_sso")
+            .x_www_form_urlencoded_tuple("token", SESSION_TOKEN);
+        then.status(200)
+            .json_body(serde_json::json!({ "access_token": "bearer" }));
+    });
+
+    let bearer_token = api(&server).exchange_session_token(SESSION_TOKEN).unwrap();
+
+    sso.assert();
+    assert_eq!(*bearer_token, "bearer");
+}
+
+#[test]
+fn restores_device_secret() {
+    let server = MockServer::start();
+    let device = server.mock(|when, then| {
+        when.method(POST)
+            .path("/v1/authenticator/device")
+            .header("authorization", "Bearer bearer")
+            .json_body(serde_json::json!({ "serial": SERIAL, "restoreCode": RESTORE_CODE }));
+        then.status(200)
+            .json_body(serde_json::json!({ "deviceSecret": "3132333435" }));
+    });
+
+    let device_secret = api(&server)
+        .device_secret("bearer", SERIAL, RESTORE_CODE)
+        .unwrap();
+
+    device.assert();
+    assert_eq!(*device_secret, "3132333435");
+}
+
+#[test]
+fn unauthorized_is_token_expired() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/oauth/sso");
+        then.status(401)
+            .json_body(serde_json::json!({ "error": "invalid_token" }));
+    });
+
+    let err = api(&server)
+        .exchange_session_token(SESSION_TOKEN)
+        .unwrap_err();
+
+    assert!(matches!(err, Error::TokenExpired { .. }), "{err}");
+}
+
+#[test]
+fn rate_limit_is_explained() {
+    let server = MockServer::start();
+    let device = server.mock(|when, then| {
+        when.method(POST).path("/v1/authenticator/device");
+        then.status(429).body("slow down");
+    });
+
+    let err = api(&server)
+        .device_secret("bearer", SERIAL, RESTORE_CODE)
+        .unwrap_err();
+
+    // Rate limits are not retried, that would only make them worse.
+    device.assert_hits(1);
+    assert!(matches!(err, Error::RateLimited { .. }), "{err}");
+    assert!(err.to_string().contains("rate limiting"), "{err}");
+}
+
+#[test]
+fn non_json_response_is_an_error() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/oauth/sso");
+        then.status(200).body("<html>maintenance</html>");
+    });
+
+    let err = api(&server)
+        .exchange_session_token(SESSION_TOKEN)
+        .unwrap_err();
+
+    assert!(matches!(err, Error::Parse { .. }), "{err}");
+    assert!(
+        err.to_string()
+            .contains("failed to parse SSO token exchange response"),
+        "{err}"
+    );
+}
+
+#[test]
+fn malformed_json_response_is_an_error() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/v1/authenticator/device");
+        then.status(200).body(r#"{"deviceSecret": "31"#);
+    });
+
+    let err = api(&server)
+        .device_secret("bearer", SERIAL, RESTORE_CODE)
+        .unwrap_err();
+
+    assert!(matches!(err, Error::Parse { .. }), "{err}");
+    assert!(
+        err.to_string()
+            .contains("failed to parse restore request response"),
+        "{err}"
+    );
+}
+
+#[test]
+fn missing_fields_are_reported() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/oauth/sso");
+        then.status(200).json_body(serde_json::json!({}));
+    });
+    server.mock(|when, then| {
+        when.method(POST).path("/v1/authenticator/device");
+        then.status(200)
+            .json_body(serde_json::json!({ "serial": SERIAL }));
+    });
+
+    let api = api(&server);
+    let sso_err = api.exchange_session_token(SESSION_TOKEN).unwrap_err();
+    let device_err = api
+        .device_secret("bearer", SERIAL, RESTORE_CODE)
+        .unwrap_err();
+
+    assert!(
+        matches!(&sso_err, Error::Parse { detail, .. } if detail == "missing access_token"),
+        "{sso_err}"
+    );
+    assert!(
+        matches!(&device_err, Error::Parse { detail, .. } if detail == "missing deviceSecret"),
+        "{device_err}"
+    );
+}
+
+#[test]
+fn emulates_the_mobile_app_for_chosen_requests() {
+    let server = MockServer::start();
+    let sso = server.mock(|when, then| {
+        when.method(POST)
+            .path("/oauth/sso")
+            .header("user-agent", "bnet-auth-export/0.1");
+        then.status(200)
+            .json_body(serde_json::json!({ "access_token": "bearer" }));
+    });
+    let restore = server.mock(|when, then| {
+        when.method(POST)
+            .path("/v1/authenticator/device")
+            .header("user-agent", MOBILE_USER_AGENT);
+        then.status(200)
+            .json_body(serde_json::json!({ "deviceSecret": "3132" }));
+    });
+
+    let api = Api::new(ApiOptions {
+        sso_url: Some(server.url("/oauth/sso")),
+        auth_base_url: Some(server.url("/v1/authenticator")),
+        emulate_mobile_app: vec![AppRequest::Restore],
+        retries: 0,
+        restore_limit: 0,
+        ..ApiOptions::default()
+    })
+    .unwrap();
+    let bearer_token = api.exchange_session_token(SESSION_TOKEN).unwrap();
+    api.device_secret(&bearer_token, SERIAL, RESTORE_CODE)
+        .unwrap();
+
+    sso.assert();
+    restore.assert();
+}
+
+#[test]
+fn lists_attached_devices() {
+    let server = MockServer::start();
+    let devices = server.mock(|when, then| {
+        when.method(GET)
+            .path("/v1/authenticator/devices")
+            .header("authorization", "Bearer bearer");
+        then.status(200).json_body(serde_json::json!({ "devices": [
+            { "serial": SERIAL, "type": "mobile", "attachedAt": "2021-03-04" },
+            { "serial": "EU-2101-2345-6780", "restoreCode": RESTORE_CODE },
+        ] }));
+    });
+
+    let listed = api(&server).devices("bearer").unwrap();
+
+    devices.assert();
+    assert_eq!(listed.len(), 2);
+    assert_eq!(listed[0].serial, SERIAL);
+    assert_eq!(listed[0].kind.as_deref(), Some("mobile"));
+    assert_eq!(listed[0].attached_at.as_deref(), Some("2021-03-04"));
+    assert_eq!(listed[1].kind, None);
+    assert_eq!(listed[0].restore_code, None);
+    assert_eq!(listed[1].restore_code.as_deref(), Some(RESTORE_CODE));
+}
+
+#[test]
+fn device_list_is_made_in_the_session_tokens_region() {
+    const CN_SESSION_TOKEN: &str = "CN-h6392c12abcdef1kh10n2p7-531234";
+    let server = MockServer::start();
+    let sso = server.mock(|when, then| {
+        when.method(POST)
+            .path("/oauth/sso")
+            .x_www_form_urlencoded_tuple("token", CN_SESSION_TOKEN);
+        then.status(200)
+            .json_body(serde_json::json!({ "access_token": "bearer" }));
+    });
+    let devices = server.mock(|when, then| {
+        when.method(GET)
+            .path("/v1/authenticator/devices")
+            .header("authorization", "Bearer bearer");
+        then.status(200)
+            .json_body(serde_json::json!({ "devices": [{ "serial": "CN-2101-2345-6789" }] }));
+    });
+    let api = api(&server);
+
+    let bearer_token = api.exchange_session_token(CN_SESSION_TOKEN).unwrap();
+    // Only the bearer token goes with the list, the region comes from the exchange.
+    assert_eq!(api.region_for(None), Region::Cn);
+    let listed = api.devices(&bearer_token).unwrap();
+
+    sso.assert();
+    devices.assert();
+    assert_eq!(listed[0].serial, "CN-2101-2345-6789");
+}
+
+#[test]
+fn attaches_and_detaches_authenticators() {
+    let server = MockServer::start();
+    let attach = server.mock(|when, then| {
+        when.method(POST)
+            .path("/v1/authenticator")
+            .header("authorization", "Bearer bearer");
+        then.status(200).json_body(serde_json::json!({
+            "serial": "US-2201-1111-2222",
+            "restoreCode": RESTORE_CODE,
+            "deviceSecret": "3132333435363738393031323334353637383930",
+        }));
+    });
+    let detach = server.mock(|when, then| {
+        when.method(DELETE)
+            .path("/v1/authenticator/device")
+            .header("authorization", "Bearer bearer")
+            .json_body(serde_json::json!({ "serial": SERIAL, "restoreCode": RESTORE_CODE }));
+        then.status(204);
+    });
+    let api = api(&server);
+
+    let new = api.attach("bearer").unwrap();
+    api.detach("bearer", SERIAL, RESTORE_CODE).unwrap();
+
+    attach.assert();
+    detach.assert();
+    assert_eq!(new.serial, "US-2201-1111-2222");
+    assert_eq!(new.restore_code, RESTORE_CODE);
+    assert_eq!(
+        *new.device_secret,
+        "3132333435363738393031323334353637383930"
+    );
+}
+
+#[test]
+fn server_errors_are_retried() {
+    let server = MockServer::start();
+    let sso = server.mock(|when, then| {
+        when.method(POST).path("/oauth/sso");
+        then.status(503);
+    });
+
+    let api = Api::new(ApiOptions {
+        sso_url: Some(server.url("/oauth/sso")),
+        retries: 1,
+        ..ApiOptions::default()
+    })
+    .unwrap();
+    let err = api.exchange_session_token(SESSION_TOKEN).unwrap_err();
+
+    sso.assert_hits(2);
+    assert!(
+        matches!(err, Error::SsoFailed { status, .. } if status == 503),
+        "{err}"
+    );
+}
+
+#[test]
+fn retried_restores_count_as_attempts() {
+    let server = MockServer::start();
+    let restore = server.mock(|when, then| {
+        when.method(POST).path("/v1/authenticator/device");
+        then.status(503);
+    });
+    let dir = tempfile::tempdir().unwrap();
+    let attempts = dir.path().join("restore-attempts");
+
+    let api = Api::new(ApiOptions {
+        auth_base_url: Some(server.url("/v1/authenticator")),
+        retries: 2,
+        restore_attempts_file: Some(attempts.clone()),
+        ..ApiOptions::default()
+    })
+    .unwrap();
+    api.device_secret("bearer", SERIAL, RESTORE_CODE)
+        .unwrap_err();
+
+    restore.assert_hits(3);
+    let recorded = std::fs::read_to_string(attempts).unwrap();
+    assert_eq!(recorded.lines().count(), 3);
+}
+
+#[test]
+fn rejected_session_token_suggests_a_fresh_one() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/oauth/sso");
+        then.status(400).body("");
+    });
+
+    let err = api(&server)
+        .exchange_session_token(SESSION_TOKEN)
+        .unwrap_err();
+
+    assert!(matches!(err, Error::SsoFailed { .. }), "{err}");
+    assert!(err.to_string().contains("fresh ST= value"), "{err}");
+}
+
+#[test]
+fn wrong_restore_code_is_rejected() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/v1/authenticator/device");
+        then.status(400)
+            .json_body(serde_json::json!({ "error": "invalid_restore_code" }));
+    });
+
+    let err = api(&server)
+        .device_secret("bearer", SERIAL, RESTORE_CODE)
+        .unwrap_err();
+
+    assert!(
+        matches!(err, Error::RestoreRejected { status, .. } if status == 400),
+        "{err}"
+    );
+}
+
+#[test]
+fn errors_name_the_request_and_its_trace_id() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/v1/authenticator/device");
+        then.status(400)
+            .header("x-trace-id", "0af7651916cd43dd")
+            .json_body(serde_json::json!({ "error": "invalid_restore_code" }));
+    });
+
+    let err = api(&server)
+        .device_secret("bearer", SERIAL, RESTORE_CODE)
+        .unwrap_err();
+
+    let message = err.to_string();
+    let request = format!("request {}-", bnet_auth_export::correlation::run_id());
+    assert!(message.contains(&request), "{message}");
+    assert!(message.contains("x-trace-id 0af7651916cd43dd"), "{message}");
+}
+
+#[test]
+fn serial_region_is_used_over_the_region_in_use() {
+    // --region eu, but the serial is from the US: its region is used instead.
+    let api = Api::new(ApiOptions {
+        region: Some(Region::Eu),
+        ..ApiOptions::default()
+    })
+    .unwrap();
+    assert_eq!(
+        api.check_serial_region(None, SERIAL).unwrap(),
+        Some(Region::Us)
+    );
+    assert_eq!(api.region_for(None), Region::Us);
+    assert_eq!(api.check_serial_region(None, SERIAL).unwrap(), None);
+
+    // Without --region or a region in the session token, the first serial picks the region.
+    let api = Api::new(ApiOptions::default()).unwrap();
+    let eu_serial = "EU-2101-2345-6789";
+    assert_eq!(
+        api.check_serial_region(Some("h6392c12abcdef1kh10n2p7"), eu_serial)
+            .unwrap(),
+        Some(Region::Eu)
+    );
+    assert_eq!(api.region_for(None), Region::Eu);
+    // A region in the session token is kept.
+    assert_eq!(api.region_for(Some(SESSION_TOKEN)), Region::Us);
+    assert_eq!(
+        api.check_serial_region(Some(SESSION_TOKEN), eu_serial)
+            .unwrap(),
+        None
+    );
+    // And a later serial switches again.
+    assert_eq!(
+        api.check_serial_region(None, SERIAL).unwrap(),
+        Some(Region::Us)
+    );
+}
+
+#[test]
+fn unreachable_server_is_a_network_error() {
+    // Nothing listens on the discard port.
+    let api = Api::new(ApiOptions {
+        sso_url: Some("http://127.0.0.1:9/oauth/sso".to_owned()),
+        retries: 0,
+        ..ApiOptions::default()
+    })
+    .unwrap();
+
+    let err = api.exchange_session_token(SESSION_TOKEN).unwrap_err();
+
+    assert!(matches!(err, Error::Network { .. }), "{err}");
+}
+
+#[test]
+fn audit_log_records_requests_without_secrets() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/oauth/sso");
+        then.status(200)
+            .json_body(serde_json::json!({ "access_token": "bearer" }));
+    });
+    server.mock(|when, then| {
+        when.method(POST).path("/v1/authenticator/device");
+        then.status(200)
+            .json_body(serde_json::json!({ "deviceSecret": "3132333435" }));
+    });
+    let path = std::env::temp_dir().join(format!("bnet-audit-{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let api = Api::new(ApiOptions {
+        sso_url: Some(server.url("/oauth/sso")),
+        auth_base_url: Some(server.url("/v1/authenticator")),
+        retries: 0,
+        restore_limit: 0,
+        audit_log: Some(path.clone()),
+        ..ApiOptions::default()
+    })
+    .unwrap();
+    let bearer_token = api.exchange_session_token(SESSION_TOKEN).unwrap();
+    api.device_secret(&bearer_token, SERIAL, RESTORE_CODE)
+        .unwrap();
+    let log = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let entries: Vec<serde_json::Value> = log
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0]["what"], "SSO token exchange");
+    assert_eq!(
+        entries[1]["endpoint"],
+        server.url("/v1/authenticator/device")
+    );
+    assert_eq!(entries[1]["status"], 200);
+    for secret in [SESSION_TOKEN, RESTORE_CODE, "bearer", "3132333435"] {
+        assert!(!log.contains(secret), "{log}");
+    }
+}